@@ -10,7 +10,7 @@ use glium::{
         dpi::{LogicalPosition, LogicalSize}
     },
 };
-use webrender::{PipelineInfo, api::{HitTestFlags, DevicePixel}};
+use webrender::{PipelineInfo, api::{HitTestFlags, HitTestItem, DevicePixel}};
 use image::ImageError;
 use euclid::TypedSize2D;
 #[cfg(feature = "logging")]
@@ -20,7 +20,7 @@ use {
     errors::{FontError, ClipboardError},
     window::{Window, WindowId},
     css_parser::{FontId, PixelValue},
-    text_cache::TextId,
+    text_cache::{TextId, TextCacheStats},
     dom::UpdateScreen,
     window::FakeWindow,
     css::FakeCss,
@@ -29,6 +29,7 @@ use {
     traits::Layout,
     ui_state::UiState,
     ui_description::UiDescription,
+    ui_solver::SolvedLayout,
     daemon::Daemon,
 };
 
@@ -156,6 +157,10 @@ impl<T: Layout> App<T> {
             css: FakeCss::default(),
             default_callbacks: DefaultCallbackSystem::new(),
             read_only_window: window.display.clone(),
+            solved_layout: SolvedLayout::default(),
+            scroll_requests: Vec::new(),
+            propagation_stopped: false,
+            default_prevented: false,
         });
 
         self.windows.push(window);
@@ -222,6 +227,10 @@ impl<T: Layout> App<T> {
                         continue 'window_loop;
                     }
                     window.state.update_mouse_cursor_position(event);
+                    // Mouse wheel / scrollbar events for scroll containers
+                    if window.scroll_states.handle_event(event, window.state.mouse_state.cursor_pos, Instant::now()) {
+                        frame_event_info.should_redraw_window = true;
+                    }
                     window.state.update_keyboard_modifiers(event);
                     window.state.update_keyboard_pressed_chars(event);
                 }
@@ -265,6 +274,14 @@ impl<T: Layout> App<T> {
                 window.update_from_external_window_state(&mut frame_event_info);
                 // Update the window state every frame that was set by the user
                 window.update_from_user_window_state(self.app_state.windows[idx].state.clone());
+                // Advance smooth scrolling of mouse wheel notches and momentum scrolling
+                if window.scroll_states.animate(Instant::now()) {
+                    frame_event_info.should_redraw_window = true;
+                }
+                // Apply the scroll offsets that were requested by the callbacks
+                if window.apply_scroll_requests(&mut self.app_state.windows[idx].scroll_requests) {
+                    frame_event_info.should_redraw_window = true;
+                }
                 // Reset the scroll amount to 0 (for the next frame)
                 window.clear_scroll_state();
 
@@ -301,7 +318,6 @@ impl<T: Layout> App<T> {
                     // Hot-reload CSS if necessary
                     if window.css.hot_reload_path.is_some() && Instant::now() - last_css_reload > Duration::from_millis(500) {
                         window.css.reload_css();
-                        window.css.needs_relayout = true;
                         last_css_reload = Instant::now();
                         window.events_loop.create_proxy().wakeup().unwrap_or(());
                         awakened_task[window_idx] = true;
@@ -486,6 +502,15 @@ impl<T: Layout> App<T> {
         self.app_state.clear_all_texts();
     }
 
+    /// See `AppResources::set_text_cache_memory_budget`
+    pub fn set_text_cache_memory_budget(&mut self, max_bytes: Option<usize>) {
+        self.app_state.set_text_cache_memory_budget(max_bytes);
+    }
+
+    pub fn get_text_cache_stats(&self) -> TextCacheStats {
+        self.app_state.get_text_cache_stats()
+    }
+
     /// Get the contents of the system clipboard as a string
     pub fn get_clipboard_string(&mut self)
     -> Result<String, ClipboardError>
@@ -619,11 +644,13 @@ fn do_hit_test_and_call_callbacks<T: Layout>(
     ui_state_cache: &[UiState<T>],
     app_state: &mut AppState<T>)
 {
-    use dom::UpdateScreen;
+    use dom::{UpdateScreen, Callback, On};
     use webrender::api::WorldPoint;
-    use window::WindowEvent;
-    use dom::Callback;
+    use window::{WindowEvent, EventPhase};
     use window_state::{KeyboardState, MouseState};
+    use app_state::AppStateNoData;
+    use ui_state::{DispatchedCallback, EventDispatchState};
+    use id_tree::NodeId;
 
     let cursor_location = match window.state.mouse_state.cursor_pos {
         Some(pos) => WorldPoint::new(pos.x as f32, pos.y as f32),
@@ -636,101 +663,90 @@ fn do_hit_test_and_call_callbacks<T: Layout>(
         cursor_location,
         HitTestFlags::FIND_ALL);
 
+    let ui_state = &ui_state_cache[window_id.id];
+
+    // The topmost hit node is the target of the event. Like in the HTML DOM, the event is
+    // then dispatched along the ancestors of the target, whether they were hit or not.
+    // Nodes that were hit, but are underneath the target and not one of its ancestors
+    // (i.e. overlapping siblings) don't receive the event.
+    let (target_item, target_node) = match hit_test_results.items.iter()
+        .filter(|item| !is_hidden_by_text_span(item, &hit_test_results.items))
+        .filter_map(|item| ui_state.tag_ids_to_node_ids.get(&item.tag.0).and_then(|node_id| Some((item, *node_id))))
+        .next()
+    {
+        Some(s) => s,
+        None => return,
+    };
+
+    let window_event = |current_dom_node: NodeId, phase: EventPhase, cursor_relative_to_item: (f32, f32)| WindowEvent {
+        window: window_id.id,
+        hit_dom_node: target_node,
+        current_dom_node: current_dom_node,
+        phase: phase,
+        cursor_relative_to_item: cursor_relative_to_item,
+        cursor_in_viewport: (target_item.point_in_viewport.x, target_item.point_in_viewport.y),
+        hit_text_span: get_hit_text_span(target_item),
+    };
+
     let mut should_update_screen = UpdateScreen::DontRedraw;
 
-    let callbacks_filter_list = window.state.determine_callbacks(event);
+    let callbacks_filter_list = window.state.determine_callbacks(event, &window.scroll_states.options);
 
     // TODO: this should be refactored - currently very stateful and error-prone!
     app_state.windows[window_id.id].set_keyboard_state(&window.state.keyboard_state);
     app_state.windows[window_id.id].set_mouse_state(&window.state.mouse_state);
 
+    // On::MouseOver is always dispatched, every `On` is dispatched as a separate event.
+    // See `UiState::get_event_dispatch_order` for the order in which the callbacks are invoked.
+    for on in Some(On::MouseOver).into_iter().chain(callbacks_filter_list.into_iter()) {
 
-    // Run all default callbacks - **before** the user-defined callbacks are run!
-    // TODO: duplicated code!
-    {
-        use app_state::AppStateNoData;
-
-        let mut lock = app_state.data.lock().unwrap();
-
-        for (item, callback_id_list) in hit_test_results.items.iter().filter_map(|item|
-            ui_state_cache[window_id.id].tag_ids_to_default_callbacks // <- NOTE: tag_ids_to_default_callbacks
-            .get(&item.tag.0)
-            .and_then(|callback_id_list| Some((item, callback_id_list)))
-        ) {
-            use dom::On;
-
-            let window_event = WindowEvent {
-                window: window_id.id,
-                hit_dom_node: ui_state_cache[window_id.id].tag_ids_to_node_ids[&item.tag.0],
-                cursor_relative_to_item: (item.point_in_viewport.x, item.point_in_viewport.y),
-                cursor_in_viewport: (item.point_in_viewport.x, item.point_in_viewport.y),
-            };
-
-            // Invoke On::MouseOver callback - TODO: duplicated code (due to borrowing issues)!
-            if let Some(callback_id) = callback_id_list.get(&On::MouseOver) {
+        app_state.windows[window_id.id].reset_event_state();
+        let mut dispatch_state = EventDispatchState::default();
 
-                let app_state_no_data = AppStateNoData {
-                    windows: &app_state.windows,
-                    resources: &mut app_state.resources,
-                };
+        for step in ui_state.get_event_dispatch_order(target_node, on) {
 
-                // safe unwrap, we have added the callback previously
-                if app_state.windows[window_id.id].default_callbacks.run_callback(
-                    &mut *lock, callback_id, app_state_no_data, window_event
-                    ) == UpdateScreen::Redraw {
-                    should_update_screen = UpdateScreen::Redraw;
-                }
+            if !dispatch_state.should_invoke(&step) {
+                continue;
             }
 
-            for callback_id in callbacks_filter_list.iter().filter_map(|on| callback_id_list.get(on)) {
+            // can't panic, the step is only created if the node has a callback for `on`
+            let tag_id = &ui_state.node_ids_to_tag_ids[&step.node_id];
 
-                let app_state_no_data = AppStateNoData {
-                    windows: &app_state.windows,
-                    resources: &mut app_state.resources,
-                };
+            // The cursor position relative to the node whose callback is invoked, not to the target
+            let cursor = target_item.point_in_viewport;
+            let cursor_relative_to_item = match app_state.windows[window_id.id].get_node_bounds(step.node_id) {
+                Some(bounds) => (cursor.x - bounds.origin.x, cursor.y - bounds.origin.y),
+                None => (cursor.x, cursor.y),
+            };
 
-                // safe unwrap, we have added the callback previously
-                if app_state.windows[window_id.id].default_callbacks.run_callback(
-                    &mut *lock, callback_id, app_state_no_data, window_event
-                    ) == UpdateScreen::Redraw {
-                    should_update_screen = UpdateScreen::Redraw;
-                }
-            }
-        }
-    } // unlock AppState mutex
-
-    // For all hit items, lookup the callback and call it
-    for (item, callback_list) in hit_test_results.items.iter().filter_map(|item|
-        ui_state_cache[window_id.id].tag_ids_to_callbacks
-        .get(&item.tag.0)
-        .and_then(|callback_list| Some((item, callback_list)))
-    ) {
-        use dom::On;
-
-        let window_event = WindowEvent {
-            window: window_id.id,
-            hit_dom_node: ui_state_cache[window_id.id].tag_ids_to_node_ids[&item.tag.0],
-            cursor_relative_to_item: (item.point_in_viewport.x, item.point_in_viewport.y),
-            cursor_in_viewport: (item.point_in_viewport.x, item.point_in_viewport.y),
-        };
+            let update_screen = match step.callback {
+                DispatchedCallback::Default => {
+                    let callback_id = &ui_state.tag_ids_to_default_callbacks[tag_id][&on]; // <- NOTE: tag_ids_to_default_callbacks
+                    let mut lock = app_state.data.lock().unwrap();
+                    let app_state_no_data = AppStateNoData {
+                        windows: &app_state.windows,
+                        resources: &mut app_state.resources,
+                    };
+                    app_state.windows[window_id.id].default_callbacks.run_callback(
+                        &mut *lock, callback_id, app_state_no_data, window_event(step.node_id, step.phase, cursor_relative_to_item))
+                }, // unlock AppState mutex
+                DispatchedCallback::Capture | DispatchedCallback::Bubble => {
+                    let callbacks = if step.callback == DispatchedCallback::Capture { &ui_state.tag_ids_to_capture_callbacks } else { &ui_state.tag_ids_to_callbacks };
+                    let &Callback(callback_func) = &callbacks[tag_id][&on];
+                    (callback_func)(app_state, window_event(step.node_id, step.phase, cursor_relative_to_item))
+                },
+            };
 
-        let mut invoke_callback = |&Callback(callback_func)| {
-            if (callback_func)(app_state, window_event) == UpdateScreen::Redraw {
+            if update_screen == UpdateScreen::Redraw {
                 should_update_screen = UpdateScreen::Redraw;
             }
-        };
-
-        // Invoke On::MouseOver callback
-        if let Some(callback_id) = callback_list.get(&On::MouseOver) {
-            invoke_callback(callback_id);
-        }
 
-        // Invoke user-defined callback if necessary
-        for callback_id in callbacks_filter_list.iter().filter_map(|on| callback_list.get(on)) {
-            invoke_callback(callback_id);
+            let fake_window = &app_state.windows[window_id.id];
+            dispatch_state.update(&step, fake_window.is_propagation_stopped(), fake_window.is_default_prevented());
         }
     }
 
+    app_state.windows[window_id.id].reset_event_state();
     app_state.windows[window_id.id].set_keyboard_state(&KeyboardState::default());
     app_state.windows[window_id.id].set_mouse_state(&MouseState::default());
 
@@ -746,6 +762,17 @@ fn do_hit_test_and_call_callbacks<T: Layout>(
     }
 }
 
+/// The clickable spans of a `NodeType::RichText` are hit-tested separately from their node
+/// (the second part of their tag is the index of the span + 1). If a span of a node was hit,
+/// the hit on the node itself is ignored, so that the callbacks of the node are only called once.
+fn is_hidden_by_text_span(item: &HitTestItem, hit_test_items: &[HitTestItem]) -> bool {
+    item.tag.1 == 0 && hit_test_items.iter().any(|other| other.tag.0 == item.tag.0 && other.tag.1 != 0)
+}
+
+fn get_hit_text_span(item: &HitTestItem) -> Option<usize> {
+    if item.tag.1 == 0 { None } else { Some(item.tag.1 as usize - 1) }
+}
+
 fn render<T: Layout>(
     app_data: Arc<Mutex<T>>,
     window: &mut Window<T>,
@@ -773,7 +800,9 @@ fn render<T: Layout>(
         has_window_size_changed,
         &window.state.size,
         window_id,
-        fake_window);
+        fake_window,
+        &mut window.scroll_states,
+        &window.font_render_options);
 
     // NOTE: Display list has to be rebuilt every frame, otherwise, the epochs get out of sync
     window.internal.last_display_list_builder = builder.finalize().2;
@@ -793,6 +822,14 @@ fn render<T: Layout>(
         true,
     );
 
+    // Restore the offsets of the scroll containers, since every display list starts unscrolled
+    for (scroll_frame, offset) in window.scroll_states.get_frames() {
+        txn.scroll_node_with_id(
+            LayoutPoint::new(offset.x, offset.y),
+            ExternalScrollId(scroll_frame.key.0, window.internal.pipeline_id),
+            ScrollClamping::ToContentBounds);
+    }
+
     // We don't want the epoch to increase to u32::MAX, since u32::MAX represents
     // an invalid epoch, which could confuse webrender
     window.internal.epoch = Epoch(if window.internal.epoch.0 == (u32::MAX - 1) {
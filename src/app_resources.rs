@@ -6,18 +6,23 @@ use std::{
 };
 use webrender::api::{FontKey, FontInstanceKey};
 use image::{self, ImageError};
-use FastHashMap;
+use {FastHashMap, FastHashSet};
 use app_units::Au;
 use clipboard2::{Clipboard, ClipboardError, SystemClipboard};
 use rusttype::Font;
 use {
-    text_layout::{PX_TO_PT, split_text_into_words},
-    text_cache::{TextId, TextCache},
-    font::{FontState, FontError},
+    text_layout::{self, PX_TO_PT, split_text_into_words, TextBreakOptions, TextRef, TextMeasureOptions},
+    text_shaping::{Shaper, ShapingOptions},
+    text_cache::{TextId, TextCache, TextCacheStats},
+    font::{FontState, FontError, FontRenderOptions, CharCoverage, font_has_glyph},
     images::{ImageId, ImageState, ImageType},
-    css_parser::{FontSize, FontId},
+    css_parser::{FontSize, FontId, FontFamily},
 };
 
+/// How many system fonts are read (to find a fallback font for a character) per frame at most,
+/// so that a missing glyph doesn't stall the frame until every installed font has been read
+const MAX_SYSTEM_FONTS_READ_PER_FRAME: usize = 8;
+
 /// Font and image keys
 ///
 /// The idea is that azul doesn't know where the resources come from,
@@ -46,7 +51,19 @@ pub struct AppResources {
     // After we've looked up the FontKey in the font_data map, we can then access
     // the font instance key (if there is any). If there is no font instance key,
     // we first need to create one.
-    pub(crate) fonts: FastHashMap<FontKey, FastHashMap<Au, FontInstanceKey>>,
+    pub(crate) fonts: FastHashMap<FontKey, FastHashMap<(Au, FontRenderOptions), FontInstanceKey>>,
+    /// Characters that aren't in the fonts of a font family are rendered with a system font
+    /// that contains them. Searching the system fonts is slow, so the result is cached per
+    /// character - including the characters that aren't in any system font.
+    fallback_fonts: RefCell<FastHashMap<char, Option<FontId>>>,
+    /// The characters that the system fonts contain, read lazily while searching for fallback fonts
+    system_font_index: RefCell<SystemFontIndex>,
+    /// The font fallback chains of the texts that aren't in the `text_cache` (i.e. labels), by font family
+    /// and text. Only kept for one frame (see `start_frame`), since labels change often.
+    label_fallback_chains: RefCell<FastHashMap<FontFamily, FastHashMap<String, Vec<FontId>>>>,
+    /// Texts of the `text_cache` whose fallback chain is incomplete, because the search for a
+    /// fallback font hasn't finished in this frame. Their words are re-created in the next frame.
+    incomplete_text_fallback_chains: RefCell<FastHashSet<TextId>>,
     /// Stores long texts across frames
     pub(crate) text_cache: TextCache,
     /// Keyboard clipboard storage and retrieval functionality
@@ -59,6 +76,10 @@ impl Default for AppResources {
             css_ids_to_image_ids: FastHashMap::default(),
             fonts: FastHashMap::default(),
             font_data: RefCell::new(FastHashMap::default()),
+            fallback_fonts: RefCell::new(FastHashMap::default()),
+            system_font_index: RefCell::new(SystemFontIndex::default()),
+            label_fallback_chains: RefCell::new(FastHashMap::default()),
+            incomplete_text_fallback_chains: RefCell::new(FastHashSet::default()),
             images: FastHashMap::default(),
             text_cache: TextCache::default(),
             clipboard: SystemClipboard::new().unwrap(),
@@ -155,6 +176,7 @@ impl AppResources {
                 data.read_to_end(&mut font_data).map_err(|e| FontError::IoError(e))?;
                 let (parsed_font, fd) = font::rusttype_load_font(font_data.clone(), None)?;
                 v.insert((Rc::new(parsed_font), Rc::new(fd), Rc::new(RefCell::new(FontState::ReadyForUpload(font_data)))));
+                self.clear_fallback_chains();
                 Ok(Some(()))
             },
         }
@@ -193,6 +215,131 @@ impl AppResources {
         self.get_font_internal(id).and_then(|(font, bytes, _)| Some((font, bytes)))
     }
 
+    /// Returns the fonts that are necessary to render the `text` with the `font_family` (the "font fallback chain"):
+    ///
+    /// First the fonts of the `font_family` that could be loaded (in order), followed by
+    /// the system fonts for the characters that aren't contained in any font of the `font_family`.
+    /// Characters that aren't in any font are rendered with the first font.
+    ///
+    /// The chain is cached until the end of the frame. For texts of the `text_cache`, use
+    /// `get_text_fallback_chain` instead, which keeps the chain as long as the text is cached.
+    pub(crate) fn get_font_fallback_chain(&self, font_family: &FontFamily, text: &str) -> Vec<FontId> {
+
+        if let Some(chain) = self.label_fallback_chains.borrow().get(font_family).and_then(|texts| texts.get(text)) {
+            return chain.clone();
+        }
+
+        // An incomplete chain is completed in the next frame, since the chain is only cached for this frame
+        let (chain, _) = self.calculate_font_fallback_chain(font_family, text);
+        self.label_fallback_chains.borrow_mut()
+            .entry(font_family.clone()).or_insert_with(|| FastHashMap::default())
+            .insert(text.to_string(), chain.clone());
+        chain
+    }
+
+    /// Same as `get_font_fallback_chain`, but for a text of the `text_cache`: the chain is stored
+    /// next to the words of the text. Returns `None` if the text doesn't exist.
+    pub(crate) fn get_text_fallback_chain(&self, font_family: &FontFamily, text_id: &TextId) -> Option<Vec<FontId>> {
+
+        if let Some(chain) = self.text_cache.get_fallback_chain(text_id, font_family) {
+            return Some(chain);
+        }
+
+        let (chain, is_complete) = self.calculate_font_fallback_chain(font_family, self.text_cache.string_cache.get(text_id)?);
+        self.text_cache.insert_fallback_chain(*text_id, font_family.clone(), chain.clone());
+        if !is_complete {
+            self.incomplete_text_fallback_chains.borrow_mut().insert(*text_id);
+        }
+        Some(chain)
+    }
+
+    /// Called at the start of each frame: removes the cached fallback chains of the labels,
+    /// resets the budget for reading system fonts and evicts the words of the texts whose
+    /// fallback chain was incomplete, so that they are re-created with the complete chain.
+    pub(crate) fn start_frame(&mut self) {
+        self.label_fallback_chains.borrow_mut().clear();
+        self.system_font_index.borrow_mut().fonts_read_in_frame = 0;
+        for text_id in self.incomplete_text_fallback_chains.borrow_mut().drain() {
+            self.text_cache.evict_words(text_id);
+        }
+    }
+
+    /// Removes all cached fallback chains and fallback fonts, since they depend on the fonts that are loaded
+    fn clear_fallback_chains(&self) {
+        self.label_fallback_chains.borrow_mut().clear();
+        self.fallback_fonts.borrow_mut().clear();
+        self.text_cache.clear_fallback_chains();
+    }
+
+    /// Returns the fallback chain and whether it is complete, i.e. whether the search
+    /// for a fallback font finished for all characters that the font family doesn't contain
+    fn calculate_font_fallback_chain(&self, font_family: &FontFamily, text: &str) -> (Vec<FontId>, bool) {
+
+        let mut chain = Vec::<(FontId, Rc<Font<'static>>)>::new();
+
+        for font_id in &font_family.fonts {
+            if let Some((font, _)) = self.get_font(font_id) {
+                if !chain.iter().any(|(id, _)| id == font_id) {
+                    chain.push((font_id.clone(), font));
+                }
+            }
+        }
+
+        let mut missing_chars = text.chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+            .collect::<Vec<char>>();
+        missing_chars.sort();
+        missing_chars.dedup();
+
+        let mut is_complete = true;
+
+        for c in missing_chars {
+            if chain.iter().any(|(_, font)| font_has_glyph(font, c)) {
+                continue;
+            }
+            let fallback_font = match self.get_system_fallback_font(c) {
+                FallbackFontSearch::Found(id) => self.get_font(&id).and_then(|(font, _)| Some((id, font))),
+                FallbackFontSearch::NotFound => None,
+                FallbackFontSearch::Pending => { is_complete = false; None },
+            };
+            if let Some(fallback_font) = fallback_font {
+                chain.push(fallback_font);
+            }
+        }
+
+        (chain.into_iter().map(|(id, _)| id).collect(), is_complete)
+    }
+
+    /// Searches the system fonts for a font that contains the character
+    fn get_system_fallback_font(&self, c: char) -> FallbackFontSearch {
+
+        if let Some(cached) = self.fallback_fonts.borrow().get(&c) {
+            return match cached {
+                Some(font_id) => FallbackFontSearch::Found(font_id.clone()),
+                None => FallbackFontSearch::NotFound,
+            };
+        }
+
+        // Prefer the fonts that are already loaded
+        let loaded_font = self.font_data.borrow().iter()
+            .find(|(_, (font, _, _))| font_has_glyph(font, c))
+            .and_then(|(id, _)| Some(id.clone()));
+
+        let search = match loaded_font {
+            Some(font_id) => FallbackFontSearch::Found(font_id),
+            None => self.system_font_index.borrow_mut().find_font(c),
+        };
+
+        // Don't cache the result of an unfinished search, it is continued in the next frame
+        match search {
+            FallbackFontSearch::Found(ref font_id) => { self.fallback_fonts.borrow_mut().insert(c, Some(font_id.clone())); },
+            FallbackFontSearch::NotFound => { self.fallback_fonts.borrow_mut().insert(c, None); },
+            FallbackFontSearch::Pending => { },
+        }
+
+        search
+    }
+
     /// Note the pub(crate) here: We don't want to expose the FontState in the public API
     pub(crate) fn get_font_state(&self, id: &FontId) -> Option<Rc<RefCell<FontState>>> {
         self.get_font_internal(id).and_then(|(_, _, state)| Some(state))
@@ -222,6 +369,7 @@ impl AppResources {
 
         let mut borrow_mut = self.font_data.borrow_mut();
         *borrow_mut.get_mut(&id).unwrap().2.borrow_mut() = FontState::AboutToBeDeleted(to_delete_font_key);
+        self.clear_fallback_chains();
         Some(())
     }
 
@@ -253,13 +401,17 @@ impl AppResources {
         // Otherwise, how would the TextId be valid?
         let text = self.text_cache.string_cache.get(&id).expect("Invalid text Id");
         let font_size_no_line_height = Scale::uniform(size.0.to_pixels() * PX_TO_PT);
-        let rusttype_font = self.get_font(&font).expect("Invalid font ID");
-        let words = split_text_into_words(text.as_ref(), &rusttype_font.0, font_size_no_line_height);
 
-        self.text_cache.cached_strings
-            .entry(id).or_insert_with(|| FastHashMap::default())
-            .entry(font).or_insert_with(|| FastHashMap::default())
-            .insert(size, words);
+        // The primary font, followed by the system fonts for the characters that the primary font doesn't have
+        let font_chain = self.get_text_fallback_chain(&FontFamily { fonts: vec![font.clone()] }, &id).expect("Invalid text Id");
+        let rusttype_fonts = font_chain.iter().filter_map(|id| self.get_font(id)).collect::<Vec<_>>();
+        assert!(!rusttype_fonts.is_empty(), "Invalid font ID");
+        let shapers = rusttype_fonts.iter().map(|f| Shaper::new(&f.1, &ShapingOptions::default())).collect::<Vec<_>>();
+        let fonts = rusttype_fonts.iter().zip(shapers.iter()).map(|(f, shaper)| (&*f.0, shaper)).collect::<Vec<_>>();
+        let break_options = TextBreakOptions::default();
+        let words = split_text_into_words(text.as_ref(), &fonts, font_size_no_line_height, &break_options);
+
+        self.text_cache.insert_words(id, font, break_options.direction, size, words);
     }
 
     pub fn delete_text(&mut self, id: TextId) {
@@ -270,6 +422,36 @@ impl AppResources {
         self.text_cache.clear_all_texts();
     }
 
+    /// Limits the memory that the layout of the cached texts can take up, see `TextCache::set_memory_budget`
+    pub fn set_text_cache_memory_budget(&mut self, max_bytes: Option<usize>) {
+        self.text_cache.set_memory_budget(max_bytes);
+    }
+
+    /// Returns statistics about the memory usage of the text cache
+    pub fn get_text_cache_stats(&self) -> TextCacheStats {
+        self.text_cache.get_stats()
+    }
+
+    /// Measures a text without laying it out, i.e. to size the content of a `GlTexture` or
+    /// `IFrame` callback. `text` can be a `&str` or the `TextId` of a text that was added with
+    /// `add_text_cached` / `add_text_uncached` - the words of cached texts are re-used.
+    ///
+    /// If `max_width` is set, the text is wrapped at `max_width` (like a `div` of that width would
+    /// wrap it). Returns `(width, height, line_count)`, where `width` is the width of the widest line,
+    /// or `None` if the font isn't loaded or the `TextId` doesn't exist.
+    pub fn measure_text<'a, T: Into<TextRef<'a>>>(&mut self, text: T, font_id: &FontId, font_size: FontSize, max_width: Option<f32>)
+    -> Option<(f32, f32, usize)>
+    {
+        self.measure_text_with_options(text, font_id, font_size, max_width, &TextMeasureOptions::default())
+    }
+
+    /// Same as `measure_text`, but respects the `line-height` and `letter-spacing` of the text
+    pub fn measure_text_with_options<'a, T: Into<TextRef<'a>>>(&mut self, text: T, font_id: &FontId, font_size: FontSize, max_width: Option<f32>, options: &TextMeasureOptions)
+    -> Option<(f32, f32, usize)>
+    {
+        text_layout::measure_text(self, text.into(), font_id, font_size, max_width, options)
+    }
+
     pub fn get_clipboard_string(&self)
     -> Result<String, ClipboardError>
     {
@@ -283,6 +465,59 @@ impl AppResources {
     }
 }
 
+/// Result of searching the system fonts for a font that contains a character
+#[derive(Debug, Clone, PartialEq)]
+enum FallbackFontSearch {
+    Found(FontId),
+    NotFound,
+    /// Not all system fonts could be searched, because too many fonts were read in this frame
+    Pending,
+}
+
+/// The characters that the system fonts contain. `font-loader` can't query the fonts by
+/// character, so the `cmap` of each system font is read from the disk - but only when a
+/// character is searched that isn't in any of the fonts read so far, and only up to
+/// `MAX_SYSTEM_FONTS_READ_PER_FRAME` fonts per frame.
+#[derive(Debug, Default)]
+struct SystemFontIndex {
+    /// The names of the system font families, queried on first use
+    families: Option<Vec<String>>,
+    /// The characters of each system font that was read, `None` if the font couldn't be read
+    coverage: FastHashMap<String, Option<CharCoverage>>,
+    /// How many system fonts were read in this frame
+    fonts_read_in_frame: usize,
+}
+
+impl SystemFontIndex {
+
+    fn find_font(&mut self, c: char) -> FallbackFontSearch {
+        use font_loader::system_fonts::{self, FontPropertyBuilder};
+
+        let families = self.families.get_or_insert_with(system_fonts::query_all);
+
+        for family in families.iter() {
+
+            if !self.coverage.contains_key(family) {
+                if self.fonts_read_in_frame >= MAX_SYSTEM_FONTS_READ_PER_FRAME {
+                    return FallbackFontSearch::Pending;
+                }
+                self.fonts_read_in_frame += 1;
+                let coverage = system_fonts::get(&FontPropertyBuilder::new().family(family).build())
+                    .and_then(|(font_bytes, idx)| CharCoverage::from_font_bytes(&font_bytes, idx.max(0) as usize));
+                self.coverage.insert(family.clone(), coverage);
+            }
+
+            // The `cmap` can be wrong, so only use the font if rusttype finds the glyph, too
+            let contains_char = self.coverage[family].as_ref().map(|coverage| coverage.contains(c)).unwrap_or(false);
+            if contains_char && AppResources::get_builtin_font(family.clone()).and_then(|(font, _, _)| Some(font_has_glyph(&font, c))).unwrap_or(false) {
+                return FallbackFontSearch::Found(FontId::BuiltinFont(family.clone()));
+            }
+        }
+
+        FallbackFontSearch::NotFound
+    }
+}
+
 // Empty test, for some reason codecov doesn't detect any files (and therefore
 // doesn't report codecov % correctly) except if they have at least one test in
 // the file. This is an empty test, which should be updated later on
@@ -6,10 +6,12 @@ use std::{
 };
 use image::ImageError;
 use rusttype::Font;
+use webrender::api::LayoutRect;
 use {
     FastHashMap,
-    text_cache::TextId,
+    text_cache::{TextId, TextCacheStats},
     window::FakeWindow,
+    id_tree::NodeId,
     task::Task,
     dom::UpdateScreen,
     traits::Layout,
@@ -261,6 +263,26 @@ impl<T: Layout> AppState<T> {
         }
     }
 
+    /// Returns the bounds of a node in the given window, as it was laid out in the last frame.
+    /// Shorthand for `app_state.windows[window].get_node_bounds(node_id)`, see
+    /// `FakeWindow::get_node_bounds`.
+    ///
+    /// ```no_run,ignore
+    /// // position the popover below the clicked node
+    /// if let Some(bounds) = app_state.get_node_bounds(event.window, event.hit_dom_node) {
+    ///     app_state.data.modify(|state| state.popover_position = (bounds.origin.x, bounds.max_y()));
+    /// }
+    /// ```
+    pub fn get_node_bounds(&self, window: usize, node_id: NodeId) -> Option<LayoutRect> {
+        self.windows.get(window).and_then(|window| window.get_node_bounds(node_id))
+    }
+
+    /// Returns the bounds of the first node with the given CSS id (see `Dom::with_id`)
+    /// in the given window, as it was laid out in the last frame
+    pub fn get_node_bounds_by_id(&self, window: usize, id: &str) -> Option<LayoutRect> {
+        self.windows.get(window).and_then(|window| window.get_node_bounds_by_id(id))
+    }
+
     pub fn add_text_uncached<S: Into<String>>(&mut self, text: S)
     -> TextId
     {
@@ -282,6 +304,15 @@ impl<T: Layout> AppState<T> {
         self.resources.clear_all_texts();
     }
 
+    /// See `AppResources::set_text_cache_memory_budget`
+    pub fn set_text_cache_memory_budget(&mut self, max_bytes: Option<usize>) {
+        self.resources.set_text_cache_memory_budget(max_bytes);
+    }
+
+    pub fn get_text_cache_stats(&self) -> TextCacheStats {
+        self.resources.get_text_cache_stats()
+    }
+
     /// Get the contents of the system clipboard as a string
     pub fn get_clipboard_string(&mut self)
     -> Result<String, ClipboardError>
@@ -34,6 +34,17 @@
 //! Lastly, we go through the `HashMap<(DomHash, bool)>` and remove the edit variables if the `bool` is false,
 //! meaning that the variable was not present in the current DOM tree, so leaving the variables in the solver
 //! would be garbage.
+//!
+//! # Current implementation
+//!
+//! Since the `Arena` stores the nodes in depth-first order, the previous and the current tree are simply
+//! compared node-by-node (the hash and the parent / sibling / child links of each node). Every node that
+//! differs is reported as added, every node that doesn't exist anymore is reported as removed. Adding a
+//! subtree shifts all following nodes, so all nodes after the inserted subtree are reported as changed.
+//!
+//! The variables of each rectangle are stored per `NodeId` (not per `DomHash`, since two identical nodes
+//! would then share the same variables), so a node that changes its content keeps its variables,
+//! only its constraints have to be rebuilt.
 
 use std::{
     ops::Deref,
@@ -55,16 +66,24 @@ pub(crate) struct DomTreeCache {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct DomChangeSet {
-    // TODO: calculate the constraints that have to be updated
+    /// Nodes that were added or that changed their content or their position in the tree
     pub(crate) added_nodes: BTreeMap<NodeId, DomHash>,
+    /// Nodes of the previous tree that don't exist anymore
+    pub(crate) removed_nodes: Vec<NodeId>,
 }
 
 impl DomChangeSet {
     pub(crate) fn empty() -> Self {
         Self {
             added_nodes: BTreeMap::new(),
+            removed_nodes: Vec::new(),
         }
     }
+
+    /// Returns whether the DOM is the same as in the last frame
+    pub(crate) fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty() && self.removed_nodes.is_empty()
+    }
 }
 
 impl Deref for DomChangeSet {
@@ -88,128 +107,41 @@ impl DomTreeCache {
 
     pub(crate) fn update<T: Layout>(&mut self, new_root: NodeId, new_nodes_arena: &Arena<NodeData<T>>) -> DomChangeSet {
 
-        if let Some(previous_root) = self.previous_layout.root {
-            // let mut changeset = DomChangeSet::empty();
-            let new_tree = new_nodes_arena.transform(|data, _| data.calculate_node_data_hash());
-            // Self::update_tree_inner(previous_root, &self.previous_layout.arena, new_root, &new_nodes_arena, &mut changeset);
-            let changeset = Self::update_tree_inner_2(&self.previous_layout.arena, &new_tree);
-            self.previous_layout.arena = new_tree;
-            changeset
+        let new_tree = new_nodes_arena.transform(|data, _| data.calculate_node_data_hash());
+
+        let changeset = if self.previous_layout.root.is_some() {
+            Self::diff_trees(&self.previous_layout.arena, &new_tree)
         } else {
             // initialize arena
-            self.previous_layout.arena = new_nodes_arena.transform(|data, _| data.calculate_node_data_hash());
-            self.previous_layout.root = Some(new_root);
             DomChangeSet {
-                added_nodes: self.previous_layout.arena.get_all_node_ids(),
+                added_nodes: new_tree.get_all_node_ids(),
+                removed_nodes: Vec::new(),
             }
-        }
+        };
+
+        self.previous_layout.arena = new_tree;
+        self.previous_layout.root = Some(new_root);
+        changeset
     }
 
-    fn update_tree_inner_2(previous_arena: &Arena<DomHash>, next_arena: &Arena<DomHash>) -> DomChangeSet {
+    /// Compares the two trees node-by-node. Nodes are compared by their hash
+    /// and their links, so that moved nodes are reported as changed, too
+    fn diff_trees(previous_arena: &Arena<DomHash>, next_arena: &Arena<DomHash>) -> DomChangeSet {
 
-        let mut previous_iter = previous_arena.nodes.iter();
-        let mut next_iter = next_arena.nodes.iter().enumerate();
         let mut changeset = DomChangeSet::empty();
 
-        while let Some((next_idx, next_hash)) = next_iter.next() {
-            if let Some(old_hash) = previous_iter.next() {
-                if old_hash.data != next_hash.data {
-                    changeset.added_nodes.insert(NodeId::new(next_idx), next_hash.data);
-                }
-            } else {
-                // println!("chrildren: no old hash, but subtree has to be added: {:?}!", new_next_id);
-                changeset.added_nodes.insert(NodeId::new(next_idx), next_hash.data);
+        for (next_idx, next_node) in next_arena.nodes.iter().enumerate() {
+            match previous_arena.nodes.get(next_idx) {
+                Some(old_node) if old_node == next_node => { },
+                _ => { changeset.added_nodes.insert(NodeId::new(next_idx), next_node.data); },
             }
         }
 
-        changeset
-    }
-
-    fn update_tree_inner<T>(previous_root: NodeId,
-                            previous_hash_arena: &Arena<DomHash>,
-                            current_root: NodeId,
-                            current_dom_arena: &Arena<NodeData<T>>,
-                            changeset: &mut DomChangeSet)
-    where T: Layout
-    {
-        let mut old_child_iterator = previous_root.children(previous_hash_arena);
-        let mut new_child_iterator = current_root.children(previous_hash_arena);
-
-        // children first
-        loop {
-            // skip the root node itself, although it wouldn't be necessary here
-            // old_child_iterator.next();
-            // new_child_iterator.next();
-            let old_child_next = old_child_iterator.next();
-            let new_child_next = new_child_iterator.next();
-
-            match (old_child_next, new_child_next) {
-                (None, None) => {
-                    // println!("chrildren: old has no children, new has no children!");
-                    break;
-                },
-                (Some(old_hash), None) => {
-                    // meaning, the whole subtree should be removed
-                    // println!("chrildren: old has children at id: {:?}, new has children at id:", old_hash);
-                },
-                (None, Some(new_next_id)) => {
-                    // println!("chrildren: no old hash, but subtree has to be added: {:?}!", new_next_id);
-                    // TODO: add subtree
-                },
-                (Some(old_hash_id), Some(new_next_node_id)) => {
-                    let old_hash = previous_hash_arena[old_hash_id].data;
-                    let new_hash = current_dom_arena[new_next_node_id].data.calculate_node_data_hash();
-
-                    if old_hash == new_hash {
-                        // println!("chrildren: children are the same!");
-                    } else {
-                        // hashes differ
-                        // println!("chrildren: children are different!");
-                        // changeset.added_nodes.insert(new_next_node_id, new_hash);
-                    }
-                }
-            }
+        for removed_idx in next_arena.nodes_len()..previous_arena.nodes_len() {
+            changeset.removed_nodes.push(NodeId::new(removed_idx));
         }
 
-        let mut old_iterator = previous_root.following_siblings(previous_hash_arena);
-        let mut new_iterator = current_root.following_siblings(current_dom_arena);
-
-        // now iterate over siblings
-        loop {
-            let old_next = old_iterator.next();
-            let new_next = new_iterator.next();
-
-            match (old_next, new_next) {
-                (None, None) => {
-                    // both old and new node have the same length
-                    break;
-                },
-                (None, Some(new_next_node_id)) => {
-                    // new node was pushed as a child
-                    let new_hash = current_dom_arena[new_next_node_id].data.calculate_node_data_hash();
-                    changeset.added_nodes.insert(new_next_node_id, new_hash);
-                    // println!("siblings: node was added as a child!");
-                },
-                (Some(old_hash_id), None) => {
-                    // node was removed as a child
-                    // mark node as inactive
-                    let old_hash = previous_hash_arena[old_hash_id].data;
-                    // println!("siblings: node was removed as a child: {:?}", old_hash);
-                },
-                (Some(old_hash_id), Some(new_next_node_id)) => {
-                    let old_hash = previous_hash_arena[old_hash_id].data;
-                    let new_hash = current_dom_arena[new_next_node_id].data.calculate_node_data_hash();
-
-                    if old_hash == new_hash {
-                        // println!("siblings: hashes are the same!");
-                    } else {
-                        // hashes differ
-                        // println!("siblings: hashes differ");
-                        changeset.added_nodes.insert(new_next_node_id, new_hash);
-                    }
-                }
-            }
-        }
+        changeset
     }
 }
 
@@ -233,7 +165,7 @@ pub(crate) struct DomNodeHash {
 
 #[derive(Debug)]
 pub(crate) struct EditVariableCache {
-    pub(crate) map: BTreeMap<DomHash, (bool, RectConstraintVariables)>
+    pub(crate) map: BTreeMap<NodeId, RectConstraintVariables>
 }
 
 impl EditVariableCache {
@@ -243,40 +175,50 @@ impl EditVariableCache {
         }
     }
 
+    /// Creates the variables for all nodes that didn't exist in the last frame.
+    /// Nodes that only changed their content keep their variables
     pub(crate) fn initialize_new_rectangles(&mut self, rects: &DomChangeSet) {
-        use std::collections::btree_map::Entry::*;
-
-        for dom_hash in rects.added_nodes.values() {
-
-            let map_entry = self.map.entry(*dom_hash);
-            match map_entry {
-                Occupied(e) => {
-                    e.into_mut().0 = true;
-                },
-                Vacant(e) => {
-                    let rect = RectConstraintVariables::default();
-                    e.insert((true, rect));
-                }
-            }
+        for node_id in rects.added_nodes.keys() {
+            self.map.entry(*node_id).or_insert_with(|| RectConstraintVariables::default());
         }
     }
 
     /// Last step of the caching algorithm:
-    /// Remove all edit variables where the `bool` is set to false
-    pub(crate) fn remove_unused_variables(&mut self) {
-
-        let mut to_be_removed = Vec::<DomHash>::new();
-
-        for (key, &(active, _)) in &self.map {
-            if !active {
-                to_be_removed.push(*key);
-            }
+    /// Remove the variables of all nodes that don't exist anymore
+    pub(crate) fn remove_unused_variables(&mut self, rects: &DomChangeSet) {
+        for node_id in &rects.removed_nodes {
+            self.map.remove(node_id);
         }
+    }
+}
 
-        for hash in &to_be_removed {
-            self.map.remove(hash);
+#[test]
+fn test_dom_tree_diff() {
+
+    fn build_tree(hashes: &[u64]) -> Arena<DomHash> {
+        let mut arena = Arena::new();
+        let root = arena.new_node(DomHash(hashes[0]));
+        for hash in &hashes[1..] {
+            let child = arena.new_node(DomHash(*hash));
+            root.append(child, &mut arena);
         }
+        arena
     }
+
+    let previous = build_tree(&[1, 2, 3]);
+
+    // last child removed: the root and the first child change their links
+    let changeset = DomTreeCache::diff_trees(&previous, &build_tree(&[1, 2]));
+    assert_eq!(changeset.added_nodes.keys().cloned().collect::<Vec<_>>(), vec![NodeId::new(0), NodeId::new(1)]);
+    assert_eq!(changeset.removed_nodes, vec![NodeId::new(2)]);
+
+    // content of the last child changed
+    let changeset = DomTreeCache::diff_trees(&previous, &build_tree(&[1, 2, 4]));
+    assert_eq!(changeset.added_nodes.keys().cloned().collect::<Vec<_>>(), vec![NodeId::new(2)]);
+    assert!(changeset.removed_nodes.is_empty());
+
+    // nothing changed
+    assert!(DomTreeCache::diff_trees(&previous, &build_tree(&[1, 2, 3])).is_empty());
 }
 
 // Empty test, for some reason codecov doesn't detect any files (and therefore
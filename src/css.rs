@@ -17,10 +17,18 @@ pub const NATIVE_CSS: &str = include_str!("styles/native_linux.css");
 pub const NATIVE_CSS: &str = include_str!("styles/native_macos.css");
 
 /// All the keys that, when changed, can trigger a re-layout
-const RELAYOUT_RULES: [&str; 13] = [
-    "border", "width", "height", "min-width", "min-height", "max-width", "max-height",
-    "direction", "wrap", "justify-content", "align-items", "align-content",
-    "order"
+const RELAYOUT_RULES: [&str; 47] = [
+    "width", "height", "min-width", "min-height", "max-width", "max-height", "aspect-ratio",
+    "position", "top", "right", "bottom", "left",
+    "padding", "padding-inline-start", "padding-inline-end", "direction",
+    "flex-direction", "flex-wrap", "justify-content", "align-items", "align-content",
+    "display", "grid-template-columns", "grid-template-rows", "grid-template-areas",
+    "gap", "grid-gap", "grid-column", "grid-row", "grid-area",
+    // Properties that change the intrinsic size of text
+    "font-size", "font-family", "line-height", "letter-spacing", "tab-size", "tab-stops",
+    "font-kerning", "font-feature-settings", "text-align", "text-wrap",
+    "white-space", "word-break", "overflow-wrap", "word-wrap",
+    "text-overflow", "line-clamp", "-webkit-line-clamp",
 ];
 
 /// Wrapper for a `Vec<CssRule>` - the CSS is immutable at runtime, it can only be
@@ -169,9 +177,7 @@ impl DynamicCssProperty {
 
 impl CssRule {
     pub fn needs_relayout(&self) -> bool {
-        // RELAYOUT_RULES.iter().any(|r| self.declaration.0 == *r)
-        // TODO
-        true
+        RELAYOUT_RULES.iter().any(|r| self.declaration.0 == *r)
     }
 }
 
@@ -263,6 +269,11 @@ impl Css {
         parsed_css.dynamic_css_overrides = self.dynamic_css_overrides.clone();
         parsed_css.hot_reload_override_native = self.hot_reload_override_native;
 
+        // Only force a re-layout if a layout-relevant rule has changed
+        let layout_rules_changed = !self.rules.iter().filter(|r| r.needs_relayout())
+            .eq(parsed_css.rules.iter().filter(|r| r.needs_relayout()));
+        parsed_css.needs_relayout = self.needs_relayout || layout_rules_changed;
+
         *self = parsed_css;
     }
 
@@ -544,4 +555,11 @@ fn test_detect_static_or_dynamic_property() {
         determine_static_or_dynamic_css_property("text-align", "[[ |  ]]"),
         Err(DynamicCssParseError::EmptyBraces)
     );
+}
+
+#[test]
+fn test_css_rule_needs_relayout() {
+    let css = Css::new_from_str("div { width: 100px; background-color: red; grid-area: main; letter-spacing: 2px; -webkit-line-clamp: 2; }").unwrap();
+    let relayout = css.rules.iter().map(|r| (r.declaration.0.as_str(), r.needs_relayout())).collect::<Vec<_>>();
+    assert_eq!(relayout, vec![("width", true), ("background-color", false), ("grid-area", true), ("letter-spacing", true), ("-webkit-line-clamp", true)]);
 }
\ No newline at end of file
@@ -7,10 +7,10 @@ pub use {
         BorderRadius, BorderWidths, BorderDetails, NormalBorder,
         NinePatchBorder, LayoutPixel, BoxShadowClipMode, ColorU,
         ColorF, LayoutVector2D, Gradient, RadialGradient, LayoutPoint,
-        LayoutSize, ExtendMode
+        LayoutSize, LayoutRect, ExtendMode
     },
 };
-use webrender::api::{BorderStyle, BorderSide, LayoutRect};
+use webrender::api::{BorderStyle, BorderSide};
 use euclid::TypedPoint2D;
 
 pub(crate) const EM_HEIGHT: f32 = 16.0;
@@ -83,8 +83,26 @@ pub enum ParsedCssProperty {
     FontSize(FontSize),
     FontFamily(FontFamily),
     TextAlign(TextAlignmentHorz),
+    VerticalAlign(TextAlignmentVert),
     BoxShadow(Option<BoxShadowPreDisplayItem>),
     LineHeight(LineHeight),
+    LetterSpacing(LetterSpacing),
+    TabSize(TabSize),
+    TabStops(TabStops),
+    FontKerning(FontKerning),
+    FontFeatureSettings(FontFeatureSettings),
+    FontSmoothing(FontSmoothing),
+    FontHinting(FontHinting),
+    FontSubpixelPositioning(FontSubpixelPositioning),
+    FontLcdFilter(FontLcdFilter),
+    TextWrap(TextWrap),
+    WhiteSpace(WhiteSpace),
+    WordBreak(WordBreak),
+    OverflowWrap(OverflowWrap),
+    TextOverflow(TextOverflowStyle),
+    LineClamp(LineClamp),
+    ObjectFit(LayoutObjectFit),
+    ObjectPosition(LayoutObjectPosition),
 
     Width(LayoutWidth),
     Height(LayoutHeight),
@@ -92,6 +110,9 @@ pub enum ParsedCssProperty {
     MinHeight(LayoutMinHeight),
     MaxWidth(LayoutMaxWidth),
     MaxHeight(LayoutMaxHeight),
+    IntrinsicWidth(LayoutIntrinsicSize),
+    IntrinsicHeight(LayoutIntrinsicSize),
+    AspectRatio(LayoutAspectRatio),
 
     Position(LayoutPosition),
     Top(LayoutTop),
@@ -100,13 +121,25 @@ pub enum ParsedCssProperty {
     Bottom(LayoutBottom),
 
     Padding(LayoutPadding),
+    PaddingInlineStart(LayoutPaddingInlineStart),
+    PaddingInlineEnd(LayoutPaddingInlineEnd),
 
+    TextDirection(LayoutTextDirection),
     FlexWrap(LayoutWrap),
     FlexDirection(LayoutDirection),
     JustifyContent(LayoutJustifyContent),
     AlignItems(LayoutAlignItems),
     AlignContent(LayoutAlignContent),
     Overflow(LayoutOverflow),
+
+    Display(LayoutDisplay),
+    GridTemplateColumns(LayoutGridTemplateColumns),
+    GridTemplateRows(LayoutGridTemplateRows),
+    GridTemplateAreas(LayoutGridTemplateAreas),
+    Gap(LayoutGap),
+    GridColumn(LayoutGridColumn),
+    GridRow(LayoutGridRow),
+    GridArea(LayoutGridArea),
 }
 
 impl ParsedCssProperty {
@@ -118,7 +151,22 @@ impl ParsedCssProperty {
             | FontFamily(_)
             | FontSize(_)
             | LineHeight(_)
-            | TextAlign(_) => true,
+            | LetterSpacing(_)
+            | TabSize(_)
+            | TabStops(_)
+            | FontKerning(_)
+            | FontSmoothing(_)
+            | FontHinting(_)
+            | FontSubpixelPositioning(_)
+            | FontLcdFilter(_)
+            | FontFeatureSettings(_)
+            | TextWrap(_)
+            | WhiteSpace(_)
+            | WordBreak(_)
+            | OverflowWrap(_)
+            | TextAlign(_)
+            | VerticalAlign(_)
+            | TextDirection(_) => true,
             _ => false,
         }
     }
@@ -130,7 +178,25 @@ impl_from_no_lifetimes!(FontSize, ParsedCssProperty::FontSize);
 impl_from_no_lifetimes!(FontFamily, ParsedCssProperty::FontFamily);
 impl_from_no_lifetimes!(LayoutOverflow, ParsedCssProperty::Overflow);
 impl_from_no_lifetimes!(TextAlignmentHorz, ParsedCssProperty::TextAlign);
+impl_from_no_lifetimes!(TextAlignmentVert, ParsedCssProperty::VerticalAlign);
 impl_from_no_lifetimes!(LineHeight, ParsedCssProperty::LineHeight);
+impl_from_no_lifetimes!(LetterSpacing, ParsedCssProperty::LetterSpacing);
+impl_from_no_lifetimes!(TabSize, ParsedCssProperty::TabSize);
+impl_from_no_lifetimes!(TabStops, ParsedCssProperty::TabStops);
+impl_from_no_lifetimes!(FontKerning, ParsedCssProperty::FontKerning);
+impl_from_no_lifetimes!(FontSmoothing, ParsedCssProperty::FontSmoothing);
+impl_from_no_lifetimes!(FontHinting, ParsedCssProperty::FontHinting);
+impl_from_no_lifetimes!(FontSubpixelPositioning, ParsedCssProperty::FontSubpixelPositioning);
+impl_from_no_lifetimes!(FontLcdFilter, ParsedCssProperty::FontLcdFilter);
+impl_from_no_lifetimes!(FontFeatureSettings, ParsedCssProperty::FontFeatureSettings);
+impl_from_no_lifetimes!(TextWrap, ParsedCssProperty::TextWrap);
+impl_from_no_lifetimes!(WhiteSpace, ParsedCssProperty::WhiteSpace);
+impl_from_no_lifetimes!(WordBreak, ParsedCssProperty::WordBreak);
+impl_from_no_lifetimes!(OverflowWrap, ParsedCssProperty::OverflowWrap);
+impl_from_no_lifetimes!(TextOverflowStyle, ParsedCssProperty::TextOverflow);
+impl_from_no_lifetimes!(LineClamp, ParsedCssProperty::LineClamp);
+impl_from_no_lifetimes!(LayoutObjectFit, ParsedCssProperty::ObjectFit);
+impl_from_no_lifetimes!(LayoutObjectPosition, ParsedCssProperty::ObjectPosition);
 
 impl_from_no_lifetimes!(LayoutWidth, ParsedCssProperty::Width);
 impl_from_no_lifetimes!(LayoutHeight, ParsedCssProperty::Height);
@@ -138,6 +204,7 @@ impl_from_no_lifetimes!(LayoutMinWidth, ParsedCssProperty::MinWidth);
 impl_from_no_lifetimes!(LayoutMinHeight, ParsedCssProperty::MinHeight);
 impl_from_no_lifetimes!(LayoutMaxWidth, ParsedCssProperty::MaxWidth);
 impl_from_no_lifetimes!(LayoutMaxHeight, ParsedCssProperty::MaxHeight);
+impl_from_no_lifetimes!(LayoutAspectRatio, ParsedCssProperty::AspectRatio);
 
 impl_from_no_lifetimes!(LayoutPosition, ParsedCssProperty::Position);
 impl_from_no_lifetimes!(LayoutTop, ParsedCssProperty::Top);
@@ -146,6 +213,10 @@ impl_from_no_lifetimes!(LayoutRight, ParsedCssProperty::Right);
 impl_from_no_lifetimes!(LayoutLeft, ParsedCssProperty::Left);
 
 impl_from_no_lifetimes!(LayoutPadding, ParsedCssProperty::Padding);
+impl_from_no_lifetimes!(LayoutPaddingInlineStart, ParsedCssProperty::PaddingInlineStart);
+impl_from_no_lifetimes!(LayoutPaddingInlineEnd, ParsedCssProperty::PaddingInlineEnd);
+
+impl_from_no_lifetimes!(LayoutTextDirection, ParsedCssProperty::TextDirection);
 
 impl_from_no_lifetimes!(LayoutWrap, ParsedCssProperty::FlexWrap);
 impl_from_no_lifetimes!(LayoutDirection, ParsedCssProperty::FlexDirection);
@@ -153,6 +224,15 @@ impl_from_no_lifetimes!(LayoutJustifyContent, ParsedCssProperty::JustifyContent)
 impl_from_no_lifetimes!(LayoutAlignItems, ParsedCssProperty::AlignItems);
 impl_from_no_lifetimes!(LayoutAlignContent, ParsedCssProperty::AlignContent);
 
+impl_from_no_lifetimes!(LayoutDisplay, ParsedCssProperty::Display);
+impl_from_no_lifetimes!(LayoutGridTemplateColumns, ParsedCssProperty::GridTemplateColumns);
+impl_from_no_lifetimes!(LayoutGridTemplateRows, ParsedCssProperty::GridTemplateRows);
+impl_from_no_lifetimes!(LayoutGridTemplateAreas, ParsedCssProperty::GridTemplateAreas);
+impl_from_no_lifetimes!(LayoutGap, ParsedCssProperty::Gap);
+impl_from_no_lifetimes!(LayoutGridColumn, ParsedCssProperty::GridColumn);
+impl_from_no_lifetimes!(LayoutGridRow, ParsedCssProperty::GridRow);
+impl_from_no_lifetimes!(LayoutGridArea, ParsedCssProperty::GridArea);
+
 impl_from_no_lifetimes!(BackgroundColor, ParsedCssProperty::BackgroundColor);
 impl_from_no_lifetimes!(TextColor, ParsedCssProperty::TextColor);
 
@@ -184,13 +264,31 @@ impl ParsedCssProperty {
             "font-family"       => Ok(parse_css_font_family(value)?.into()),
             "box-shadow"        => Ok(parse_css_box_shadow(value)?.into()),
             "line-height"       => Ok(parse_line_height(value)?.into()),
-
-            "width"             => Ok(parse_layout_width(value)?.into()),
-            "height"            => Ok(parse_layout_height(value)?.into()),
+            "letter-spacing"    => Ok(parse_letter_spacing(value)?.into()),
+            "tab-size"          => Ok(parse_layout_tab_size(value)?.into()),
+            "tab-stops"         => Ok(parse_layout_tab_stops(value)?.into()),
+            "font-kerning"      => Ok(parse_css_font_kerning(value)?.into()),
+            "font-feature-settings" => Ok(parse_css_font_feature_settings(value)?.into()),
+            "font-smoothing" | "-webkit-font-smoothing" => Ok(parse_css_font_smoothing(value)?.into()),
+            "font-hinting"      => Ok(parse_css_font_hinting(value)?.into()),
+            "font-subpixel-positioning" => Ok(parse_css_font_subpixel_positioning(value)?.into()),
+            "font-lcd-filter"   => Ok(parse_css_font_lcd_filter(value)?.into()),
+            "object-fit"        => Ok(parse_layout_object_fit(value)?.into()),
+            "object-position"   => Ok(parse_layout_object_position(value)?.into()),
+
+            "width"             => match parse_layout_intrinsic_size(value) {
+                Ok(intrinsic_width) => Ok(ParsedCssProperty::IntrinsicWidth(intrinsic_width)),
+                Err(_) => Ok(parse_layout_width(value)?.into()),
+            },
+            "height"            => match parse_layout_intrinsic_size(value) {
+                Ok(intrinsic_height) => Ok(ParsedCssProperty::IntrinsicHeight(intrinsic_height)),
+                Err(_) => Ok(parse_layout_height(value)?.into()),
+            },
             "min-width"         => Ok(parse_layout_min_width(value)?.into()),
             "min-height"        => Ok(parse_layout_min_height(value)?.into()),
             "max-width"         => Ok(parse_layout_max_width(value)?.into()),
             "max-height"        => Ok(parse_layout_max_height(value)?.into()),
+            "aspect-ratio"      => Ok(parse_layout_aspect_ratio(value)?.into()),
 
             "position"          => Ok(parse_layout_position(value)?.into()),
             "top"               => Ok(parse_layout_top(value)?.into()),
@@ -199,6 +297,10 @@ impl ParsedCssProperty {
             "bottom"            => Ok(parse_layout_bottom(value)?.into()),
 
             "padding"           => Ok(parse_layout_padding(value)?.into()),
+            "padding-inline-start" => Ok(parse_layout_padding_inline_start(value)?.into()),
+            "padding-inline-end"   => Ok(parse_layout_padding_inline_end(value)?.into()),
+
+            "direction"         => Ok(parse_layout_text_direction(value)?.into()),
 
             "flex-wrap"         => Ok(parse_layout_wrap(value)?.into()),
             "flex-direction"    => Ok(parse_layout_direction(value)?.into()),
@@ -227,6 +329,22 @@ impl ParsedCssProperty {
                 }.into())
             },
             "text-align"        => Ok(parse_layout_text_align(value)?.into()),
+            "vertical-align" | "align-text" => Ok(parse_layout_vertical_align(value)?.into()),
+            "text-wrap"         => Ok(parse_layout_text_wrap(value)?.into()),
+            "white-space"       => Ok(parse_layout_white_space(value)?.into()),
+            "word-break"        => Ok(parse_layout_word_break(value)?.into()),
+            "overflow-wrap" | "word-wrap" => Ok(parse_layout_overflow_wrap(value)?.into()),
+            "text-overflow"     => Ok(parse_layout_text_overflow_style(value)?.into()),
+            "line-clamp" | "-webkit-line-clamp" => Ok(parse_layout_line_clamp(value)?.into()),
+
+            "display"               => Ok(parse_layout_display(value)?.into()),
+            "grid-template-columns" => Ok(parse_layout_grid_template_columns(value)?.into()),
+            "grid-template-rows"    => Ok(parse_layout_grid_template_rows(value)?.into()),
+            "grid-template-areas"   => Ok(parse_layout_grid_template_areas(value)?.into()),
+            "gap" | "grid-gap"      => Ok(parse_layout_gap(value)?.into()),
+            "grid-column"           => Ok(parse_layout_grid_column(value)?.into()),
+            "grid-row"              => Ok(parse_layout_grid_row(value)?.into()),
+            "grid-area"             => Ok(parse_layout_grid_area(value)?.into()),
 
             _ => Err((key, value).into())
         }
@@ -284,6 +402,10 @@ pub enum CssParsingError<'a> {
     CssColorParseError(CssColorParseError<'a>),
     CssBorderRadiusParseError(CssBorderRadiusParseError<'a>),
     PaddingParseError(LayoutPaddingParseError<'a>),
+    GridParseError(GridParseError<'a>),
+    ObjectPositionParseError(ObjectPositionParseError<'a>),
+    AspectRatioParseError(AspectRatioParseError<'a>),
+    FontFeatureSettingsParseError(FontFeatureSettingsParseError<'a>),
     /// Key is not supported, i.e. `#div { aldfjasdflk: 400px }` results in an
     /// `UnsupportedCssKey("aldfjasdflk", "400px")` error
     UnsupportedCssKey(&'a str, &'a str),
@@ -299,6 +421,10 @@ impl_from!(CssFontFamilyParseError, CssParsingError::CssFontFamilyParseError);
 impl_from!(CssBackgroundParseError, CssParsingError::CssBackgroundParseError);
 impl_from!(CssBorderRadiusParseError, CssParsingError::CssBorderRadiusParseError);
 impl_from!(LayoutPaddingParseError, CssParsingError::PaddingParseError);
+impl_from!(GridParseError, CssParsingError::GridParseError);
+impl_from!(ObjectPositionParseError, CssParsingError::ObjectPositionParseError);
+impl_from!(AspectRatioParseError, CssParsingError::AspectRatioParseError);
+impl_from!(FontFeatureSettingsParseError, CssParsingError::FontFeatureSettingsParseError);
 
 impl<'a> From<(&'a str, &'a str)> for CssParsingError<'a> {
     fn from((a, b): (&'a str, &'a str)) -> Self {
@@ -573,7 +699,7 @@ fn parse_css_background_color<'a>(input: &'a str)
     parse_css_color(input).and_then(|ok| Ok(BackgroundColor(ok)))
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct TextColor(pub ColorU);
 
 fn parse_css_text_color<'a>(input: &'a str)
@@ -1669,6 +1795,40 @@ pub struct LayoutMinHeight(pub PixelValue);
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct LayoutMaxHeight(pub PixelValue);
 
+/// Keywords for `width` / `height` that size a rectangle based on its content
+/// (the text of a `Label` / `Text` or the dimensions of an `Image`)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum LayoutIntrinsicSize {
+    /// Sized by the content, but not larger than the parent (same as `fit-content`
+    /// for rectangles with content, otherwise sized by the parent)
+    Auto,
+    /// As small as possible, i.e. the width of the longest word
+    MinContent,
+    /// As large as the content wants to be, i.e. the text is not wrapped
+    MaxContent,
+    /// `max-content`, but limited to the size of the parent
+    FitContent,
+}
+
+impl Default for LayoutIntrinsicSize {
+    fn default() -> Self {
+        LayoutIntrinsicSize::Auto
+    }
+}
+
+multi_type_parser!(parse_layout_intrinsic_size, LayoutIntrinsicSize,
+                    ["auto", Auto],
+                    ["min-content", MinContent],
+                    ["max-content", MaxContent],
+                    ["fit-content", FitContent]);
+
+/// `padding-inline-start`: padding on the left side in `direction: ltr`, on the right side in `direction: rtl`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LayoutPaddingInlineStart(pub PixelValue);
+/// `padding-inline-end`: padding on the right side in `direction: ltr`, on the left side in `direction: rtl`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LayoutPaddingInlineEnd(pub PixelValue);
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct LayoutTop(pub PixelValue);
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -1681,6 +1841,31 @@ pub struct LayoutBottom(pub PixelValue);
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct LineHeight(pub PercentageValue);
 
+/// `letter-spacing` property: additional space after each grapheme cluster
+/// of a text (`normal` = `0px`, negative values move the characters closer together)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LetterSpacing(pub PixelValue);
+
+/// `tab-size` property: the distance between two tab stops,
+/// either in spaces (`tab-size: 4`) or as a length (`tab-size: 30px`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TabSize {
+    Spaces(usize),
+    Length(PixelValue),
+}
+
+impl Default for TabSize {
+    fn default() -> Self {
+        TabSize::Spaces(4)
+    }
+}
+
+/// `tab-stops` property: explicit positions of the tab stops (from the start of the line),
+/// i.e. `tab-stops: 100px 250px`. After the last explicit tab stop, the tab stops
+/// are `tab-size` apart. `tab-stops: none` is the default.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct TabStops(pub Vec<PixelValue>);
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LayoutDirection {
     Row,
@@ -1689,11 +1874,45 @@ pub enum LayoutDirection {
     ColumnReverse,
 }
 
+impl LayoutDirection {
+    /// Returns the physical direction of a flex container: in a right-to-left container,
+    /// `row` flows from the right to the left (like `row-reverse` does in a left-to-right one)
+    pub fn resolve(&self, text_direction: LayoutTextDirection) -> LayoutDirection {
+        use self::LayoutDirection::*;
+        match (self, text_direction) {
+            (Row, LayoutTextDirection::Rtl) => RowReverse,
+            (RowReverse, LayoutTextDirection::Rtl) => Row,
+            (direction, _) => *direction,
+        }
+    }
+}
+
+/// `direction` property: the direction in which text, `flex-direction: row`
+/// and `start / end` alignments flow. Inherited by the children of a node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LayoutTextDirection {
+    /// Left to right (default)
+    Ltr,
+    /// Right to left, i.e. for Arabic or Hebrew
+    Rtl,
+}
+
+impl Default for LayoutTextDirection {
+    fn default() -> Self {
+        LayoutTextDirection::Ltr
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LayoutPosition {
     Static,
     Relative,
     Absolute,
+    /// Positioned relative to the window, doesn't move when the content is scrolled
+    Fixed,
+    /// Positioned like `Relative`, but sticks to the edges of the
+    /// parent rectangle (via `top`, `left`, `right`, `bottom`) when scrolled
+    Sticky,
 }
 
 impl Default for LayoutPosition {
@@ -1734,6 +1953,12 @@ pub enum LayoutJustifyContent {
     SpaceAround,
 }
 
+impl Default for LayoutJustifyContent {
+    fn default() -> Self {
+        LayoutJustifyContent::Start
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LayoutAlignItems {
     /// Items are stretched to fit the container
@@ -1746,6 +1971,12 @@ pub enum LayoutAlignItems {
     End,
 }
 
+impl Default for LayoutAlignItems {
+    fn default() -> Self {
+        LayoutAlignItems::Stretch
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LayoutAlignContent {
     /// Default value. Lines stretch to take up the remaining space
@@ -1762,6 +1993,12 @@ pub enum LayoutAlignContent {
     SpaceAround,
 }
 
+impl Default for LayoutAlignContent {
+    fn default() -> Self {
+        LayoutAlignContent::Stretch
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TextOverflowBehaviour {
     NotModified,
@@ -1797,6 +2034,25 @@ pub enum TextAlignmentHorz {
     Left,
     Center,
     Right,
+    /// Left in `direction: ltr`, right in `direction: rtl`
+    Start,
+    /// Right in `direction: ltr`, left in `direction: rtl`
+    End,
+    /// Stretches the spaces so that every line except the last line of
+    /// a paragraph fills the whole width, implies `text-wrap: pretty`
+    Justify,
+}
+
+impl TextAlignmentHorz {
+    /// Resolves `Start` and `End` to `Left` or `Right`, depending on the text direction
+    pub fn resolve(&self, text_direction: LayoutTextDirection) -> TextAlignmentHorz {
+        use self::TextAlignmentHorz::*;
+        match (self, text_direction) {
+            (Start, LayoutTextDirection::Ltr) | (End, LayoutTextDirection::Rtl) => Left,
+            (Start, LayoutTextDirection::Rtl) | (End, LayoutTextDirection::Ltr) => Right,
+            (alignment, _) => *alignment,
+        }
+    }
 }
 
 impl Default for TextAlignmentHorz {
@@ -1805,6 +2061,7 @@ impl Default for TextAlignmentHorz {
     }
 }
 
+/// `vertical-align` property: vertical alignment of the lines of a text inside of its rectangle
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TextAlignmentVert {
     Top,
@@ -1812,6 +2069,137 @@ pub enum TextAlignmentVert {
     Bottom,
 }
 
+/// `text-wrap` property: how the lines of a text are broken
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextWrap {
+    /// Fill each line with as many words as possible (fast)
+    Wrap,
+    /// Break the lines so that the whole paragraph looks as even as possible (Knuth-Plass)
+    Pretty,
+}
+
+impl Default for TextWrap {
+    fn default() -> Self {
+        TextWrap::Wrap
+    }
+}
+
+/// `white-space` property: how spaces and newlines are handled and if the text wraps
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WhiteSpace {
+    /// Collapse spaces and newlines, wrap lines
+    Normal,
+    /// Collapse spaces and newlines, never wrap lines
+    NoWrap,
+    /// Preserve spaces, tabs and newlines, never wrap lines
+    Pre,
+    /// Preserve spaces, tabs and newlines, wrap lines
+    PreWrap,
+    /// Collapse spaces, preserve newlines, wrap lines
+    PreLine,
+}
+
+impl WhiteSpace {
+    pub fn collapses_spaces(&self) -> bool {
+        use self::WhiteSpace::*;
+        match self {
+            Normal | NoWrap | PreLine => true,
+            Pre | PreWrap => false,
+        }
+    }
+
+    pub fn preserves_newlines(&self) -> bool {
+        use self::WhiteSpace::*;
+        match self {
+            Normal | NoWrap => false,
+            Pre | PreWrap | PreLine => true,
+        }
+    }
+
+    pub fn wraps(&self) -> bool {
+        use self::WhiteSpace::*;
+        match self {
+            Normal | PreWrap | PreLine => true,
+            NoWrap | Pre => false,
+        }
+    }
+}
+
+/// Unlike in browsers, the default is `pre-line`, since newlines in
+/// texts (i.e. labels) have always started a new line in azul
+impl Default for WhiteSpace {
+    fn default() -> Self {
+        WhiteSpace::PreLine
+    }
+}
+
+/// `word-break` property: where lines may be broken inside of words
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WordBreak {
+    /// Break at the line break opportunities of the Unicode line breaking algorithm (UAX #14)
+    Normal,
+    /// Lines may be broken between any two characters (grapheme clusters)
+    BreakAll,
+    /// Only break at spaces, i.e. not between CJK characters
+    KeepAll,
+}
+
+impl Default for WordBreak {
+    fn default() -> Self {
+        WordBreak::Normal
+    }
+}
+
+/// `overflow-wrap` (or `word-wrap`) property: whether words that are too long
+/// for a line may be broken at arbitrary points
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OverflowWrap {
+    /// Long words overflow the line
+    Normal,
+    /// Long words are broken, the breaks are considered for the min-content width
+    Anywhere,
+    /// Long words are broken, but the min-content width is the width of the longest word
+    BreakWord,
+}
+
+impl Default for OverflowWrap {
+    fn default() -> Self {
+        OverflowWrap::Normal
+    }
+}
+
+/// `text-overflow` property: how a line that overflows its rectangle is signaled to the user
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextOverflowStyle {
+    /// The text is clipped at the edge of the rectangle
+    Clip,
+    /// The truncated text ends in an ellipsis ("…")
+    Ellipsis,
+    /// The truncated text ends in a custom string, i.e. `text-overflow: " [more]"`
+    String(String),
+}
+
+impl Default for TextOverflowStyle {
+    fn default() -> Self {
+        TextOverflowStyle::Clip
+    }
+}
+
+/// `line-clamp` (or `-webkit-line-clamp`) property: limits the text to a maximum
+/// number of lines, the last visible line ends in the `text-overflow` marker
+/// (or an ellipsis if `text-overflow` is `clip`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LineClamp {
+    None,
+    Lines(usize),
+}
+
+impl Default for LineClamp {
+    fn default() -> Self {
+        LineClamp::None
+    }
+}
+
 impl Default for TextAlignmentVert {
     fn default() -> Self {
         TextAlignmentVert::Center
@@ -1840,14 +2228,50 @@ pub(crate) struct RectStyle {
     pub(crate) font_color: Option<TextColor>,
     /// Text alignment
     pub(crate) text_align: Option<TextAlignmentHorz>,
+    /// Vertical text alignment, overrides the alignment from `align-items`
+    pub(crate) vertical_align: Option<TextAlignmentVert>,
+    /// `text-wrap` property, i.e. which line breaking algorithm should be used
+    pub(crate) text_wrap: Option<TextWrap>,
+    /// `white-space` property
+    pub(crate) white_space: Option<WhiteSpace>,
+    /// `word-break` property
+    pub(crate) word_break: Option<WordBreak>,
+    /// `overflow-wrap` property
+    pub(crate) overflow_wrap: Option<OverflowWrap>,
+    /// `text-overflow` property
+    pub(crate) text_overflow: Option<TextOverflowStyle>,
+    /// `line-clamp` property
+    pub(crate) line_clamp: Option<LineClamp>,
     /// Text overflow behaviour
     pub(crate) overflow: Option<LayoutOverflow>,
     /// `line-height` property
     pub(crate) line_height: Option<LineHeight>,
+    /// `letter-spacing` property
+    pub(crate) letter_spacing: Option<LetterSpacing>,
+    /// `tab-size` property
+    pub(crate) tab_size: Option<TabSize>,
+    /// `tab-stops` property
+    pub(crate) tab_stops: Option<TabStops>,
+    /// `font-kerning` property
+    pub(crate) font_kerning: Option<FontKerning>,
+    /// `font-feature-settings` property, i.e. which OpenType features to use when shaping text
+    pub(crate) font_feature_settings: Option<FontFeatureSettings>,
+    /// `font-smoothing` property, overrides the antialiasing of the window
+    pub(crate) font_smoothing: Option<FontSmoothing>,
+    /// `font-hinting` property, overrides the hinting of the window
+    pub(crate) font_hinting: Option<FontHinting>,
+    /// `font-subpixel-positioning` property, overrides the subpixel positioning of the window
+    pub(crate) font_subpixel_positioning: Option<FontSubpixelPositioning>,
+    /// `font-lcd-filter` property, overrides the LCD filter of the window
+    pub(crate) font_lcd_filter: Option<FontLcdFilter>,
+    /// How the content of an image / texture is fitted into the rectangle
+    pub(crate) object_fit: Option<LayoutObjectFit>,
+    /// Alignment of the content of an image / texture inside the rectangle
+    pub(crate) object_position: Option<LayoutObjectPosition>,
 }
 
 // Layout constraints for a given rectangle, such as "width", "min-width", "height", etc.
-#[derive(Default, Debug, Copy, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct RectLayout {
 
     pub width: Option<LayoutWidth>,
@@ -1856,6 +2280,12 @@ pub struct RectLayout {
     pub min_height: Option<LayoutMinHeight>,
     pub max_width: Option<LayoutMaxWidth>,
     pub max_height: Option<LayoutMaxHeight>,
+    /// `width: auto / min-content / ...`, only used if `width` is not set
+    pub intrinsic_width: Option<LayoutIntrinsicSize>,
+    /// `height: auto / min-content / ...`, only used if `height` is not set
+    pub intrinsic_height: Option<LayoutIntrinsicSize>,
+    /// `width / height` ratio, only used if `width` or `height` is not set
+    pub aspect_ratio: Option<LayoutAspectRatio>,
 
     pub direction: Option<LayoutDirection>,
     pub wrap: Option<LayoutWrap>,
@@ -1870,6 +2300,57 @@ pub struct RectLayout {
     pub left: Option<LayoutLeft>,
 
     pub padding: Option<LayoutPadding>,
+    /// Logical padding, already resolved into `padding` (see `RectLayout::resolve_logical_padding`)
+    pub padding_inline_start: Option<LayoutPaddingInlineStart>,
+    pub padding_inline_end: Option<LayoutPaddingInlineEnd>,
+
+    /// `direction: ltr / rtl`
+    pub text_direction: Option<LayoutTextDirection>,
+
+    pub display: Option<LayoutDisplay>,
+    pub grid_template_columns: Option<LayoutGridTemplateColumns>,
+    pub grid_template_rows: Option<LayoutGridTemplateRows>,
+    pub grid_template_areas: Option<LayoutGridTemplateAreas>,
+    pub gap: Option<LayoutGap>,
+    pub grid_column: Option<LayoutGridColumn>,
+    pub grid_row: Option<LayoutGridRow>,
+    pub grid_area: Option<LayoutGridArea>,
+}
+
+impl RectLayout {
+
+    /// Direction of the text and the `row` layouts of this rectangle (`ltr` by default)
+    pub fn get_text_direction(&self) -> LayoutTextDirection {
+        self.text_direction.unwrap_or_default()
+    }
+
+    /// Physical flex direction, taking `direction: rtl` into account
+    pub fn get_flex_direction(&self) -> LayoutDirection {
+        self.direction.unwrap_or_default().resolve(self.get_text_direction())
+    }
+
+    /// Applies `padding-inline-start / end` to the left or right side of the `padding`,
+    /// depending on the text direction. Has to be called after all properties are set.
+    ///
+    /// NOTE: There is no `margin-inline-start / end`, since the layout doesn't support
+    /// margins at all yet - once `margin` is added, its logical sides should be resolved here.
+    pub fn resolve_logical_padding(&mut self) {
+        if self.padding_inline_start.is_none() && self.padding_inline_end.is_none() {
+            return;
+        }
+
+        let is_rtl = self.get_text_direction() == LayoutTextDirection::Rtl;
+        let mut padding = self.padding.unwrap_or(LayoutPadding { top: None, bottom: None, left: None, right: None });
+
+        if let Some(LayoutPaddingInlineStart(start)) = self.padding_inline_start {
+            if is_rtl { padding.right = Some(start); } else { padding.left = Some(start); }
+        }
+        if let Some(LayoutPaddingInlineEnd(end)) = self.padding_inline_end {
+            if is_rtl { padding.left = Some(end); } else { padding.right = Some(end); }
+        }
+
+        self.padding = Some(padding);
+    }
 }
 
 typed_pixel_value_parser!(parse_layout_width, LayoutWidth);
@@ -1884,12 +2365,63 @@ typed_pixel_value_parser!(parse_layout_bottom, LayoutBottom);
 typed_pixel_value_parser!(parse_layout_right, LayoutRight);
 typed_pixel_value_parser!(parse_layout_left, LayoutLeft);
 
+typed_pixel_value_parser!(parse_layout_padding_inline_start, LayoutPaddingInlineStart);
+typed_pixel_value_parser!(parse_layout_padding_inline_end, LayoutPaddingInlineEnd);
+
 fn parse_line_height(input: &str)
 -> Result<LineHeight, PercentageParseError>
 {
     parse_percentage_value(input).and_then(|e| Ok(LineHeight(e)))
 }
 
+fn parse_letter_spacing<'a>(input: &'a str)
+-> Result<LetterSpacing, PixelParseError<'a>>
+{
+    match input.trim() {
+        "normal" => Ok(LetterSpacing(PixelValue::from_metric(CssMetric::Px, 0.0))),
+        other => parse_pixel_value(other).and_then(|e| Ok(LetterSpacing(e))),
+    }
+}
+
+fn parse_layout_tab_size<'a>(input: &'a str)
+-> Result<TabSize, InvalidValueErr<'a>>
+{
+    let input = input.trim();
+    if let Ok(spaces) = input.parse::<usize>() {
+        return Ok(TabSize::Spaces(spaces));
+    }
+    match parse_pixel_value(input) {
+        Ok(length) if length.to_pixels() >= 0.0 => Ok(TabSize::Length(length)),
+        _ => Err(InvalidValueErr(input)),
+    }
+}
+
+// Parses `none` or a list of ascending lengths, i.e. `100px 250px 400px`
+fn parse_layout_tab_stops<'a>(input: &'a str)
+-> Result<TabStops, InvalidValueErr<'a>>
+{
+    let input = input.trim();
+    if input == "none" {
+        return Ok(TabStops(Vec::new()));
+    }
+
+    let mut tab_stops = Vec::<PixelValue>::new();
+    for stop in input.split_whitespace() {
+        let stop = parse_pixel_value(stop).map_err(|_| InvalidValueErr(input))?;
+        let is_ascending = tab_stops.last().map(|last| stop.to_pixels() > last.to_pixels()).unwrap_or(stop.to_pixels() >= 0.0);
+        if !is_ascending {
+            return Err(InvalidValueErr(input));
+        }
+        tab_stops.push(stop);
+    }
+
+    if tab_stops.is_empty() {
+        Err(InvalidValueErr(input))
+    } else {
+        Ok(TabStops(tab_stops))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
 pub struct FontSize(pub(crate) PixelValue);
 
@@ -1916,12 +2448,21 @@ impl FontSize {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct FontFamily {
     // parsed fonts, in order, i.e. "Webly Sleeky UI", "monospace", etc.
     pub(crate) fonts: Vec<FontId>
 }
 
+impl FontFamily {
+    /// Creates a font family from the fonts, in order of preference
+    pub fn new(fonts: Vec<FontId>) -> Self {
+        Self {
+            fonts: fonts,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum FontId {
     BuiltinFont(String),
@@ -1970,6 +2511,149 @@ pub(crate) fn parse_css_font_family<'a>(input: &'a str) -> Result<FontFamily, Cs
     })
 }
 
+/// `font-kerning`: whether the kerning information of the font should be used
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontKerning {
+    /// Kerning is applied (same as `Normal`)
+    Auto,
+    Normal,
+    None,
+}
+
+impl Default for FontKerning {
+    fn default() -> Self {
+        FontKerning::Auto
+    }
+}
+
+multi_type_parser!(parse_css_font_kerning, FontKerning,
+                    ["auto", Auto],
+                    ["normal", Normal],
+                    ["none", None]);
+
+/// `font-smoothing` (or `-webkit-font-smoothing`): how the glyphs are antialiased
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontSmoothing {
+    /// No antialiasing, the glyphs are rendered in a single color
+    None,
+    /// Grayscale antialiasing (`antialiased`)
+    Grayscale,
+    /// Subpixel (LCD) antialiasing (`subpixel-antialiased`)
+    Subpixel,
+}
+
+multi_type_parser!(parse_css_font_smoothing, FontSmoothing,
+                    ["none", None],
+                    ["antialiased", Grayscale],
+                    ["grayscale", Grayscale],
+                    ["subpixel-antialiased", Subpixel],
+                    ["subpixel", Subpixel]);
+
+/// `font-hinting`: how strongly the outlines of the glyphs are fitted to the pixel grid.
+/// Only used by FreeType (Linux), `full` uses the strongest hinting for the antialiasing mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontHinting {
+    None,
+    Light,
+    Normal,
+    Full,
+}
+
+multi_type_parser!(parse_css_font_hinting, FontHinting,
+                    ["none", None],
+                    ["light", Light],
+                    ["normal", Normal],
+                    ["full", Full]);
+
+/// `font-subpixel-positioning`: whether glyphs are positioned at fractional pixel offsets
+/// (`auto`) or snapped to whole pixels (`none`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontSubpixelPositioning {
+    Auto,
+    None,
+}
+
+multi_type_parser!(parse_css_font_subpixel_positioning, FontSubpixelPositioning,
+                    ["auto", Auto],
+                    ["none", None]);
+
+/// `font-lcd-filter`: the filter that reduces the color fringes of subpixel antialiased text.
+/// Only used by FreeType (Linux).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontLcdFilter {
+    None,
+    Default,
+    Light,
+    Legacy,
+}
+
+multi_type_parser!(parse_css_font_lcd_filter, FontLcdFilter,
+                    ["none", None],
+                    ["default", Default],
+                    ["light", Light],
+                    ["legacy", Legacy]);
+
+/// A single OpenType feature, i.e. `"liga" 0` or `"smcp"`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FontFeature {
+    /// Four-letter OpenType feature tag, i.e. `liga`
+    pub tag: [u8; 4],
+    /// 0 disables the feature, 1 enables it, higher values select alternates
+    pub value: u32,
+}
+
+/// `font-feature-settings`: enables / disables OpenType features, `normal` is an empty list
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct FontFeatureSettings(pub Vec<FontFeature>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontFeatureSettingsParseError<'a> {
+    /// The tag has to consist of four ASCII characters in quotes
+    InvalidTag(&'a str),
+    /// The value has to be a positive integer, `on` or `off`
+    InvalidValue(&'a str),
+}
+
+impl<'a> From<UnclosedQuotesError<'a>> for FontFeatureSettingsParseError<'a> {
+    fn from(err: UnclosedQuotesError<'a>) -> Self {
+        FontFeatureSettingsParseError::InvalidTag(err.0)
+    }
+}
+
+/// Parses `normal` or a comma-separated list of features, i.e. `"liga" 0, "kern", "ss01" on`
+fn parse_css_font_feature_settings<'a>(input: &'a str)
+-> Result<FontFeatureSettings, FontFeatureSettingsParseError<'a>>
+{
+    if input == "normal" {
+        return Ok(FontFeatureSettings::default());
+    }
+
+    let mut features = Vec::new();
+
+    for feature in input.split(',') {
+        let feature = feature.trim();
+        let mut tag_iter = feature.splitn(2, |c: char| c.is_whitespace());
+        let tag = strip_quotes(tag_iter.next().unwrap_or(""))?.0;
+        let tag_bytes = tag.as_bytes();
+        if tag_bytes.len() != 4 || !tag_bytes.iter().all(|b| *b >= 0x20 && *b <= 0x7E) {
+            return Err(FontFeatureSettingsParseError::InvalidTag(tag));
+        }
+
+        let value = match tag_iter.next().and_then(|v| Some(v.trim())) {
+            None | Some("on") => 1,
+            Some("off") => 0,
+            Some(v) => v.parse::<u32>().map_err(|_| FontFeatureSettingsParseError::InvalidValue(v))?,
+        };
+
+        features.push(FontFeature {
+            tag: [tag_bytes[0], tag_bytes[1], tag_bytes[2], tag_bytes[3]],
+            value: value,
+        });
+    }
+
+    Ok(FontFeatureSettings(features))
+}
+
 multi_type_parser!(parse_layout_direction, LayoutDirection,
                     ["row", Row],
                     ["row-reverse", RowReverse],
@@ -2008,7 +2692,9 @@ multi_type_parser!(parse_shape, Shape,
 multi_type_parser!(parse_layout_position, LayoutPosition,
                     ["static", Static],
                     ["absolute", Absolute],
-                    ["relative", Relative]);
+                    ["relative", Relative],
+                    ["fixed", Fixed],
+                    ["sticky", Sticky]);
 
 multi_type_parser!(parse_layout_text_overflow, TextOverflowBehaviourInner,
                     ["auto", Auto],
@@ -2019,58 +2705,739 @@ multi_type_parser!(parse_layout_text_overflow, TextOverflowBehaviourInner,
 multi_type_parser!(parse_layout_text_align, TextAlignmentHorz,
                     ["center", Center],
                     ["left", Left],
-                    ["right", Right]);
+                    ["right", Right],
+                    ["start", Start],
+                    ["end", End],
+                    ["justify", Justify]);
+
+multi_type_parser!(parse_layout_vertical_align, TextAlignmentVert,
+                    ["top", Top],
+                    ["middle", Center],
+                    ["center", Center],
+                    ["bottom", Bottom]);
 
-/// CssColor is simply a wrapper around the internal CSS color parsing methods.
-///
-/// Sometimes you'd want to load and parse a CSS color, but you don't want to
-/// write your own parser for that. Since azul already has a parser for CSS colors,
-/// this API exposes
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub struct CssColor {
-    internal: ColorU,
+multi_type_parser!(parse_layout_text_wrap, TextWrap,
+                    ["wrap", Wrap],
+                    ["pretty", Pretty]);
+
+multi_type_parser!(parse_layout_white_space, WhiteSpace,
+                    ["normal", Normal],
+                    ["nowrap", NoWrap],
+                    ["pre", Pre],
+                    ["pre-wrap", PreWrap],
+                    ["pre-line", PreLine]);
+
+multi_type_parser!(parse_layout_word_break, WordBreak,
+                    ["normal", Normal],
+                    ["break-all", BreakAll],
+                    ["keep-all", KeepAll]);
+
+multi_type_parser!(parse_layout_overflow_wrap, OverflowWrap,
+                    ["normal", Normal],
+                    ["anywhere", Anywhere],
+                    ["break-word", BreakWord]);
+
+fn parse_layout_text_overflow_style<'a>(input: &'a str)
+-> Result<TextOverflowStyle, InvalidValueErr<'a>>
+{
+    let input = input.trim();
+    match input {
+        "clip" => Ok(TextOverflowStyle::Clip),
+        "ellipsis" => Ok(TextOverflowStyle::Ellipsis),
+        _ => strip_quotes(input)
+            .map_err(|_| InvalidValueErr(input))
+            .and_then(|s| Ok(TextOverflowStyle::String(s.0.to_string()))),
+    }
 }
 
-impl CssColor {
-    /// Can parse a CSS color with or without prefixed hash or capitalization, i.e. `#aabbcc`
-    pub fn from_str<'a>(input: &'a str) -> Result<Self, CssColorParseError<'a>> {
-        let color = parse_css_color(input)?;
-        Ok(Self {
-            internal: color,
-        })
+fn parse_layout_line_clamp<'a>(input: &'a str)
+-> Result<LineClamp, InvalidValueErr<'a>>
+{
+    let input = input.trim();
+    if input == "none" {
+        return Ok(LineClamp::None);
     }
-
-    /// Returns the internal parsed color, but in a `0.0 - 1.0` range instead of `0 - 255`
-    pub fn to_color_f(&self) -> ColorF {
-        self.internal.into()
+    match input.parse::<usize>() {
+        Ok(lines) if lines > 0 => Ok(LineClamp::Lines(lines)),
+        _ => Err(InvalidValueErr(input)),
     }
+}
 
-    /// Returns the internal parsed color
-    pub fn to_color_u(&self) -> ColorU {
-        self.internal
-    }
+multi_type_parser!(parse_layout_text_direction, LayoutTextDirection,
+                    ["ltr", Ltr],
+                    ["rtl", Rtl]);
 
-    /// If `prefix_hash` is set to false, you only get the string, without a hash, in lowercase
-    ///
-    /// If `self.alpha` is `FF`, it wil be omitted from the final result (since `FF` is the default for CSS colors)
-    pub fn to_string(&self, prefix_hash: bool) -> String {
-        let prefix = if prefix_hash { "#" } else { "" };
-        let alpha = if self.internal.a == 255 { String::new() } else { format!("{:02x}", self.internal.a) };
-        format!("{}{:02x}{:02x}{:02x}{}", prefix, self.internal.r, self.internal.g, self.internal.b, alpha)
-    }
+/// `display` property - decides how the children of a rectangle are laid out
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LayoutDisplay {
+    /// Children are laid out according to the `flex-*` properties (default)
+    Flex,
+    /// Children are laid out in the rows / columns given by `grid-template-*`
+    Grid,
 }
 
-impl Into<ColorF> for CssColor {
-    fn into(self) -> ColorF {
-        self.to_color_f()
+impl Default for LayoutDisplay {
+    fn default() -> Self {
+        LayoutDisplay::Flex
     }
 }
 
-impl Into<ColorU> for CssColor {
-    fn into(self) -> ColorU {
-        self.to_color_u()
-    }
-}
+multi_type_parser!(parse_layout_display, LayoutDisplay,
+                    ["flex", Flex],
+                    ["grid", Grid]);
+
+/// Size of a single track (a row or a column) of a grid, i.e. `100px`, `1fr` or `auto`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GridTrackBreadth {
+    /// Fixed size, i.e. `100px`
+    Fixed(PixelValue),
+    /// Fraction of the remaining space, i.e. `1fr`
+    Fraction(f32),
+    /// Size is determined by the grid container
+    Auto,
+}
+
+/// One entry of `grid-template-columns` or `grid-template-rows`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GridTrackSize {
+    Breadth(GridTrackBreadth),
+    /// `minmax(min, max)`, the minimum can't be a fraction
+    MinMax(GridTrackBreadth, GridTrackBreadth),
+}
+
+impl Default for GridTrackSize {
+    fn default() -> Self {
+        GridTrackSize::Breadth(GridTrackBreadth::Auto)
+    }
+}
+
+/// `grid-template-columns`, `repeat()` is already expanded
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LayoutGridTemplateColumns(pub Vec<GridTrackSize>);
+
+/// `grid-template-rows`, `repeat()` is already expanded
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LayoutGridTemplateRows(pub Vec<GridTrackSize>);
+
+/// `grid-template-areas`, i.e. `"header header" "sidebar main"`.
+///
+/// Empty cells (`.`) are stored as `None`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LayoutGridTemplateAreas {
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+impl LayoutGridTemplateAreas {
+
+    /// Number of columns in the area template
+    pub fn num_columns(&self) -> usize {
+        self.rows.first().and_then(|row| Some(row.len())).unwrap_or(0)
+    }
+
+    /// Number of rows in the area template
+    pub fn num_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns the `(column_start, column_end, row_start, row_end)` of the named area,
+    /// zero-based, the end is exclusive
+    pub fn get_area(&self, name: &str) -> Option<(usize, usize, usize, usize)> {
+        let mut area: Option<(usize, usize, usize, usize)> = None;
+        for (row_idx, row) in self.rows.iter().enumerate() {
+            for (column_idx, cell) in row.iter().enumerate() {
+                if cell.as_ref().and_then(|c| Some(c.as_str())) != Some(name) {
+                    continue;
+                }
+                area = Some(match area {
+                    None => (column_idx, column_idx + 1, row_idx, row_idx + 1),
+                    Some((c_start, c_end, r_start, r_end)) => (
+                        c_start.min(column_idx), c_end.max(column_idx + 1),
+                        r_start.min(row_idx), r_end.max(row_idx + 1),
+                    ),
+                });
+            }
+        }
+        area
+    }
+}
+
+/// `gap` / `grid-gap` - space between the rows and columns of a grid
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LayoutGap {
+    pub row: PixelValue,
+    pub column: PixelValue,
+}
+
+/// Start or end of a `grid-column` / `grid-row` placement
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GridLinePosition {
+    Auto,
+    /// Line number, starting at 1 - negative numbers count from the last line
+    Line(isize),
+    /// `span 2` - the item spans the given number of tracks
+    Span(usize),
+}
+
+impl Default for GridLinePosition {
+    fn default() -> Self {
+        GridLinePosition::Auto
+    }
+}
+
+/// `grid-column` / `grid-row` value, i.e. `1 / 3`, `2 / span 2` or `span 2`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct GridLine {
+    pub start: GridLinePosition,
+    pub end: GridLinePosition,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LayoutGridColumn(pub GridLine);
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LayoutGridRow(pub GridLine);
+
+/// `grid-area: header` - places the item in the named area of the `grid-template-areas`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutGridArea(pub String);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridParseError<'a> {
+    /// Not a valid track size, such as `1fr`, `100px`, `auto` or `minmax(100px, 1fr)`
+    InvalidTrackSize(&'a str),
+    /// Error in a `repeat(count, tracks)` statement
+    InvalidRepeat(&'a str),
+    /// The track list expands to more than `MAX_GRID_TRACKS` tracks
+    TooManyTracks(&'a str),
+    /// Opening and closing braces don't match up
+    UnclosedBraces(&'a str),
+    /// Invalid `grid-column` / `grid-row` line
+    InvalidLine(&'a str),
+    /// Rows in `grid-template-areas` must be quoted and have the same number of columns
+    InvalidAreas(&'a str),
+    /// Too many or too few values for `gap`
+    InvalidGap(&'a str),
+    PixelParseError(PixelParseError<'a>),
+}
+
+impl_from!(PixelParseError, GridParseError::PixelParseError);
+
+/// Splits the input at whitespace, but not inside of braces:
+///
+/// "100px repeat(2, 1fr) minmax(10px, auto)" => ["100px", "repeat(2, 1fr)", "minmax(10px, auto)"]
+fn split_grid_tracks<'a>(input: &'a str)
+-> Result<Vec<&'a str>, GridParseError<'a>>
+{
+    let mut tracks = Vec::new();
+    let mut depth = 0;
+    let mut track_start = None;
+
+    for (idx, ch) in input.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => {
+                if depth == 0 {
+                    return Err(GridParseError::UnclosedBraces(input));
+                }
+                depth -= 1;
+            },
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(start) = track_start.take() {
+                    tracks.push(&input[start..idx]);
+                }
+                continue;
+            },
+            _ => { },
+        }
+        if track_start.is_none() {
+            track_start = Some(idx);
+        }
+    }
+
+    if depth != 0 {
+        return Err(GridParseError::UnclosedBraces(input));
+    }
+
+    if let Some(start) = track_start {
+        tracks.push(&input[start..]);
+    }
+
+    Ok(tracks)
+}
+
+/// Parses "100px", "1fr" or "auto"
+fn parse_grid_track_breadth<'a>(input: &'a str)
+-> Result<GridTrackBreadth, GridParseError<'a>>
+{
+    let input = input.trim();
+    if input == "auto" {
+        Ok(GridTrackBreadth::Auto)
+    } else if input.ends_with("fr") {
+        let fraction = input[..input.len() - 2].parse::<f32>().map_err(|_| GridParseError::InvalidTrackSize(input))?;
+        if fraction < 0.0 {
+            return Err(GridParseError::InvalidTrackSize(input));
+        }
+        Ok(GridTrackBreadth::Fraction(fraction))
+    } else {
+        Ok(GridTrackBreadth::Fixed(parse_pixel_value(input)?))
+    }
+}
+
+/// Parses "100px", "1fr", "auto" or "minmax(100px, 1fr)"
+fn parse_grid_track_size<'a>(input: &'a str)
+-> Result<GridTrackSize, GridParseError<'a>>
+{
+    if !input.starts_with("minmax(") {
+        return Ok(GridTrackSize::Breadth(parse_grid_track_breadth(input)?));
+    }
+
+    if !input.ends_with(')') {
+        return Err(GridParseError::UnclosedBraces(input));
+    }
+
+    let brace_contents = &input["minmax(".len()..input.len() - 1];
+    let mut brace_iter = brace_contents.split(',');
+
+    let min = parse_grid_track_breadth(brace_iter.next().ok_or(GridParseError::InvalidTrackSize(input))?)?;
+    let max = parse_grid_track_breadth(brace_iter.next().ok_or(GridParseError::InvalidTrackSize(input))?)?;
+
+    if brace_iter.next().is_some() {
+        return Err(GridParseError::InvalidTrackSize(input));
+    }
+
+    if let GridTrackBreadth::Fraction(_) = min {
+        return Err(GridParseError::InvalidTrackSize(input));
+    }
+
+    Ok(GridTrackSize::MinMax(min, max))
+}
+
+/// Upper limit for the number of tracks in a track list (after expanding `repeat()`),
+/// same as in browsers. Otherwise `repeat(4000000000, 1fr)` would allocate without limit.
+const MAX_GRID_TRACKS: usize = 10_000;
+
+/// Parses a track list such as "100px repeat(3, 1fr) minmax(50px, auto)"
+fn parse_grid_track_list<'a>(input: &'a str)
+-> Result<Vec<GridTrackSize>, GridParseError<'a>>
+{
+    let input = input.trim();
+    if input == "none" {
+        return Ok(Vec::new());
+    }
+
+    let mut track_list = Vec::new();
+
+    for track in split_grid_tracks(input)? {
+
+        if !track.starts_with("repeat(") {
+            if track_list.len() >= MAX_GRID_TRACKS {
+                return Err(GridParseError::TooManyTracks(input));
+            }
+            track_list.push(parse_grid_track_size(track)?);
+            continue;
+        }
+
+        // "repeat(3, 1fr 100px)"
+        if !track.ends_with(')') {
+            return Err(GridParseError::UnclosedBraces(track));
+        }
+
+        let brace_contents = &track["repeat(".len()..track.len() - 1];
+        let mut brace_iter = brace_contents.splitn(2, ',');
+
+        let count = brace_iter.next()
+            .and_then(|count| count.trim().parse::<usize>().ok())
+            .ok_or(GridParseError::InvalidRepeat(track))?;
+        let repeated_tracks = brace_iter.next().ok_or(GridParseError::InvalidRepeat(track))?.trim();
+
+        if repeated_tracks.starts_with("repeat(") {
+            return Err(GridParseError::InvalidRepeat(track));
+        }
+
+        let repeated_tracks = split_grid_tracks(repeated_tracks)?
+            .into_iter()
+            .map(|t| parse_grid_track_size(t))
+            .collect::<Result<Vec<GridTrackSize>, GridParseError>>()?;
+
+        let num_tracks = count.checked_mul(repeated_tracks.len())
+            .and_then(|n| n.checked_add(track_list.len()));
+        match num_tracks {
+            Some(n) if n <= MAX_GRID_TRACKS => { },
+            _ => return Err(GridParseError::TooManyTracks(input)),
+        }
+
+        for _ in 0..count {
+            track_list.extend(repeated_tracks.iter().cloned());
+        }
+    }
+
+    Ok(track_list)
+}
+
+fn parse_layout_grid_template_columns<'a>(input: &'a str)
+-> Result<LayoutGridTemplateColumns, GridParseError<'a>>
+{
+    parse_grid_track_list(input).and_then(|e| Ok(LayoutGridTemplateColumns(e)))
+}
+
+fn parse_layout_grid_template_rows<'a>(input: &'a str)
+-> Result<LayoutGridTemplateRows, GridParseError<'a>>
+{
+    parse_grid_track_list(input).and_then(|e| Ok(LayoutGridTemplateRows(e)))
+}
+
+/// Parses `"header header" "sidebar main"`
+fn parse_layout_grid_template_areas<'a>(input: &'a str)
+-> Result<LayoutGridTemplateAreas, GridParseError<'a>>
+{
+    let input = input.trim();
+    if input == "none" {
+        return Ok(LayoutGridTemplateAreas::default());
+    }
+
+    let mut rows = Vec::new();
+    let mut quote_iter = input.split('"');
+
+    // Everything outside of the quotes has to be whitespace
+    if quote_iter.next().map(|s| !s.trim().is_empty()).unwrap_or(false) {
+        return Err(GridParseError::InvalidAreas(input));
+    }
+
+    loop {
+        let row = match quote_iter.next() {
+            Some(r) => r,
+            None => break,
+        };
+
+        match quote_iter.next() {
+            Some(between) => if !between.trim().is_empty() {
+                return Err(GridParseError::InvalidAreas(input));
+            },
+            None => return Err(GridParseError::InvalidAreas(input)),
+        }
+
+        let row = row.split_whitespace().map(|cell| {
+            if cell.chars().all(|c| c == '.') { None } else { Some(cell.to_string()) }
+        }).collect::<Vec<Option<String>>>();
+
+        if row.is_empty() {
+            return Err(GridParseError::InvalidAreas(input));
+        }
+
+        rows.push(row);
+    }
+
+    let num_columns = rows.first().and_then(|r| Some(r.len())).unwrap_or(0);
+    if rows.is_empty() || rows.iter().any(|row| row.len() != num_columns) {
+        return Err(GridParseError::InvalidAreas(input));
+    }
+
+    Ok(LayoutGridTemplateAreas { rows })
+}
+
+/// Parses "10px" (row and column gap) or "10px 20px" (row gap, column gap)
+fn parse_layout_gap<'a>(input: &'a str)
+-> Result<LayoutGap, GridParseError<'a>>
+{
+    let mut input_iter = input.split_whitespace();
+    let row = parse_pixel_value(input_iter.next().ok_or(GridParseError::InvalidGap(input))?)?;
+    let column = match input_iter.next() {
+        Some(c) => parse_pixel_value(c)?,
+        None => row,
+    };
+
+    if input_iter.next().is_some() {
+        return Err(GridParseError::InvalidGap(input));
+    }
+
+    Ok(LayoutGap { row, column })
+}
+
+/// Parses "auto", "2", "-1" or "span 2"
+fn parse_grid_line_position<'a>(input: &'a str)
+-> Result<GridLinePosition, GridParseError<'a>>
+{
+    let input = input.trim();
+    let mut input_iter = input.split_whitespace();
+
+    match (input_iter.next(), input_iter.next(), input_iter.next()) {
+        (Some("auto"), None, None) => Ok(GridLinePosition::Auto),
+        (Some("span"), Some(count), None) => {
+            match count.parse::<usize>() {
+                Ok(c) if c > 0 => Ok(GridLinePosition::Span(c)),
+                _ => Err(GridParseError::InvalidLine(input)),
+            }
+        },
+        (Some(line), None, None) => {
+            match line.parse::<isize>() {
+                Ok(l) if l != 0 => Ok(GridLinePosition::Line(l)),
+                _ => Err(GridParseError::InvalidLine(input)),
+            }
+        },
+        _ => Err(GridParseError::InvalidLine(input)),
+    }
+}
+
+/// Parses "1", "1 / 3", "1 / span 2" or "span 2"
+fn parse_grid_line<'a>(input: &'a str)
+-> Result<GridLine, GridParseError<'a>>
+{
+    let mut input_iter = input.split('/');
+    let start = parse_grid_line_position(input_iter.next().ok_or(GridParseError::InvalidLine(input))?)?;
+    let end = match input_iter.next() {
+        Some(e) => parse_grid_line_position(e)?,
+        None => GridLinePosition::Auto,
+    };
+
+    if input_iter.next().is_some() {
+        return Err(GridParseError::InvalidLine(input));
+    }
+
+    Ok(GridLine { start, end })
+}
+
+fn parse_layout_grid_column<'a>(input: &'a str)
+-> Result<LayoutGridColumn, GridParseError<'a>>
+{
+    parse_grid_line(input).and_then(|e| Ok(LayoutGridColumn(e)))
+}
+
+fn parse_layout_grid_row<'a>(input: &'a str)
+-> Result<LayoutGridRow, GridParseError<'a>>
+{
+    parse_grid_line(input).and_then(|e| Ok(LayoutGridRow(e)))
+}
+
+fn parse_layout_grid_area<'a>(input: &'a str)
+-> Result<LayoutGridArea, InvalidValueErr<'a>>
+{
+    let input = input.trim();
+    if input.is_empty() || input.contains(char::is_whitespace) {
+        Err(InvalidValueErr(input))
+    } else {
+        Ok(LayoutGridArea(input.to_string()))
+    }
+}
+
+/// `object-fit`: how the content of an `Image` or a `GlTexture` is fitted into its rectangle
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum LayoutObjectFit {
+    /// Stretch the content to the size of the rectangle (may distort the content)
+    Fill,
+    /// Scale the content so that it fits into the rectangle, keeping the aspect ratio
+    Contain,
+    /// Scale the content so that it covers the whole rectangle, keeping the aspect
+    /// ratio - the parts of the content outside of the rectangle are clipped
+    Cover,
+    /// Don't scale the content at all
+    None,
+    /// Same as `None` or `Contain`, whichever results in the smaller content
+    ScaleDown,
+}
+
+impl Default for LayoutObjectFit {
+    fn default() -> Self {
+        LayoutObjectFit::Fill
+    }
+}
+
+multi_type_parser!(parse_layout_object_fit, LayoutObjectFit,
+                    ["fill", Fill],
+                    ["contain", Contain],
+                    ["cover", Cover],
+                    ["none", None],
+                    ["scale-down", ScaleDown]);
+
+/// One axis of the `object-position`: either a fixed offset or a fraction of the free
+/// space between the content and the rectangle (`left` = `0%`, `center` = `50%`, `right` = `100%`)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ObjectPositionComponent {
+    Pixels(PixelValue),
+    Percentage(PercentageValue),
+}
+
+impl ObjectPositionComponent {
+    /// Returns the offset of the content, given the free space (`rect size - content size`) on this axis
+    pub(crate) fn resolve(&self, free_space: f32) -> f32 {
+        match self {
+            ObjectPositionComponent::Pixels(p) => p.to_pixels(),
+            ObjectPositionComponent::Percentage(p) => free_space * p.number,
+        }
+    }
+}
+
+/// `object-position`, i.e. `left top`, `50% 50%` or `10px bottom`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LayoutObjectPosition {
+    pub horizontal: ObjectPositionComponent,
+    pub vertical: ObjectPositionComponent,
+}
+
+impl Default for LayoutObjectPosition {
+    fn default() -> Self {
+        let center = ObjectPositionComponent::Percentage(PercentageValue { number: 0.5 });
+        LayoutObjectPosition {
+            horizontal: center,
+            vertical: center,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectPositionParseError<'a> {
+    /// More than two values or both values on the same axis, such as `left right`
+    InvalidPosition(&'a str),
+    PixelParseError(PixelParseError<'a>),
+    PercentageParseError(PercentageParseError),
+}
+
+impl_from!(PixelParseError, ObjectPositionParseError::PixelParseError);
+
+impl<'a> From<PercentageParseError> for ObjectPositionParseError<'a> {
+    fn from(e: PercentageParseError) -> Self {
+        ObjectPositionParseError::PercentageParseError(e)
+    }
+}
+
+/// Parses one value of the `object-position`, returns whether the value is a keyword
+/// that only applies to the horizontal (`Some(true)`) or vertical (`Some(false)`) axis
+fn parse_object_position_component<'a>(input: &'a str)
+-> Result<(ObjectPositionComponent, Option<bool>), ObjectPositionParseError<'a>>
+{
+    use self::ObjectPositionComponent::*;
+
+    let percentage = |number| Percentage(PercentageValue { number: number });
+
+    match input {
+        "left"      => Ok((percentage(0.0), Some(true))),
+        "right"     => Ok((percentage(1.0), Some(true))),
+        "top"       => Ok((percentage(0.0), Some(false))),
+        "bottom"    => Ok((percentage(1.0), Some(false))),
+        "center"    => Ok((percentage(0.5), None)),
+        other if other.ends_with('%') => Ok((Percentage(parse_percentage_value(other)?), None)),
+        other => Ok((Pixels(parse_pixel_value(other)?), None)),
+    }
+}
+
+fn parse_layout_object_position<'a>(input: &'a str)
+-> Result<LayoutObjectPosition, ObjectPositionParseError<'a>>
+{
+    let center = ObjectPositionComponent::Percentage(PercentageValue { number: 0.5 });
+    let mut input_iter = input.split_whitespace();
+
+    let first = parse_object_position_component(input_iter.next().ok_or(ObjectPositionParseError::InvalidPosition(input))?)?;
+    let second = match input_iter.next() {
+        Some(s) => Some(parse_object_position_component(s)?),
+        None => None,
+    };
+
+    if input_iter.next().is_some() {
+        return Err(ObjectPositionParseError::InvalidPosition(input));
+    }
+
+    let (horizontal, vertical) = match second {
+        // "top" = "center top"
+        None => match first {
+            (vertical, Some(false)) => (center, vertical),
+            (horizontal, _) => (horizontal, center),
+        },
+        Some(second) => match (first.1, second.1) {
+            (Some(a), Some(b)) if a == b => return Err(ObjectPositionParseError::InvalidPosition(input)),
+            // "top left" = "left top"
+            (Some(false), _) | (_, Some(true)) => (second.0, first.0),
+            _ => (first.0, second.0),
+        },
+    };
+
+    Ok(LayoutObjectPosition { horizontal, vertical })
+}
+
+/// `aspect-ratio`, stored as `width / height`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LayoutAspectRatio(pub f32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AspectRatioParseError<'a> {
+    /// The ratio has to be a positive number or two positive numbers, separated by a `/`
+    InvalidRatio(&'a str),
+    ParseFloat(ParseFloatError),
+}
+
+impl<'a> From<ParseFloatError> for AspectRatioParseError<'a> {
+    fn from(e: ParseFloatError) -> Self {
+        AspectRatioParseError::ParseFloat(e)
+    }
+}
+
+/// Parses "16 / 9" or "1.5"
+fn parse_layout_aspect_ratio<'a>(input: &'a str)
+-> Result<LayoutAspectRatio, AspectRatioParseError<'a>>
+{
+    let mut input_iter = input.split('/');
+
+    let width = input_iter.next().ok_or(AspectRatioParseError::InvalidRatio(input))?.trim().parse::<f32>()?;
+    let height = match input_iter.next() {
+        Some(h) => h.trim().parse::<f32>()?,
+        None => 1.0,
+    };
+
+    if input_iter.next().is_some() || !(width > 0.0) || !(height > 0.0) {
+        return Err(AspectRatioParseError::InvalidRatio(input));
+    }
+
+    Ok(LayoutAspectRatio(width / height))
+}
+
+/// CssColor is simply a wrapper around the internal CSS color parsing methods.
+///
+/// Sometimes you'd want to load and parse a CSS color, but you don't want to
+/// write your own parser for that. Since azul already has a parser for CSS colors,
+/// this API exposes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CssColor {
+    internal: ColorU,
+}
+
+impl CssColor {
+    /// Can parse a CSS color with or without prefixed hash or capitalization, i.e. `#aabbcc`
+    pub fn from_str<'a>(input: &'a str) -> Result<Self, CssColorParseError<'a>> {
+        let color = parse_css_color(input)?;
+        Ok(Self {
+            internal: color,
+        })
+    }
+
+    /// Returns the internal parsed color, but in a `0.0 - 1.0` range instead of `0 - 255`
+    pub fn to_color_f(&self) -> ColorF {
+        self.internal.into()
+    }
+
+    /// Returns the internal parsed color
+    pub fn to_color_u(&self) -> ColorU {
+        self.internal
+    }
+
+    /// If `prefix_hash` is set to false, you only get the string, without a hash, in lowercase
+    ///
+    /// If `self.alpha` is `FF`, it wil be omitted from the final result (since `FF` is the default for CSS colors)
+    pub fn to_string(&self, prefix_hash: bool) -> String {
+        let prefix = if prefix_hash { "#" } else { "" };
+        let alpha = if self.internal.a == 255 { String::new() } else { format!("{:02x}", self.internal.a) };
+        format!("{}{:02x}{:02x}{:02x}{}", prefix, self.internal.r, self.internal.g, self.internal.b, alpha)
+    }
+}
+
+impl Into<ColorF> for CssColor {
+    fn into(self) -> ColorF {
+        self.to_color_f()
+    }
+}
+
+impl Into<ColorU> for CssColor {
+    fn into(self) -> ColorU {
+        self.to_color_u()
+    }
+}
 
 impl Into<String> for CssColor {
     fn into(self) -> String {
@@ -2541,4 +3908,233 @@ mod css_tests {
             left: Some(PixelValue::from_metric(CssMetric::Px, 100.0)),
         }));
     }
+
+    #[test]
+    fn test_parse_layout_position() {
+        assert_eq!(parse_layout_position("static"), Ok(LayoutPosition::Static));
+        assert_eq!(parse_layout_position("fixed"), Ok(LayoutPosition::Fixed));
+        assert_eq!(parse_layout_position("sticky"), Ok(LayoutPosition::Sticky));
+        assert!(parse_layout_position("floating").is_err());
+    }
+
+    #[test]
+    fn test_parse_grid_template_1() {
+        assert_eq!(parse_grid_track_list("100px 1fr auto"), Ok(vec![
+            GridTrackSize::Breadth(GridTrackBreadth::Fixed(PixelValue::from_metric(CssMetric::Px, 100.0))),
+            GridTrackSize::Breadth(GridTrackBreadth::Fraction(1.0)),
+            GridTrackSize::Breadth(GridTrackBreadth::Auto),
+        ]));
+    }
+
+    #[test]
+    fn test_parse_grid_template_2() {
+        assert_eq!(parse_grid_track_list("repeat(2, 1fr 50px) minmax(100px, 2fr)"), Ok(vec![
+            GridTrackSize::Breadth(GridTrackBreadth::Fraction(1.0)),
+            GridTrackSize::Breadth(GridTrackBreadth::Fixed(PixelValue::from_metric(CssMetric::Px, 50.0))),
+            GridTrackSize::Breadth(GridTrackBreadth::Fraction(1.0)),
+            GridTrackSize::Breadth(GridTrackBreadth::Fixed(PixelValue::from_metric(CssMetric::Px, 50.0))),
+            GridTrackSize::MinMax(
+                GridTrackBreadth::Fixed(PixelValue::from_metric(CssMetric::Px, 100.0)),
+                GridTrackBreadth::Fraction(2.0)),
+        ]));
+    }
+
+    #[test]
+    fn test_parse_grid_template_3() {
+        assert_eq!(parse_grid_track_list("minmax(1fr, 100px)"), Err(GridParseError::InvalidTrackSize("minmax(1fr, 100px)")));
+        assert_eq!(parse_grid_track_list("repeat(2, 1fr"), Err(GridParseError::UnclosedBraces("repeat(2, 1fr")));
+    }
+
+    #[test]
+    fn test_parse_grid_template_too_many_tracks() {
+        assert_eq!(parse_grid_track_list("repeat(10000, 1fr)").map(|t| t.len()), Ok(10000));
+        assert_eq!(parse_grid_track_list("repeat(4000000000, 1fr)"), Err(GridParseError::TooManyTracks("repeat(4000000000, 1fr)")));
+        assert_eq!(parse_grid_track_list("repeat(5000, 1fr 1fr) 10px"), Err(GridParseError::TooManyTracks("repeat(5000, 1fr 1fr) 10px")));
+        assert_eq!(parse_grid_track_list("10px repeat(5000, 1fr 1fr)"), Err(GridParseError::TooManyTracks("10px repeat(5000, 1fr 1fr)")));
+    }
+
+    #[test]
+    fn test_parse_grid_template_areas() {
+        assert_eq!(parse_layout_grid_template_areas("\"header header\" \"sidebar .\""), Ok(LayoutGridTemplateAreas {
+            rows: vec![
+                vec![Some(String::from("header")), Some(String::from("header"))],
+                vec![Some(String::from("sidebar")), None],
+            ],
+        }));
+        assert_eq!(parse_layout_grid_template_areas("\"a b\" \"c\""), Err(GridParseError::InvalidAreas("\"a b\" \"c\"")));
+    }
+
+    #[test]
+    fn test_parse_grid_line() {
+        assert_eq!(parse_grid_line("2"), Ok(GridLine { start: GridLinePosition::Line(2), end: GridLinePosition::Auto }));
+        assert_eq!(parse_grid_line("1 / -1"), Ok(GridLine { start: GridLinePosition::Line(1), end: GridLinePosition::Line(-1) }));
+        assert_eq!(parse_grid_line("1 / span 2"), Ok(GridLine { start: GridLinePosition::Line(1), end: GridLinePosition::Span(2) }));
+        assert_eq!(parse_grid_line("span 0"), Err(GridParseError::InvalidLine("span 0")));
+    }
+
+    #[test]
+    fn test_parse_gap() {
+        assert_eq!(parse_layout_gap("10px 20px"), Ok(LayoutGap {
+            row: PixelValue::from_metric(CssMetric::Px, 10.0),
+            column: PixelValue::from_metric(CssMetric::Px, 20.0),
+        }));
+    }
+
+    #[test]
+    fn test_parse_intrinsic_width() {
+        assert_eq!(ParsedCssProperty::from_kv("width", "min-content"), Ok(ParsedCssProperty::IntrinsicWidth(LayoutIntrinsicSize::MinContent)));
+        assert_eq!(ParsedCssProperty::from_kv("height", "auto"), Ok(ParsedCssProperty::IntrinsicHeight(LayoutIntrinsicSize::Auto)));
+        assert_eq!(ParsedCssProperty::from_kv("width", "10px"), Ok(ParsedCssProperty::Width(LayoutWidth(PixelValue::from_metric(CssMetric::Px, 10.0)))));
+    }
+
+    #[test]
+    fn test_parse_object_fit() {
+        assert_eq!(parse_layout_object_fit("scale-down"), Ok(LayoutObjectFit::ScaleDown));
+        assert_eq!(parse_layout_object_fit("none"), Ok(LayoutObjectFit::None));
+        assert!(parse_layout_object_fit("stretch").is_err());
+    }
+
+    #[test]
+    fn test_parse_object_position() {
+        use self::ObjectPositionComponent::*;
+        assert_eq!(parse_layout_object_position("top left"), Ok(LayoutObjectPosition {
+            horizontal: Percentage(PercentageValue { number: 0.0 }),
+            vertical: Percentage(PercentageValue { number: 0.0 }),
+        }));
+        assert_eq!(parse_layout_object_position("bottom"), Ok(LayoutObjectPosition {
+            horizontal: Percentage(PercentageValue { number: 0.5 }),
+            vertical: Percentage(PercentageValue { number: 1.0 }),
+        }));
+        assert_eq!(parse_layout_object_position("10px 25%"), Ok(LayoutObjectPosition {
+            horizontal: Pixels(PixelValue::from_metric(CssMetric::Px, 10.0)),
+            vertical: Percentage(PercentageValue { number: 0.25 }),
+        }));
+        assert!(parse_layout_object_position("left right").is_err());
+    }
+
+    #[test]
+    fn test_parse_aspect_ratio() {
+        assert_eq!(parse_layout_aspect_ratio("16 / 8"), Ok(LayoutAspectRatio(2.0)));
+        assert_eq!(parse_layout_aspect_ratio("1.5"), Ok(LayoutAspectRatio(1.5)));
+        assert!(parse_layout_aspect_ratio("0 / 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_text_direction() {
+        assert_eq!(ParsedCssProperty::from_kv("direction", "rtl"), Ok(ParsedCssProperty::TextDirection(LayoutTextDirection::Rtl)));
+        assert!(ParsedCssProperty::TextDirection(LayoutTextDirection::Rtl).is_inheritable());
+        assert!(parse_layout_text_direction("right-to-left").is_err());
+
+        assert_eq!(LayoutDirection::Row.resolve(LayoutTextDirection::Rtl), LayoutDirection::RowReverse);
+        assert_eq!(LayoutDirection::Column.resolve(LayoutTextDirection::Rtl), LayoutDirection::Column);
+        assert_eq!(parse_layout_text_align("start").unwrap().resolve(LayoutTextDirection::Rtl), TextAlignmentHorz::Right);
+        assert_eq!(TextAlignmentHorz::Left.resolve(LayoutTextDirection::Rtl), TextAlignmentHorz::Left);
+    }
+
+    #[test]
+    fn test_parse_font_kerning() {
+        assert_eq!(ParsedCssProperty::from_kv("font-kerning", "none"), Ok(ParsedCssProperty::FontKerning(FontKerning::None)));
+        assert!(ParsedCssProperty::FontKerning(FontKerning::None).is_inheritable());
+        assert!(parse_css_font_kerning("off").is_err());
+    }
+
+    #[test]
+    fn test_parse_font_rendering() {
+        assert_eq!(ParsedCssProperty::from_kv("-webkit-font-smoothing", "antialiased"), Ok(ParsedCssProperty::FontSmoothing(FontSmoothing::Grayscale)));
+        assert_eq!(ParsedCssProperty::from_kv("font-hinting", "light"), Ok(ParsedCssProperty::FontHinting(FontHinting::Light)));
+        assert_eq!(ParsedCssProperty::from_kv("font-subpixel-positioning", "none"), Ok(ParsedCssProperty::FontSubpixelPositioning(FontSubpixelPositioning::None)));
+        assert_eq!(ParsedCssProperty::from_kv("font-lcd-filter", "legacy"), Ok(ParsedCssProperty::FontLcdFilter(FontLcdFilter::Legacy)));
+        assert!(parse_css_font_hinting("slight").is_err());
+        assert!(ParsedCssProperty::FontSmoothing(FontSmoothing::None).is_inheritable());
+    }
+
+    #[test]
+    fn test_parse_font_feature_settings() {
+        assert_eq!(parse_css_font_feature_settings("normal"), Ok(FontFeatureSettings(Vec::new())));
+        assert_eq!(parse_css_font_feature_settings("\"liga\" 0, 'kern', \"ss01\" on, \"smcp\" off, \"salt\" 3"), Ok(FontFeatureSettings(vec![
+            FontFeature { tag: *b"liga", value: 0 },
+            FontFeature { tag: *b"kern", value: 1 },
+            FontFeature { tag: *b"ss01", value: 1 },
+            FontFeature { tag: *b"smcp", value: 0 },
+            FontFeature { tag: *b"salt", value: 3 },
+        ])));
+        assert_eq!(parse_css_font_feature_settings("\"ligatures\" 0"), Err(FontFeatureSettingsParseError::InvalidTag("ligatures")));
+        assert_eq!(parse_css_font_feature_settings("\"liga\" yes"), Err(FontFeatureSettingsParseError::InvalidValue("yes")));
+        assert!(parse_css_font_feature_settings("liga").is_err());
+    }
+
+    #[test]
+    fn test_parse_text_wrap() {
+        assert_eq!(ParsedCssProperty::from_kv("text-align", "justify"), Ok(ParsedCssProperty::TextAlign(TextAlignmentHorz::Justify)));
+        assert_eq!(TextAlignmentHorz::Justify.resolve(LayoutTextDirection::Rtl), TextAlignmentHorz::Justify);
+        assert_eq!(ParsedCssProperty::from_kv("text-wrap", "pretty"), Ok(ParsedCssProperty::TextWrap(TextWrap::Pretty)));
+        assert!(ParsedCssProperty::TextWrap(TextWrap::Pretty).is_inheritable());
+        assert!(parse_layout_text_wrap("balance").is_err());
+    }
+
+    #[test]
+    fn test_parse_vertical_align() {
+        assert_eq!(ParsedCssProperty::from_kv("vertical-align", "top"), Ok(ParsedCssProperty::VerticalAlign(TextAlignmentVert::Top)));
+        assert_eq!(ParsedCssProperty::from_kv("align-text", "bottom"), Ok(ParsedCssProperty::VerticalAlign(TextAlignmentVert::Bottom)));
+        assert_eq!(parse_layout_vertical_align("middle"), Ok(TextAlignmentVert::Center));
+        assert!(parse_layout_vertical_align("baseline").is_err());
+        assert!(ParsedCssProperty::VerticalAlign(TextAlignmentVert::Top).is_inheritable());
+    }
+
+    #[test]
+    fn test_parse_white_space() {
+        assert_eq!(ParsedCssProperty::from_kv("white-space", "pre-wrap"), Ok(ParsedCssProperty::WhiteSpace(WhiteSpace::PreWrap)));
+        assert_eq!(ParsedCssProperty::from_kv("word-break", "keep-all"), Ok(ParsedCssProperty::WordBreak(WordBreak::KeepAll)));
+        assert_eq!(ParsedCssProperty::from_kv("word-wrap", "break-word"), Ok(ParsedCssProperty::OverflowWrap(OverflowWrap::BreakWord)));
+        assert_eq!(ParsedCssProperty::from_kv("overflow-wrap", "anywhere"), Ok(ParsedCssProperty::OverflowWrap(OverflowWrap::Anywhere)));
+        assert!(ParsedCssProperty::WhiteSpace(WhiteSpace::Pre).is_inheritable());
+        assert!(parse_layout_white_space("break-spaces").is_err());
+
+        assert!(!WhiteSpace::Pre.wraps());
+        assert!(!WhiteSpace::Pre.collapses_spaces());
+        assert!(WhiteSpace::PreLine.preserves_newlines());
+        assert!(!WhiteSpace::Normal.preserves_newlines());
+    }
+
+    #[test]
+    fn test_parse_text_overflow() {
+        assert_eq!(ParsedCssProperty::from_kv("text-overflow", "ellipsis"), Ok(ParsedCssProperty::TextOverflow(TextOverflowStyle::Ellipsis)));
+        assert_eq!(parse_layout_text_overflow_style("\" [more]\""), Ok(TextOverflowStyle::String(" [more]".into())));
+        assert!(parse_layout_text_overflow_style("fade").is_err());
+        assert_eq!(ParsedCssProperty::from_kv("-webkit-line-clamp", "3"), Ok(ParsedCssProperty::LineClamp(LineClamp::Lines(3))));
+        assert_eq!(parse_layout_line_clamp("none"), Ok(LineClamp::None));
+        assert!(parse_layout_line_clamp("0").is_err());
+        assert!(!ParsedCssProperty::LineClamp(LineClamp::Lines(3)).is_inheritable());
+    }
+
+    #[test]
+    fn test_parse_tab_size() {
+        assert_eq!(ParsedCssProperty::from_kv("tab-size", "8"), Ok(ParsedCssProperty::TabSize(TabSize::Spaces(8))));
+        assert_eq!(parse_layout_tab_size("30px"), Ok(TabSize::Length(PixelValue::from_metric(CssMetric::Px, 30.0))));
+        assert!(parse_layout_tab_size("-2px").is_err());
+        assert_eq!(
+            ParsedCssProperty::from_kv("tab-stops", "100px 250px"),
+            Ok(ParsedCssProperty::TabStops(TabStops(vec![PixelValue::from_metric(CssMetric::Px, 100.0), PixelValue::from_metric(CssMetric::Px, 250.0)]))));
+        assert_eq!(parse_layout_tab_stops("none"), Ok(TabStops(Vec::new())));
+        assert!(parse_layout_tab_stops("250px 100px").is_err());
+        assert!(ParsedCssProperty::TabSize(TabSize::Spaces(2)).is_inheritable());
+    }
+
+    #[test]
+    fn test_parse_letter_spacing() {
+        assert_eq!(
+            ParsedCssProperty::from_kv("letter-spacing", "2px"),
+            Ok(ParsedCssProperty::LetterSpacing(LetterSpacing(PixelValue::from_metric(CssMetric::Px, 2.0)))));
+        assert_eq!(parse_letter_spacing("normal"), Ok(LetterSpacing(PixelValue::from_metric(CssMetric::Px, 0.0))));
+        assert_eq!(parse_letter_spacing("-0.5px"), Ok(LetterSpacing(PixelValue::from_metric(CssMetric::Px, -0.5))));
+        assert!(parse_letter_spacing("wide").is_err());
+        assert!(ParsedCssProperty::LetterSpacing(LetterSpacing(PixelValue::from_metric(CssMetric::Px, 1.0))).is_inheritable());
+    }
+
+    #[test]
+    fn test_parse_padding_inline() {
+        assert_eq!(
+            ParsedCssProperty::from_kv("padding-inline-start", "10px"),
+            Ok(ParsedCssProperty::PaddingInlineStart(LayoutPaddingInlineStart(PixelValue::from_metric(CssMetric::Px, 10.0)))));
+    }
 }
\ No newline at end of file
@@ -1,27 +1,37 @@
 #![allow(unused_variables)]
 #![allow(unused_macros)]
 
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    collections::{BTreeMap, BTreeSet},
+};
 use webrender::api::*;
 use app_units::{AU_PER_PX, MIN_AU, MAX_AU, Au};
 use euclid::{TypedRect, TypedSize2D};
+use glium::glutin::dpi::LogicalSize;
 use {
     FastHashMap,
     app_resources::AppResources,
     traits::Layout,
     ui_state::UiState,
     ui_description::{UiDescription, StyledNode},
-    ui_solver::UiSolver,
+    ui_solver::{UiSolver, SolvedLayout, LayoutContent},
+    cache::DomChangeSet,
     window_state::WindowSize,
     id_tree::{Arena, NodeId},
     css_parser::*,
     dom::{NodeData, NodeType::{self, *}},
     css::Css,
-    text_layout::{TextOverflowPass2, ScrollbarInfo},
+    text_layout::{TextOverflowPass2, ScrollbarInfo, TextIntrinsicSize, TextBreakOptions, TextTruncationOptions, TabOptions, LineBreaking},
+    rich_text,
     images::ImageId,
     text_cache::TextId,
+    text_shaping::ShapingOptions,
     compositor::new_opengl_texture_id,
-    window::{WindowId, FakeWindow},
+    font::FontRenderOptions,
+    window::{WindowId, FakeWindow, LayoutEngine},
+    scroll::{ScrollStates, ScrollFrame, ScrollAxis, is_scroll_container, get_scroll_key},
+    flex_layout,
 };
 
 const DEFAULT_FONT_COLOR: TextColor = TextColor(ColorU { r: 0, b: 0, g: 0, a: 255 });
@@ -116,6 +126,30 @@ impl<'a, T: Layout + 'a> DisplayList<'a, T> {
         }
     }
 
+    /// Resolves the font fallback chains of all texts, which loads the fonts that are
+    /// necessary to render the characters that are not in the `font-family` of the text.
+    /// The newly loaded fonts are then uploaded in `update_font_resources`.
+    fn load_fallback_fonts(&self, app_resources: &AppResources) {
+        let arena = self.ui_descr.ui_descr_arena.borrow();
+
+        for rect_idx in self.rectangles.linear_iter() {
+            let font_family = match self.rectangles[rect_idx].data.style.font_family {
+                Some(ref ff) => ff,
+                None => continue,
+            };
+            match &arena[rect_idx].data.node_type {
+                Label(text) => { app_resources.get_font_fallback_chain(font_family, text); },
+                Text(text_id) => { app_resources.get_text_fallback_chain(font_family, text_id); },
+                RichText(text) => {
+                    for span in &text.spans {
+                        app_resources.get_font_fallback_chain(span.style.font_family.as_ref().unwrap_or(font_family), &span.text);
+                    }
+                },
+                _ => { },
+            }
+        }
+    }
+
     /// Looks if any new images need to be uploaded and stores the in the image resources
     fn update_resources(
         api: &RenderApi,
@@ -225,97 +259,188 @@ impl<'a, T: Layout + 'a> DisplayList<'a, T> {
         }
     }
 
-    pub fn into_display_list_builder(
+    /// Determines the correct implicit z-index rendering order of every rectangle
+    fn get_rects_in_rendering_order(&self) -> BTreeMap<usize, Vec<NodeId>> {
+
+        let mut rects_in_rendering_order = BTreeMap::<usize, Vec<NodeId>>::new();
+
+        for rect_id in self.rectangles.linear_iter() {
+
+            // how many z-levels does this rectangle have until we get to the root?
+            let z_index = {
+                let mut index = 0;
+                let mut cur_rect_idx = rect_id;
+                while let Some(parent) = self.rectangles[cur_rect_idx].parent() {
+                    index += 1;
+                    cur_rect_idx = parent;
+                }
+                index
+            };
+
+            rects_in_rendering_order
+                .entry(z_index)
+                .or_insert_with(|| Vec::new())
+                .push(rect_id);
+        }
+
+        rects_in_rendering_order
+    }
+
+    /// Lays out all rectangles (with the layout engine of the `ui_solver`), only the
+    /// rectangles that changed since the last frame are laid out again
+    pub(crate) fn layout(
         &self,
-        app_data: Arc<Mutex<T>>,
-        pipeline_id: PipelineId,
-        current_epoch: Epoch,
         ui_solver: &mut UiSolver,
         css: &mut Css,
-        app_resources: &mut AppResources,
-        render_api: &RenderApi,
+        app_resources: &AppResources,
         mut has_window_size_changed: bool,
-        window_size: &WindowSize,
-        window_id: WindowId,
-        fake_window: &mut FakeWindow<T>)
-    -> DisplayListBuilder
+        window_size: &LogicalSize)
     {
-        use glium::glutin::dpi::LogicalSize;
-        use std::collections::BTreeMap;
-
-        let changeset = self.ui_descr.ui_descr_root.as_ref().and_then(|root| {
-            let changeset = ui_solver.update_dom(root, &*(self.ui_descr.ui_descr_arena.borrow()));
-            if changeset.is_empty() { None } else { Some(changeset) }
-        });
-
         let root = match self.ui_descr.ui_descr_root {
             Some(r) => r,
             None => panic!("Dom has no root element!"),
         };
 
-        if css.needs_relayout || changeset.is_some() {
-            // inefficient for now, but prevents memory leak
-            ui_solver.clear_all_constraints();
-            for rect_idx in self.rectangles.linear_iter() {
-                let constraints = create_layout_constraints(
-                    rect_idx,
-                    &self.rectangles,
-                    &*self.ui_descr.ui_descr_arena.borrow(),
-                    &ui_solver,
-                );
-                ui_solver.insert_css_constraints_for_rect(&constraints);
-                ui_solver.push_added_constraints(rect_idx, constraints);
-            }
+        {
+            let arena = self.ui_descr.ui_descr_arena.borrow();
+            let changeset = ui_solver.update_dom(&root, &*arena);
+            let mut dirty_rects = get_dirty_rects(&self.rectangles, &*arena, &changeset, ui_solver, app_resources);
+
+            match ui_solver.get_layout_engine() {
+                LayoutEngine::Cassowary => {
+
+                    if css.needs_relayout {
+                        ui_solver.clear_all_constraints();
+                        dirty_rects = self.rectangles.linear_iter().collect::<BTreeSet<NodeId>>();
+                    } else if has_window_size_changed {
+                        // The height of text can depend on the window width (see `get_available_width`)
+                        dirty_rects.extend(self.rectangles.linear_iter().filter(|rect_idx| match arena[*rect_idx].data.node_type {
+                            Label(_) | Text(_) | RichText(_) => true,
+                            _ => false,
+                        }));
+                    }
 
-            // If we push or pop constraints that means we also need to re-layout the window
-            has_window_size_changed = true;
-        }
+                    // Only rebuild the constraints of the rectangles that have changed,
+                    // style-only changes (such as colors) don't touch the solver at all
+                    for rect_idx in dirty_rects.iter().cloned() {
+                        ui_solver.remove_constraints_for_rect(rect_idx);
+                        let available_width = get_available_width(rect_idx, &self.rectangles, window_size.width as f32);
+                        let intrinsic_size = get_intrinsic_size(&self.rectangles[rect_idx].data, &arena[rect_idx].data.node_type, app_resources, available_width);
+                        let constraints = create_layout_constraints(
+                            rect_idx,
+                            &self.rectangles,
+                            &*arena,
+                            &ui_solver,
+                            intrinsic_size,
+                        );
+                        ui_solver.insert_css_constraints_for_rect(&constraints);
+                        ui_solver.push_added_constraints(rect_idx, constraints);
+                    }
 
-        // TODO: early return based on changeset?
+                    // If we push or pop constraints that means we also need to re-layout the window
+                    if !dirty_rects.is_empty() {
+                        has_window_size_changed = true;
+                    }
 
-        // Recalculate the actual layout
-        if has_window_size_changed {
-            ui_solver.update_window_size(&window_size.dimensions);
+                    // Recalculate the actual layout
+                    if has_window_size_changed {
+                        ui_solver.update_window_size(window_size);
+                        ui_solver.update_layout_cache();
+                    }
+                },
+                LayoutEngine::Flexbox => {
+                    // The direct layout is cheap enough to always lay out the whole tree
+                    if css.needs_relayout || has_window_size_changed || !dirty_rects.is_empty() {
+                        let intrinsic_sizes = self.rectangles.linear_iter().filter_map(|rect_idx| {
+                            let available_width = get_available_width(rect_idx, &self.rectangles, window_size.width as f32);
+                            get_intrinsic_size(&self.rectangles[rect_idx].data, &arena[rect_idx].data.node_type, app_resources, available_width)
+                                .and_then(|intrinsic_size| Some((rect_idx, intrinsic_size)))
+                        }).collect::<BTreeMap<NodeId, IntrinsicSize>>();
+                        let window_size = LayoutSize::new(window_size.width as f32, window_size.height as f32);
+                        ui_solver.set_direct_layout(flex_layout::layout_rects(&self.rectangles, &intrinsic_sizes, window_size));
+                    }
+                },
+            }
         }
-        ui_solver.update_layout_cache();
 
         css.needs_relayout = false;
+    }
+
+    pub fn into_display_list_builder(
+        &self,
+        app_data: Arc<Mutex<T>>,
+        pipeline_id: PipelineId,
+        current_epoch: Epoch,
+        ui_solver: &mut UiSolver,
+        css: &mut Css,
+        app_resources: &mut AppResources,
+        render_api: &RenderApi,
+        has_window_size_changed: bool,
+        window_size: &WindowSize,
+        window_id: WindowId,
+        fake_window: &mut FakeWindow<T>,
+        scroll_states: &mut ScrollStates,
+        font_render_options: &FontRenderOptions)
+    -> DisplayListBuilder
+    {
+        // The fallback chains of the labels are calculated once per frame (for the layout, to load
+        // the fallback fonts and to lay out the glyphs), since the labels can change in every frame
+        app_resources.start_frame();
+
+        self.layout(ui_solver, css, app_resources, has_window_size_changed, &window_size.dimensions);
 
         let LogicalSize { width, height } = window_size.dimensions;
         let mut builder = DisplayListBuilder::with_capacity(pipeline_id, TypedSize2D::new(width as f32, height as f32), self.rectangles.nodes_len());
         let mut resource_updates = Vec::<ResourceUpdate>::new();
         let full_screen_rect = LayoutRect::new(LayoutPoint::zero(), builder.content_size());;
 
+        // Load the fallback fonts of all texts, so that they can be uploaded
+        self.load_fallback_fonts(app_resources);
+
         // Upload image and font resources
         Self::update_resources(render_api, app_resources, &mut resource_updates);
 
         let arena = self.ui_descr.ui_descr_arena.borrow();
 
-        // Determine the correct implicit z-index rendering order of every rectangle
-        let mut rects_in_rendering_order = BTreeMap::<usize, Vec<NodeId>>::new();
+        // Store the layout, so that callbacks can query the bounds of the nodes
+        fake_window.solved_layout = SolvedLayout::new(&*arena, ui_solver);
 
-        for rect_id in self.rectangles.linear_iter() {
+        let rects_in_rendering_order = self.get_rects_in_rendering_order();
 
-            // how many z-levels does this rectangle have until we get to the root?
-            let z_index = {
-                let mut index = 0;
-                let mut cur_rect_idx = rect_id;
-                while let Some(parent) = self.rectangles[cur_rect_idx].parent() {
-                    index += 1;
-                    cur_rect_idx = parent;
-                }
-                index
-            };
+        // Clip IDs of all rectangles with `position: sticky` and of all scroll containers,
+        // so that the children of these rectangles move with their parent. The vector is
+        // the sum of the scroll offsets of all scroll frames that the clip is nested in.
+        let mut frames = BTreeMap::<NodeId, (ClipId, LayoutVector2D)>::new();
 
-            rects_in_rendering_order
-                .entry(z_index)
-                .or_insert_with(|| Vec::new())
-                .push(rect_id);
-        }
+        // Scroll containers whose scrollbars still have to be pushed, with the clip of their parent
+        let mut scrollbars = Vec::<(NodeId, Option<ClipId>)>::new();
+
+        scroll_states.begin_frame();
 
         for (z_index, rects) in rects_in_rendering_order.into_iter() {
             for rect_idx in rects {
                 let bounds = ui_solver.query_bounds_of_rect(rect_idx);
+
+                // Since we render in z-order, the frames of all parents are already defined
+                let parent_frame = get_parent_frame(&self.rectangles, rect_idx, &frames);
+                let parent_offset = parent_frame.and_then(|(_, offset)| Some(offset)).unwrap_or(LayoutVector2D::zero());
+
+                if let Some((parent_frame, _)) = parent_frame {
+                    builder.push_clip_id(parent_frame);
+                }
+
+                let sticky_frame = if self.rectangles[rect_idx].data.layout.position == Some(LayoutPosition::Sticky) {
+                    let containing_block = self.rectangles[rect_idx].parent()
+                        .and_then(|parent| Some(ui_solver.query_bounds_of_rect(parent)))
+                        .unwrap_or(full_screen_rect);
+                    let sticky_frame = push_sticky_frame(&mut builder, &self.rectangles[rect_idx].data.layout, &bounds, &containing_block);
+                    frames.insert(rect_idx, (sticky_frame, parent_offset));
+                    builder.push_clip_id(sticky_frame);
+                    Some(sticky_frame)
+                } else {
+                    None
+                };
+
                 displaylist_handle_rect(
                     &mut builder,
                     current_epoch,
@@ -329,7 +454,58 @@ impl<'a, T: Layout + 'a> DisplayList<'a, T> {
                     &mut resource_updates,
                     &app_data,
                     window_id,
-                    fake_window);
+                    fake_window,
+                    font_render_options);
+
+                // The content of a scroll container is pushed into a webrender scroll frame,
+                // text nodes handle their overflow themselves (see `push_text`)
+                let is_scrollable = self.rectangles[rect_idx].first_child().is_some() &&
+                    self.rectangles[rect_idx].data.style.overflow.as_ref().map(is_scroll_container).unwrap_or(false);
+
+                if is_scrollable {
+                    let overflow = self.rectangles[rect_idx].data.style.overflow.unwrap_or_default();
+                    let key = get_scroll_key(&*arena, rect_idx);
+                    let content_size = get_content_size(&self.rectangles, rect_idx, &bounds, ui_solver);
+                    let text_direction = self.rectangles[rect_idx].data.layout.get_text_direction();
+                    if let Some(scroll_frame) = ScrollFrame::new(rect_idx, key, bounds, content_size, &overflow, parent_offset, z_index, text_direction) {
+                        let content_rect = LayoutRect::new(bounds.origin, scroll_frame.content_size);
+                        let clip_rect = scroll_frame.viewport();
+                        let offset = scroll_states.add_frame(scroll_frame);
+                        let scroll_frame_id = builder.define_scroll_frame(
+                            Some(ExternalScrollId(key.0, pipeline_id)),
+                            content_rect,
+                            clip_rect,
+                            Vec::<ComplexClipRegion>::new(),
+                            None,
+                            ScrollSensitivity::Script);
+                        frames.insert(rect_idx, (scroll_frame_id, parent_offset + offset));
+                        scrollbars.push((rect_idx, sticky_frame.or(parent_frame.and_then(|(clip_id, _)| Some(clip_id)))));
+                    }
+                }
+
+                if sticky_frame.is_some() {
+                    builder.pop_clip_id();
+                }
+
+                if parent_frame.is_some() {
+                    builder.pop_clip_id();
+                }
+            }
+        }
+
+        scroll_states.remove_unused();
+
+        // The scrollbars are drawn on top of the content
+        for (scroll_frame, offset) in scroll_states.get_frames() {
+            let parent_clip = scrollbars.iter()
+                .find(|(node_id, _)| *node_id == scroll_frame.node_id)
+                .and_then(|(_, parent_clip)| *parent_clip);
+            if let Some(parent_clip) = parent_clip {
+                builder.push_clip_id(parent_clip);
+            }
+            push_container_scrollbars(&mut builder, scroll_frame, offset);
+            if parent_clip.is_some() {
+                builder.pop_clip_id();
             }
         }
 
@@ -337,47 +513,71 @@ impl<'a, T: Layout + 'a> DisplayList<'a, T> {
 
         builder
     }
+
+    /// Builds a display list without a renderer, i.e. for testing. Only the style of the
+    /// rectangles (backgrounds, borders, box shadows) is pushed - text, images, GL textures
+    /// and IFrames need resources that are uploaded to a renderer, so they are left out.
+    ///
+    /// Expects that the rectangles are already laid out (see `DisplayList::layout`).
+    pub(crate) fn into_headless_display_list_builder(
+        &self,
+        pipeline_id: PipelineId,
+        ui_solver: &UiSolver,
+        app_resources: &AppResources,
+        window_size: &LogicalSize)
+    -> DisplayListBuilder
+    {
+        let content_size = TypedSize2D::new(window_size.width as f32, window_size.height as f32);
+        let mut builder = DisplayListBuilder::with_capacity(pipeline_id, content_size, self.rectangles.nodes_len());
+        let full_screen_rect = LayoutRect::new(LayoutPoint::zero(), content_size);
+
+        for (_, rects) in self.get_rects_in_rendering_order() {
+            for rect_idx in rects {
+                let rect = &self.rectangles[rect_idx].data;
+                let bounds = ui_solver.query_bounds_of_rect(rect_idx);
+                let info = LayoutPrimitiveInfo {
+                    rect: bounds,
+                    clip_rect: bounds,
+                    is_backface_visible: false,
+                    tag: rect.tag.and_then(|tag| Some((tag, 0))),
+                };
+                if push_rectangle_style(&mut builder, &info, &bounds, &full_screen_rect, rect, app_resources).is_some() {
+                    builder.pop_clip_id();
+                }
+            }
+        }
+
+        builder
+    }
 }
 
-fn displaylist_handle_rect<'a, T: Layout>(
+/// Pushes the parts of a rectangle that only depend on its style: the box shadows, the background
+/// and the border. Returns the clip of the `border-radius` (if any), which has to be popped after
+/// the content of the rectangle is pushed.
+fn push_rectangle_style<'a>(
     builder: &mut DisplayListBuilder,
-    current_epoch: Epoch,
-    rect_idx: NodeId,
-    arena: &Arena<DisplayRectangle<'a>>,
-    html_node: &NodeType<T>,
-    bounds: TypedRect<f32, LayoutPixel>,
-    full_screen_rect: TypedRect<f32, LayoutPixel>,
-    app_resources: &mut AppResources,
-    render_api: &RenderApi,
-    resource_updates: &mut Vec<ResourceUpdate>,
-    app_data: &Arc<Mutex<T>>,
-    window_id: WindowId,
-    fake_window: &mut FakeWindow<T>)
+    info: &LayoutPrimitiveInfo,
+    bounds: &TypedRect<f32, LayoutPixel>,
+    full_screen_rect: &TypedRect<f32, LayoutPixel>,
+    rect: &DisplayRectangle<'a>,
+    app_resources: &AppResources)
+-> Option<ClipId>
 {
-    let rect = &arena[rect_idx].data;
-
-    let info = LayoutPrimitiveInfo {
-        rect: bounds,
-        clip_rect: bounds,
-        is_backface_visible: false,
-        tag: rect.tag.and_then(|tag| Some((tag, 0))),
-    };
-
     let clip_region_id = rect.style.border_radius.and_then(|border_radius| {
         let region = ComplexClipRegion {
-            rect: bounds,
+            rect: *bounds,
             radii: border_radius,
             mode: ClipMode::Clip,
         };
-        Some(builder.define_clip(bounds, vec![region], None))
+        Some(builder.define_clip(*bounds, vec![region], None))
     });
 
     // Push the "outset" box shadow, before the clip is active
     push_box_shadow(
         builder,
         &rect.style,
-        &bounds,
-        &full_screen_rect,
+        bounds,
+        full_screen_rect,
         BoxShadowClipMode::Outset);
 
     if let Some(id) = clip_region_id {
@@ -385,40 +585,64 @@ fn displaylist_handle_rect<'a, T: Layout>(
     }
 
     if let Some(ref bg_col) = rect.style.background_color {
-        push_rect(&info, builder, bg_col);
+        push_rect(info, builder, bg_col);
     }
 
     if let Some(ref bg) = rect.style.background {
         push_background(
-            &info,
-            &bounds,
+            info,
+            bounds,
             builder,
             bg,
-            &app_resources);
+            app_resources);
     };
 
     // Push the inset shadow (if any)
     push_box_shadow(builder,
                     &rect.style,
-                    &bounds,
-                    &full_screen_rect,
+                    bounds,
+                    full_screen_rect,
                     BoxShadowClipMode::Inset);
 
     push_border(
-        &info,
+        info,
         builder,
         &rect.style);
 
-    let (horz_alignment, vert_alignment) = determine_text_alignment(rect);
+    clip_region_id
+}
+
+fn displaylist_handle_rect<'a, T: Layout>(
+    builder: &mut DisplayListBuilder,
+    current_epoch: Epoch,
+    rect_idx: NodeId,
+    arena: &Arena<DisplayRectangle<'a>>,
+    html_node: &NodeType<T>,
+    bounds: TypedRect<f32, LayoutPixel>,
+    full_screen_rect: TypedRect<f32, LayoutPixel>,
+    app_resources: &mut AppResources,
+    render_api: &RenderApi,
+    resource_updates: &mut Vec<ResourceUpdate>,
+    app_data: &Arc<Mutex<T>>,
+    window_id: WindowId,
+    fake_window: &mut FakeWindow<T>,
+    font_render_options: &FontRenderOptions)
+{
+    let rect = &arena[rect_idx].data;
 
-    let scrollbar_style = ScrollbarInfo {
-        width: 17,
-        padding: 2,
-        background_color: BackgroundColor(ColorU { r: 241, g: 241, b: 241, a: 255 }),
-        triangle_color: BackgroundColor(ColorU { r: 163, g: 163, b: 163, a: 255 }),
-        bar_color: BackgroundColor(ColorU { r: 193, g: 193, b: 193, a: 255 }),
+    let info = LayoutPrimitiveInfo {
+        rect: bounds,
+        clip_rect: bounds,
+        is_backface_visible: false,
+        tag: rect.tag.and_then(|tag| Some((tag, 0))),
     };
 
+    let clip_region_id = push_rectangle_style(builder, &info, &bounds, &full_screen_rect, rect, app_resources);
+
+    let (horz_alignment, vert_alignment) = determine_text_alignment(rect);
+
+    let scrollbar_style = ScrollbarInfo::default();
+
     // The only thing changed between TextId and String is
     //`TextInfo::Cached` vs `TextInfo::Uncached` - reduce code duplication
     let push_text_wrapper = |
@@ -458,7 +682,8 @@ fn displaylist_handle_rect<'a, T: Layout>(
             resource_updates,
             horz_alignment,
             vert_alignment,
-            &scrollbar_style);
+            &scrollbar_style,
+            font_render_options);
 /*
         if text_clip_region_id.is_some() {
             builder.pop_clip_id();
@@ -471,6 +696,11 @@ fn displaylist_handle_rect<'a, T: Layout>(
     // width and height of their container to calculate their content
     use window::WindowInfo;
 
+    // Images and textures are drawn inside of the padding
+    let content_bounds = rect.layout.padding.as_ref().and_then(|padding| {
+        Some(subtract_padding(&bounds, padding))
+    }).unwrap_or(bounds);
+
     let hidpi_factor = fake_window.read_only_window().get_hidpi_factor();
     let bounds_width = (bounds.size.width * hidpi_factor as f32) as usize;
     let bounds_height = (bounds.size.height * hidpi_factor as f32) as usize;
@@ -484,8 +714,11 @@ fn displaylist_handle_rect<'a, T: Layout>(
         Text(text_id) => {
             push_text_wrapper(&TextInfo::Cached(*text_id), builder, app_resources, resource_updates)
         },
+        RichText(text) => {
+            push_rich_text(&info, text, builder, &rect.style, app_resources, &render_api, &content_bounds, resource_updates, horz_alignment, vert_alignment, font_render_options)
+        },
         Image(image_id) => {
-            push_image(&info, builder, &bounds, app_resources, image_id)
+            push_image(&info, builder, &content_bounds, &rect.style, app_resources, image_id)
         },
         GlTexture((texture_callback, texture_stack_ptr)) => {
 
@@ -520,14 +753,18 @@ fn displaylist_handle_rect<'a, T: Layout>(
                     AddImage { key, descriptor, data, tiling: None }
                 ));
 
-                builder.push_image(
-                    &info,
-                    bounds.size,
-                    LayoutSize::zero(),
-                    ImageRendering::Auto,
-                    AlphaType::Alpha,
-                    key,
-                    ColorF::WHITE);
+                // The texture is in physical pixels, the bounds are in logical pixels
+                let texture_size = LayoutSize::new(
+                    texture.inner.width() as f32 / hidpi_factor as f32,
+                    texture.inner.height() as f32 / hidpi_factor as f32);
+
+                let texture_bounds = get_object_fit_bounds(
+                    &content_bounds,
+                    texture_size,
+                    rect.style.object_fit.unwrap_or_default(),
+                    rect.style.object_position.unwrap_or_default());
+
+                push_fitted_image(&info, builder, &content_bounds, &texture_bounds, key, AlphaType::Alpha);
             }
 
             None
@@ -567,6 +804,137 @@ fn displaylist_handle_rect<'a, T: Layout>(
 
 }
 
+/// Returns the clip (and the scroll offset of that clip) of the nearest sticky or scroll frame
+/// that the rectangle is nested in. Rectangles with `position: fixed` don't move with any
+/// frame, so the search stops at the first fixed rectangle.
+fn get_parent_frame(
+    rectangles: &Arena<DisplayRectangle>,
+    rect_idx: NodeId,
+    frames: &BTreeMap<NodeId, (ClipId, LayoutVector2D)>)
+-> Option<(ClipId, LayoutVector2D)>
+{
+    let is_fixed = |node_id: NodeId| rectangles[node_id].data.layout.position == Some(LayoutPosition::Fixed);
+
+    if is_fixed(rect_idx) {
+        return None;
+    }
+
+    for ancestor in rect_idx.ancestors(rectangles).skip(1) {
+        if let Some(frame) = frames.get(&ancestor) {
+            return Some(*frame);
+        }
+        if is_fixed(ancestor) {
+            return None;
+        }
+    }
+
+    None
+}
+
+/// Returns the size of the content of a scroll container, measured from the top left of the
+/// container. Nested scroll containers only contribute their own bounds, and rectangles with
+/// `position: fixed` don't contribute anything, since they don't scroll with the container.
+fn get_content_size(
+    rectangles: &Arena<DisplayRectangle>,
+    rect_idx: NodeId,
+    bounds: &TypedRect<f32, LayoutPixel>,
+    ui_solver: &UiSolver)
+-> LayoutSize
+{
+    let mut content_size = LayoutSize::zero();
+
+    for child in rect_idx.children(rectangles) {
+        let child_rect = &rectangles[child].data;
+        if child_rect.layout.position == Some(LayoutPosition::Fixed) {
+            continue;
+        }
+
+        let child_bounds = ui_solver.query_bounds_of_rect(child);
+        content_size.width = content_size.width.max(child_bounds.max_x() - bounds.min_x());
+        content_size.height = content_size.height.max(child_bounds.max_y() - bounds.min_y());
+
+        let is_nested_scroll_container = child_rect.style.overflow.as_ref().map(is_scroll_container).unwrap_or(false);
+        if !is_nested_scroll_container {
+            let nested_size = get_content_size(rectangles, child, bounds, ui_solver);
+            content_size.width = content_size.width.max(nested_size.width);
+            content_size.height = content_size.height.max(nested_size.height);
+        }
+    }
+
+    content_size
+}
+
+/// Pushes the scrollbars of a scroll container (`overflow: scroll` / `overflow: auto`),
+/// the thumb is positioned according to the current scroll `offset`
+fn push_container_scrollbars(
+    builder: &mut DisplayListBuilder,
+    scroll_frame: &ScrollFrame,
+    offset: LayoutVector2D)
+{
+    let scrollbar_style = ScrollbarInfo::default();
+
+    for axis in &[ScrollAxis::Vertical, ScrollAxis::Horizontal] {
+        let scrollbar = match scroll_frame.get_scrollbar(*axis, offset) {
+            Some(scrollbar) => scrollbar,
+            None => continue,
+        };
+
+        // Hit-testing for the scrollbars is done by the `ScrollStates`,
+        // so that clicks on the scrollbar don't need a callback
+        let track_info = PrimitiveInfo {
+            rect: scrollbar.track,
+            clip_rect: scroll_frame.bounds,
+            is_backface_visible: false,
+            tag: None,
+        };
+        push_rect(&track_info, builder, &scrollbar_style.background_color);
+
+        let thumb_info = PrimitiveInfo {
+            rect: scrollbar.thumb,
+            clip_rect: scroll_frame.bounds,
+            is_backface_visible: false,
+            tag: None,
+        };
+        push_rect(&thumb_info, builder, &scrollbar_style.bar_color);
+    }
+}
+
+/// Defines a webrender sticky frame for a rectangle with `position: sticky`.
+///
+/// The `containing_block` (usually the parent rectangle) limits how far the
+/// rectangle can be moved, so that a sticky header doesn't leave its table.
+fn push_sticky_frame(
+    builder: &mut DisplayListBuilder,
+    layout: &RectLayout,
+    bounds: &TypedRect<f32, LayoutPixel>,
+    containing_block: &TypedRect<f32, LayoutPixel>)
+-> ClipId
+{
+    use euclid::SideOffsets2D;
+
+    let margins = SideOffsets2D::new(
+        layout.top.and_then(|top| Some(top.0.to_pixels())),
+        layout.right.and_then(|right| Some(right.0.to_pixels())),
+        layout.bottom.and_then(|bottom| Some(bottom.0.to_pixels())),
+        layout.left.and_then(|left| Some(left.0.to_pixels())),
+    );
+
+    let vertical_offset_bounds = StickyOffsetBounds::new(
+        containing_block.min_y() - bounds.min_y(),
+        containing_block.max_y() - bounds.max_y());
+
+    let horizontal_offset_bounds = StickyOffsetBounds::new(
+        containing_block.min_x() - bounds.min_x(),
+        containing_block.max_x() - bounds.max_x());
+
+    builder.define_sticky_frame(
+        *bounds,
+        margins,
+        vertical_offset_bounds,
+        horizontal_offset_bounds,
+        LayoutVector2D::zero())
+}
+
 #[inline]
 fn push_rect(
     info: &PrimitiveInfo<LayoutPixel>,
@@ -594,7 +962,8 @@ fn push_text(
     resource_updates: &mut Vec<ResourceUpdate>,
     horz_alignment: TextAlignmentHorz,
     vert_alignment: TextAlignmentVert,
-    scrollbar_info: &ScrollbarInfo)
+    scrollbar_info: &ScrollbarInfo,
+    font_render_options: &FontRenderOptions)
 -> Option<OverflowInfo>
 {
     use text_layout;
@@ -611,7 +980,8 @@ fn push_text(
     let font_size = style.font_size.unwrap_or(DEFAULT_FONT_SIZE);
     let font_size_app_units = Au((font_size.0.to_pixels() as i32) * AU_PER_PX as i32);
     let font_id = match font_family.fonts.get(0) { Some(s) => s, None => { error!("div @ {:?} has no font assigned!", bounds); return None; }};
-    let font_result = push_font(font_id, font_size_app_units, resource_updates, app_resources, render_api);
+    let render_options = font_render_options.with_style(style);
+    let font_result = push_font(font_id, font_size_app_units, &render_options, resource_updates, app_resources, render_api);
 
     let font_instance_key = match font_result {
         Some(f) => f,
@@ -619,39 +989,173 @@ fn push_text(
     };
 
     let line_height = style.line_height;
+    let shaping_options = ShapingOptions::new(style.font_kerning, style.font_feature_settings.as_ref());
+    let break_options = TextBreakOptions::new(style);
+    let tab_options = TabOptions::new(style);
+    let truncation_options = TextTruncationOptions::new(style);
 
     let overflow_behaviour = style.overflow.unwrap_or(LayoutOverflow::default());
 
-    let (positioned_glyphs, text_overflow) = text_layout::get_glyphs(
+    let (glyphs_by_font, text_overflow) = text_layout::get_glyphs(
         app_resources,
         bounds,
         horz_alignment,
         vert_alignment,
-        &font_id,
+        font_family,
         &font_size,
         line_height,
+        style.letter_spacing,
+        style.text_wrap.unwrap_or_default(),
+        &shaping_options,
+        &break_options,
+        &tab_options,
+        &truncation_options,
         text,
         &overflow_behaviour,
         scrollbar_info
     );
 
     let font_color = style.font_color.unwrap_or(DEFAULT_FONT_COLOR).0.into();
-    let mut flags = FontInstanceFlags::empty();
-    flags.set(FontInstanceFlags::SUBPIXEL_BGR, true);
-    flags.set(FontInstanceFlags::FONT_SMOOTHING, true);
-    flags.set(FontInstanceFlags::FORCE_AUTOHINT, true);
-    flags.set(FontInstanceFlags::LCD_VERTICAL, true);
-
-    let options = GlyphOptions {
-        render_mode: FontRenderMode::Subpixel,
-        flags: flags,
-    };
+    let options = render_options.get_glyph_options(false);
 
-    builder.push_text(&info, &positioned_glyphs, font_instance_key, font_color, Some(options));
+    for (glyph_font_id, positioned_glyphs) in glyphs_by_font {
+        // Fallback fonts are uploaded in `update_font_resources`, if they aren't available yet,
+        // the glyphs will be drawn in the next frame
+        let glyph_font_instance_key = if glyph_font_id == *font_id {
+            font_instance_key
+        } else {
+            match push_font(&glyph_font_id, font_size_app_units, &render_options, resource_updates, app_resources, render_api) {
+                Some(f) => f,
+                None => continue,
+            }
+        };
+        builder.push_text(&info, &positioned_glyphs, glyph_font_instance_key, font_color, Some(options));
+    }
 
     Some(OverflowInfo { text_overflow })
 }
 
+/// Lays out the spans of a `RichText` as one paragraph and pushes the glyphs of every span
+/// with its own font, size and color. Also pushes the text decorations and - if the node
+/// is hit-tested - the hit-testing rectangles of the clickable spans.
+fn push_rich_text(
+    info: &PrimitiveInfo<LayoutPixel>,
+    text: &rich_text::RichText,
+    builder: &mut DisplayListBuilder,
+    style: &RectStyle,
+    app_resources: &mut AppResources,
+    render_api: &RenderApi,
+    bounds: &TypedRect<f32, LayoutPixel>,
+    resource_updates: &mut Vec<ResourceUpdate>,
+    horz_alignment: TextAlignmentHorz,
+    vert_alignment: TextAlignmentVert,
+    font_render_options: &FontRenderOptions)
+-> Option<OverflowInfo>
+{
+    use text_layout::{self, RichTextSpanInfo, TextOverflow};
+    use rich_text::{FontWeight, TextDecoration};
+    use euclid::TypedPoint2D;
+
+    if text.is_empty() {
+        return None;
+    }
+
+    // Every span needs a font, either its own font or the font of the node
+    let span_infos = text.spans.iter().map(|span| Some(RichTextSpanInfo {
+        text: &span.text,
+        font_family: span.style.font_family.as_ref().or(style.font_family.as_ref())?,
+        font_size: span.style.font_size.or(style.font_size).unwrap_or(DEFAULT_FONT_SIZE),
+    })).collect::<Option<Vec<_>>>();
+
+    let span_infos = match span_infos {
+        Some(s) => s,
+        None => { error!("rich text @ {:?} has a span without a font!", bounds); return None; },
+    };
+
+    let shaping_options = ShapingOptions::new(style.font_kerning, style.font_feature_settings.as_ref());
+    let break_options = TextBreakOptions::new(style);
+    let overflow_behaviour = style.overflow.unwrap_or(LayoutOverflow::default());
+    let max_width = if overflow_behaviour.allows_horizontal_overflow() { None } else { Some(bounds.size.width) };
+
+    let tab_options = TabOptions::new(style);
+    let render_options = font_render_options.with_style(style);
+
+    let mut layout = text_layout::layout_rich_text(&*app_resources, &span_infos, max_width, style.line_height, style.letter_spacing, &shaping_options, &break_options, &tab_options);
+    layout.align(bounds, horz_alignment, vert_alignment);
+
+    let span_color = |span_idx: usize| text.spans[span_idx].style.font_color.or(style.font_color).unwrap_or(DEFAULT_FONT_COLOR);
+
+    // Each (span, font) combination has to be pushed separately
+    let mut glyphs_by_font = vec![Vec::new(); layout.fonts.len()];
+    for (glyph, glyph_info) in layout.glyphs.iter().zip(layout.glyph_infos.iter()) {
+        glyphs_by_font[glyph_info.font_index].push(*glyph);
+    }
+
+    for ((span_idx, font_id), glyphs) in layout.fonts.iter().zip(glyphs_by_font.into_iter()) {
+        if glyphs.is_empty() {
+            continue;
+        }
+        let font_size_app_units = Au((span_infos[*span_idx].font_size.0.to_pixels() as i32) * AU_PER_PX as i32);
+        let font_instance_key = match push_font(font_id, font_size_app_units, &render_options, resource_updates, app_resources, render_api) {
+            Some(f) => f,
+            None => continue,
+        };
+        let is_bold = text.spans[*span_idx].style.font_weight.unwrap_or_default() == FontWeight::Bold;
+        builder.push_text(&info, &glyphs, font_instance_key, span_color(*span_idx).0.into(), Some(render_options.get_glyph_options(is_bold)));
+    }
+
+    for run in layout.get_span_runs() {
+        let span = &text.spans[run.span_index];
+        let metrics = &layout.span_metrics[run.span_index];
+
+        let decoration_y = match span.style.text_decoration.unwrap_or_default() {
+            TextDecoration::None => None,
+            TextDecoration::Underline => Some(run.baseline + metrics.descent / 2.0),
+            TextDecoration::Overline => Some(run.baseline - metrics.ascent),
+            TextDecoration::LineThrough => Some(run.baseline - metrics.ascent / 3.0),
+        };
+
+        if let Some(decoration_y) = decoration_y {
+            let thickness = ((metrics.ascent + metrics.descent) / 16.0).max(1.0);
+            let decoration_info = PrimitiveInfo {
+                rect: TypedRect::new(
+                    TypedPoint2D::new(run.bounds.origin.x, decoration_y - thickness / 2.0),
+                    TypedSize2D::new(run.bounds.size.width, thickness)),
+                clip_rect: info.clip_rect,
+                is_backface_visible: false,
+                tag: None,
+            };
+            push_rect(&decoration_info, builder, &BackgroundColor(span_color(run.span_index).0));
+        }
+
+        // The second part of the tag is the index of the span + 1, see `WindowEvent::hit_text_span`
+        if span.is_clickable {
+            if let Some((node_tag, _)) = info.tag {
+                let span_hit_info = PrimitiveInfo {
+                    rect: run.bounds,
+                    clip_rect: info.clip_rect,
+                    is_backface_visible: false,
+                    tag: Some((node_tag, run.span_index as u16 + 1)),
+                };
+                push_rect(&span_hit_info, builder, &BackgroundColor(ColorU { r: 0, g: 0, b: 0, a: 0 }));
+            }
+        }
+    }
+
+    let get_overflow = |content: f32, available: f32| if content > available {
+        TextOverflow::IsOverflowing(content - available)
+    } else {
+        TextOverflow::InBounds(available - content)
+    };
+
+    Some(OverflowInfo {
+        text_overflow: TextOverflowPass2 {
+            horizontal: get_overflow(layout.width, bounds.size.width),
+            vertical: get_overflow(layout.height, bounds.size.height),
+        }
+    })
+}
+
 /// Adds a scrollbar to the left or bottom side of a rectangle.
 /// TODO: make styling configurable (like the width / style of the scrollbar)
 fn push_scrollbar(
@@ -912,8 +1416,13 @@ fn push_background(
             builder.push_gradient(&info, gradient, bounds.size, LayoutSize::zero());
         },
         Background::Image(css_image_id) => {
-            if let Some(image_id) = app_resources.css_ids_to_image_ids.get(&css_image_id.0) {
-                push_image(info, builder, bounds, app_resources, image_id);
+            use images::ImageState::*;
+            // `object-fit` only applies to the content of the rectangle,
+            // background images are always stretched to the bounds
+            let image_state = app_resources.css_ids_to_image_ids.get(&css_image_id.0)
+                .and_then(|image_id| app_resources.images.get(image_id));
+            if let Some(Uploaded(image_info)) = image_state {
+                push_fitted_image(info, builder, bounds, bounds, image_info.key, AlphaType::PremultipliedAlpha);
             }
         },
         Background::NoBackground => { },
@@ -925,6 +1434,7 @@ fn push_image(
     info: &PrimitiveInfo<LayoutPixel>,
     builder: &mut DisplayListBuilder,
     bounds: &TypedRect<f32, LayoutPixel>,
+    style: &RectStyle,
     app_resources: &AppResources,
     image_id: &ImageId)
 -> Option<OverflowInfo>
@@ -935,58 +1445,84 @@ fn push_image(
 
     match image_info {
         Uploaded(image_info) => {
-
-            let mut image_bounds = *bounds;
-
-            let image_key = image_info.key;
             let image_size = image_info.descriptor.size;
+            let image_bounds = get_object_fit_bounds(
+                bounds,
+                LayoutSize::new(image_size.width as f32, image_size.height as f32),
+                style.object_fit.unwrap_or_default(),
+                style.object_position.unwrap_or_default());
 
-            // For now, adjust the width and height based on the
-            if image_size.width < bounds.size.width as u32 && image_size.height < bounds.size.height as u32 {
-                image_bounds.size.width = image_size.width as f32;
-                image_bounds.size.height = image_size.height as f32;
-            } else {
-                let scale_factor_w = image_size.width as f32 / bounds.size.width;
-                let scale_factor_h = image_size.height as f32 / bounds.size.height;
-
-                if image_size.width < bounds.size.width as u32 {
-                    // if the image fits horizontally
-                    image_bounds.size.width = image_size.width as f32;
-                    image_bounds.size.height = image_size.height as f32 * scale_factor_w;
-                } else if image_size.height < bounds.size.height as u32 {
-                    // if the image fits vertically
-                    image_bounds.size.width = image_size.width as f32 * scale_factor_h;
-                    image_bounds.size.height = image_size.height as f32;
-                } else {
-                    // image fits neither horizontally nor vertically
-                    let scale_factor_smaller = scale_factor_w.max(scale_factor_w);
-                    let new_width = image_size.width as f32 * scale_factor_smaller;
-                    let new_height = image_size.height as f32 * scale_factor_smaller;
-                    image_bounds.size.width = new_width;
-                    image_bounds.size.height = new_height;
-                }
-            }
-
-            // Just for testing
-            image_bounds.size.width /= 2.0;
-            image_bounds.size.height /= 2.0;
-
-            builder.push_image(
-                    &info,
-                    image_bounds.size,
-                    LayoutSize::zero(),
-                    ImageRendering::Auto,
-                    AlphaType::PremultipliedAlpha,
-                    image_key,
-                    ColorF::WHITE);
+            push_fitted_image(info, builder, bounds, &image_bounds, image_info.key, AlphaType::PremultipliedAlpha);
         },
         _ => { },
     }
 
-    // TODO: determine if image has overflown its container
+    // The image is clipped to its bounds, so it can't overflow
     None
 }
 
+/// Pushes an image (or an OpenGL texture), which was positioned with `get_object_fit_bounds`,
+/// clipped to the `bounds` of the rectangle
+#[inline]
+fn push_fitted_image(
+    info: &PrimitiveInfo<LayoutPixel>,
+    builder: &mut DisplayListBuilder,
+    bounds: &TypedRect<f32, LayoutPixel>,
+    image_bounds: &TypedRect<f32, LayoutPixel>,
+    image_key: ImageKey,
+    alpha_type: AlphaType)
+{
+    let image_info = LayoutPrimitiveInfo {
+        rect: *image_bounds,
+        clip_rect: info.clip_rect.intersection(bounds).unwrap_or(LayoutRect::zero()),
+        .. *info
+    };
+
+    builder.push_image(
+        &image_info,
+        image_bounds.size,
+        LayoutSize::zero(),
+        ImageRendering::Auto,
+        alpha_type,
+        image_key,
+        ColorF::WHITE);
+}
+
+/// Calculates where the content of an `Image` or a `GlTexture` has to be drawn,
+/// given its natural size and the `object-fit` and `object-position` of the rectangle.
+///
+/// Note: The returned rectangle can be larger than the `bounds` (for `cover` and `none`),
+/// so the content has to be clipped to the `bounds`.
+fn get_object_fit_bounds(
+    bounds: &TypedRect<f32, LayoutPixel>,
+    content_size: TypedSize2D<f32, LayoutPixel>,
+    object_fit: LayoutObjectFit,
+    object_position: LayoutObjectPosition)
+-> TypedRect<f32, LayoutPixel>
+{
+    if content_size.width <= 0.0 || content_size.height <= 0.0 {
+        return *bounds;
+    }
+
+    let scale_x = bounds.size.width / content_size.width;
+    let scale_y = bounds.size.height / content_size.height;
+
+    let scale = match object_fit {
+        LayoutObjectFit::Fill => return *bounds,
+        LayoutObjectFit::Contain => scale_x.min(scale_y),
+        LayoutObjectFit::Cover => scale_x.max(scale_y),
+        LayoutObjectFit::None => 1.0,
+        LayoutObjectFit::ScaleDown => scale_x.min(scale_y).min(1.0),
+    };
+
+    let size = LayoutSize::new(content_size.width * scale, content_size.height * scale);
+    let origin = LayoutPoint::new(
+        bounds.origin.x + object_position.horizontal.resolve(bounds.size.width - size.width),
+        bounds.origin.y + object_position.vertical.resolve(bounds.size.height - size.height));
+
+    TypedRect::new(origin, size)
+}
+
 #[inline]
 fn push_border(
     info: &PrimitiveInfo<LayoutPixel>,
@@ -1007,6 +1543,7 @@ fn push_border(
 fn push_font(
     font_id: &FontId,
     font_size_app_units: Au,
+    render_options: &FontRenderOptions,
     resource_updates: &mut Vec<ResourceUpdate>,
     app_resources: &mut AppResources,
     render_api: &RenderApi)
@@ -1027,7 +1564,8 @@ fn push_font(
         FontState::Uploaded(font_key) => {
             let font_sizes_hashmap = app_resources.fonts.entry(*font_key)
                                      .or_insert(FastHashMap::default());
-            let font_instance_key = font_sizes_hashmap.entry(font_size_app_units)
+            // Every combination of size and rendering options needs its own font instance
+            let font_instance_key = font_sizes_hashmap.entry((font_size_app_units, *render_options))
                 .or_insert_with(|| {
                     let f_instance_key = render_api.generate_font_instance_key();
                     resource_updates.push(ResourceUpdate::AddFontInstance(
@@ -1035,8 +1573,8 @@ fn push_font(
                             key: f_instance_key,
                             font_key: *font_key,
                             glyph_size: font_size_app_units,
-                            options: None,
-                            platform_options: None,
+                            options: Some(render_options.get_instance_options()),
+                            platform_options: render_options.get_platform_options(),
                             variations: Vec::new(),
                         }
                     ));
@@ -1077,8 +1615,8 @@ fn determine_text_alignment<'a>(rect: &DisplayRectangle<'a>)
         use css_parser::LayoutJustifyContent;
         // Horizontal text alignment
         match justify_content {
-            LayoutJustifyContent::Start => horz_alignment = TextAlignmentHorz::Left,
-            LayoutJustifyContent::End => horz_alignment = TextAlignmentHorz::Right,
+            LayoutJustifyContent::Start => horz_alignment = TextAlignmentHorz::Start,
+            LayoutJustifyContent::End => horz_alignment = TextAlignmentHorz::End,
             _ => horz_alignment = TextAlignmentHorz::Center,
         }
     }
@@ -1088,11 +1626,17 @@ fn determine_text_alignment<'a>(rect: &DisplayRectangle<'a>)
         horz_alignment = text_align;
     }
 
-    (horz_alignment, vert_alignment)
+    if let Some(vertical_align) = rect.style.vertical_align {
+        // Vertical text alignment with higher priority
+        vert_alignment = vertical_align;
+    }
+
+    // `start` / `end` depend on the direction of the text
+    (horz_alignment.resolve(rect.layout.get_text_direction()), vert_alignment)
 }
 
 /// Populate the CSS style properties of the `DisplayRectangle`
-fn populate_css_properties(rect: &mut DisplayRectangle, css_overrides: &FastHashMap<String, ParsedCssProperty>)
+pub(crate) fn populate_css_properties(rect: &mut DisplayRectangle, css_overrides: &FastHashMap<String, ParsedCssProperty>)
 {
     use css_parser::ParsedCssProperty::{self, *};
 
@@ -1113,11 +1657,32 @@ fn populate_css_properties(rect: &mut DisplayRectangle, css_overrides: &FastHash
                 }
             },
             TextAlign(ta)               => { rect.style.text_align = Some(*ta);                     },
+            VerticalAlign(va)           => { rect.style.vertical_align = Some(*va);                 },
+            TextWrap(tw)                => { rect.style.text_wrap = Some(*tw);                      },
+            WhiteSpace(ws)              => { rect.style.white_space = Some(*ws);                    },
+            WordBreak(wb)               => { rect.style.word_break = Some(*wb);                     },
+            OverflowWrap(ow)            => { rect.style.overflow_wrap = Some(*ow);                  },
+            TextOverflow(to)            => { rect.style.text_overflow = Some(to.clone());           },
+            LineClamp(lc)               => { rect.style.line_clamp = Some(*lc);                     },
             BoxShadow(opt_box_shadow)   => { rect.style.box_shadow = *opt_box_shadow;               },
             LineHeight(lh)              => { rect.style.line_height = Some(*lh);                     },
-
-            Width(w)                    => { rect.layout.width = Some(*w);                          },
-            Height(h)                   => { rect.layout.height = Some(*h);                         },
+            LetterSpacing(ls)           => { rect.style.letter_spacing = Some(*ls);                  },
+            TabSize(ts)                 => { rect.style.tab_size = Some(*ts);                        },
+            TabStops(ts)                => { rect.style.tab_stops = Some(ts.clone());                },
+            FontKerning(fk)             => { rect.style.font_kerning = Some(*fk);                    },
+            FontFeatureSettings(ffs)    => { rect.style.font_feature_settings = Some(ffs.clone());   },
+            FontSmoothing(fs)           => { rect.style.font_smoothing = Some(*fs);                  },
+            FontHinting(fh)             => { rect.style.font_hinting = Some(*fh);                    },
+            FontSubpixelPositioning(sp) => { rect.style.font_subpixel_positioning = Some(*sp);       },
+            FontLcdFilter(lf)           => { rect.style.font_lcd_filter = Some(*lf);                 },
+            ObjectFit(of)               => { rect.style.object_fit = Some(*of);                      },
+            ObjectPosition(op)          => { rect.style.object_position = Some(*op);                 },
+
+            Width(w)                    => { rect.layout.width = Some(*w); rect.layout.intrinsic_width = None;          },
+            Height(h)                   => { rect.layout.height = Some(*h); rect.layout.intrinsic_height = None;        },
+            IntrinsicWidth(w)           => { rect.layout.intrinsic_width = Some(*w); rect.layout.width = None;          },
+            IntrinsicHeight(h)          => { rect.layout.intrinsic_height = Some(*h); rect.layout.height = None;        },
+            AspectRatio(ar)             => { rect.layout.aspect_ratio = Some(*ar);                  },
             MinWidth(mw)                => { rect.layout.min_width = Some(*mw);                     },
             MinHeight(mh)               => { rect.layout.min_height = Some(*mh);                    },
             MaxWidth(mw)                => { rect.layout.max_width = Some(*mw);                     },
@@ -1130,13 +1695,31 @@ fn populate_css_properties(rect: &mut DisplayRectangle, css_overrides: &FastHash
             Left(l)                     => { rect.layout.left = Some(*l);                           },
 
             // TODO: merge new padding with existing padding
-            Padding(p)                  => { rect.layout.padding = Some(*p);                        },
+            Padding(p)                  => {
+                // The shorthand overrides the logical padding that was set before
+                rect.layout.padding = Some(*p);
+                rect.layout.padding_inline_start = None;
+                rect.layout.padding_inline_end = None;
+            },
+            PaddingInlineStart(p)       => { rect.layout.padding_inline_start = Some(*p);           },
+            PaddingInlineEnd(p)         => { rect.layout.padding_inline_end = Some(*p);             },
+
+            TextDirection(d)            => { rect.layout.text_direction = Some(*d);                 },
 
             FlexWrap(w)                 => { rect.layout.wrap = Some(*w);                           },
             FlexDirection(d)            => { rect.layout.direction = Some(*d);                      },
             JustifyContent(j)           => { rect.layout.justify_content = Some(*j);                },
             AlignItems(a)               => { rect.layout.align_items = Some(*a);                    },
             AlignContent(a)             => { rect.layout.align_content = Some(*a);                  },
+
+            Display(d)                  => { rect.layout.display = Some(*d);                        },
+            GridTemplateColumns(c)      => { rect.layout.grid_template_columns = Some(c.clone());   },
+            GridTemplateRows(r)         => { rect.layout.grid_template_rows = Some(r.clone());      },
+            GridTemplateAreas(a)        => { rect.layout.grid_template_areas = Some(a.clone());     },
+            Gap(g)                      => { rect.layout.gap = Some(*g);                            },
+            GridColumn(c)               => { rect.layout.grid_column = Some(*c);                    },
+            GridRow(r)                  => { rect.layout.grid_row = Some(*r);                       },
+            GridArea(a)                 => { rect.layout.grid_area = Some(a.clone());               },
         }
     }
 
@@ -1168,29 +1751,33 @@ fn populate_css_properties(rect: &mut DisplayRectangle, css_overrides: &FastHash
             }
         }
     }
+
+    // The text direction is only known once all properties are applied
+    rect.layout.resolve_logical_padding();
 }
 
-use cassowary::Constraint;
+use cassowary::{Constraint, Expression, Variable};
+
+const WEAK: f64 = 3.0;
+const MEDIUM: f64 = 30.0;
+const STRONG: f64 = 300.0;
+const REQUIRED: f64 = ::std::f64::MAX;
 
 // Returns the constraints for one rectangle
-fn create_layout_constraints<'a, T: Layout>(
+pub(crate) fn create_layout_constraints<'a, T: Layout>(
     node_id: NodeId,
     display_rectangles: &Arena<DisplayRectangle<'a>>,
     dom: &Arena<NodeData<T>>,
-    ui_solver: &UiSolver)
+    ui_solver: &UiSolver,
+    intrinsic_size: Option<IntrinsicSize>)
 -> Vec<Constraint>
 {
     use cassowary::{
         WeightedRelation::{EQ, GE, LE},
     };
     use ui_solver::RectConstraintVariables;
-    use std::f64;
     use css_parser::LayoutDirection::*;
-
-    const WEAK: f64 = 3.0;
-    const MEDIUM: f64 = 30.0;
-    const STRONG: f64 = 300.0;
-    const REQUIRED: f64 = f64::MAX;
+    use css_parser::LayoutIntrinsicSize::{Auto, MinContent, MaxContent, FitContent};
 
     let rect = &display_rectangles[node_id].data;
     let self_rect = ui_solver.get_rect_constraints(node_id).unwrap();
@@ -1201,6 +1788,19 @@ fn create_layout_constraints<'a, T: Layout>(
 
     let window_constraints = ui_solver.get_window_constraints();
 
+    // The containing block of a `position: fixed` rectangle is the window, not the parent
+    let is_fixed = rect.layout.position == Some(LayoutPosition::Fixed);
+
+    // Children of a `display: grid` rectangle are sized by their grid area
+    let is_grid_item = !is_fixed && dom_node.parent.map(|parent| {
+        display_rectangles[parent].data.layout.display == Some(LayoutDisplay::Grid)
+    }).unwrap_or(false);
+
+    // `position: fixed` children don't take up any space in their parent
+    let count_in_flow_children = |parent: NodeId| parent.children(dom)
+        .filter(|child_id| display_rectangles[*child_id].data.layout.position != Some(LayoutPosition::Fixed))
+        .count();
+
     // Insert the max height and width constraints
     //
     // min-width and max-width are stronger than width because
@@ -1210,8 +1810,31 @@ fn create_layout_constraints<'a, T: Layout>(
     if let Some(min_width) = rect.layout.min_width {
         layout_constraints.push(self_rect.width | GE(REQUIRED) | min_width.0.to_pixels());
     }
+    let intrinsic_width = rect.layout.intrinsic_width.unwrap_or_default();
     if let Some(width) = rect.layout.width {
         layout_constraints.push(self_rect.width | EQ(STRONG) | width.0.to_pixels());
+    } else if is_grid_item && intrinsic_width == Auto {
+        // width is determined by the grid area, see create_grid_constraints
+    } else if let Some(intrinsic_size) = intrinsic_size {
+        match intrinsic_width {
+            MinContent => layout_constraints.push(self_rect.width | EQ(STRONG) | intrinsic_size.min_content_width),
+            MaxContent => layout_constraints.push(self_rect.width | EQ(STRONG) | intrinsic_size.max_content_width),
+            Auto | FitContent => {
+                // fit-content = min(max-content, max(min-content, available width))
+                let available_width = dom_node.parent
+                    .filter(|_| !is_fixed)
+                    .and_then(|parent| ui_solver.get_rect_constraints(parent))
+                    .and_then(|parent_rect| Some(parent_rect.width))
+                    .unwrap_or(window_constraints.width_var);
+                layout_constraints.push(self_rect.width | LE(STRONG) | available_width);
+                layout_constraints.push(self_rect.width | GE(STRONG) | intrinsic_size.min_content_width);
+                layout_constraints.push(self_rect.width | EQ(MEDIUM) | intrinsic_size.max_content_width);
+            },
+        }
+    } else if let (Some(aspect_ratio), Some(height)) = (rect.layout.aspect_ratio, rect.layout.height) {
+        layout_constraints.push(self_rect.width | EQ(STRONG) | height.0.to_pixels() * aspect_ratio.0);
+    } else if is_fixed {
+        layout_constraints.push(self_rect.width | EQ(STRONG) | window_constraints.width_var);
     } else {
         if let Some(parent) = dom_node.parent {
             // If the parent has a flex-direction: row, divide the
@@ -1220,7 +1843,7 @@ fn create_layout_constraints<'a, T: Layout>(
             let parent_direction = &display_rectangles[parent].data.layout.direction.unwrap_or_default();
             match parent_direction {
                 Row | RowReverse => {
-                    let num_children = parent.children(dom).count();
+                    let num_children = count_in_flow_children(parent);
                     layout_constraints.push(self_rect.width | EQ(STRONG) | parent_rect.width / (num_children as f32));
                     layout_constraints.push(self_rect.width | EQ(WEAK) | parent_rect.width);
                 },
@@ -1240,8 +1863,37 @@ fn create_layout_constraints<'a, T: Layout>(
     if let Some(min_height) = rect.layout.min_height {
         layout_constraints.push(self_rect.height | GE(REQUIRED) | min_height.0.to_pixels());
     }
+    let intrinsic_height = rect.layout.intrinsic_height.unwrap_or_default();
     if let Some(height) = rect.layout.height {
         layout_constraints.push(self_rect.height | EQ(STRONG) | height.0.to_pixels());
+    } else if is_grid_item && intrinsic_height == Auto {
+        // height is determined by the grid area, see create_grid_constraints
+    } else if let Some(intrinsic_size) = intrinsic_size {
+        // The height of text doesn't depend on min- or max-content, since the
+        // content height is always the height of the (wrapped) text
+        let is_fit_content = intrinsic_height == Auto || intrinsic_height == FitContent;
+        let strength = if is_fit_content { MEDIUM } else { STRONG };
+        match intrinsic_size.aspect_ratio {
+            Some(ratio) => {
+                // Images keep their aspect ratio
+                let (padding_horz, padding_vert) = intrinsic_size.padding;
+                let content_width = self_rect.width - padding_horz as f64;
+                layout_constraints.push(self_rect.height | EQ(strength) | content_width * ratio as f64 + padding_vert as f64);
+            },
+            None => layout_constraints.push(self_rect.height | EQ(strength) | intrinsic_size.content_height),
+        }
+        if is_fit_content {
+            let available_height = dom_node.parent
+                .filter(|_| !is_fixed)
+                .and_then(|parent| ui_solver.get_rect_constraints(parent))
+                .and_then(|parent_rect| Some(parent_rect.height))
+                .unwrap_or(window_constraints.height_var);
+            layout_constraints.push(self_rect.height | LE(STRONG) | available_height);
+        }
+    } else if let Some(aspect_ratio) = rect.layout.aspect_ratio {
+        layout_constraints.push(self_rect.height | EQ(STRONG) | self_rect.width / aspect_ratio.0);
+    } else if is_fixed {
+        layout_constraints.push(self_rect.height | EQ(STRONG) | window_constraints.height_var);
     } else {
         if let Some(parent) = dom_node.parent {
             // If the parent has a flex-direction: column, divide the
@@ -1253,7 +1905,7 @@ fn create_layout_constraints<'a, T: Layout>(
                     layout_constraints.push(self_rect.height | EQ(STRONG) | parent_rect.height);
                 },
                 Column | ColumnReverse => {
-                    let num_children = parent.children(dom).count();
+                    let num_children = count_in_flow_children(parent);
                     layout_constraints.push(self_rect.height | EQ(STRONG) | parent_rect.height / (num_children as f32));
                     layout_constraints.push(self_rect.height | EQ(WEAK) | parent_rect.height);
                 }
@@ -1272,10 +1924,12 @@ fn create_layout_constraints<'a, T: Layout>(
         layout_constraints.push(self_rect.left | EQ(REQUIRED) | 0.0);
     }
 
-    // Node has children: Push the constraints for `flex-direction`
-    if dom_node.first_child.is_some() {
+    // Node has children: Push the constraints for `display: grid` or `flex-direction`
+    if dom_node.first_child.is_some() && rect.layout.display == Some(LayoutDisplay::Grid) {
+        layout_constraints.extend(create_grid_constraints(node_id, display_rectangles, dom, ui_solver));
+    } else if dom_node.first_child.is_some() {
 
-        let direction = rect.layout.direction.unwrap_or_default();
+        let direction = rect.layout.get_flex_direction();
 
         let mut next_child_id = dom_node.first_child;
         let mut previous_child: Option<RectConstraintVariables> = None;
@@ -1286,6 +1940,13 @@ fn create_layout_constraints<'a, T: Layout>(
             let child = &display_rectangles[child_id].data;
             let child_rect = ui_solver.get_rect_constraints(child_id).unwrap();
 
+            // position: fixed takes the child out of the flow of its siblings,
+            // it is positioned relative to the window instead (see below)
+            if child.layout.position == Some(LayoutPosition::Fixed) {
+                next_child_id = dom[child_id].next_sibling;
+                continue;
+            }
+
             let should_respect_relative_positioning = child.layout.position == Some(LayoutPosition::Relative);
 
             let (relative_top, relative_left, relative_right, relative_bottom) = if should_respect_relative_positioning {(
@@ -1357,13 +2018,480 @@ fn create_layout_constraints<'a, T: Layout>(
         }
     }
 
+    // Handle position: fixed
+    if is_fixed {
+
+        let top = rect.layout.top.and_then(|top| Some(top.0.to_pixels()));
+        let left = rect.layout.left.and_then(|left| Some(left.0.to_pixels()));
+        let right = rect.layout.right.and_then(|right| Some(right.0.to_pixels()));
+        let bottom = rect.layout.bottom.and_then(|bottom| Some(bottom.0.to_pixels()));
+
+        // The window always starts at (0, 0), `right` and `bottom` are only
+        // respected if `left` and `top` are not set
+        match (top, bottom) {
+            (None, Some(bottom)) => layout_constraints.push((self_rect.top + self_rect.height) | EQ(REQUIRED) | window_constraints.height_var - bottom),
+            (top, _) => layout_constraints.push(self_rect.top | EQ(REQUIRED) | top.unwrap_or(0.0)),
+        }
+
+        match (left, right) {
+            (None, Some(right)) => layout_constraints.push((self_rect.left + self_rect.width) | EQ(REQUIRED) | window_constraints.width_var - right),
+            (left, _) => layout_constraints.push(self_rect.left | EQ(REQUIRED) | left.unwrap_or(0.0)),
+        }
+    }
+
+    layout_constraints
+}
+
+/// Returns the rectangles whose layout constraints have to be rebuilt: all rectangles that
+/// were added or changed in the DOM or whose layout-relevant CSS properties have changed since
+/// the last frame, plus the rectangles whose constraints depend on them (the parent, the siblings
+/// and the children). Also stores the layout inputs of all rectangles for the next frame.
+fn get_dirty_rects<'a, T: Layout>(
+    display_rectangles: &Arena<DisplayRectangle<'a>>,
+    dom: &Arena<NodeData<T>>,
+    changeset: &DomChangeSet,
+    ui_solver: &mut UiSolver,
+    app_resources: &AppResources)
+-> BTreeSet<NodeId>
+{
+    let mut changed_rects = changeset.added_nodes.keys().cloned().collect::<BTreeSet<NodeId>>();
+    // Rectangles whose `position` changed (absolutely positioned descendants may now be relative
+    // to a different ancestor) or whose width changed (descendant text may wrap differently,
+    // see `get_available_width`)
+    let mut changed_subtrees = BTreeSet::<NodeId>::new();
+
+    for rect_idx in display_rectangles.linear_iter() {
+        let rect = &display_rectangles[rect_idx].data;
+        let content = get_layout_content(&dom[rect_idx].data.node_type, app_resources);
+        if let Some(descendants_changed) = ui_solver.update_layout_inputs(rect_idx, &rect.layout, &rect.style, content) {
+            changed_rects.insert(rect_idx);
+            if descendants_changed {
+                changed_subtrees.insert(rect_idx);
+            }
+        }
+    }
+
+    let mut dirty_rects = BTreeSet::<NodeId>::new();
+
+    for rect_idx in changed_rects {
+        dirty_rects.insert(rect_idx);
+        dirty_rects.extend(rect_idx.children(dom));
+        if let Some(parent) = dom[rect_idx].parent {
+            dirty_rects.insert(parent);
+            dirty_rects.extend(parent.children(dom));
+        }
+    }
+
+    for rect_idx in changed_subtrees {
+        dirty_rects.extend(rect_idx.descendants(dom));
+    }
+
+    dirty_rects
+}
+
+/// Returns the content of a `Text` or `Image` that its intrinsic size depends on: if the
+/// string of a `TextId` changes or an image is loaded, the node data stays the same
+fn get_layout_content<T: Layout>(node_type: &NodeType<T>, app_resources: &AppResources) -> LayoutContent {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use images::ImageState;
+
+    match node_type {
+        Text(text_id) => {
+            let mut hasher = DefaultHasher::new();
+            app_resources.text_cache.string_cache.get(text_id).hash(&mut hasher);
+            LayoutContent::Text(hasher.finish())
+        },
+        Image(image_id) => {
+            let image_size = app_resources.images.get(image_id).and_then(|image_state| match image_state {
+                ImageState::Uploaded(image_info) => Some(image_info.descriptor.size),
+                ImageState::ReadyForUpload((_, descriptor)) => Some(descriptor.size),
+                ImageState::AboutToBeDeleted(_) => None,
+            });
+            LayoutContent::Image(image_size.and_then(|size| Some((size.width as f32, size.height as f32))))
+        },
+        _ => LayoutContent::None,
+    }
+}
+
+/// Content-based size of a rectangle (including its padding), used
+/// for `width / height: auto`, `min-content`, `max-content` and `fit-content`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct IntrinsicSize {
+    pub(crate) min_content_width: f32,
+    pub(crate) max_content_width: f32,
+    pub(crate) content_height: f32,
+    /// `height / width` of the content, if the content should keep its aspect ratio
+    /// (images or rectangles with an `aspect-ratio`)
+    pub(crate) aspect_ratio: Option<f32>,
+    /// Horizontal and vertical padding
+    pub(crate) padding: (f32, f32),
+}
+
+/// Returns the width that is available to a rectangle without an explicit `width`, i.e. the width
+/// that its text is wrapped at when measuring its height: the content width of the nearest ancestor
+/// with a `width` or `max-width` (or the window width), limited by the `max-width` of the rectangle.
+///
+/// The intrinsic size is measured before the layout is solved, so the space that is taken
+/// up by the siblings of the rectangle isn't subtracted.
+fn get_available_width<'a>(node_id: NodeId, display_rectangles: &Arena<DisplayRectangle<'a>>, window_width: f32) -> f32 {
+
+    let get_max_width = |layout: &RectLayout| {
+        let width = layout.width.and_then(|width| Some(width.0.to_pixels()));
+        let max_width = layout.max_width.and_then(|max_width| Some(max_width.0.to_pixels()));
+        match (width, max_width) {
+            (Some(width), Some(max_width)) => Some(width.min(max_width)),
+            (width, max_width) => width.or(max_width),
+        }
+    };
+
+    let parent_content_width = node_id.ancestors(display_rectangles).skip(1).filter_map(|ancestor| {
+        let layout = &display_rectangles[ancestor].data.layout;
+        let padding_horz = layout.padding.and_then(|p| Some(
+            p.left.and_then(|l| Some(l.to_pixels())).unwrap_or(0.0) + p.right.and_then(|r| Some(r.to_pixels())).unwrap_or(0.0)
+        )).unwrap_or(0.0);
+        get_max_width(layout).and_then(|width| Some((width - padding_horz).max(0.0)))
+    }).next().unwrap_or(window_width);
+
+    match display_rectangles[node_id].data.layout.max_width {
+        Some(max_width) => parent_content_width.min(max_width.0.to_pixels()),
+        None => parent_content_width,
+    }
+}
+
+/// Measures the content of a `Label`, `Text`, `RichText` or `Image`. Returns `None` for all other
+/// node types (their size is determined by their parent) or if the content can't be measured.
+/// Text is wrapped at the `width` of the rectangle or, if it has none, at the `available_width`.
+fn get_intrinsic_size<'a, T: Layout>(rect: &DisplayRectangle<'a>, node_type: &NodeType<T>, app_resources: &AppResources, available_width: f32)
+-> Option<IntrinsicSize>
+{
+    use images::ImageState;
+
+    let padding_horz = rect.layout.padding.and_then(|p| Some(
+        p.left.and_then(|l| Some(l.to_pixels())).unwrap_or(0.0) + p.right.and_then(|r| Some(r.to_pixels())).unwrap_or(0.0)
+    )).unwrap_or(0.0);
+    let padding_vert = rect.layout.padding.and_then(|p| Some(
+        p.top.and_then(|t| Some(t.to_pixels())).unwrap_or(0.0) + p.bottom.and_then(|b| Some(b.to_pixels())).unwrap_or(0.0)
+    )).unwrap_or(0.0);
+
+    // The `aspect-ratio` overrides the natural aspect ratio of the content
+    let css_aspect_ratio = rect.layout.aspect_ratio.and_then(|ratio| Some(1.0 / ratio.0));
+
+    let content_width = rect.layout.width.and_then(|width| Some(width.0.to_pixels())).unwrap_or(available_width) - padding_horz;
+
+    let text_size = match node_type {
+        Label(text) => get_intrinsic_text_size(rect, text, None, app_resources, content_width)?,
+        Text(text_id) => {
+            let text = app_resources.text_cache.string_cache.get(text_id)?;
+            get_intrinsic_text_size(rect, text, Some(text_id), app_resources, content_width)?
+        },
+        RichText(text) => get_intrinsic_rich_text_size(rect, text, app_resources, content_width)?,
+        Image(image_id) => {
+            let image_size = match app_resources.images.get(image_id)? {
+                ImageState::Uploaded(image_info) => image_info.descriptor.size,
+                ImageState::ReadyForUpload((_, descriptor)) => descriptor.size,
+                ImageState::AboutToBeDeleted(_) => return None,
+            };
+            let (width, height) = (image_size.width as f32, image_size.height as f32);
+            return Some(IntrinsicSize {
+                // images can be scaled down, so they have no minimum width
+                min_content_width: padding_horz,
+                max_content_width: width + padding_horz,
+                content_height: height + padding_vert,
+                aspect_ratio: css_aspect_ratio.or(if width > 0.0 { Some(height / width) } else { None }),
+                padding: (padding_horz, padding_vert),
+            });
+        },
+        _ => return None,
+    };
+
+    Some(IntrinsicSize {
+        min_content_width: text_size.min_content_width + padding_horz,
+        max_content_width: text_size.max_content_width + padding_horz,
+        content_height: text_size.content_height + padding_vert,
+        aspect_ratio: css_aspect_ratio,
+        padding: (padding_horz, padding_vert),
+    })
+}
+
+/// Measures the text using the fonts of the `font-family`. The height is calculated
+/// as if the text was wrapped at the `content_width` (the width without the padding).
+fn get_intrinsic_text_size<'a>(
+    rect: &DisplayRectangle<'a>,
+    text: &str,
+    text_id: Option<&TextId>,
+    app_resources: &AppResources,
+    content_width: f32)
+-> Option<TextIntrinsicSize>
+{
+    use text_layout::{self, FontMetrics};
+    use text_shaping::Shaper;
+
+    let font_family = rect.style.font_family.as_ref()?;
+    let font_id = font_family.fonts.get(0)?;
+    let font_size = rect.style.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+    let font_ids = match text_id {
+        Some(text_id) => app_resources.get_text_fallback_chain(font_family, text_id)?,
+        None => app_resources.get_font_fallback_chain(font_family, text),
+    };
+    let target_fonts = font_ids.iter().filter_map(|id| app_resources.get_font(id)).collect::<Vec<_>>();
+    let font_metrics = FontMetrics::new(&target_fonts.get(0)?.0, &font_size, rect.style.line_height).with_tab_options(&TabOptions::new(&rect.style));
+    let shaping_options = ShapingOptions::new(rect.style.font_kerning, rect.style.font_feature_settings.as_ref());
+    let break_options = TextBreakOptions::new(&rect.style);
+    let is_default_options = shaping_options == ShapingOptions::default() && break_options.is_cacheable();
+
+    // Re-use the words of cached texts if possible (cached texts are shaped and split with
+    // the default options and the fallback chain of a single font, separately for each direction)
+    let is_single_font = font_family.fonts.len() == 1 && font_ids[0] == *font_id;
+    let cached_words = text_id
+        .and_then(|text_id| if is_default_options && is_single_font { Some(text_id) } else { None })
+        .and_then(|text_id| app_resources.text_cache.cached_strings.get(text_id))
+        .and_then(|fonts| fonts.get(font_id))
+        .and_then(|font_sizes| font_sizes.get(&(font_size, break_options.direction)));
+
+    let words_owned;
+    let words = match cached_words {
+        Some(words) => words,
+        None => {
+            let shapers = target_fonts.iter().map(|f| Shaper::new(&f.1, &shaping_options)).collect::<Vec<_>>();
+            let fonts = target_fonts.iter().zip(shapers.iter()).map(|(f, shaper)| (&*f.0, shaper)).collect::<Vec<_>>();
+            words_owned = text_layout::split_text_into_words(text, &fonts, font_metrics.font_size_no_line_height, &break_options);
+            &words_owned
+        },
+    };
+
+    let words_spaced;
+    let words = match text_layout::get_letter_spacing_px(rect.style.letter_spacing) {
+        Some(letter_spacing) => {
+            words_spaced = text_layout::with_letter_spacing(words, letter_spacing);
+            &words_spaced
+        },
+        None => words,
+    };
+
+    // `white-space: nowrap / pre` text is only broken at newlines
+    let max_width = if break_options.white_space.wraps() { Some(content_width.max(0.0)) } else { None };
+
+    // `text-overflow` / `line-clamp`: measure the text the way it is truncated when it is laid out
+    let truncation = {
+        let shaper = Shaper::new(&target_fonts[0].1, &shaping_options);
+        text_layout::TextTruncation::new(&TextTruncationOptions::new(&rect.style), break_options.white_space, &*target_fonts[0].0, &shaper, font_metrics.font_size_no_line_height)
+    };
+
+    let words_truncated;
+    let words = match truncation.as_ref().and_then(|truncation| text_layout::truncate_words(words, max_width, truncation, &font_metrics)) {
+        Some(truncated) => {
+            words_truncated = truncated;
+            &words_truncated
+        },
+        None => words,
+    };
+
+    // Break the lines like the text is laid out (`text-align: justify` / `text-wrap: pretty`)
+    let (horz_alignment, _) = determine_text_alignment(rect);
+    let mut line_breaking = LineBreaking::new(horz_alignment, rect.style.text_wrap.unwrap_or_default(), break_options.white_space);
+
+    // The text is truncated at the greedy line breaks (see `text_layout::get_glyphs`)
+    if truncation.is_some() {
+        line_breaking.optimal = false;
+    }
+
+    let mut intrinsic_size = text_layout::get_intrinsic_text_size(words, &font_metrics, max_width, line_breaking);
+
+    if !break_options.white_space.wraps() {
+        intrinsic_size.min_content_width = intrinsic_size.max_content_width;
+    }
+
+    // `overflow-wrap: anywhere` can break the text between any two grapheme clusters,
+    // so the min-content width is the width of the widest grapheme cluster
+    if break_options.overflow_wrap == OverflowWrap::Anywhere && break_options.white_space.wraps() {
+        intrinsic_size.min_content_width = text_layout::break_long_words(words, 0.0).longest_word_width;
+    }
+
+    Some(intrinsic_size)
+}
+
+/// Measures a `RichText` by laying it out without a maximum width and wrapped at the
+/// `content_width` (the width without the padding)
+fn get_intrinsic_rich_text_size<'a>(
+    rect: &DisplayRectangle<'a>,
+    text: &rich_text::RichText,
+    app_resources: &AppResources,
+    content_width: f32)
+-> Option<TextIntrinsicSize>
+{
+    use text_layout::{self, RichTextSpanInfo};
+
+    let span_infos = text.spans.iter().map(|span| Some(RichTextSpanInfo {
+        text: &span.text,
+        font_family: span.style.font_family.as_ref().or(rect.style.font_family.as_ref())?,
+        font_size: span.style.font_size.or(rect.style.font_size).unwrap_or(DEFAULT_FONT_SIZE),
+    })).collect::<Option<Vec<_>>>()?;
+
+    let shaping_options = ShapingOptions::new(rect.style.font_kerning, rect.style.font_feature_settings.as_ref());
+    let break_options = TextBreakOptions::new(&rect.style);
+    let tab_options = TabOptions::new(&rect.style);
+
+    let unwrapped = text_layout::layout_rich_text(app_resources, &span_infos, None, rect.style.line_height, rect.style.letter_spacing, &shaping_options, &break_options, &tab_options);
+
+    // `white-space: nowrap / pre` text is only broken at newlines
+    let max_width = if break_options.white_space.wraps() { Some(content_width.max(0.0)) } else { None };
+
+    let wrapped = max_width.map(|max_width| {
+        text_layout::layout_rich_text(app_resources, &span_infos, Some(max_width), rect.style.line_height, rect.style.letter_spacing, &shaping_options, &break_options, &tab_options)
+    });
+    let content = wrapped.as_ref().unwrap_or(&unwrapped);
+
+    Some(TextIntrinsicSize {
+        min_content_width: if break_options.white_space.wraps() { unwrapped.longest_word_width } else { unwrapped.width },
+        max_content_width: unwrapped.width,
+        content_width: content.width,
+        content_height: content.height,
+        line_count: content.lines.len(),
+    })
+}
+
+/// Returns the constraints for the children of a `display: grid` rectangle,
+/// i.e. the sizes of the rows / columns and the position of every child
+fn create_grid_constraints<'a, T: Layout>(
+    node_id: NodeId,
+    display_rectangles: &Arena<DisplayRectangle<'a>>,
+    dom: &Arena<NodeData<T>>,
+    ui_solver: &UiSolver)
+-> Vec<Constraint>
+{
+    use cassowary::WeightedRelation::EQ;
+    use grid_layout::{GridItem, place_grid_items};
+
+    let rect = &display_rectangles[node_id].data;
+    let self_rect = ui_solver.get_rect_constraints(node_id).unwrap();
+
+    let column_templates = rect.layout.grid_template_columns.as_ref().and_then(|c| Some(&c.0[..])).unwrap_or(&[]);
+    let row_templates = rect.layout.grid_template_rows.as_ref().and_then(|r| Some(&r.0[..])).unwrap_or(&[]);
+    let (row_gap, column_gap) = rect.layout.gap.and_then(|gap| Some((gap.row.to_pixels(), gap.column.to_pixels()))).unwrap_or((0.0, 0.0));
+
+    // position: fixed children are not part of the grid
+    let children = node_id.children(dom)
+        .filter(|child_id| display_rectangles[*child_id].data.layout.position != Some(LayoutPosition::Fixed))
+        .collect::<Vec<NodeId>>();
+
+    let items = children.iter().map(|child_id| {
+        let child_layout = &display_rectangles[*child_id].data.layout;
+        GridItem {
+            column: child_layout.grid_column.and_then(|c| Some(c.0)),
+            row: child_layout.grid_row.and_then(|r| Some(r.0)),
+            area: child_layout.grid_area.as_ref().and_then(|a| Some(a.0.as_str())),
+        }
+    }).collect::<Vec<GridItem>>();
+
+    let placement = place_grid_items(&items, column_templates.len(), row_templates.len(), rect.layout.grid_template_areas.as_ref());
+
+    let mut layout_constraints = Vec::new();
+
+    let columns = create_grid_track_constraints(column_templates, placement.num_columns, self_rect.width, column_gap, &mut layout_constraints);
+    let rows = create_grid_track_constraints(row_templates, placement.num_rows, self_rect.height, row_gap, &mut layout_constraints);
+
+    for (child_id, cell) in children.iter().zip(placement.cells.iter()) {
+
+        let child = &display_rectangles[*child_id].data;
+        let child_rect = ui_solver.get_rect_constraints(*child_id).unwrap();
+
+        layout_constraints.push(child_rect.left | EQ(MEDIUM) | self_rect.left + grid_track_span(&columns, 0, cell.column_start, column_gap, true));
+        layout_constraints.push(child_rect.top | EQ(MEDIUM) | self_rect.top + grid_track_span(&rows, 0, cell.row_start, row_gap, true));
+
+        // Children without an explicit size stretch over their whole grid area
+        if child.layout.width.is_none() && child.layout.intrinsic_width.unwrap_or_default() == LayoutIntrinsicSize::Auto {
+            layout_constraints.push(child_rect.width | EQ(STRONG) | grid_track_span(&columns, cell.column_start, cell.column_end, column_gap, false));
+        }
+        if child.layout.height.is_none() && child.layout.intrinsic_height.unwrap_or_default() == LayoutIntrinsicSize::Auto {
+            layout_constraints.push(child_rect.height | EQ(STRONG) | grid_track_span(&rows, cell.row_start, cell.row_end, row_gap, false));
+        }
+    }
+
     layout_constraints
 }
 
+/// Creates one variable per grid track (row or column) and pushes the constraints for
+/// the size of each track. The tracks (plus the gaps) fill up the `container_size`.
+///
+/// `auto` tracks are currently treated the same as `1fr` tracks.
+fn create_grid_track_constraints(
+    templates: &[GridTrackSize],
+    num_tracks: usize,
+    container_size: Variable,
+    gap: f32,
+    layout_constraints: &mut Vec<Constraint>)
+-> Vec<Variable>
+{
+    use cassowary::WeightedRelation::{EQ, GE, LE};
+    use css_parser::{GridTrackSize::*, GridTrackBreadth::*};
+
+    let tracks = (0..num_tracks).map(|_| Variable::new()).collect::<Vec<Variable>>();
+
+    if tracks.is_empty() {
+        return tracks;
+    }
+
+    // Size of `1fr`, i.e. the space that is left over after the fixed tracks
+    let fraction = Variable::new();
+    layout_constraints.push(fraction | GE(REQUIRED) | 0.0);
+
+    for (track_idx, track) in tracks.iter().enumerate() {
+
+        layout_constraints.push(*track | GE(REQUIRED) | 0.0);
+
+        // Implicitly created tracks are sized `auto`
+        match templates.get(track_idx).cloned().unwrap_or_default() {
+            Breadth(Fixed(size)) => layout_constraints.push(*track | EQ(STRONG) | size.to_pixels()),
+            Breadth(Fraction(f)) => layout_constraints.push(*track | EQ(STRONG) | fraction * f as f64),
+            Breadth(Auto) => layout_constraints.push(*track | EQ(STRONG) | fraction),
+            MinMax(min, max) => {
+                let min_size = match min {
+                    Fixed(size) => size.to_pixels(),
+                    Fraction(_) | Auto => 0.0,
+                };
+                layout_constraints.push(*track | GE(REQUIRED) | min_size);
+                match max {
+                    Fixed(size) => {
+                        // minmax(200px, 100px) is treated as 200px
+                        if size.to_pixels() >= min_size {
+                            layout_constraints.push(*track | LE(REQUIRED) | size.to_pixels());
+                        }
+                        layout_constraints.push(*track | EQ(MEDIUM) | size.to_pixels());
+                    },
+                    Fraction(f) => layout_constraints.push(*track | EQ(STRONG) | fraction * f as f64),
+                    Auto => layout_constraints.push(*track | EQ(STRONG) | fraction),
+                }
+            },
+        }
+    }
+
+    let total_gap = gap as f64 * (num_tracks - 1) as f64;
+    let tracks_size = tracks.iter().fold(Expression::from_constant(total_gap), |size, track| size + *track);
+    layout_constraints.push(tracks_size | EQ(MEDIUM) | container_size);
+
+    tracks
+}
+
+/// Returns the size of the tracks from `start` to `end` (exclusive), including the gaps between
+/// them. If `trailing_gap` is set, the gap after the last track is included, too (used for offsets).
+fn grid_track_span(tracks: &[Variable], start: usize, end: usize, gap: f32, trailing_gap: bool)
+-> Expression
+{
+    let num_gaps = match (end.saturating_sub(start), trailing_gap) {
+        (0, _) => 0,
+        (n, true) => n,
+        (n, false) => n - 1,
+    };
+
+    tracks[start.min(tracks.len())..end.min(tracks.len())].iter()
+        .fold(Expression::from_constant(gap as f64 * num_gaps as f64), |size, track| size + *track)
+}
+
 /// Subtracts the padding from the bounds, returning the new bounds
 ///
 /// Warning: The resulting rectangle may have negative width or height
-fn subtract_padding(bounds: &TypedRect<f32, LayoutPixel>, padding: &LayoutPadding)
+pub(crate) fn subtract_padding(bounds: &TypedRect<f32, LayoutPixel>, padding: &LayoutPadding)
 -> TypedRect<f32, LayoutPixel>
 {
     let top     = padding.top.and_then(|top| Some(top.to_pixels())).unwrap_or(0.0);
@@ -1402,6 +2530,85 @@ fn get_nearest_positioned_ancestor<'a>(start_node_id: NodeId, arena: &Arena<Disp
     None
 }
 
+#[test]
+fn test_object_fit_bounds() {
+    let bounds = LayoutRect::new(LayoutPoint::new(0.0, 0.0), LayoutSize::new(200.0, 100.0));
+    let content_size = LayoutSize::new(100.0, 100.0);
+    let center = LayoutObjectPosition::default();
+
+    let contain = get_object_fit_bounds(&bounds, content_size, LayoutObjectFit::Contain, center);
+    assert_eq!(contain, LayoutRect::new(LayoutPoint::new(50.0, 0.0), LayoutSize::new(100.0, 100.0)));
+
+    let cover = get_object_fit_bounds(&bounds, content_size, LayoutObjectFit::Cover, center);
+    assert_eq!(cover, LayoutRect::new(LayoutPoint::new(0.0, -50.0), LayoutSize::new(200.0, 200.0)));
+
+    let fill = get_object_fit_bounds(&bounds, content_size, LayoutObjectFit::Fill, center);
+    assert_eq!(fill, bounds);
+
+    let top_left = LayoutObjectPosition {
+        horizontal: ObjectPositionComponent::Percentage(PercentageValue { number: 0.0 }),
+        vertical: ObjectPositionComponent::Percentage(PercentageValue { number: 0.0 }),
+    };
+    let scale_down = get_object_fit_bounds(&bounds, LayoutSize::new(50.0, 20.0), LayoutObjectFit::ScaleDown, top_left);
+    assert_eq!(scale_down, LayoutRect::new(LayoutPoint::new(0.0, 0.0), LayoutSize::new(50.0, 20.0)));
+}
+
+/// Measures the layout time of a DOM with 10 000 nodes: the first frame (all constraints are
+/// built), a frame without any changes and a frame in which only a color has changed.
+///
+/// Fails (and reports the timings) if a frame without layout changes isn't faster than the first one.
+/// Run with `cargo test --release bench_incremental_relayout -- --ignored`
+#[test]
+#[ignore]
+fn bench_incremental_relayout_10k_nodes() {
+    use std::time::{Duration, Instant};
+    use dom::Dom;
+
+    struct TestLayout { }
+
+    impl Layout for TestLayout {
+        fn layout(&self) -> Dom<Self> {
+            Dom::new(NodeType::Div)
+        }
+    }
+
+    // 100 rows with 100 cells each
+    fn frame(css: &mut Css, ui_solver: &mut UiSolver, app_resources: &AppResources, window_size: &LogicalSize) -> Duration {
+        let start = Instant::now();
+        let dom = (0..100).map(|_| {
+            (0..100).map(|_| Dom::new(NodeType::Div).with_class("cell")).collect::<Dom<TestLayout>>().with_class("row")
+        }).collect::<Dom<TestLayout>>();
+        let ui_state = UiState::from_dom(dom);
+        let ui_description = UiDescription::from_ui_state(&ui_state, css);
+        let display_list = DisplayList::new_from_ui_description(&ui_description, &ui_state);
+        display_list.layout(ui_solver, css, app_resources, false, window_size);
+        start.elapsed()
+    }
+
+    fn as_millis(duration: Duration) -> f64 {
+        duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+    }
+
+    let mut css = Css::new_from_str("
+        .row { flex-direction: row; }
+        .cell { background-color: [[ cell_color | #ffffff ]]; }
+    ").unwrap();
+    let app_resources = AppResources::default();
+    let window_size = LogicalSize::new(1000.0, 1000.0);
+    let mut ui_solver = UiSolver::new(&window_size, LayoutEngine::Cassowary);
+
+    let full_layout = frame(&mut css, &mut ui_solver, &app_resources, &window_size);
+    let unchanged = frame(&mut css, &mut ui_solver, &app_resources, &window_size);
+    css.dynamic_css_overrides.insert(String::from("cell_color"), ParsedCssProperty::BackgroundColor(BackgroundColor(ColorU { r: 255, g: 0, b: 0, a: 255 })));
+    let color_changed = frame(&mut css, &mut ui_solver, &app_resources, &window_size);
+
+    // Neither of the following frames may rebuild the constraints
+    assert!(unchanged < full_layout,
+        "unchanged frame: {:.1} ms, full layout: {:.1} ms", as_millis(unchanged), as_millis(full_layout));
+    assert!(color_changed < full_layout,
+        "color changed frame: {:.1} ms, full layout: {:.1} ms", as_millis(color_changed), as_millis(full_layout));
+}
+
 // Empty test, for some reason codecov doesn't detect any files (and therefore
 // doesn't report codecov % correctly) except if they have at least one test in
 // the file. This is an empty test, which should be updated later on
@@ -12,6 +12,7 @@ use {
     images::ImageId,
     cache::DomHash,
     text_cache::TextId,
+    rich_text::RichText,
     traits::Layout,
     app_state::AppState,
     id_tree::{NodeId, Node, Arena},
@@ -81,6 +82,8 @@ pub enum NodeType<T: Layout> {
     Label(String),
     /// Larger amount of text, that has to be cached
     Text(TextId),
+    /// Text that consists of multiple spans with different styles, laid out as one paragraph
+    RichText(RichText),
     /// An image that is rendered by webrender. The id is aquired by the
     /// `AppState::add_image()` function
     Image(ImageId),
@@ -163,6 +166,7 @@ impl<T: Layout> fmt::Debug for NodeType<T> {
             Div => write!(f, "NodeType::Div"),
             Label(a) => write!(f, "NodeType::Label {{ {:?} }}", a),
             Text(a) => write!(f, "NodeType::Text {{ {:?} }}", a),
+            RichText(a) => write!(f, "NodeType::RichText {{ {:?} }}", a),
             Image(a) => write!(f, "NodeType::Image {{ {:?} }}", a),
             GlTexture((ptr, cb)) => write!(f, "NodeType::GlTexture {{ ptr: {:?}, callback: {:?} }}", ptr, cb),
             IFrame((ptr, cb)) => write!(f, "NodeType::IFrame {{ ptr: {:?}, callback: {:?} }}", ptr, cb),
@@ -177,6 +181,7 @@ impl<T: Layout> Clone for NodeType<T> {
             Div => Div,
             Label(a) => Label(a.clone()),
             Text(a) => Text(a.clone()),
+            RichText(a) => RichText(a.clone()),
             Image(a) => Image(a.clone()),
             GlTexture((ptr, a)) => GlTexture((ptr.clone(), a.clone())),
             IFrame((ptr, a)) => IFrame((ptr.clone(), a.clone())),
@@ -193,6 +198,7 @@ impl<T: Layout> Hash for NodeType<T> {
             Div => { },
             Label(a) => a.hash(state),
             Text(a) => a.hash(state),
+            RichText(a) => a.hash(state),
             Image(a) => a.hash(state),
             GlTexture((ptr, a)) => {
                 ptr.hash(state);
@@ -213,6 +219,7 @@ impl<T: Layout> PartialEq for NodeType<T> {
             (Div, Div) => true,
             (Label(a), Label(b)) => a == b,
             (Text(a), Text(b)) => a == b,
+            (RichText(a), RichText(b)) => a == b,
             (Image(a), Image(b)) => a == b,
             (GlTexture((ptr_a, a)), GlTexture((ptr_b, b))) => {
                 a == b && ptr_a == ptr_b
@@ -232,7 +239,7 @@ impl<T: Layout> NodeType<T> {
         use self::NodeType::*;
         match self {
             Div => "div",
-            Label(_) | Text(_) => "p",
+            Label(_) | Text(_) | RichText(_) => "p",
             Image(_) => "image",
             GlTexture(_) => "texture",
             IFrame(_) => "iframe",
@@ -435,11 +442,15 @@ impl<T: Layout> fmt::Debug for NodeData<T> {
 
 impl<T: Layout> PartialEq for CallbackList<T> {
   fn eq(&self, rhs: &Self) -> bool {
-    if self.callbacks.len() != rhs.callbacks.len() {
+    if self.callbacks.len() != rhs.callbacks.len() ||
+       self.capture_callbacks.len() != rhs.capture_callbacks.len() {
         return false;
     }
     self.callbacks.iter().all(|(key, val)| {
         rhs.callbacks.get(key) == Some(val)
+    }) &&
+    self.capture_callbacks.iter().all(|(key, val)| {
+        rhs.capture_callbacks.get(key) == Some(val)
     })
   }
 }
@@ -448,8 +459,14 @@ impl<T: Layout> CallbackList<T> {
     fn special_clone(&self) -> Self {
         Self {
             callbacks: self.callbacks.clone(),
+            capture_callbacks: self.capture_callbacks.clone(),
         }
     }
+
+    /// Returns whether the node has neither bubbling nor capturing callbacks
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty() && self.capture_callbacks.is_empty()
+    }
 }
 
 impl<T: Layout> NodeData<T> {
@@ -501,13 +518,18 @@ impl<T: Layout> fmt::Debug for Dom<T> {
 
 #[derive(Clone, Eq)]
 pub struct CallbackList<T: Layout> {
-    pub callbacks: BTreeMap<On, Callback<T>>
+    /// Callbacks that are invoked when the node is hit or while the event bubbles up from a child
+    pub callbacks: BTreeMap<On, Callback<T>>,
+    /// Callbacks that are invoked while the event travels down to a hit child
+    /// (before the callbacks of the child are invoked)
+    pub capture_callbacks: BTreeMap<On, Callback<T>>,
 }
 
 impl<T: Layout> Default for CallbackList<T> {
     fn default() -> Self {
         Self {
             callbacks: BTreeMap::default(),
+            capture_callbacks: BTreeMap::default(),
         }
     }
 }
@@ -517,12 +539,15 @@ impl<T: Layout> Hash for CallbackList<T> {
         for callback in &self.callbacks {
             callback.hash(state);
         }
+        for callback in &self.capture_callbacks {
+            callback.hash(state);
+        }
     }
 }
 
 impl<T: Layout> fmt::Debug for CallbackList<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "CallbackList (length: {:?})", self.callbacks.len())
+        write!(f, "CallbackList (length: {:?}, capturing: {:?})", self.callbacks.len(), self.capture_callbacks.len())
     }
 }
 
@@ -530,6 +555,7 @@ impl<T: Layout> CallbackList<T> {
     pub fn new() -> Self {
         Self {
             callbacks: BTreeMap::new(),
+            capture_callbacks: BTreeMap::new(),
         }
     }
 }
@@ -773,6 +799,13 @@ impl<T: Layout> Dom<T> {
         self
     }
 
+    /// Same as `push_capture_callback`, but easier to use for method chaining in a builder-style pattern
+    #[inline]
+    pub fn with_capture_callback(mut self, on: On, callback: Callback<T>) -> Self {
+        self.push_capture_callback(on, callback);
+        self
+    }
+
     #[inline]
     pub fn with_child(mut self, child: Self) -> Self {
         self.add_child(child);
@@ -800,6 +833,13 @@ impl<T: Layout> Dom<T> {
         self.arena.borrow_mut()[self.head].data.events.callbacks.insert(on, callback);
     }
 
+    /// Adds a callback that is invoked in the capture phase, i.e. when a child of this
+    /// node is hit, this callback runs before the callbacks of the child
+    #[inline]
+    pub fn push_capture_callback(&mut self, on: On, callback: Callback<T>) {
+        self.arena.borrow_mut()[self.head].data.events.capture_callbacks.insert(on, callback);
+    }
+
     #[inline]
     pub fn push_default_callback_id(&mut self, on: On, id: DefaultCallbackId) {
         self.arena.borrow_mut()[self.head].data.default_callback_ids.insert(on, id);
@@ -817,6 +857,7 @@ impl<T: Layout> Dom<T> {
     pub(crate) fn collect_callbacks(
         &self,
         tag_ids_to_callback_list: &mut BTreeMap<TagId, BTreeMap<On, Callback<T>>>,
+        tag_ids_to_capture_callback_list: &mut BTreeMap<TagId, BTreeMap<On, Callback<T>>>,
         tag_ids_to_default_callback_list: &mut BTreeMap<TagId, BTreeMap<On, DefaultCallbackId>>,
         node_ids_to_tag_ids: &mut BTreeMap<NodeId, TagId>,
         tag_ids_to_node_ids: &mut BTreeMap<TagId, NodeId>)
@@ -827,9 +868,10 @@ impl<T: Layout> Dom<T> {
 
             let mut node_tag_id = None;
 
-            if !item.data.events.callbacks.is_empty() {
+            if !item.data.events.is_empty() {
                 let tag_id = new_tag_id();
                 tag_ids_to_callback_list.insert(tag_id, item.data.events.callbacks.clone());
+                tag_ids_to_capture_callback_list.insert(tag_id, item.data.events.capture_callbacks.clone());
                 node_tag_id = Some(tag_id);
             }
 
@@ -937,4 +979,38 @@ fn test_dom_from_iter_1() {
             events: CallbackList::default(),
         }
     }));
+}
+
+#[test]
+fn test_dom_collect_capture_callbacks() {
+
+    struct TestLayout { }
+
+    impl Layout for TestLayout {
+        fn layout(&self) -> Dom<Self> {
+            Dom::new(NodeType::Div)
+                .with_capture_callback(On::MouseUp, Callback(on_mouse_up))
+                .with_child(Dom::new(NodeType::Label(String::from("child"))))
+        }
+    }
+
+    fn on_mouse_up(_: &mut AppState<TestLayout>, _: WindowEvent) -> UpdateScreen {
+        UpdateScreen::DontRedraw
+    }
+
+    let dom = TestLayout{ }.layout();
+
+    let mut callbacks = BTreeMap::new();
+    let mut capture_callbacks = BTreeMap::new();
+    let mut default_callbacks = BTreeMap::new();
+    let mut node_ids_to_tag_ids = BTreeMap::new();
+    let mut tag_ids_to_node_ids = BTreeMap::new();
+
+    dom.collect_callbacks(&mut callbacks, &mut capture_callbacks, &mut default_callbacks, &mut node_ids_to_tag_ids, &mut tag_ids_to_node_ids);
+
+    // Only the parent has a callback, so only the parent is tagged
+    assert_eq!(node_ids_to_tag_ids.len(), 1);
+    let tag_id = node_ids_to_tag_ids[&dom.root];
+    assert!(capture_callbacks[&tag_id].contains_key(&On::MouseUp));
+    assert!(callbacks[&tag_id].is_empty());
 }
\ No newline at end of file
@@ -0,0 +1,972 @@
+//! Direct (non-cassowary) layout engine, selected with `LayoutEngine::Flexbox`.
+//!
+//! Instead of emitting constraints for a solver, the rectangles are laid out top-down:
+//! For every rectangle, the children are first measured (the hypothetical size of each
+//! child on the main axis) and then arranged (flexible lengths resolved, lines and items
+//! aligned). This follows the [CSS flexbox layout algorithm], with a few simplifications:
+//!
+//! - There are no `flex-grow` / `flex-shrink` / `flex-basis` properties (yet). Children
+//!   without a size and without content (i.e. `Div`s) grow equally, as if they had `flex: 1`.
+//!   Children with content (text, images) use their intrinsic size as the flex base size
+//!   and can shrink down to their `min-content` size.
+//! - `Div`s without a size on the cross axis always stretch over the whole line, since
+//!   the size of their content isn't known.
+//!
+//! [CSS flexbox layout algorithm]: https://www.w3.org/TR/css-flexbox-1/#layout-algorithm
+
+use std::{f32, collections::BTreeMap};
+use webrender::api::{LayoutRect, LayoutPoint, LayoutSize};
+use {
+    id_tree::{Arena, NodeId},
+    display_list::{DisplayRectangle, IntrinsicSize, subtract_padding},
+    css_parser::{
+        RectLayout, LayoutWrap, LayoutJustifyContent, LayoutAlignItems, LayoutAlignContent,
+        LayoutPosition, LayoutDisplay, LayoutIntrinsicSize, GridTrackSize,
+    },
+};
+#[cfg(test)]
+use window::LayoutEngine;
+
+/// How the size of a rectangle on one axis is determined
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum AxisSize {
+    /// Fixed size, from an explicit size or `min-content` / `max-content`
+    Definite(f32),
+    /// Sized by the content (`auto` / `fit-content`), but can shrink down to `min`
+    Content { preferred: f32, min: f32 },
+    /// No size and no content: the rectangle stretches over the available space
+    Stretch,
+}
+
+/// One child of a flex container, during the layout of the container
+#[derive(Debug, Copy, Clone)]
+struct FlexItem {
+    node_id: NodeId,
+    /// Flex base size
+    base: f32,
+    /// Minimum and maximum size on the main axis
+    min_main: f32,
+    max_main: f32,
+    /// Whether the item grows to fill up the free space
+    grows: bool,
+    /// Scaled flex shrink factor, `0.0` if the item can't shrink
+    shrink_scale: f32,
+    /// Final size on the main axis
+    main: f32,
+    /// Size on the cross axis, before `align-items` is applied
+    cross: AxisSize,
+}
+
+/// Lays out all rectangles, starting from the root node (`NodeId::new(0)`), which fills the window.
+///
+/// Returns the bounds of each rectangle, indexed by the `NodeId`
+pub(crate) fn layout_rects<'a>(
+    display_rectangles: &Arena<DisplayRectangle<'a>>,
+    intrinsic_sizes: &BTreeMap<NodeId, IntrinsicSize>,
+    window_size: LayoutSize)
+-> Vec<LayoutRect>
+{
+    let mut rects = vec![LayoutRect::zero(); display_rectangles.nodes_len()];
+
+    if display_rectangles.is_empty() {
+        return rects;
+    }
+
+    let window_rect = LayoutRect::new(LayoutPoint::zero(), window_size);
+    let root = NodeId::new(0);
+    let root_layout = &display_rectangles[root].data.layout;
+
+    // The root node fills the window, unless it has an explicit size
+    let root_width = match get_width(root_layout, None) {
+        AxisSize::Definite(width) => width,
+        _ => window_size.width,
+    };
+    let root_height = match get_height(root_layout, None, Some(root_width)) {
+        AxisSize::Definite(height) => height,
+        _ => window_size.height,
+    };
+
+    rects[root.index()] = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(
+        clamp_width(root_layout, root_width),
+        clamp_height(root_layout, root_height)));
+
+    // Parents are always laid out before their children
+    let mut stack = vec![root];
+
+    while let Some(node_id) = stack.pop() {
+        arrange_children(node_id, display_rectangles, intrinsic_sizes, &window_rect, &mut rects);
+        stack.extend(node_id.children(display_rectangles));
+    }
+
+    rects
+}
+
+/// Positions all children of the (already positioned) `node_id`
+fn arrange_children<'a>(
+    node_id: NodeId,
+    display_rectangles: &Arena<DisplayRectangle<'a>>,
+    intrinsic_sizes: &BTreeMap<NodeId, IntrinsicSize>,
+    window_rect: &LayoutRect,
+    rects: &mut [LayoutRect])
+{
+    let layout = &display_rectangles[node_id].data.layout;
+
+    let mut content_box = layout.padding.as_ref()
+        .and_then(|padding| Some(subtract_padding(&rects[node_id.index()], padding)))
+        .unwrap_or(rects[node_id.index()]);
+    content_box.size.width = content_box.size.width.max(0.0);
+    content_box.size.height = content_box.size.height.max(0.0);
+
+    let (in_flow, out_of_flow): (Vec<NodeId>, Vec<NodeId>) = node_id.children(display_rectangles)
+        .partition(|child_id| !is_out_of_flow(&display_rectangles[*child_id].data.layout));
+
+    if layout.display == Some(LayoutDisplay::Grid) {
+        arrange_grid_items(layout, &content_box, &in_flow, display_rectangles, intrinsic_sizes, rects);
+    } else {
+        arrange_flex_items(layout, &content_box, &in_flow, display_rectangles, intrinsic_sizes, rects);
+    }
+
+    for child_id in &in_flow {
+        apply_relative_offset(&display_rectangles[*child_id].data.layout, &mut rects[child_id.index()]);
+    }
+
+    for child_id in out_of_flow {
+        let child_layout = &display_rectangles[child_id].data.layout;
+        let containing_block = match child_layout.position {
+            Some(LayoutPosition::Fixed) => *window_rect,
+            _ => get_nearest_positioned_ancestor(child_id, display_rectangles)
+                .and_then(|ancestor| Some(rects[ancestor.index()]))
+                .unwrap_or(*window_rect),
+        };
+        rects[child_id.index()] = arrange_absolute_item(child_layout, intrinsic_sizes.get(&child_id), &containing_block, &content_box);
+    }
+}
+
+/// Lays out the children of a `display: flex` container
+fn arrange_flex_items<'a>(
+    layout: &RectLayout,
+    content_box: &LayoutRect,
+    children: &[NodeId],
+    display_rectangles: &Arena<DisplayRectangle<'a>>,
+    intrinsic_sizes: &BTreeMap<NodeId, IntrinsicSize>,
+    rects: &mut [LayoutRect])
+{
+    use css_parser::LayoutDirection::*;
+
+    // `direction: rtl` mirrors `row` and `row-reverse`
+    let direction = layout.get_flex_direction();
+    let is_row = direction == Row || direction == RowReverse;
+    let is_reverse = direction == RowReverse || direction == ColumnReverse;
+
+    let (main_size, cross_size) = if is_row {
+        (content_box.size.width, content_box.size.height)
+    } else {
+        (content_box.size.height, content_box.size.width)
+    };
+
+    // 1. Measure the hypothetical main size of each item
+    let mut items = children.iter().map(|child_id| {
+
+        let child_layout = &display_rectangles[*child_id].data.layout;
+        let intrinsic_size = intrinsic_sizes.get(child_id);
+
+        let (main_axis, cross, min_main, max_main) = if is_row {
+            let (min, max) = get_min_max_width(child_layout);
+            // The height depends on the width (images), it is resolved after the width is known
+            (get_width(child_layout, intrinsic_size), AxisSize::Stretch, min, max)
+        } else {
+            // Resolve the width first, so that images can keep their aspect ratio
+            let width = get_width(child_layout, intrinsic_size);
+            let resolved_width = clamp_width(child_layout, match width {
+                AxisSize::Definite(w) => w,
+                AxisSize::Content { preferred, min } => preferred.min(cross_size).max(min),
+                AxisSize::Stretch => cross_size,
+            });
+            let (min, max) = get_min_max_height(child_layout);
+            let height = get_height(child_layout, intrinsic_size, match width {
+                AxisSize::Stretch => None,
+                _ => Some(resolved_width),
+            });
+            (height, width, min, max)
+        };
+
+        let (base, grows, shrink_scale, min_main) = match main_axis {
+            AxisSize::Definite(size) => (size, false, 0.0, min_main),
+            AxisSize::Content { preferred, min } => (preferred, false, preferred, min_main.max(min)),
+            AxisSize::Stretch => (0.0, true, 0.0, min_main),
+        };
+
+        FlexItem {
+            node_id: *child_id,
+            base: base,
+            min_main: min_main,
+            max_main: max_main,
+            grows: grows,
+            shrink_scale: shrink_scale,
+            main: clamp(base, min_main, max_main),
+            cross: cross,
+        }
+    }).collect::<Vec<FlexItem>>();
+
+    // 2. Collect the items into lines
+    let mut lines = Vec::<(usize, usize)>::new();
+    match layout.wrap.unwrap_or_default() {
+        LayoutWrap::NoWrap => lines.push((0, items.len())),
+        LayoutWrap::Wrap => {
+            let mut line_start = 0;
+            let mut line_size = 0.0;
+            for (item_idx, item) in items.iter().enumerate() {
+                if item_idx > line_start && line_size + item.main > main_size {
+                    lines.push((line_start, item_idx));
+                    line_start = item_idx;
+                    line_size = 0.0;
+                }
+                line_size += item.main;
+            }
+            lines.push((line_start, items.len()));
+        },
+    }
+
+    // 3. Resolve the flexible lengths of each line, then the cross size of the items
+    for (line_start, line_end) in &lines {
+        resolve_flexible_lengths(&mut items[*line_start..*line_end], main_size);
+    }
+
+    if is_row {
+        for item in items.iter_mut() {
+            let child_layout = &display_rectangles[item.node_id].data.layout;
+            item.cross = get_height(child_layout, intrinsic_sizes.get(&item.node_id), Some(item.main));
+        }
+    }
+
+    // 4. Determine the cross size of the lines
+    let mut line_cross_sizes = if lines.len() == 1 {
+        vec![cross_size]
+    } else {
+        lines.iter().map(|(line_start, line_end)| {
+            items[*line_start..*line_end].iter().map(|item| match item.cross {
+                AxisSize::Definite(size) => size,
+                AxisSize::Content { preferred, .. } => preferred,
+                AxisSize::Stretch => 0.0,
+            }).fold(0.0_f32, |max, size| max.max(size))
+        }).collect::<Vec<f32>>()
+    };
+
+    let align_content = layout.align_content.unwrap_or_default();
+    let free_cross_space = cross_size - line_cross_sizes.iter().sum::<f32>();
+
+    if align_content == LayoutAlignContent::Stretch && free_cross_space > 0.0 {
+        let num_lines = line_cross_sizes.len() as f32;
+        for line_cross_size in line_cross_sizes.iter_mut() {
+            *line_cross_size += free_cross_space / num_lines;
+        }
+    }
+
+    let (mut line_position, line_spacing) = distribute_free_space(
+        cross_size - line_cross_sizes.iter().sum::<f32>(),
+        lines.len(),
+        match align_content {
+            LayoutAlignContent::Stretch | LayoutAlignContent::Start => LayoutJustifyContent::Start,
+            LayoutAlignContent::End => LayoutJustifyContent::End,
+            LayoutAlignContent::Center => LayoutJustifyContent::Center,
+            LayoutAlignContent::SpaceBetween => LayoutJustifyContent::SpaceBetween,
+            LayoutAlignContent::SpaceAround => LayoutJustifyContent::SpaceAround,
+        });
+
+    let align_items = layout.align_items.unwrap_or_default();
+    let justify_content = layout.justify_content.unwrap_or_default();
+
+    // 5. Position the items
+    for ((line_start, line_end), line_cross_size) in lines.iter().zip(line_cross_sizes.iter()) {
+
+        let line = &items[*line_start..*line_end];
+        let (mut main_position, item_spacing) = distribute_free_space(
+            main_size - line.iter().map(|item| item.main).sum::<f32>(),
+            line.len(),
+            justify_content);
+
+        for item in line {
+
+            let child_layout = &display_rectangles[item.node_id].data.layout;
+
+            let item_cross = match (item.cross, align_items) {
+                (AxisSize::Definite(size), _) => size,
+                (_, LayoutAlignItems::Stretch) | (AxisSize::Stretch, _) => *line_cross_size,
+                (AxisSize::Content { preferred, min }, _) => preferred.min(*line_cross_size).max(min),
+            };
+            let item_cross = if is_row { clamp_height(child_layout, item_cross) } else { clamp_width(child_layout, item_cross) };
+
+            let cross_position = line_position + match align_items {
+                LayoutAlignItems::Stretch | LayoutAlignItems::Start => 0.0,
+                LayoutAlignItems::End => line_cross_size - item_cross,
+                LayoutAlignItems::Center => (line_cross_size - item_cross) / 2.0,
+            };
+
+            // Reversed directions start at the end of the main axis
+            let item_main_position = if is_reverse { main_size - main_position - item.main } else { main_position };
+
+            rects[item.node_id.index()] = if is_row {
+                LayoutRect::new(
+                    LayoutPoint::new(content_box.origin.x + item_main_position, content_box.origin.y + cross_position),
+                    LayoutSize::new(item.main, item_cross))
+            } else {
+                LayoutRect::new(
+                    LayoutPoint::new(content_box.origin.x + cross_position, content_box.origin.y + item_main_position),
+                    LayoutSize::new(item_cross, item.main))
+            };
+
+            main_position += item.main + item_spacing;
+        }
+
+        line_position += line_cross_size + line_spacing;
+    }
+}
+
+/// Grows or shrinks the items of one line, so that they fill up the `main_size`
+/// ([spec](https://www.w3.org/TR/css-flexbox-1/#resolve-flexible-lengths))
+fn resolve_flexible_lengths(items: &mut [FlexItem], main_size: f32) {
+
+    let hypothetical_size = items.iter().map(|item| item.main).sum::<f32>();
+    let is_growing = hypothetical_size < main_size;
+
+    // Items that can't flex keep their hypothetical size
+    let mut frozen = items.iter().map(|item| {
+        if is_growing { !item.grows } else { item.shrink_scale <= 0.0 }
+    }).collect::<Vec<bool>>();
+
+    let mut violations = vec![0.0; items.len()];
+
+    while frozen.iter().any(|f| !*f) {
+
+        let free_space = main_size - items.iter().zip(frozen.iter())
+            .map(|(item, is_frozen)| if *is_frozen { item.main } else { item.base })
+            .sum::<f32>();
+
+        let num_unfrozen = frozen.iter().filter(|f| !**f).count() as f32;
+        let total_shrink_scale = items.iter().zip(frozen.iter())
+            .filter(|(_, is_frozen)| !**is_frozen)
+            .map(|(item, _)| item.shrink_scale)
+            .sum::<f32>();
+
+        let mut total_violation = 0.0;
+
+        for (item_idx, item) in items.iter_mut().enumerate() {
+            if frozen[item_idx] {
+                continue;
+            }
+            let unclamped = if is_growing {
+                item.base + free_space / num_unfrozen
+            } else {
+                item.base + free_space * item.shrink_scale / total_shrink_scale
+            };
+            item.main = clamp(unclamped, item.min_main, item.max_main);
+            violations[item_idx] = item.main - unclamped;
+            total_violation += violations[item_idx];
+        }
+
+        // Freeze the items that violated their min / max size, or all items if there were no violations
+        for (item_idx, is_frozen) in frozen.iter_mut().enumerate() {
+            if *is_frozen {
+                continue;
+            }
+            let violation = violations[item_idx];
+            *is_frozen = total_violation == 0.0
+                      || (total_violation > 0.0 && violation > 0.0)
+                      || (total_violation < 0.0 && violation < 0.0);
+        }
+    }
+}
+
+/// Returns the offset of the first item and the space between two items
+/// when distributing the `free_space` according to the `justify_content`
+fn distribute_free_space(free_space: f32, num_items: usize, justify_content: LayoutJustifyContent)
+-> (f32, f32)
+{
+    use css_parser::LayoutJustifyContent::*;
+
+    // Overflowing items are aligned to the start
+    if free_space <= 0.0 || num_items == 0 {
+        return match justify_content {
+            End => (free_space, 0.0),
+            Center => (free_space / 2.0, 0.0),
+            _ => (0.0, 0.0),
+        };
+    }
+
+    match justify_content {
+        Start => (0.0, 0.0),
+        End => (free_space, 0.0),
+        Center => (free_space / 2.0, 0.0),
+        SpaceBetween if num_items == 1 => (0.0, 0.0),
+        SpaceBetween => (0.0, free_space / (num_items - 1) as f32),
+        SpaceAround => {
+            let spacing = free_space / num_items as f32;
+            (spacing / 2.0, spacing)
+        },
+    }
+}
+
+/// Lays out the children of a `display: grid` container. `auto` tracks are treated as `1fr`
+/// tracks and children without a size stretch over their whole grid area, same as in the
+/// cassowary-based layout.
+fn arrange_grid_items<'a>(
+    layout: &RectLayout,
+    content_box: &LayoutRect,
+    children: &[NodeId],
+    display_rectangles: &Arena<DisplayRectangle<'a>>,
+    intrinsic_sizes: &BTreeMap<NodeId, IntrinsicSize>,
+    rects: &mut [LayoutRect])
+{
+    use grid_layout::{GridItem, place_grid_items};
+
+    let column_templates = layout.grid_template_columns.as_ref().and_then(|c| Some(&c.0[..])).unwrap_or(&[]);
+    let row_templates = layout.grid_template_rows.as_ref().and_then(|r| Some(&r.0[..])).unwrap_or(&[]);
+    let (row_gap, column_gap) = layout.gap.and_then(|gap| Some((gap.row.to_pixels(), gap.column.to_pixels()))).unwrap_or((0.0, 0.0));
+
+    let items = children.iter().map(|child_id| {
+        let child_layout = &display_rectangles[*child_id].data.layout;
+        GridItem {
+            column: child_layout.grid_column.and_then(|c| Some(c.0)),
+            row: child_layout.grid_row.and_then(|r| Some(r.0)),
+            area: child_layout.grid_area.as_ref().and_then(|a| Some(a.0.as_str())),
+        }
+    }).collect::<Vec<GridItem>>();
+
+    let placement = place_grid_items(&items, column_templates.len(), row_templates.len(), layout.grid_template_areas.as_ref());
+
+    let columns = resolve_grid_tracks(column_templates, placement.num_columns, content_box.size.width, column_gap);
+    let rows = resolve_grid_tracks(row_templates, placement.num_rows, content_box.size.height, row_gap);
+
+    for (child_id, cell) in children.iter().zip(placement.cells.iter()) {
+
+        let child_layout = &display_rectangles[*child_id].data.layout;
+        let intrinsic_size = intrinsic_sizes.get(child_id);
+
+        let (area_left, area_width) = grid_track_span(&columns, cell.column_start, cell.column_end, column_gap);
+        let (area_top, area_height) = grid_track_span(&rows, cell.row_start, cell.row_end, row_gap);
+
+        // Children without an explicit size stretch over their whole grid area
+        let width = match get_width(child_layout, intrinsic_size) {
+            AxisSize::Definite(width) => width,
+            AxisSize::Content { preferred, min } if child_layout.intrinsic_width.unwrap_or_default() != LayoutIntrinsicSize::Auto => {
+                preferred.min(area_width).max(min)
+            },
+            _ => area_width,
+        };
+        let width = clamp_width(child_layout, width);
+
+        let height = match get_height(child_layout, intrinsic_size, Some(width)) {
+            AxisSize::Definite(height) => height,
+            AxisSize::Content { preferred, min } if child_layout.intrinsic_height.unwrap_or_default() != LayoutIntrinsicSize::Auto => {
+                preferred.min(area_height).max(min)
+            },
+            _ => area_height,
+        };
+        let height = clamp_height(child_layout, height);
+
+        rects[child_id.index()] = LayoutRect::new(
+            LayoutPoint::new(content_box.origin.x + area_left, content_box.origin.y + area_top),
+            LayoutSize::new(width, height));
+    }
+}
+
+/// Resolves the size of each grid track (row or column), so that the tracks
+/// plus the gaps fill up the `container_size`
+fn resolve_grid_tracks(templates: &[GridTrackSize], num_tracks: usize, container_size: f32, gap: f32)
+-> Vec<f32>
+{
+    use css_parser::{GridTrackSize::*, GridTrackBreadth::*};
+
+    // Implicitly created tracks are sized `auto`
+    let tracks = (0..num_tracks).map(|track_idx| templates.get(track_idx).cloned().unwrap_or_default()).collect::<Vec<GridTrackSize>>();
+
+    // (fixed size, fraction) of each track
+    let sizes = tracks.iter().map(|track| match track {
+        Breadth(Fixed(size)) => (size.to_pixels(), 0.0),
+        Breadth(Fraction(f)) => (0.0, *f),
+        Breadth(Auto) => (0.0, 1.0),
+        MinMax(_, Fixed(size)) => (size.to_pixels(), 0.0),
+        MinMax(_, Fraction(f)) => (0.0, *f),
+        MinMax(_, Auto) => (0.0, 1.0),
+    }).collect::<Vec<(f32, f32)>>();
+
+    let total_gap = gap * num_tracks.saturating_sub(1) as f32;
+    let total_fixed = sizes.iter().map(|(fixed, _)| fixed).sum::<f32>();
+    let total_fraction = sizes.iter().map(|(_, fraction)| fraction).sum::<f32>();
+
+    let fraction_size = if total_fraction > 0.0 {
+        ((container_size - total_gap - total_fixed) / total_fraction).max(0.0)
+    } else {
+        0.0
+    };
+
+    tracks.iter().zip(sizes.iter()).map(|(track, (fixed, fraction))| {
+        let size = fixed + fraction * fraction_size;
+        match track {
+            // minmax(200px, 100px) is treated as 200px
+            MinMax(Fixed(min), _) => size.max(min.to_pixels()),
+            _ => size,
+        }
+    }).collect()
+}
+
+/// Returns the offset and the size of the tracks from `start` to `end` (exclusive)
+fn grid_track_span(tracks: &[f32], start: usize, end: usize, gap: f32) -> (f32, f32) {
+    let start = start.min(tracks.len());
+    let end = end.min(tracks.len()).max(start);
+    let offset = tracks[..start].iter().sum::<f32>() + gap * start as f32;
+    let size = tracks[start..end].iter().sum::<f32>() + gap * end.saturating_sub(start).saturating_sub(1) as f32;
+    (offset, size)
+}
+
+/// Positions a `position: absolute` or `position: fixed` rectangle relative to its `containing_block`.
+/// If neither `top` nor `bottom` (or `left` / `right`) are set, the rectangle stays at the start of
+/// the `parent_content_box`.
+fn arrange_absolute_item(
+    layout: &RectLayout,
+    intrinsic_size: Option<&IntrinsicSize>,
+    containing_block: &LayoutRect,
+    parent_content_box: &LayoutRect)
+-> LayoutRect
+{
+    let top = layout.top.and_then(|top| Some(top.0.to_pixels()));
+    let left = layout.left.and_then(|left| Some(left.0.to_pixels()));
+    let right = layout.right.and_then(|right| Some(right.0.to_pixels()));
+    let bottom = layout.bottom.and_then(|bottom| Some(bottom.0.to_pixels()));
+
+    let width = match (get_width(layout, intrinsic_size), left, right) {
+        (AxisSize::Definite(width), _, _) => width,
+        (_, Some(left), Some(right)) => containing_block.size.width - left - right,
+        (AxisSize::Content { preferred, min }, _, _) => preferred.min(containing_block.size.width).max(min),
+        (AxisSize::Stretch, _, _) => containing_block.size.width,
+    };
+    let width = clamp_width(layout, width);
+
+    let height = match (get_height(layout, intrinsic_size, Some(width)), top, bottom) {
+        (AxisSize::Definite(height), _, _) => height,
+        (_, Some(top), Some(bottom)) => containing_block.size.height - top - bottom,
+        (AxisSize::Content { preferred, min }, _, _) => preferred.min(containing_block.size.height).max(min),
+        (AxisSize::Stretch, _, _) => containing_block.size.height,
+    };
+    let height = clamp_height(layout, height);
+
+    let x = match (left, right) {
+        (Some(left), _) => containing_block.origin.x + left,
+        (None, Some(right)) => containing_block.max_x() - right - width,
+        (None, None) => parent_content_box.origin.x,
+    };
+    let y = match (top, bottom) {
+        (Some(top), _) => containing_block.origin.y + top,
+        (None, Some(bottom)) => containing_block.max_y() - bottom - height,
+        (None, None) => parent_content_box.origin.y,
+    };
+
+    LayoutRect::new(LayoutPoint::new(x, y), LayoutSize::new(width, height))
+}
+
+/// Moves a `position: relative` rectangle by its `top` / `left` (or `bottom` / `right`) offset
+fn apply_relative_offset(layout: &RectLayout, rect: &mut LayoutRect) {
+
+    if layout.position != Some(LayoutPosition::Relative) {
+        return;
+    }
+
+    let top = layout.top.and_then(|top| Some(top.0.to_pixels()));
+    let left = layout.left.and_then(|left| Some(left.0.to_pixels()));
+    let right = layout.right.and_then(|right| Some(right.0.to_pixels()));
+    let bottom = layout.bottom.and_then(|bottom| Some(bottom.0.to_pixels()));
+
+    rect.origin.x += left.or(right.and_then(|right| Some(-right))).unwrap_or(0.0);
+    rect.origin.y += top.or(bottom.and_then(|bottom| Some(-bottom))).unwrap_or(0.0);
+}
+
+/// Absolute and fixed rectangles are taken out of the flow of their parent
+fn is_out_of_flow(layout: &RectLayout) -> bool {
+    match layout.position {
+        Some(LayoutPosition::Absolute) | Some(LayoutPosition::Fixed) => true,
+        _ => false,
+    }
+}
+
+/// Returns the nearest ancestor with a `position` other than `static`
+fn get_nearest_positioned_ancestor<'a>(node_id: NodeId, display_rectangles: &Arena<DisplayRectangle<'a>>)
+-> Option<NodeId>
+{
+    node_id.ancestors(display_rectangles).skip(1).find(|ancestor| {
+        match display_rectangles[*ancestor].data.layout.position {
+            None | Some(LayoutPosition::Static) => false,
+            _ => true,
+        }
+    })
+}
+
+fn get_width(layout: &RectLayout, intrinsic_size: Option<&IntrinsicSize>) -> AxisSize {
+    use css_parser::LayoutIntrinsicSize::*;
+
+    if let Some(width) = layout.width {
+        return AxisSize::Definite(width.0.to_pixels());
+    }
+
+    if let (Some(aspect_ratio), Some(height)) = (layout.aspect_ratio, layout.height) {
+        return AxisSize::Definite(height.0.to_pixels() * aspect_ratio.0);
+    }
+
+    match intrinsic_size {
+        Some(intrinsic_size) => match layout.intrinsic_width.unwrap_or_default() {
+            MinContent => AxisSize::Definite(intrinsic_size.min_content_width),
+            MaxContent => AxisSize::Definite(intrinsic_size.max_content_width),
+            Auto | FitContent => AxisSize::Content {
+                preferred: intrinsic_size.max_content_width,
+                min: intrinsic_size.min_content_width,
+            },
+        },
+        None => AxisSize::Stretch,
+    }
+}
+
+/// The `width` is necessary to keep the aspect ratio of images / rectangles with an `aspect-ratio`
+fn get_height(layout: &RectLayout, intrinsic_size: Option<&IntrinsicSize>, width: Option<f32>) -> AxisSize {
+    use css_parser::LayoutIntrinsicSize::*;
+
+    if let Some(height) = layout.height {
+        return AxisSize::Definite(height.0.to_pixels());
+    }
+
+    match intrinsic_size {
+        Some(intrinsic_size) => {
+            let content_height = match (intrinsic_size.aspect_ratio, width) {
+                (Some(ratio), Some(width)) => {
+                    let (padding_horz, padding_vert) = intrinsic_size.padding;
+                    (width - padding_horz).max(0.0) * ratio + padding_vert
+                },
+                _ => intrinsic_size.content_height,
+            };
+            match layout.intrinsic_height.unwrap_or_default() {
+                MinContent | MaxContent => AxisSize::Definite(content_height),
+                // Same as in the cassowary layout, text may overflow its parent vertically
+                Auto | FitContent => AxisSize::Content { preferred: content_height, min: 0.0 },
+            }
+        },
+        None => match (layout.aspect_ratio, width) {
+            (Some(aspect_ratio), Some(width)) => AxisSize::Definite(width / aspect_ratio.0),
+            _ => AxisSize::Stretch,
+        },
+    }
+}
+
+fn get_min_max_width(layout: &RectLayout) -> (f32, f32) {
+    (
+        layout.min_width.and_then(|min| Some(min.0.to_pixels())).unwrap_or(0.0),
+        layout.max_width.and_then(|max| Some(max.0.to_pixels())).unwrap_or(f32::INFINITY),
+    )
+}
+
+fn get_min_max_height(layout: &RectLayout) -> (f32, f32) {
+    (
+        layout.min_height.and_then(|min| Some(min.0.to_pixels())).unwrap_or(0.0),
+        layout.max_height.and_then(|max| Some(max.0.to_pixels())).unwrap_or(f32::INFINITY),
+    )
+}
+
+fn clamp_width(layout: &RectLayout, width: f32) -> f32 {
+    let (min, max) = get_min_max_width(layout);
+    clamp(width, min, max)
+}
+
+fn clamp_height(layout: &RectLayout, height: f32) -> f32 {
+    let (min, max) = get_min_max_height(layout);
+    clamp(height, min, max)
+}
+
+/// Clamps the value between `min` and `max`. Like in CSS, `min` wins if `min > max`
+fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    value.min(max).max(min).max(0.0)
+}
+
+/// Parses a list of declarations such as `"width: 100px; flex-direction: row"`
+#[cfg(test)]
+fn styled_node_from_str(css: &str) -> ::ui_description::StyledNode {
+    use css::CssDeclaration;
+    use css_parser::ParsedCssProperty;
+    use ui_description::{StyledNode, CssConstraintList};
+
+    let list = css.split(';').filter(|declaration| !declaration.trim().is_empty()).map(|declaration| {
+        let mut key_value = declaration.splitn(2, ':');
+        let key = key_value.next().unwrap();
+        let value = key_value.next().unwrap();
+        CssDeclaration::Static(ParsedCssProperty::from_kv(key, value).unwrap())
+    }).collect();
+
+    StyledNode {
+        z_level: 0,
+        css_constraints: CssConstraintList { list: list },
+    }
+}
+
+#[cfg(test)]
+struct FixtureLayout { }
+
+#[cfg(test)]
+impl ::traits::Layout for FixtureLayout {
+    fn layout(&self) -> ::dom::Dom<Self> {
+        ::dom::Dom::new(::dom::NodeType::Div)
+    }
+}
+
+/// Lays out a layout fixture with the given layout engine and returns the bounds of all nodes,
+/// as returned by `UiSolver::query_bounds_of_rect`. Each node is given as `(parent index, css)`,
+/// the first node is the root node
+#[cfg(test)]
+fn layout_fixture_with_engine(
+    nodes: &[(Option<usize>, &str)],
+    intrinsic_sizes: &BTreeMap<NodeId, IntrinsicSize>,
+    window_size: (f32, f32),
+    layout_engine: LayoutEngine)
+-> Vec<LayoutRect>
+{
+    use FastHashMap;
+    use glium::glutin::dpi::LogicalSize;
+    use dom::{NodeData, NodeType};
+    use display_list::{populate_css_properties, create_layout_constraints};
+    use ui_solver::UiSolver;
+
+    let styled_nodes = nodes.iter().map(|(_, css)| styled_node_from_str(css)).collect::<Vec<_>>();
+    let mut arena = Arena::new();
+    let mut dom = Arena::new();
+
+    for ((parent, _), styled_node) in nodes.iter().zip(styled_nodes.iter()) {
+        let mut rect = DisplayRectangle::new(None, styled_node);
+        populate_css_properties(&mut rect, &FastHashMap::default());
+        let node_id = arena.new_node(rect);
+        dom.new_node(NodeData::<FixtureLayout>::new(NodeType::Div));
+        if let Some(parent) = parent {
+            NodeId::new(*parent).append(node_id, &mut arena);
+            NodeId::new(*parent).append(node_id, &mut dom);
+        }
+    }
+
+    let window_size_logical = LogicalSize::new(window_size.0 as f64, window_size.1 as f64);
+    let mut ui_solver = UiSolver::new(&window_size_logical, layout_engine);
+
+    match layout_engine {
+        LayoutEngine::Cassowary => {
+            ui_solver.update_dom(&NodeId::new(0), &dom);
+            for rect_idx in arena.linear_iter() {
+                let constraints = create_layout_constraints(rect_idx, &arena, &dom, &ui_solver, intrinsic_sizes.get(&rect_idx).cloned());
+                ui_solver.insert_css_constraints_for_rect(&constraints);
+                ui_solver.push_added_constraints(rect_idx, constraints);
+            }
+            ui_solver.update_window_size(&window_size_logical);
+            ui_solver.update_layout_cache();
+        },
+        LayoutEngine::Flexbox => {
+            ui_solver.set_direct_layout(layout_rects(&arena, intrinsic_sizes, LayoutSize::new(window_size.0, window_size.1)));
+        },
+    }
+
+    arena.linear_iter().map(|rect_idx| ui_solver.query_bounds_of_rect(rect_idx)).collect()
+}
+
+/// Lays out a layout fixture with the flexbox engine
+#[cfg(test)]
+fn layout_fixture(nodes: &[(Option<usize>, &str)], intrinsic_sizes: &BTreeMap<NodeId, IntrinsicSize>, window_size: (f32, f32))
+-> Vec<LayoutRect>
+{
+    layout_fixture_with_engine(nodes, intrinsic_sizes, window_size, LayoutEngine::Flexbox)
+}
+
+/// Lays out a layout fixture with both layout engines and asserts that they produce the same
+/// bounds for every node (up to rounding errors of the cassowary solver).
+///
+/// The cassowary engine doesn't implement padding, `justify-content`, `align-items`,
+/// `flex-wrap`, the `right` / `bottom` offsets of absolutely positioned rectangles and
+/// growing a rectangle beyond its equal share of the parent. Fixtures that use these
+/// are only laid out with `layout_fixture`.
+#[cfg(test)]
+fn layout_fixture_conformance(nodes: &[(Option<usize>, &str)], intrinsic_sizes: &BTreeMap<NodeId, IntrinsicSize>, window_size: (f32, f32))
+-> Vec<LayoutRect>
+{
+    let flexbox = layout_fixture_with_engine(nodes, intrinsic_sizes, window_size, LayoutEngine::Flexbox);
+    let cassowary = layout_fixture_with_engine(nodes, intrinsic_sizes, window_size, LayoutEngine::Cassowary);
+
+    let is_close = |a: f32, b: f32| (a - b).abs() < 0.01;
+
+    for (node_idx, (flexbox_rect, cassowary_rect)) in flexbox.iter().zip(cassowary.iter()).enumerate() {
+        assert!(
+            is_close(flexbox_rect.origin.x, cassowary_rect.origin.x) &&
+            is_close(flexbox_rect.origin.y, cassowary_rect.origin.y) &&
+            is_close(flexbox_rect.size.width, cassowary_rect.size.width) &&
+            is_close(flexbox_rect.size.height, cassowary_rect.size.height),
+            "node {}: flexbox {:?} != cassowary {:?}", node_idx, flexbox_rect, cassowary_rect);
+    }
+
+    flexbox
+}
+
+#[cfg(test)]
+fn rect(x: f32, y: f32, width: f32, height: f32) -> LayoutRect {
+    LayoutRect::new(LayoutPoint::new(x, y), LayoutSize::new(width, height))
+}
+
+#[test]
+fn test_flex_layout_column_equal_split() {
+    let rects = layout_fixture_conformance(&[(None, ""), (Some(0), ""), (Some(0), ""), (Some(0), "")], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(rects, vec![
+        rect(0.0, 0.0, 400.0, 300.0),
+        rect(0.0, 0.0, 400.0, 100.0),
+        rect(0.0, 100.0, 400.0, 100.0),
+        rect(0.0, 200.0, 400.0, 100.0),
+    ]);
+}
+
+#[test]
+fn test_flex_layout_row_equal_split() {
+    let rects = layout_fixture_conformance(&[(None, "flex-direction: row"), (Some(0), ""), (Some(0), "")], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(rects[1], rect(0.0, 0.0, 200.0, 300.0));
+    assert_eq!(rects[2], rect(200.0, 0.0, 200.0, 300.0));
+}
+
+#[test]
+fn test_flex_layout_nested() {
+    let rects = layout_fixture_conformance(&[
+        (None, "flex-direction: row"),
+        (Some(0), ""),
+        (Some(0), "flex-direction: column"),
+        (Some(2), ""),
+        (Some(2), ""),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(rects[1], rect(0.0, 0.0, 200.0, 300.0));
+    assert_eq!(rects[3], rect(200.0, 0.0, 200.0, 150.0));
+    assert_eq!(rects[4], rect(200.0, 150.0, 200.0, 150.0));
+}
+
+// Not supported by the cassowary engine: justify-content
+#[test]
+fn test_flex_layout_justify_content() {
+    let center = layout_fixture(&[
+        (None, "flex-direction: row; justify-content: center"),
+        (Some(0), "width: 100px"),
+        (Some(0), "width: 100px"),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(center[1], rect(100.0, 0.0, 100.0, 300.0));
+    assert_eq!(center[2], rect(200.0, 0.0, 100.0, 300.0));
+
+    let space_between = layout_fixture(&[
+        (None, "flex-direction: row; justify-content: space-between"),
+        (Some(0), "width: 100px"),
+        (Some(0), "width: 100px"),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(space_between[1], rect(0.0, 0.0, 100.0, 300.0));
+    assert_eq!(space_between[2], rect(300.0, 0.0, 100.0, 300.0));
+}
+
+// Not supported by the cassowary engine: wrapping
+#[test]
+fn test_flex_layout_wrap() {
+    let rects = layout_fixture(&[
+        (None, "flex-direction: row"),
+        (Some(0), "width: 150px; height: 50px"),
+        (Some(0), "width: 150px; height: 50px"),
+        (Some(0), "width: 150px; height: 50px"),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    // align-content: stretch distributes the remaining 200px over both lines
+    assert_eq!(rects[1], rect(0.0, 0.0, 150.0, 50.0));
+    assert_eq!(rects[2], rect(150.0, 0.0, 150.0, 50.0));
+    assert_eq!(rects[3], rect(0.0, 150.0, 150.0, 50.0));
+}
+
+// Not supported by the cassowary engine: align-items
+#[test]
+fn test_flex_layout_align_items_center_with_content() {
+    let mut intrinsic_sizes = BTreeMap::new();
+    intrinsic_sizes.insert(NodeId::new(1), IntrinsicSize {
+        min_content_width: 40.0,
+        max_content_width: 80.0,
+        content_height: 20.0,
+        aspect_ratio: None,
+        padding: (0.0, 0.0),
+    });
+    let rects = layout_fixture(&[(None, "flex-direction: row; align-items: center"), (Some(0), "")], &intrinsic_sizes, (400.0, 300.0));
+    assert_eq!(rects[1], rect(0.0, 140.0, 80.0, 20.0));
+}
+
+// Not supported by the cassowary engine: the second rectangle grows beyond its min-width
+#[test]
+fn test_flex_layout_min_max_size() {
+    let rects = layout_fixture(&[
+        (None, "flex-direction: row"),
+        (Some(0), "max-width: 100px"),
+        (Some(0), "min-width: 250px"),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(rects[1], rect(0.0, 0.0, 100.0, 300.0));
+    assert_eq!(rects[2], rect(100.0, 0.0, 300.0, 300.0));
+}
+
+// Not supported by the cassowary engine: padding
+#[test]
+fn test_flex_layout_padding() {
+    let rects = layout_fixture(&[(None, "padding: 10px"), (Some(0), "")], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(rects[1], rect(10.0, 10.0, 380.0, 280.0));
+}
+
+// Not supported by the cassowary engine: padding and `right` of absolute rectangles
+#[test]
+fn test_flex_layout_absolute_and_fixed() {
+    let rects = layout_fixture(&[
+        (None, "position: relative"),
+        (Some(0), "padding: 20px"),
+        (Some(0), "position: absolute; top: 10px; right: 20px; width: 50px; height: 50px"),
+        (Some(1), "position: fixed; left: 5px; bottom: 5px; width: 10px; height: 10px"),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    // Out-of-flow rectangles don't take up space in their parent
+    assert_eq!(rects[1], rect(0.0, 0.0, 400.0, 300.0));
+    assert_eq!(rects[2], rect(330.0, 10.0, 50.0, 50.0));
+    assert_eq!(rects[3], rect(5.0, 285.0, 10.0, 10.0));
+}
+
+#[test]
+fn test_flex_layout_row_reverse() {
+    let rects = layout_fixture_conformance(&[
+        (None, "flex-direction: row-reverse"),
+        (Some(0), "width: 100px"),
+        (Some(0), "width: 50px"),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(rects[1], rect(300.0, 0.0, 100.0, 300.0));
+    assert_eq!(rects[2], rect(250.0, 0.0, 50.0, 300.0));
+}
+
+#[test]
+fn test_flex_layout_rtl() {
+    let rects = layout_fixture_conformance(&[
+        (None, "flex-direction: row; direction: rtl"),
+        (Some(0), "width: 100px"),
+        (Some(0), "width: 50px"),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(rects[1], rect(300.0, 0.0, 100.0, 300.0));
+    assert_eq!(rects[2], rect(250.0, 0.0, 50.0, 300.0));
+
+    // Not supported by the cassowary engine: padding
+    let rects = layout_fixture(&[
+        (None, "flex-direction: row; direction: rtl; padding-inline-start: 20px"),
+        (Some(0), "width: 100px"),
+        (Some(0), "width: 50px"),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    // The first item starts at the right edge, after the padding (which is on the right side)
+    assert_eq!(rects[1], rect(280.0, 0.0, 100.0, 300.0));
+    assert_eq!(rects[2], rect(230.0, 0.0, 50.0, 300.0));
+}
+
+#[test]
+fn test_flex_layout_grid() {
+    let rects = layout_fixture_conformance(&[
+        (None, "display: grid; grid-template-columns: 100px 1fr; gap: 10px"),
+        (Some(0), ""),
+        (Some(0), ""),
+        (Some(0), ""),
+        (Some(0), ""),
+    ], &BTreeMap::new(), (400.0, 300.0));
+    assert_eq!(rects[1], rect(0.0, 0.0, 100.0, 145.0));
+    assert_eq!(rects[2], rect(110.0, 0.0, 290.0, 145.0));
+    assert_eq!(rects[3], rect(0.0, 155.0, 100.0, 145.0));
+    assert_eq!(rects[4], rect(110.0, 155.0, 290.0, 145.0));
+}
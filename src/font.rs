@@ -1,6 +1,10 @@
 //! Module for loading and handling fonts
-use webrender::api::FontKey;
+use webrender::api::{
+    FontKey, FontInstanceFlags, FontInstanceOptions, FontInstancePlatformOptions,
+    FontRenderMode, GlyphOptions,
+};
 use rusttype::{Error as RusttypeError, Font, FontCollection};
+use css_parser::{RectStyle, FontSmoothing, FontHinting, FontSubpixelPositioning, FontLcdFilter};
 
 #[derive(Debug, Clone)]
 pub(crate) enum FontState {
@@ -33,6 +37,86 @@ impl From<RusttypeError> for FontError {
     }
 }
 
+/// Returns whether the font contains a glyph for the character (i.e. it isn't rendered as the `.notdef` glyph)
+pub(crate) fn font_has_glyph<'a>(font: &Font<'a>, c: char) -> bool {
+    font.glyph(c).id().0 != 0
+}
+
+/// The characters that a font contains, read from the Unicode subtable of its `cmap` table
+/// without parsing the whole font. Used to search the system fonts for a character.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct CharCoverage {
+    /// Sorted, inclusive ranges of the covered code points
+    ranges: Vec<(u32, u32)>,
+}
+
+impl CharCoverage {
+    /// Reads the coverage of the font at `index` of the font (collection). Only `cmap` subtables of
+    /// format 4 (BMP) and 12 (full Unicode) are supported, returns `None` if the font has neither.
+    ///
+    /// NOTE: A format 4 segment may map some of its characters to the `.notdef` glyph,
+    /// so the coverage can contain characters that aren't actually in the font.
+    pub(crate) fn from_font_bytes(font: &[u8], index: usize) -> Option<Self> {
+        use text_shaping::{find_table_at, read_u16, read_u32};
+
+        let cmap = find_table_at(font, index, b"cmap")?;
+        let num_tables = read_u16(cmap, 2)? as usize;
+
+        // Format 12 is preferred, since it also contains the characters outside of the BMP
+        let mut best_subtable: Option<(u16, &[u8])> = None;
+
+        for i in 0..num_tables {
+            let record = 4 + i * 8;
+            let platform_id = read_u16(cmap, record)?;
+            let encoding_id = read_u16(cmap, record + 2)?;
+            let is_unicode = platform_id == 0 || (platform_id == 3 && (encoding_id == 1 || encoding_id == 10));
+            if !is_unicode {
+                continue;
+            }
+            let subtable = cmap.get(read_u32(cmap, record + 4)? as usize..)?;
+            let format = read_u16(subtable, 0)?;
+            if (format == 4 || format == 12) && best_subtable.map(|(best, _)| format > best).unwrap_or(true) {
+                best_subtable = Some((format, subtable));
+            }
+        }
+
+        let (format, subtable) = best_subtable?;
+        let mut ranges = Vec::new();
+
+        if format == 12 {
+            let num_groups = read_u32(subtable, 12)? as usize;
+            for i in 0..num_groups {
+                let group = 16 + i * 12;
+                ranges.push((read_u32(subtable, group)?, read_u32(subtable, group + 4)?));
+            }
+        } else {
+            let seg_count = read_u16(subtable, 6)? as usize / 2;
+            for i in 0..seg_count {
+                let end_code = read_u16(subtable, 14 + i * 2)? as u32;
+                let start_code = read_u16(subtable, 16 + seg_count * 2 + i * 2)? as u32;
+                // The last segment (0xFFFF) only terminates the table
+                if start_code != 0xFFFF {
+                    ranges.push((start_code, end_code));
+                }
+            }
+        }
+
+        ranges.sort();
+        Some(Self { ranges: ranges })
+    }
+
+    /// Returns whether the character is (probably) in the font
+    pub(crate) fn contains(&self, c: char) -> bool {
+        let c = c as u32;
+        // Number of ranges that start at or before `c`
+        let preceding_ranges = match self.ranges.binary_search_by(|(start, _)| start.cmp(&c)) {
+            Ok(idx) => idx + 1,
+            Err(idx) => idx,
+        };
+        preceding_ranges > 0 && self.ranges[preceding_ranges - 1].1 >= c
+    }
+}
+
 /// Read font data to get font information, v_metrics, glyph info etc.
 pub fn rusttype_load_font(data: Vec<u8>, index: Option<i32>) -> Result<(Font<'static>, Vec<u8>), FontError> {
     let collection = FontCollection::from_bytes(data.clone())?;
@@ -40,6 +124,217 @@ pub fn rusttype_load_font(data: Vec<u8>, index: Option<i32>) -> Result<(Font<'st
     Ok((font, data))
 }
 
+/// Order of the color subpixels of the screen, used for subpixel antialiasing
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SubpixelOrder {
+    /// Horizontal, red - green - blue (most common, the default)
+    Rgb,
+    /// Horizontal, blue - green - red
+    Bgr,
+    /// Vertical, red at the top
+    VerticalRgb,
+    /// Vertical, blue at the top
+    VerticalBgr,
+}
+
+/// How the glyphs of a window are rasterized. Set the defaults for a window in the
+/// `WindowCreateOptions`, the `font-smoothing`, `font-hinting`, `font-subpixel-positioning`
+/// and `font-lcd-filter` CSS properties override them for single nodes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FontRenderOptions {
+    /// Antialiasing mode: none (monochrome), grayscale or subpixel (LCD)
+    pub antialiasing: FontSmoothing,
+    /// Hinting strength (FreeType only)
+    pub hinting: FontHinting,
+    /// Whether glyphs are positioned at fractional pixel offsets
+    pub subpixel_positioning: bool,
+    /// Filter against color fringes of subpixel antialiased text (FreeType only)
+    pub lcd_filter: FontLcdFilter,
+    /// Order of the color subpixels of the screen
+    pub subpixel_order: SubpixelOrder,
+    /// Gamma correction in percent (Windows only)
+    pub gamma: u16,
+    /// Contrast enhancement in percent (Windows only)
+    pub contrast: u16,
+}
+
+impl Default for FontRenderOptions {
+    fn default() -> Self {
+        Self {
+            antialiasing: FontSmoothing::Subpixel,
+            hinting: FontHinting::Full,
+            subpixel_positioning: false,
+            lcd_filter: FontLcdFilter::Default,
+            subpixel_order: SubpixelOrder::Rgb,
+            gamma: 180,
+            contrast: 100,
+        }
+    }
+}
+
+impl FontRenderOptions {
+
+    /// Returns the options with the font rendering CSS properties of the `style` applied
+    pub(crate) fn with_style(&self, style: &RectStyle) -> Self {
+        let mut options = *self;
+        if let Some(antialiasing) = style.font_smoothing {
+            options.antialiasing = antialiasing;
+        }
+        if let Some(hinting) = style.font_hinting {
+            options.hinting = hinting;
+        }
+        if let Some(subpixel_positioning) = style.font_subpixel_positioning {
+            options.subpixel_positioning = subpixel_positioning == FontSubpixelPositioning::Auto;
+        }
+        if let Some(lcd_filter) = style.font_lcd_filter {
+            options.lcd_filter = lcd_filter;
+        }
+        options
+    }
+
+    pub(crate) fn get_render_mode(&self) -> FontRenderMode {
+        match self.antialiasing {
+            FontSmoothing::None => FontRenderMode::Mono,
+            FontSmoothing::Grayscale => FontRenderMode::Alpha,
+            FontSmoothing::Subpixel => FontRenderMode::Subpixel,
+        }
+    }
+
+    pub(crate) fn get_flags(&self, synthetic_bold: bool) -> FontInstanceFlags {
+        let mut flags = FontInstanceFlags::empty();
+
+        let (bgr, vertical) = match self.subpixel_order {
+            SubpixelOrder::Rgb => (false, false),
+            SubpixelOrder::Bgr => (true, false),
+            SubpixelOrder::VerticalRgb => (false, true),
+            SubpixelOrder::VerticalBgr => (true, true),
+        };
+
+        flags.set(FontInstanceFlags::SUBPIXEL_BGR, bgr);
+        flags.set(FontInstanceFlags::LCD_VERTICAL, vertical);
+        flags.set(FontInstanceFlags::SUBPIXEL_POSITION, self.subpixel_positioning);
+        flags.set(FontInstanceFlags::SYNTHETIC_BOLD, synthetic_bold);
+
+        // FONT_SMOOTHING (macOS), FORCE_AUTOHINT (FreeType) and FORCE_GDI (Windows)
+        // share the same bit, so only set the flag that is meant for this platform.
+        // FORCE_AUTOHINT is never set: the hinting strength is passed via the platform
+        // options, forcing the autohinter would ignore the hinting instructions of the font.
+        if cfg!(target_os = "macos") {
+            flags.set(FontInstanceFlags::FONT_SMOOTHING, self.antialiasing != FontSmoothing::None);
+        }
+
+        flags
+    }
+
+    pub(crate) fn get_instance_options(&self) -> FontInstanceOptions {
+        FontInstanceOptions {
+            render_mode: self.get_render_mode(),
+            flags: self.get_flags(false),
+            .. Default::default()
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    pub(crate) fn get_platform_options(&self) -> Option<FontInstancePlatformOptions> {
+        use webrender::api::{FontHinting as WrFontHinting, FontLCDFilter as WrFontLcdFilter};
+
+        let lcd_filter = match self.lcd_filter {
+            FontLcdFilter::None => WrFontLcdFilter::None,
+            FontLcdFilter::Default => WrFontLcdFilter::Default,
+            FontLcdFilter::Light => WrFontLcdFilter::Light,
+            FontLcdFilter::Legacy => WrFontLcdFilter::Legacy,
+        };
+
+        // "full" hinting uses the strongest hinting that fits the antialiasing mode
+        let hinting = match (self.hinting, self.antialiasing) {
+            (FontHinting::None, _) => WrFontHinting::None,
+            (FontHinting::Light, _) => WrFontHinting::Light,
+            (FontHinting::Normal, _) => WrFontHinting::Normal,
+            (FontHinting::Full, FontSmoothing::None) => WrFontHinting::Mono,
+            (FontHinting::Full, FontSmoothing::Grayscale) => WrFontHinting::Normal,
+            (FontHinting::Full, FontSmoothing::Subpixel) => WrFontHinting::LCD,
+        };
+
+        Some(FontInstancePlatformOptions {
+            lcd_filter: lcd_filter,
+            hinting: hinting,
+        })
+    }
+
+    #[cfg(target_os = "windows")]
+    pub(crate) fn get_platform_options(&self) -> Option<FontInstancePlatformOptions> {
+        Some(FontInstancePlatformOptions {
+            gamma: self.gamma,
+            contrast: self.contrast,
+        })
+    }
+
+    #[cfg(target_os = "macos")]
+    pub(crate) fn get_platform_options(&self) -> Option<FontInstancePlatformOptions> {
+        None
+    }
+
+    pub(crate) fn get_glyph_options(&self, synthetic_bold: bool) -> GlyphOptions {
+        GlyphOptions {
+            render_mode: self.get_render_mode(),
+            flags: self.get_flags(synthetic_bold),
+        }
+    }
+}
+
+#[test]
+fn test_font_render_options_with_style() {
+    let mut style = RectStyle::default();
+    style.font_smoothing = Some(FontSmoothing::Grayscale);
+    style.font_subpixel_positioning = Some(FontSubpixelPositioning::Auto);
+
+    let options = FontRenderOptions::default().with_style(&style);
+    assert_eq!(options.antialiasing, FontSmoothing::Grayscale);
+    assert_eq!(options.hinting, FontHinting::Full);
+    assert_eq!(options.get_render_mode(), FontRenderMode::Alpha);
+
+    let flags = options.get_flags(true);
+    assert!(flags.contains(FontInstanceFlags::SUBPIXEL_POSITION));
+    assert!(flags.contains(FontInstanceFlags::SYNTHETIC_BOLD));
+    assert!(!flags.intersects(FontInstanceFlags::SUBPIXEL_BGR | FontInstanceFlags::LCD_VERTICAL));
+    if !cfg!(target_os = "macos") {
+        assert!(!flags.contains(FontInstanceFlags::FORCE_AUTOHINT));
+    }
+}
+
+#[test]
+fn test_char_coverage_from_font_bytes() {
+
+    fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+        bytes.extend_from_slice(&[(value >> 8) as u8, value as u8]);
+    }
+
+    fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+        push_u16(bytes, (value >> 16) as u16);
+        push_u16(bytes, value as u16);
+    }
+
+    // Table directory with a single `cmap` table at offset 28
+    let mut font = Vec::new();
+    push_u32(&mut font, 0x0001_0000);
+    for value in &[1, 0, 0, 0] { push_u16(&mut font, *value); }
+    font.extend_from_slice(b"cmap");
+    for value in &[0, 28, 40] { push_u32(&mut font, *value); }
+
+    // `cmap` header with one Windows Unicode BMP subtable (format 4): "0-9", "a-z" and the 0xFFFF segment
+    for value in &[0, 1, 3, 1] { push_u16(&mut font, *value); }
+    push_u32(&mut font, 12);
+    for value in &[4, 28, 0, 6, 0, 0, 0] { push_u16(&mut font, *value); }
+    for value in &[0x39, 0x7A, 0xFFFF, 0, 0x30, 0x61, 0xFFFF] { push_u16(&mut font, *value); }
+
+    let coverage = CharCoverage::from_font_bytes(&font, 0).unwrap();
+    assert!(coverage.contains('a') && coverage.contains('z') && coverage.contains('5'));
+    assert!(!coverage.contains('A') && !coverage.contains('{') && !coverage.contains('\u{FFFF}'));
+
+    // Fonts without a Unicode `cmap` don't cover anything
+    assert_eq!(CharCoverage::from_font_bytes(&font[..12], 0), None);
+}
+
 // Empty test, for some reason codecov doesn't detect any files (and therefore
 // doesn't report codecov % correctly) except if they have at least one test in
 // the file. This is an empty test, which should be updated later on
@@ -0,0 +1,271 @@
+//! Placement of the children of a `display: grid` rectangle into the rows and
+//! columns of the grid. The sizes of the tracks are solved later on by cassowary,
+//! this module only determines which cells each child occupies.
+
+use {
+    FastHashSet,
+    css_parser::{GridLine, GridLinePosition, LayoutGridTemplateAreas},
+};
+
+/// The cells that one item occupies in the grid. All values are
+/// zero-based, the `*_end` values are exclusive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct GridCell {
+    pub(crate) column_start: usize,
+    pub(crate) column_end: usize,
+    pub(crate) row_start: usize,
+    pub(crate) row_end: usize,
+}
+
+/// Placement-relevant properties of one child of the grid
+#[derive(Debug, Copy, Clone, Default)]
+pub(crate) struct GridItem<'a> {
+    pub(crate) column: Option<GridLine>,
+    pub(crate) row: Option<GridLine>,
+    pub(crate) area: Option<&'a str>,
+}
+
+/// Result of placing all items: the cell of each item (in the same order as the items)
+/// and the final number of tracks, including implicitly created ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GridPlacement {
+    pub(crate) cells: Vec<GridCell>,
+    pub(crate) num_columns: usize,
+    pub(crate) num_rows: usize,
+}
+
+/// Resolves a `grid-column` or `grid-row` value against the number of explicit tracks
+///
+/// Returns the start track (`None` if the item should be auto-placed) and the number
+/// of tracks the item spans.
+fn resolve_line(line: &GridLine, num_explicit_tracks: usize) -> (Option<usize>, usize) {
+
+    use self::GridLinePosition::*;
+
+    // Converts a (1-based, possibly negative) line number into a zero-based line index
+    let line_to_index = |line: isize| -> usize {
+        if line > 0 {
+            (line - 1) as usize
+        } else {
+            // -1 is the last line of the explicit grid
+            (num_explicit_tracks as isize + 1 + line).max(0) as usize
+        }
+    };
+
+    match (line.start, line.end) {
+        (Line(start), Line(end)) => {
+            let (start, end) = (line_to_index(start), line_to_index(end));
+            let (start, end) = if end < start { (end, start) } else { (start, end) };
+            (Some(start), (end - start).max(1))
+        },
+        (Line(start), Span(span)) => (Some(line_to_index(start)), span),
+        (Line(start), Auto) => (Some(line_to_index(start)), 1),
+        (Span(span), Line(end)) => {
+            let end = line_to_index(end);
+            (Some(end.saturating_sub(span)), span.min(end).max(1))
+        },
+        (Auto, Line(end)) => (Some(line_to_index(end).saturating_sub(1)), 1),
+        (Span(span), _) | (Auto, Span(span)) => (None, span),
+        (Auto, Auto) => (None, 1),
+    }
+}
+
+/// Returns whether all cells in the given area are unoccupied
+fn is_free(occupied: &FastHashSet<(usize, usize)>, column: usize, row: usize, column_span: usize, row_span: usize) -> bool {
+    (column..column + column_span).all(|c| (row..row + row_span).all(|r| !occupied.contains(&(c, r))))
+}
+
+/// Marks all cells of the `cell` as occupied
+fn occupy(occupied: &mut FastHashSet<(usize, usize)>, cell: GridCell) -> GridCell {
+    for c in cell.column_start..cell.column_end {
+        for r in cell.row_start..cell.row_end {
+            occupied.insert((c, r));
+        }
+    }
+    cell
+}
+
+/// Places all items of a grid, following (a simplified version of) the CSS grid
+/// auto-placement algorithm:
+///
+/// 1. Items with a `grid-area` or a definite row and column are placed first
+/// 2. Items with only a definite row are placed in the first free column of that row
+/// 3. Items with only a definite column are placed in the first free row of that column
+/// 4. The remaining items are placed row-by-row in the next free cell
+///
+/// Rows are created implicitly if the explicit rows aren't enough to hold all items.
+pub(crate) fn place_grid_items(
+    items: &[GridItem],
+    num_explicit_columns: usize,
+    num_explicit_rows: usize,
+    areas: Option<&LayoutGridTemplateAreas>)
+-> GridPlacement
+{
+    let num_explicit_columns = areas.map(|a| a.num_columns()).unwrap_or(0).max(num_explicit_columns).max(1);
+    let num_explicit_rows = areas.map(|a| a.num_rows()).unwrap_or(0).max(num_explicit_rows);
+
+    let mut occupied = FastHashSet::<(usize, usize)>::default();
+    let mut cells = vec![None; items.len()];
+
+    let mut num_columns = num_explicit_columns;
+    let mut num_rows = num_explicit_rows;
+
+    let resolved = items.iter().map(|item| {
+        let area = item.area.and_then(|name| areas.and_then(|a| a.get_area(name)));
+        let column = item.column.map(|c| resolve_line(&c, num_explicit_columns)).unwrap_or((None, 1));
+        let row = item.row.map(|r| resolve_line(&r, num_explicit_rows)).unwrap_or((None, 1));
+        (area, column, row)
+    }).collect::<Vec<_>>();
+
+    // Definite columns outside of the explicit grid create implicit columns
+    for (_, (column_start, column_span), _) in &resolved {
+        if let Some(start) = column_start {
+            num_columns = num_columns.max(start + column_span);
+        }
+    }
+
+    // 1. Named areas and fully definite items
+    for (idx, (area, (column_start, column_span), (row_start, row_span))) in resolved.iter().enumerate() {
+        let cell = match (area, column_start, row_start) {
+            (Some((c_start, c_end, r_start, r_end)), _, _) => GridCell {
+                column_start: *c_start, column_end: *c_end, row_start: *r_start, row_end: *r_end,
+            },
+            (None, Some(c), Some(r)) => GridCell {
+                column_start: *c, column_end: c + column_span, row_start: *r, row_end: r + row_span,
+            },
+            _ => continue,
+        };
+        cells[idx] = Some(occupy(&mut occupied, cell));
+    }
+
+    // 2. Items with a definite row, but no definite column
+    for (idx, (area, (column_start, column_span), (row_start, row_span))) in resolved.iter().enumerate() {
+        if cells[idx].is_some() || area.is_some() || column_start.is_some() {
+            continue;
+        }
+        let row = match row_start { Some(r) => *r, None => continue };
+        let column_span = (*column_span).min(num_columns);
+        // Only a finite number of cells is occupied, so there is always a free slot - if the
+        // row is full, the slot extends past the explicit columns (creating implicit columns)
+        let column = (0..).find(|c| is_free(&occupied, *c, row, column_span, *row_span)).unwrap();
+        num_columns = num_columns.max(column + column_span);
+        cells[idx] = Some(occupy(&mut occupied, GridCell {
+            column_start: column, column_end: column + column_span, row_start: row, row_end: row + row_span,
+        }));
+    }
+
+    // 3. Items with a definite column, but no definite row
+    for (idx, (area, (column_start, column_span), (row_start, row_span))) in resolved.iter().enumerate() {
+        if cells[idx].is_some() || area.is_some() || row_start.is_some() {
+            continue;
+        }
+        let column = match column_start { Some(c) => *c, None => continue };
+        let row = (0..).find(|r| is_free(&occupied, column, *r, *column_span, *row_span)).unwrap();
+        cells[idx] = Some(occupy(&mut occupied, GridCell {
+            column_start: column, column_end: column + column_span, row_start: row, row_end: row + row_span,
+        }));
+    }
+
+    // 4. Auto-placement of the remaining items (row-major, "sparse" packing)
+    let (mut cursor_column, mut cursor_row) = (0, 0);
+    for (idx, (_, (_, column_span), (_, row_span))) in resolved.iter().enumerate() {
+        if cells[idx].is_some() {
+            continue;
+        }
+
+        let column_span = (*column_span).min(num_columns);
+
+        loop {
+            if cursor_column + column_span > num_columns {
+                cursor_column = 0;
+                cursor_row += 1;
+            }
+            if is_free(&occupied, cursor_column, cursor_row, column_span, *row_span) {
+                break;
+            }
+            cursor_column += 1;
+        }
+
+        cells[idx] = Some(occupy(&mut occupied, GridCell {
+            column_start: cursor_column,
+            column_end: cursor_column + column_span,
+            row_start: cursor_row,
+            row_end: cursor_row + row_span,
+        }));
+
+        cursor_column += column_span;
+    }
+
+    let cells = cells.into_iter().map(|c| c.unwrap()).collect::<Vec<GridCell>>();
+
+    for cell in &cells {
+        num_columns = num_columns.max(cell.column_end);
+        num_rows = num_rows.max(cell.row_end);
+    }
+
+    GridPlacement {
+        cells,
+        num_columns,
+        num_rows,
+    }
+}
+
+#[test]
+fn test_grid_auto_placement() {
+    let items = vec![GridItem::default(); 5];
+    let placement = place_grid_items(&items, 2, 0, None);
+    assert_eq!(placement.num_columns, 2);
+    assert_eq!(placement.num_rows, 3);
+    assert_eq!(placement.cells[2], GridCell { column_start: 0, column_end: 1, row_start: 1, row_end: 2 });
+    assert_eq!(placement.cells[4], GridCell { column_start: 0, column_end: 1, row_start: 2, row_end: 3 });
+}
+
+#[test]
+fn test_grid_definite_placement() {
+    let spanning = GridItem {
+        column: Some(GridLine { start: GridLinePosition::Line(1), end: GridLinePosition::Line(-1) }),
+        .. GridItem::default()
+    };
+    let items = vec![GridItem::default(), spanning, GridItem::default()];
+    let placement = place_grid_items(&items, 3, 0, None);
+    // the definite item is placed first, the auto-placed items flow around it
+    assert_eq!(placement.cells[1], GridCell { column_start: 0, column_end: 3, row_start: 0, row_end: 1 });
+    assert_eq!(placement.cells[0], GridCell { column_start: 0, column_end: 1, row_start: 1, row_end: 2 });
+    assert_eq!(placement.cells[2], GridCell { column_start: 1, column_end: 2, row_start: 1, row_end: 2 });
+}
+
+#[test]
+fn test_grid_definite_row_in_full_row() {
+    let in_column = |column| GridItem {
+        column: Some(GridLine { start: GridLinePosition::Line(column), end: GridLinePosition::Auto }),
+        row: Some(GridLine { start: GridLinePosition::Line(1), end: GridLinePosition::Auto }),
+        .. GridItem::default()
+    };
+    let row_only = GridItem {
+        row: Some(GridLine { start: GridLinePosition::Line(1), end: GridLinePosition::Auto }),
+        .. GridItem::default()
+    };
+    // the first row is full and column 3 is taken, so the item must not overlap any of them
+    let items = vec![in_column(1), in_column(2), in_column(3), row_only];
+    let placement = place_grid_items(&items, 2, 1, None);
+    assert_eq!(placement.cells[3], GridCell { column_start: 3, column_end: 4, row_start: 0, row_end: 1 });
+    assert_eq!(placement.num_columns, 4);
+    assert_eq!(placement.num_rows, 1);
+}
+
+#[test]
+fn test_grid_area_placement() {
+    let areas = LayoutGridTemplateAreas {
+        rows: vec![
+            vec![Some("header".into()), Some("header".into())],
+            vec![Some("sidebar".into()), Some("main".into())],
+        ],
+    };
+    let items = vec![
+        GridItem { area: Some("main"), .. GridItem::default() },
+        GridItem { area: Some("header"), .. GridItem::default() },
+    ];
+    let placement = place_grid_items(&items, 0, 0, Some(&areas));
+    assert_eq!(placement.cells[0], GridCell { column_start: 1, column_end: 2, row_start: 1, row_end: 2 });
+    assert_eq!(placement.cells[1], GridCell { column_start: 0, column_end: 2, row_start: 0, row_end: 1 });
+}
@@ -0,0 +1,288 @@
+//! Styling and layout without a window (and without OpenGL), for testing the
+//! screens of an application on machines that don't have a display.
+//!
+//! ```no_run,ignore
+//! let css = Css::native();
+//! let resources = AppResources::default();
+//! let layout = layout_headless(my_screen(), &css, &resources, &HeadlessOptions::default());
+//! let bounds = layout.get_node_bounds_by_id("submit_button").unwrap();
+//! assert_eq!(bounds.size.width, 200.0);
+//! ```
+
+use std::fmt;
+use glium::glutin::dpi::LogicalSize;
+use webrender::api::{LayoutRect, BuiltDisplayList, PipelineId};
+use {
+    css::{Css, CssDeclaration},
+    css_parser::{ParsedCssProperty, RectLayout},
+    dom::Dom,
+    traits::Layout,
+    id_tree::NodeId,
+    app_resources::AppResources,
+    ui_state::UiState,
+    ui_description::UiDescription,
+    ui_solver::{UiSolver, SolvedLayout},
+    display_list::DisplayList,
+    window::LayoutEngine,
+};
+
+/// Options for `layout_headless`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeadlessOptions {
+    /// Size of the (simulated) window
+    pub viewport: LogicalSize,
+    /// Which layout engine should be used, same as `WindowCreateOptions::layout_engine`
+    pub layout_engine: LayoutEngine,
+    /// Whether the webrender display list should be built, see `HeadlessLayout::display_list`
+    pub build_display_list: bool,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            viewport: LogicalSize::new(800.0, 600.0),
+            layout_engine: LayoutEngine::default(),
+            build_display_list: false,
+        }
+    }
+}
+
+/// One node of the styled DOM
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessNode {
+    pub node_id: NodeId,
+    pub parent: Option<NodeId>,
+    /// CSS id of the node (see `Dom::with_id`)
+    pub id: Option<String>,
+    /// CSS classes of the node (see `Dom::with_class`)
+    pub classes: Vec<String>,
+    /// The CSS properties that apply to this node, after the cascading step
+    /// and after the dynamic CSS properties have been resolved
+    pub css_properties: Vec<ParsedCssProperty>,
+    /// The parsed layout properties of the node
+    pub layout: RectLayout,
+    /// The solved bounds of the node, relative to the top left of the viewport
+    pub bounds: LayoutRect,
+}
+
+/// Result of `layout_headless`
+pub struct HeadlessLayout {
+    /// All nodes of the DOM, indexed by the `NodeId`. The first node is the root `Div`
+    /// that azul wraps around the DOM returned from `Layout::layout`
+    pub nodes: Vec<HeadlessNode>,
+    /// The display list, if `HeadlessOptions::build_display_list` was set. Only contains the
+    /// style of the rectangles (backgrounds, borders, box shadows) - text, images, GL textures
+    /// and IFrames are not pushed, since they need resources that are uploaded to a renderer.
+    pub display_list: Option<BuiltDisplayList>,
+    solved_layout: SolvedLayout,
+}
+
+impl fmt::Debug for HeadlessLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+            "HeadlessLayout {{ \
+                nodes: {:?}, \
+                display_list: {:?}, \
+            }}", self.nodes, self.display_list.as_ref().and_then(|_| Some("BuiltDisplayList")))
+    }
+}
+
+impl HeadlessLayout {
+
+    /// Returns the bounds of the node, same as `FakeWindow::get_node_bounds`
+    pub fn get_node_bounds(&self, node_id: NodeId) -> Option<LayoutRect> {
+        self.solved_layout.get_bounds(node_id)
+    }
+
+    /// Returns the bounds of the first node with the given CSS id, same as `FakeWindow::get_node_bounds_by_id`
+    pub fn get_node_bounds_by_id(&self, id: &str) -> Option<LayoutRect> {
+        self.solved_layout.get_bounds_by_id(id)
+    }
+}
+
+/// Styles the `dom` with the `css` and lays it out in a viewport of the given size,
+/// exactly like a window would do on the first frame - but without creating a window.
+///
+/// The `app_resources` are necessary to measure text, so any fonts or images that
+/// the DOM uses have to be added beforehand.
+pub fn layout_headless<T: Layout>(
+    dom: Dom<T>,
+    css: &Css,
+    app_resources: &AppResources,
+    options: &HeadlessOptions)
+-> HeadlessLayout
+{
+    let mut css = css.clone();
+    let ui_state = UiState::from_dom(dom);
+    let ui_description = UiDescription::from_ui_state(&ui_state, &css);
+    let display_list = DisplayList::new_from_ui_description(&ui_description, &ui_state);
+
+    let mut ui_solver = UiSolver::new(&options.viewport, options.layout_engine);
+    display_list.layout(&mut ui_solver, &mut css, app_resources, true, &options.viewport);
+
+    let arena = ui_description.ui_descr_arena.borrow();
+    let solved_layout = SolvedLayout::new(&*arena, &ui_solver);
+
+    let nodes = arena.linear_iter().map(|node_id| {
+        let node = &arena[node_id];
+        let styled_node = ui_description.styled_nodes.get(&node_id).unwrap_or(&ui_description.default_style_of_node);
+        let css_properties = styled_node.css_constraints.list.iter().map(|declaration| match declaration {
+            CssDeclaration::Static(property) => property.clone(),
+            CssDeclaration::Dynamic(dynamic) => ui_description.dynamic_css_overrides.get(&dynamic.dynamic_id)
+                .cloned()
+                .unwrap_or(dynamic.default.clone()),
+        }).collect();
+
+        HeadlessNode {
+            node_id: node_id,
+            parent: node.parent(),
+            id: node.data.id.clone(),
+            classes: node.data.classes.clone(),
+            css_properties: css_properties,
+            layout: display_list.rectangles[node_id].data.layout.clone(),
+            bounds: solved_layout.rects[node_id.index()],
+        }
+    }).collect();
+
+    let built_display_list = if options.build_display_list {
+        let builder = display_list.into_headless_display_list_builder(PipelineId(0, 0), &ui_solver, app_resources, &options.viewport);
+        Some(builder.finalize().2)
+    } else {
+        None
+    };
+
+    HeadlessLayout {
+        nodes: nodes,
+        display_list: built_display_list,
+        solved_layout: solved_layout,
+    }
+}
+
+#[test]
+fn test_layout_headless() {
+    use dom::NodeType;
+
+    struct TestLayout { }
+
+    impl Layout for TestLayout {
+        fn layout(&self) -> Dom<Self> {
+            Dom::new(NodeType::Div)
+        }
+    }
+
+    let dom = Dom::<TestLayout>::new(NodeType::Div)
+        .with_id("main")
+        .with_child(Dom::new(NodeType::Div).with_id("sidebar"))
+        .with_child(Dom::new(NodeType::Div).with_id("content"));
+
+    let css = Css::new_from_str("
+        #main { flex-direction: row; }
+        #sidebar { width: 100px; background-color: #ff0000; }
+    ").unwrap();
+
+    let options = HeadlessOptions {
+        viewport: LogicalSize::new(800.0, 600.0),
+        layout_engine: LayoutEngine::Flexbox,
+        build_display_list: true,
+    };
+
+    let layout = layout_headless(dom, &css, &AppResources::default(), &options);
+
+    // The root div that wraps the DOM + the three nodes of the DOM
+    assert_eq!(layout.nodes.len(), 4);
+    assert_eq!(layout.nodes[2].id, Some(String::from("sidebar")));
+    assert_eq!(layout.nodes[2].parent, Some(NodeId::new(1)));
+
+    let sidebar = layout.get_node_bounds_by_id("sidebar").unwrap();
+    assert_eq!((sidebar.origin.x, sidebar.size.width, sidebar.size.height), (0.0, 100.0, 600.0));
+    let content = layout.get_node_bounds_by_id("content").unwrap();
+    assert_eq!((content.origin.x, content.size.width), (100.0, 700.0));
+
+    assert!(layout.display_list.is_some());
+}
+
+#[test]
+fn test_layout_headless_cassowary() {
+    use dom::NodeType;
+
+    struct TestLayout { }
+
+    impl Layout for TestLayout {
+        fn layout(&self) -> Dom<Self> {
+            Dom::new(NodeType::Div)
+        }
+    }
+
+    let dom = Dom::<TestLayout>::new(NodeType::Div)
+        .with_id("main")
+        .with_child(Dom::new(NodeType::Div).with_id("sidebar"))
+        .with_child(Dom::new(NodeType::Div).with_id("content"));
+
+    let css = Css::new_from_str("
+        #main { flex-direction: row; }
+        #sidebar { width: 100px; }
+    ").unwrap();
+
+    // The default layout engine, which is used by windows
+    let options = HeadlessOptions {
+        viewport: LogicalSize::new(800.0, 600.0),
+        layout_engine: LayoutEngine::Cassowary,
+        build_display_list: false,
+    };
+
+    let layout = layout_headless(dom, &css, &AppResources::default(), &options);
+
+    assert_eq!(layout.nodes.len(), 4);
+    assert!(layout.display_list.is_none());
+
+    let main = layout.get_node_bounds_by_id("main").unwrap();
+    assert_eq!((main.origin.x, main.origin.y, main.size.width.round(), main.size.height.round()), (0.0, 0.0, 800.0, 600.0));
+    let sidebar = layout.get_node_bounds_by_id("sidebar").unwrap();
+    assert_eq!((sidebar.origin.x, sidebar.size.width.round(), sidebar.size.height.round()), (0.0, 100.0, 600.0));
+    // The content starts after the sidebar (the cassowary engine doesn't grow it into the remaining space)
+    let content = layout.get_node_bounds_by_id("content").unwrap();
+    assert_eq!(content.origin.x.round(), 100.0);
+}
+
+#[test]
+fn test_layout_headless_fixed_and_sticky() {
+    use dom::NodeType;
+
+    struct TestLayout { }
+
+    impl Layout for TestLayout {
+        fn layout(&self) -> Dom<Self> {
+            Dom::new(NodeType::Div)
+        }
+    }
+
+    let dom = Dom::<TestLayout>::new(NodeType::Div)
+        .with_id("main")
+        .with_child(Dom::new(NodeType::Div).with_id("overlay"))
+        .with_child(Dom::new(NodeType::Div).with_id("header"))
+        .with_child(Dom::new(NodeType::Div).with_id("content"));
+
+    let css = Css::new_from_str("
+        #main { width: 400px; height: 300px; flex-direction: column; }
+        #overlay { position: fixed; top: 10px; left: 20px; }
+        #header { position: sticky; top: 0px; }
+    ").unwrap();
+
+    let options = HeadlessOptions {
+        viewport: LogicalSize::new(800.0, 600.0),
+        layout_engine: LayoutEngine::Cassowary,
+        build_display_list: false,
+    };
+
+    let layout = layout_headless(dom, &css, &AppResources::default(), &options);
+    let bounds = |id: &str| {
+        let rect = layout.get_node_bounds_by_id(id).unwrap();
+        (rect.origin.x.round(), rect.origin.y.round(), rect.size.width.round(), rect.size.height.round())
+    };
+
+    // The fixed node is sized and positioned relative to the window, not to its parent
+    assert_eq!(bounds("overlay"), (20.0, 10.0, 800.0, 600.0));
+    // The sticky node stays in the flow, the fixed node doesn't take up any space
+    assert_eq!(bounds("header"), (0.0, 0.0, 400.0, 150.0));
+    assert_eq!(bounds("content"), (0.0, 150.0, 400.0, 150.0));
+}
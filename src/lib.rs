@@ -48,6 +48,9 @@ extern crate simplecss;
 extern crate rusttype;
 extern crate app_units;
 extern crate unicode_normalization;
+extern crate unicode_bidi;
+extern crate unicode_segmentation;
+extern crate xi_unicode;
 extern crate tinyfiledialogs;
 extern crate stb_truetype;
 extern crate clipboard2;
@@ -76,12 +79,16 @@ pub mod dialogs;
 pub mod dom;
 /// Font handling
 pub mod font;
+/// Styling and layout without a window, i.e. for testing
+pub mod headless;
 /// Async IO / task system
 pub mod task;
 /// Module for caching long texts (including their layout / character positions) across multiple frames
 pub mod text_cache;
 /// Text layout helper functions - useful for text layout outside of standard containers
 pub mod text_layout;
+/// Texts that consist of differently styled (and clickable) spans
+pub mod rich_text;
 /// The layout traits for creating a layout-able application
 pub mod traits;
 /// Built-in widgets
@@ -127,6 +134,14 @@ mod compositor;
 mod logging;
 /// Cassowary-based UI solver
 mod ui_solver;
+/// Item placement for `display: grid`
+mod grid_layout;
+/// Direct (non-cassowary) flexbox layout engine
+mod flex_layout;
+/// Scroll containers, scroll offsets and scrollbar interaction
+mod scroll;
+/// OpenType shaping (ligatures, kerning, mark positioning) of text runs
+mod text_shaping;
 
 /// Faster implementation of a HashMap
 type FastHashMap<T, U> = ::std::collections::HashMap<T, U, ::std::hash::BuildHasherDefault<::twox_hash::XxHash>>;
@@ -146,23 +161,33 @@ pub mod prelude {
     pub use traits::{Layout, Modify};
     pub use window::{MonitorIter, Window, WindowCreateOptions, WindowId,
                      MouseMode, UpdateBehaviour, UpdateMode,
-                     WindowMonitorTarget, RendererType, WindowEvent, WindowInfo, ReadOnlyWindow};
+                     WindowMonitorTarget, RendererType, LayoutEngine, ScrollOptions, WindowEvent, EventPhase, WindowInfo, ReadOnlyWindow};
     pub use window_state::{WindowState, KeyboardState, MouseState};
     pub use images::{ImageType, ImageId};
-    pub use text_cache::{TextCache, TextId};
+    pub use text_cache::{TextCache, TextId, TextCacheStats};
+    pub use font::{FontRenderOptions, SubpixelOrder};
+    pub use text_layout::{TextRef, TextMeasureOptions};
+    pub use rich_text::{RichText, TextSpan, TextSpanStyle, FontWeight, TextDecoration};
     pub use css_parser::{
         ParsedCssProperty, BorderRadius, BackgroundColor, TextColor,
         BorderWidths, BorderDetails, Background, FontSize,
-        FontFamily, TextOverflowBehaviour, TextOverflowBehaviourInner, TextAlignmentHorz,
+        FontFamily, TextOverflowBehaviour, TextOverflowBehaviourInner, TextAlignmentHorz, TextAlignmentVert,
         BoxShadowPreDisplayItem, LayoutWidth, LayoutHeight,
         LayoutMinWidth, LayoutMinHeight, LayoutMaxWidth,
-        LayoutMaxHeight, LayoutWrap, LayoutDirection,
+        LayoutMaxHeight, LayoutIntrinsicSize, LayoutWrap, LayoutDirection,
         LayoutJustifyContent, LayoutAlignItems, LayoutAlignContent,
-        LayoutTop, LayoutBottom, LayoutRight, LayoutLeft,
-        LinearGradientPreInfo, RadialGradientPreInfo, CssImageId, FontId, CssColor,
+        LayoutTop, LayoutBottom, LayoutRight, LayoutLeft, LayoutTextDirection,
+        LayoutPaddingInlineStart, LayoutPaddingInlineEnd,
+        LayoutDisplay, LayoutGap, GridTrackSize, GridTrackBreadth, GridLine, GridLinePosition,
+        LayoutGridTemplateColumns, LayoutGridTemplateRows, LayoutGridTemplateAreas,
+        LayoutGridColumn, LayoutGridRow, LayoutGridArea,
+        LayoutObjectFit, LayoutObjectPosition, ObjectPositionComponent, LayoutAspectRatio,
+        FontKerning, FontFeature, FontFeatureSettings, FontSmoothing, FontHinting, FontSubpixelPositioning, FontLcdFilter, TextWrap, WhiteSpace, WordBreak, OverflowWrap,
+        TextOverflowStyle, LineClamp, LetterSpacing, TabSize, TabStops,
+        LinearGradientPreInfo, RadialGradientPreInfo, CssImageId, FontId, CssColor, RectLayout,
 
         LayoutPixel, TypedSize2D, BoxShadowClipMode, ColorU, ColorF, LayoutVector2D,
-        Gradient, SideOffsets2D, RadialGradient, LayoutPoint, LayoutSize,
+        Gradient, SideOffsets2D, RadialGradient, LayoutPoint, LayoutSize, LayoutRect,
         ExtendMode, PixelValue, PercentageValue,
     };
     pub use glium::glutin::{
@@ -173,6 +198,7 @@ pub mod prelude {
     pub use app_resources::AppResources;
     pub use daemon::{TerminateDaemon, DaemonId, DaemonCallback, Daemon};
     pub use default_callbacks::StackCheckedPointer;
+    pub use headless::{layout_headless, HeadlessOptions, HeadlessLayout, HeadlessNode};
 
     #[cfg(feature = "logging")]
     pub use log::LevelFilter;
@@ -186,6 +212,7 @@ pub mod errors {
         PercentageParseError,
         CssBackgroundParseError, CssColorParseError, CssBorderRadiusParseError,
         CssDirectionParseError, CssGradientStopParseError, CssShapeParseError,
+        GridParseError, ObjectPositionParseError, AspectRatioParseError, FontFeatureSettingsParseError,
     };
     pub use simplecss::Error as CssSyntaxError;
     pub use css::{CssParseError, DynamicCssParseError};
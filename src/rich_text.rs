@@ -0,0 +1,140 @@
+use css_parser::{FontFamily, FontSize, TextColor};
+
+/// Weight of the font of a `TextSpan`. Bold text is rendered with a synthetic bold
+/// version of the font (the glyphs are emboldened by webrender).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontWeight {
+    Normal,
+    Bold,
+}
+
+impl Default for FontWeight {
+    fn default() -> Self {
+        FontWeight::Normal
+    }
+}
+
+/// Line that is drawn under, over or through the text of a `TextSpan`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TextDecoration {
+    None,
+    Underline,
+    Overline,
+    LineThrough,
+}
+
+impl Default for TextDecoration {
+    fn default() -> Self {
+        TextDecoration::None
+    }
+}
+
+/// Style of a single `TextSpan`. All properties that are `None` are inherited
+/// from the style of the `NodeType::RichText` node.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct TextSpanStyle {
+    pub font_family: Option<FontFamily>,
+    pub font_size: Option<FontSize>,
+    pub font_color: Option<TextColor>,
+    pub font_weight: Option<FontWeight>,
+    pub text_decoration: Option<TextDecoration>,
+}
+
+/// A piece of text with its own style inside of a `RichText`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextSpan {
+    pub text: String,
+    pub style: TextSpanStyle,
+    /// Whether the span can be clicked on (i.e. a link). Callbacks on the `RichText` node
+    /// get the index of the clicked span in `WindowEvent::hit_text_span`.
+    pub is_clickable: bool,
+}
+
+impl TextSpan {
+    /// Creates a span that is styled like the `RichText` node it belongs to
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Self {
+            text: text.into(),
+            style: TextSpanStyle::default(),
+            is_clickable: false,
+        }
+    }
+
+    pub fn with_font_family(mut self, font_family: FontFamily) -> Self {
+        self.style.font_family = Some(font_family);
+        self
+    }
+
+    pub fn with_font_size(mut self, font_size: FontSize) -> Self {
+        self.style.font_size = Some(font_size);
+        self
+    }
+
+    pub fn with_font_color(mut self, font_color: TextColor) -> Self {
+        self.style.font_color = Some(font_color);
+        self
+    }
+
+    pub fn with_font_weight(mut self, font_weight: FontWeight) -> Self {
+        self.style.font_weight = Some(font_weight);
+        self
+    }
+
+    pub fn with_text_decoration(mut self, text_decoration: TextDecoration) -> Self {
+        self.style.text_decoration = Some(text_decoration);
+        self
+    }
+
+    /// Makes the span clickable, see `TextSpan::is_clickable`
+    pub fn clickable(mut self) -> Self {
+        self.is_clickable = true;
+        self
+    }
+}
+
+/// A paragraph of text that consists of differently styled spans (`NodeType::RichText`).
+///
+/// The spans are laid out as one paragraph: lines can be broken inside of and between spans,
+/// the spans of a line are aligned on a common baseline and the height of a line is the
+/// height of its highest span.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct RichText {
+    pub spans: Vec<TextSpan>,
+}
+
+impl RichText {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_span(mut self, span: TextSpan) -> Self {
+        self.spans.push(span);
+        self
+    }
+
+    pub fn push_span(&mut self, span: TextSpan) {
+        self.spans.push(span);
+    }
+
+    /// Returns the text of all spans, concatenated
+    pub fn get_text(&self) -> String {
+        self.spans.iter().map(|span| &span.text[..]).collect()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.spans.iter().all(|span| span.text.is_empty())
+    }
+}
+
+#[test]
+fn test_rich_text_builder() {
+    let rich_text = RichText::new()
+        .with_span(TextSpan::new("Read the "))
+        .with_span(TextSpan::new("docs").with_text_decoration(TextDecoration::Underline).clickable());
+
+    assert_eq!(rich_text.get_text(), "Read the docs");
+    assert!(!rich_text.spans[0].is_clickable);
+    assert!(rich_text.spans[1].is_clickable);
+    assert_eq!(rich_text.spans[1].style.text_decoration, Some(TextDecoration::Underline));
+    assert!(RichText::new().with_span(TextSpan::new("")).is_empty());
+}
@@ -0,0 +1,847 @@
+//! Scroll containers (nodes with `overflow: scroll` or `overflow: auto`)
+//!
+//! Every scroll container is backed by a webrender scroll frame. The scroll offsets are
+//! stored per window in the `ScrollStates`, keyed by a hash of the container (see
+//! `get_scroll_key`), so that they persist across frames, even though the DOM is rebuilt
+//! from scratch in every frame.
+//!
+//! While the display list is built, the geometry of each scroll container is stored as a
+//! `ScrollFrame`. Mouse wheel events, scrollbar clicks / drags and `FakeWindow::scroll_to`
+//! requests are then resolved against the scroll frames of the last frame.
+
+use std::{
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+use glium::glutin::{Event, WindowEvent, ElementState, MouseButton, MouseScrollDelta, TouchPhase, dpi::LogicalPosition};
+use webrender::api::{LayoutRect, LayoutSize, LayoutPoint, LayoutVector2D};
+use twox_hash::XxHash;
+use {
+    FastHashMap,
+    cache::DomHash,
+    dom::NodeData,
+    traits::Layout,
+    id_tree::{Arena, NodeId},
+    css_parser::{LayoutOverflow, LayoutTextDirection, TextOverflowBehaviour, TextOverflowBehaviourInner},
+    text_layout::ScrollbarInfo,
+    window_state::get_scroll_delta_px,
+    window::ScrollOptions,
+};
+
+/// Minimum length of the thumb of a scrollbar, so that it stays clickable for very long content
+const MIN_THUMB_LENGTH: f32 = 20.0;
+/// Momentum scrolling stops below this speed (in pixels per second)
+const MIN_MOMENTUM_VELOCITY: f32 = 10.0;
+/// If the fingers rest on the touchpad for longer than this before they are lifted,
+/// the gesture ends without momentum
+const MAX_MOMENTUM_PAUSE: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ScrollAxis {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct ScrollState {
+    /// Amount in pixels that the content of the container is scrolled
+    /// to the right / to the bottom
+    pub(crate) offset: LayoutVector2D,
+    /// Was the scroll state used in this frame?
+    used_this_frame: bool,
+}
+
+impl Default for ScrollState {
+    fn default() -> Self {
+        ScrollState {
+            offset: LayoutVector2D::zero(),
+            used_this_frame: true,
+        }
+    }
+}
+
+/// The track and the thumb of one scrollbar
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Scrollbar {
+    pub(crate) track: LayoutRect,
+    pub(crate) thumb: LayoutRect,
+}
+
+/// Geometry of a scroll container in the last frame
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ScrollFrame {
+    pub(crate) node_id: NodeId,
+    pub(crate) key: DomHash,
+    /// Bounds of the container (including the scrollbars), in the coordinate
+    /// space of the parent scroll frame
+    pub(crate) bounds: LayoutRect,
+    /// Size of the scrollable content, at least as large as the viewport
+    pub(crate) content_size: LayoutSize,
+    /// How far the parent scroll frames are scrolled, necessary to convert the
+    /// cursor position from window coordinates into the coordinates of this frame
+    pub(crate) parent_offset: LayoutVector2D,
+    /// Depth of the container in the DOM, the innermost container is scrolled first
+    pub(crate) depth: usize,
+    pub(crate) has_horizontal_scrollbar: bool,
+    pub(crate) has_vertical_scrollbar: bool,
+    /// In `direction: rtl`, the vertical scrollbar is on the left side
+    pub(crate) text_direction: LayoutTextDirection,
+    scrollbar_width: f32,
+    scrollbar_padding: f32,
+}
+
+impl ScrollFrame {
+
+    /// Returns `None` if the container doesn't need to scroll (i.e. `overflow: auto`
+    /// and the content fits into the container).
+    pub(crate) fn new(
+        node_id: NodeId,
+        key: DomHash,
+        bounds: LayoutRect,
+        content_size: LayoutSize,
+        overflow: &LayoutOverflow,
+        parent_offset: LayoutVector2D,
+        depth: usize,
+        text_direction: LayoutTextDirection)
+    -> Option<Self>
+    {
+        let scrollbar_style = ScrollbarInfo::default();
+        let scrollbar_width = scrollbar_style.width as f32;
+
+        let overflow_x = get_scroll_mode(&overflow.horizontal);
+        let overflow_y = get_scroll_mode(&overflow.vertical);
+
+        // A scrollbar takes away space from the other axis, which can
+        // cause the content to overflow in the other direction, too
+        let mut has_vertical_scrollbar = overflow_y == Some(TextOverflowBehaviourInner::Scroll);
+        let mut has_horizontal_scrollbar = overflow_x == Some(TextOverflowBehaviourInner::Scroll);
+
+        for _ in 0..2 {
+            let available_width = bounds.size.width - if has_vertical_scrollbar { scrollbar_width } else { 0.0 };
+            let available_height = bounds.size.height - if has_horizontal_scrollbar { scrollbar_width } else { 0.0 };
+            has_horizontal_scrollbar |= overflow_x.is_some() && content_size.width > available_width;
+            has_vertical_scrollbar |= overflow_y.is_some() && content_size.height > available_height;
+        }
+
+        if !has_horizontal_scrollbar && !has_vertical_scrollbar {
+            return None;
+        }
+
+        let mut frame = Self {
+            node_id: node_id,
+            key: key,
+            bounds: bounds,
+            content_size: content_size,
+            parent_offset: parent_offset,
+            depth: depth,
+            has_horizontal_scrollbar: has_horizontal_scrollbar,
+            has_vertical_scrollbar: has_vertical_scrollbar,
+            text_direction: text_direction,
+            scrollbar_width: scrollbar_width,
+            scrollbar_padding: scrollbar_style.padding as f32,
+        };
+
+        let viewport = frame.viewport();
+        frame.content_size = LayoutSize::new(
+            content_size.width.max(viewport.size.width),
+            content_size.height.max(viewport.size.height));
+
+        Some(frame)
+    }
+
+    /// The visible area of the content (the bounds without the scrollbars)
+    pub(crate) fn viewport(&self) -> LayoutRect {
+        let mut viewport = self.bounds;
+        if self.has_vertical_scrollbar {
+            viewport.size.width = (viewport.size.width - self.scrollbar_width).max(0.0);
+            if self.text_direction == LayoutTextDirection::Rtl {
+                viewport.origin.x = self.bounds.max_x() - viewport.size.width;
+            }
+        }
+        if self.has_horizontal_scrollbar {
+            viewport.size.height = (viewport.size.height - self.scrollbar_width).max(0.0);
+        }
+        viewport
+    }
+
+    /// How far the content can be scrolled - an axis without a scrollbar can't be scrolled
+    pub(crate) fn max_offset(&self) -> LayoutVector2D {
+        let viewport = self.viewport();
+        LayoutVector2D::new(
+            if self.has_horizontal_scrollbar { (self.content_size.width - viewport.size.width).max(0.0) } else { 0.0 },
+            if self.has_vertical_scrollbar { (self.content_size.height - viewport.size.height).max(0.0) } else { 0.0 })
+    }
+
+    pub(crate) fn clamp_offset(&self, offset: LayoutVector2D) -> LayoutVector2D {
+        let max = self.max_offset();
+        LayoutVector2D::new(offset.x.max(0.0).min(max.x), offset.y.max(0.0).min(max.y))
+    }
+
+    /// Converts a cursor position (in window coordinates) into the coordinate space of this frame
+    fn to_frame_coordinates(&self, cursor: LayoutPoint) -> LayoutPoint {
+        cursor + self.parent_offset
+    }
+
+    /// Returns the track and thumb of the scrollbar of the given axis,
+    /// in the coordinate space of the parent scroll frame
+    pub(crate) fn get_scrollbar(&self, axis: ScrollAxis, offset: LayoutVector2D) -> Option<Scrollbar> {
+        let viewport = self.viewport();
+        let max_offset = self.max_offset();
+        let (width, padding) = (self.scrollbar_width, self.scrollbar_padding);
+
+        match axis {
+            ScrollAxis::Vertical => {
+                if !self.has_vertical_scrollbar {
+                    return None;
+                }
+                let track_x = match self.text_direction {
+                    LayoutTextDirection::Ltr => viewport.max_x(),
+                    LayoutTextDirection::Rtl => self.bounds.min_x(),
+                };
+                let track = LayoutRect::new(
+                    LayoutPoint::new(track_x, viewport.min_y()),
+                    LayoutSize::new(width, viewport.size.height));
+                let (thumb_start, thumb_length) = get_thumb_position(
+                    track.size.height, viewport.size.height, self.content_size.height, offset.y, max_offset.y);
+                let thumb = LayoutRect::new(
+                    LayoutPoint::new(track.min_x() + padding, track.min_y() + thumb_start),
+                    LayoutSize::new((width - padding * 2.0).max(0.0), thumb_length));
+                Some(Scrollbar { track: track, thumb: thumb })
+            },
+            ScrollAxis::Horizontal => {
+                if !self.has_horizontal_scrollbar {
+                    return None;
+                }
+                let track = LayoutRect::new(
+                    LayoutPoint::new(viewport.min_x(), viewport.max_y()),
+                    LayoutSize::new(viewport.size.width, width));
+                let (thumb_start, thumb_length) = get_thumb_position(
+                    track.size.width, viewport.size.width, self.content_size.width, offset.x, max_offset.x);
+                let thumb = LayoutRect::new(
+                    LayoutPoint::new(track.min_x() + thumb_start, track.min_y() + padding),
+                    LayoutSize::new(thumb_length, (width - padding * 2.0).max(0.0)));
+                Some(Scrollbar { track: track, thumb: thumb })
+            },
+        }
+    }
+}
+
+/// Returns the start (relative to the start of the track) and the length of the thumb
+fn get_thumb_position(track_length: f32, viewport_length: f32, content_length: f32, offset: f32, max_offset: f32) -> (f32, f32) {
+    let thumb_length = if content_length > 0.0 {
+        (track_length * viewport_length / content_length).max(MIN_THUMB_LENGTH).min(track_length)
+    } else {
+        track_length
+    };
+    let thumb_start = if max_offset > 0.0 {
+        (track_length - thumb_length) * (offset / max_offset)
+    } else {
+        0.0
+    };
+    (thumb_start, thumb_length)
+}
+
+/// Returns `Some(Scroll)` or `Some(Auto)` if the overflow property makes the node scrollable
+fn get_scroll_mode(overflow: &TextOverflowBehaviour) -> Option<TextOverflowBehaviourInner> {
+    use self::TextOverflowBehaviourInner::*;
+    match overflow {
+        TextOverflowBehaviour::Modified(Scroll) => Some(Scroll),
+        TextOverflowBehaviour::Modified(Auto) => Some(Auto),
+        _ => None,
+    }
+}
+
+/// Returns whether the node has `overflow: scroll` or `overflow: auto` on any axis
+pub(crate) fn is_scroll_container(overflow: &LayoutOverflow) -> bool {
+    get_scroll_mode(&overflow.horizontal).is_some() || get_scroll_mode(&overflow.vertical).is_some()
+}
+
+/// Calculates the key under which the scroll offset of a container is stored.
+///
+/// If the node has an id (see `Dom::with_id`) that no other node in the DOM has, only the id
+/// is used, so the offset survives any changes to the DOM. Otherwise the key is built from the
+/// node and its position in the DOM, i.e. the offset is kept as long as the path to the container
+/// doesn't change - so that containers with the same id don't share one offset.
+pub(crate) fn get_scroll_key<T: Layout>(arena: &Arena<NodeData<T>>, node_id: NodeId) -> DomHash {
+    let mut hasher = XxHash::default();
+
+    if let Some(id) = &arena[node_id].data.id {
+        let is_unique_id = arena.linear_iter().filter(|other| arena[*other].data.id.as_ref() == Some(id)).take(2).count() == 1;
+        if is_unique_id {
+            id.hash(&mut hasher);
+            return DomHash(hasher.finish());
+        }
+    }
+
+    for ancestor in node_id.ancestors(arena) {
+        arena[ancestor].data.calculate_node_data_hash().hash(&mut hasher);
+        ancestor.preceding_siblings(arena).count().hash(&mut hasher);
+    }
+
+    DomHash(hasher.finish())
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ScrollbarDrag {
+    key: DomHash,
+    axis: ScrollAxis,
+    /// Cursor position (on the axis of the scrollbar) when the thumb was grabbed
+    start_cursor: f32,
+    /// Scroll offset (on the axis of the scrollbar) when the thumb was grabbed
+    start_offset: f32,
+}
+
+/// Scrolling that continues after the scroll event
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ScrollAnimation {
+    /// The `remaining` distance of one or more mouse wheel notches is scrolled until `end`
+    Smooth {
+        cursor: LayoutPoint,
+        remaining: LayoutVector2D,
+        last_tick: Instant,
+        end: Instant,
+    },
+    /// Scrolling with the speed of a touchpad gesture (in pixels per second), slowing down
+    /// according to `ScrollOptions::momentum_friction`
+    Momentum {
+        cursor: LayoutPoint,
+        velocity: LayoutVector2D,
+        last_tick: Instant,
+    },
+}
+
+/// Scroll offsets of all scroll containers in one window
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ScrollStates {
+    states: FastHashMap<DomHash, ScrollState>,
+    /// Scroll containers of the last frame, in rendering order
+    frames: Vec<ScrollFrame>,
+    /// The scrollbar thumb that is currently being dragged
+    drag: Option<ScrollbarDrag>,
+    pub(crate) options: ScrollOptions,
+    animation: Option<ScrollAnimation>,
+    /// Speed (in pixels per second) and time of the last event of the current touchpad gesture
+    touchpad_velocity: Option<(LayoutVector2D, Instant)>,
+}
+
+impl ScrollStates {
+
+    pub(crate) fn new(options: ScrollOptions) -> Self {
+        Self {
+            options: options,
+            .. Self::default()
+        }
+    }
+
+    /// Called before the display list is built: forgets the scroll frames of the last frame
+    pub(crate) fn begin_frame(&mut self) {
+        self.frames.clear();
+        for state in self.states.values_mut() {
+            state.used_this_frame = false;
+        }
+    }
+
+    /// Registers a scroll container of the current frame and returns its scroll
+    /// offset, clamped to the (possibly changed) size of the content
+    pub(crate) fn add_frame(&mut self, frame: ScrollFrame) -> LayoutVector2D {
+        let state = self.states.entry(frame.key).or_insert_with(ScrollState::default);
+        state.used_this_frame = true;
+        state.offset = frame.clamp_offset(state.offset);
+        let offset = state.offset;
+        self.frames.push(frame);
+        offset
+    }
+
+    /// Removes the offsets of all containers that weren't rendered in this frame
+    pub(crate) fn remove_unused(&mut self) {
+        self.states.retain(|_, state| state.used_this_frame);
+        let is_drag_target_removed = match self.drag {
+            Some(drag) => !self.states.contains_key(&drag.key),
+            None => false,
+        };
+        if is_drag_target_removed {
+            self.drag = None;
+        }
+    }
+
+    pub(crate) fn get_offset(&self, key: &DomHash) -> LayoutVector2D {
+        self.states.get(key).map(|state| state.offset).unwrap_or(LayoutVector2D::zero())
+    }
+
+    /// Returns the scroll containers of the last frame, with their current offsets
+    pub(crate) fn get_frames<'a>(&'a self) -> impl Iterator<Item = (&'a ScrollFrame, LayoutVector2D)> + 'a {
+        self.frames.iter().map(move |frame| (frame, self.get_offset(&frame.key)))
+    }
+
+    fn get_frame(&self, key: &DomHash) -> Option<&ScrollFrame> {
+        self.frames.iter().find(|frame| frame.key == *key)
+    }
+
+    /// Sets the offset of the container, returns whether the offset changed
+    fn set_offset(&mut self, key: &DomHash, offset: LayoutVector2D) -> bool {
+        let offset = match self.get_frame(key) {
+            Some(frame) => frame.clamp_offset(offset),
+            None => return false,
+        };
+        let state = self.states.entry(*key).or_insert_with(ScrollState::default);
+        let changed = state.offset != offset;
+        state.offset = offset;
+        changed
+    }
+
+    /// Scrolls the container with the given `NodeId` to the offset (clamped to the content),
+    /// returns whether the offset changed
+    pub(crate) fn scroll_to(&mut self, node_id: NodeId, offset: LayoutVector2D) -> bool {
+        let key = match self.frames.iter().find(|frame| frame.node_id == node_id) {
+            Some(frame) => frame.key,
+            None => return false,
+        };
+        self.set_offset(&key, offset)
+    }
+
+    /// Returns the keys of all containers under the cursor, innermost container first
+    fn get_frames_at(&self, cursor: LayoutPoint) -> Vec<DomHash> {
+        let mut hit_frames = self.frames.iter()
+            .filter(|frame| frame.bounds.contains(&frame.to_frame_coordinates(cursor)))
+            .collect::<Vec<_>>();
+        hit_frames.sort_by(|a, b| b.depth.cmp(&a.depth));
+        hit_frames.into_iter().map(|frame| frame.key).collect()
+    }
+
+    /// Scrolls the innermost container under the cursor by `delta` (positive values scroll
+    /// to the bottom / right). Whatever the container can't scroll (because it has reached
+    /// the end of its content) is passed on to the containers it is nested in.
+    ///
+    /// Returns whether any container was scrolled.
+    pub(crate) fn on_wheel(&mut self, cursor: LayoutPoint, delta: LayoutVector2D) -> bool {
+        let mut remaining = delta;
+        let mut changed = false;
+
+        for key in self.get_frames_at(cursor) {
+            if remaining == LayoutVector2D::zero() {
+                break;
+            }
+            let old_offset = self.get_offset(&key);
+            changed |= self.set_offset(&key, old_offset + remaining);
+            remaining -= self.get_offset(&key) - old_offset;
+        }
+
+        changed
+    }
+
+    /// Handles a `MouseWheel` event: mouse wheel notches (`LineDelta`) are animated if
+    /// `ScrollOptions::smooth_scroll_duration` is set, touchpad gestures (`PixelDelta`) are
+    /// scrolled immediately and continue with momentum once the gesture has ended.
+    ///
+    /// Returns whether any container was scrolled.
+    pub(crate) fn on_wheel_event(&mut self, cursor: LayoutPoint, delta: &MouseScrollDelta, phase: TouchPhase, now: Instant) -> bool {
+        let (scroll_x_px, scroll_y_px) = get_scroll_delta_px(delta, &self.options);
+        let delta_px = LayoutVector2D::new(scroll_x_px as f32, scroll_y_px as f32);
+
+        match delta {
+            MouseScrollDelta::LineDelta(..) => {
+                self.touchpad_velocity = None;
+                match self.options.smooth_scroll_duration {
+                    Some(duration) => {
+                        // Notches in quick succession add up, instead of restarting the animation
+                        let remaining = match self.animation {
+                            Some(ScrollAnimation::Smooth { remaining, .. }) => remaining,
+                            _ => LayoutVector2D::zero(),
+                        };
+                        self.animation = Some(ScrollAnimation::Smooth {
+                            cursor: cursor,
+                            remaining: remaining + delta_px,
+                            last_tick: now,
+                            end: now + duration,
+                        });
+                        false
+                    },
+                    None => {
+                        self.animation = None;
+                        self.on_wheel(cursor, delta_px)
+                    },
+                }
+            },
+            MouseScrollDelta::PixelDelta(_) => {
+                self.animation = None;
+                match phase {
+                    TouchPhase::Started | TouchPhase::Cancelled => {
+                        self.touchpad_velocity = None;
+                    },
+                    TouchPhase::Moved => {
+                        let velocity = match self.touchpad_velocity {
+                            Some((_, last_event)) if now > last_event => delta_px / duration_to_secs(now - last_event),
+                            _ => LayoutVector2D::zero(),
+                        };
+                        self.touchpad_velocity = Some((velocity, now));
+                    },
+                    TouchPhase::Ended => {
+                        if let Some((velocity, last_event)) = self.touchpad_velocity.take() {
+                            let is_moving = now < last_event + MAX_MOMENTUM_PAUSE && velocity.length() > MIN_MOMENTUM_VELOCITY;
+                            if self.options.momentum_scrolling && is_moving {
+                                self.animation = Some(ScrollAnimation::Momentum {
+                                    cursor: cursor,
+                                    velocity: velocity,
+                                    last_tick: now,
+                                });
+                            }
+                        }
+                    },
+                }
+                self.on_wheel(cursor, delta_px)
+            },
+        }
+    }
+
+    /// Advances the smooth / momentum scrolling, returns whether any container was scrolled
+    pub(crate) fn animate(&mut self, now: Instant) -> bool {
+        let animation = match self.animation {
+            Some(animation) => animation,
+            None => return false,
+        };
+
+        match animation {
+            ScrollAnimation::Smooth { cursor, remaining, last_tick, end } => {
+                let is_finished = now >= end;
+                let step = if is_finished || last_tick >= end {
+                    remaining
+                } else if now > last_tick {
+                    remaining * (duration_to_secs(now - last_tick) / duration_to_secs(end - last_tick))
+                } else {
+                    LayoutVector2D::zero()
+                };
+                let scrolled = self.on_wheel(cursor, step);
+                // Stop early if the content can't be scrolled any further
+                let is_blocked = step != LayoutVector2D::zero() && !scrolled;
+                self.animation = if is_finished || is_blocked {
+                    None
+                } else {
+                    Some(ScrollAnimation::Smooth { cursor: cursor, remaining: remaining - step, last_tick: now, end: end })
+                };
+                scrolled
+            },
+            ScrollAnimation::Momentum { cursor, velocity, last_tick } => {
+                if now <= last_tick {
+                    return false;
+                }
+                let dt = duration_to_secs(now - last_tick);
+                let scrolled = self.on_wheel(cursor, velocity * dt);
+                let velocity = velocity * self.options.momentum_friction.max(0.0).min(1.0).powf(dt);
+                self.animation = if !scrolled || velocity.length() < MIN_MOMENTUM_VELOCITY {
+                    None
+                } else {
+                    Some(ScrollAnimation::Momentum { cursor: cursor, velocity: velocity, last_tick: now })
+                };
+                scrolled
+            },
+        }
+    }
+
+    /// Handles a left click: grabs the thumb of a scrollbar or, if the track was clicked,
+    /// scrolls by one page towards the cursor. Returns whether the click hit a scrollbar.
+    pub(crate) fn on_mouse_down(&mut self, cursor: LayoutPoint) -> bool {
+        for key in self.get_frames_at(cursor) {
+            let frame = match self.get_frame(&key) {
+                Some(frame) => frame.clone(),
+                None => continue,
+            };
+            let cursor = frame.to_frame_coordinates(cursor);
+            let offset = self.get_offset(&key);
+            let viewport = frame.viewport();
+
+            for axis in &[ScrollAxis::Vertical, ScrollAxis::Horizontal] {
+                let scrollbar = match frame.get_scrollbar(*axis, offset) {
+                    Some(scrollbar) if scrollbar.track.contains(&cursor) => scrollbar,
+                    _ => continue,
+                };
+
+                let (cursor_pos, thumb_start, thumb_end, page, axis_offset) = match axis {
+                    ScrollAxis::Vertical => (cursor.y, scrollbar.thumb.min_y(), scrollbar.thumb.max_y(), viewport.size.height, offset.y),
+                    ScrollAxis::Horizontal => (cursor.x, scrollbar.thumb.min_x(), scrollbar.thumb.max_x(), viewport.size.width, offset.x),
+                };
+
+                if cursor_pos >= thumb_start && cursor_pos <= thumb_end {
+                    self.drag = Some(ScrollbarDrag {
+                        key: key,
+                        axis: *axis,
+                        start_cursor: cursor_pos,
+                        start_offset: axis_offset,
+                    });
+                } else {
+                    let page = if cursor_pos < thumb_start { -page } else { page };
+                    let delta = match axis {
+                        ScrollAxis::Vertical => LayoutVector2D::new(0.0, page),
+                        ScrollAxis::Horizontal => LayoutVector2D::new(page, 0.0),
+                    };
+                    self.set_offset(&key, offset + delta);
+                }
+
+                self.animation = None;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Moves the dragged thumb (if any) with the cursor, returns whether the offset changed
+    pub(crate) fn on_cursor_moved(&mut self, cursor: LayoutPoint) -> bool {
+        let drag = match self.drag {
+            Some(drag) => drag,
+            None => return false,
+        };
+
+        let frame = match self.get_frame(&drag.key) {
+            Some(frame) => frame.clone(),
+            None => return false,
+        };
+
+        let cursor = frame.to_frame_coordinates(cursor);
+        let offset = self.get_offset(&drag.key);
+        let max_offset = frame.max_offset();
+        let scrollbar = match frame.get_scrollbar(drag.axis, offset) {
+            Some(scrollbar) => scrollbar,
+            None => return false,
+        };
+
+        // How many pixels the content moves if the thumb moves by one pixel
+        let (cursor_pos, free_track_length, axis_max_offset) = match drag.axis {
+            ScrollAxis::Vertical => (cursor.y, scrollbar.track.size.height - scrollbar.thumb.size.height, max_offset.y),
+            ScrollAxis::Horizontal => (cursor.x, scrollbar.track.size.width - scrollbar.thumb.size.width, max_offset.x),
+        };
+
+        if free_track_length <= 0.0 {
+            return false;
+        }
+
+        let new_axis_offset = drag.start_offset + (cursor_pos - drag.start_cursor) * axis_max_offset / free_track_length;
+        let new_offset = match drag.axis {
+            ScrollAxis::Vertical => LayoutVector2D::new(offset.x, new_axis_offset),
+            ScrollAxis::Horizontal => LayoutVector2D::new(new_axis_offset, offset.y),
+        };
+
+        self.set_offset(&drag.key, new_offset)
+    }
+
+    /// Releases the dragged thumb
+    pub(crate) fn on_mouse_up(&mut self) -> bool {
+        self.drag.take().is_some()
+    }
+
+    /// Routes the mouse wheel and scrollbar events of the window to the scroll containers,
+    /// returns whether any container was scrolled (i.e. the window has to be redrawn)
+    pub(crate) fn handle_event(&mut self, event: &Event, cursor: Option<LogicalPosition>, now: Instant) -> bool {
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return false,
+        };
+
+        let cursor = cursor.and_then(|cursor| Some(LayoutPoint::new(cursor.x as f32, cursor.y as f32)));
+
+        match (event, cursor) {
+            (WindowEvent::MouseWheel { delta, phase, .. }, Some(cursor)) => {
+                self.on_wheel_event(cursor, delta, *phase, now)
+            },
+            (WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. }, Some(cursor)) => {
+                self.on_mouse_down(cursor)
+            },
+            (WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. }, _) => {
+                self.on_mouse_up()
+            },
+            (WindowEvent::CursorMoved { .. }, Some(cursor)) => {
+                self.on_cursor_moved(cursor)
+            },
+            _ => false,
+        }
+    }
+}
+
+fn duration_to_secs(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1_000_000_000.0
+}
+
+#[cfg(test)]
+fn test_frame(node_id: usize, bounds: LayoutRect, content_size: LayoutSize, depth: usize) -> ScrollFrame {
+    let overflow = LayoutOverflow {
+        horizontal: TextOverflowBehaviour::Modified(TextOverflowBehaviourInner::Auto),
+        vertical: TextOverflowBehaviour::Modified(TextOverflowBehaviourInner::Auto),
+    };
+    ScrollFrame::new(NodeId::new(node_id), DomHash(node_id as u64), bounds, content_size, &overflow, LayoutVector2D::zero(), depth, LayoutTextDirection::Ltr).unwrap()
+}
+
+#[test]
+fn test_scroll_frame_auto_overflow() {
+    let overflow = LayoutOverflow {
+        horizontal: TextOverflowBehaviour::NotModified,
+        vertical: TextOverflowBehaviour::Modified(TextOverflowBehaviourInner::Auto),
+    };
+    let bounds = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(100.0, 100.0));
+
+    // content fits, no scrolling necessary
+    assert!(ScrollFrame::new(NodeId::new(0), DomHash(0), bounds, LayoutSize::new(100.0, 50.0), &overflow, LayoutVector2D::zero(), 0, LayoutTextDirection::Ltr).is_none());
+
+    let frame = ScrollFrame::new(NodeId::new(0), DomHash(0), bounds, LayoutSize::new(100.0, 300.0), &overflow, LayoutVector2D::zero(), 0, LayoutTextDirection::Ltr).unwrap();
+    assert!(frame.has_vertical_scrollbar);
+    assert!(!frame.has_horizontal_scrollbar);
+    assert_eq!(frame.viewport().size, LayoutSize::new(83.0, 100.0));
+    assert_eq!(frame.max_offset(), LayoutVector2D::new(0.0, 200.0));
+    assert_eq!(frame.clamp_offset(LayoutVector2D::new(50.0, 500.0)), LayoutVector2D::new(0.0, 200.0));
+    assert_eq!(frame.get_scrollbar(ScrollAxis::Vertical, LayoutVector2D::zero()).unwrap().track.origin.x, 83.0);
+
+    // In right-to-left containers, the scrollbar is on the left side
+    let frame = ScrollFrame::new(NodeId::new(0), DomHash(0), bounds, LayoutSize::new(100.0, 300.0), &overflow, LayoutVector2D::zero(), 0, LayoutTextDirection::Rtl).unwrap();
+    assert_eq!(frame.viewport(), LayoutRect::new(LayoutPoint::new(17.0, 0.0), LayoutSize::new(83.0, 100.0)));
+    assert_eq!(frame.get_scrollbar(ScrollAxis::Vertical, LayoutVector2D::zero()).unwrap().track.origin.x, 0.0);
+}
+
+#[test]
+fn test_scroll_wheel_routing() {
+    let mut scroll_states = ScrollStates::new(ScrollOptions::default());
+    scroll_states.begin_frame();
+
+    // outer container (200px of content) with a nested container (100px of content)
+    let outer = test_frame(1, LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(100.0, 100.0)), LayoutSize::new(83.0, 300.0), 1);
+    let inner = test_frame(2, LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(50.0, 50.0)), LayoutSize::new(33.0, 150.0), 2);
+    scroll_states.add_frame(outer);
+    scroll_states.add_frame(inner);
+    scroll_states.remove_unused();
+
+    // The inner container is scrolled first ...
+    assert!(scroll_states.on_wheel(LayoutPoint::new(10.0, 10.0), LayoutVector2D::new(0.0, 60.0)));
+    assert_eq!(scroll_states.get_offset(&DomHash(2)), LayoutVector2D::new(0.0, 60.0));
+    assert_eq!(scroll_states.get_offset(&DomHash(1)), LayoutVector2D::zero());
+
+    // ... and the rest is passed on to the outer container, once the inner container is at the end
+    assert!(scroll_states.on_wheel(LayoutPoint::new(10.0, 10.0), LayoutVector2D::new(0.0, 60.0)));
+    assert_eq!(scroll_states.get_offset(&DomHash(2)), LayoutVector2D::new(0.0, 100.0));
+    assert_eq!(scroll_states.get_offset(&DomHash(1)), LayoutVector2D::new(0.0, 20.0));
+
+    // Outside of the inner container, only the outer container is scrolled
+    assert!(scroll_states.on_wheel(LayoutPoint::new(70.0, 70.0), LayoutVector2D::new(0.0, -100.0)));
+    assert_eq!(scroll_states.get_offset(&DomHash(2)), LayoutVector2D::new(0.0, 100.0));
+    assert_eq!(scroll_states.get_offset(&DomHash(1)), LayoutVector2D::zero());
+
+    // Nothing left to scroll
+    assert!(!scroll_states.on_wheel(LayoutPoint::new(70.0, 70.0), LayoutVector2D::new(0.0, -100.0)));
+}
+
+#[test]
+fn test_scrollbar_drag_and_track_click() {
+    let mut scroll_states = ScrollStates::new(ScrollOptions::default());
+    scroll_states.begin_frame();
+    scroll_states.add_frame(test_frame(1, LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(100.0, 100.0)), LayoutSize::new(83.0, 400.0), 1));
+
+    // thumb is 25px long (100 * 100 / 400), there are 75px left to move it, for 300px of content
+    let scrollbar = scroll_states.frames[0].get_scrollbar(ScrollAxis::Vertical, LayoutVector2D::zero()).unwrap();
+    assert_eq!(scrollbar.thumb.size.height, 25.0);
+
+    // grab the thumb and move it by half of the track
+    assert!(scroll_states.on_mouse_down(LayoutPoint::new(90.0, 10.0)));
+    assert!(scroll_states.on_cursor_moved(LayoutPoint::new(90.0, 47.5)));
+    assert_eq!(scroll_states.get_offset(&DomHash(1)), LayoutVector2D::new(0.0, 150.0));
+    assert!(scroll_states.on_mouse_up());
+    assert!(!scroll_states.on_cursor_moved(LayoutPoint::new(90.0, 90.0)));
+
+    // clicking the track below the thumb scrolls by one page
+    assert!(scroll_states.on_mouse_down(LayoutPoint::new(90.0, 95.0)));
+    assert_eq!(scroll_states.get_offset(&DomHash(1)), LayoutVector2D::new(0.0, 250.0));
+    scroll_states.on_mouse_up();
+
+    // scroll_to is clamped
+    assert!(scroll_states.scroll_to(NodeId::new(1), LayoutVector2D::new(0.0, 1000.0)));
+    assert_eq!(scroll_states.get_offset(&DomHash(1)), LayoutVector2D::new(0.0, 300.0));
+
+    // clicking outside of the scrollbar doesn't do anything
+    assert!(!scroll_states.on_mouse_down(LayoutPoint::new(10.0, 10.0)));
+}
+
+#[test]
+fn test_smooth_scrolling() {
+    let mut scroll_states = ScrollStates::new(ScrollOptions::default());
+    scroll_states.begin_frame();
+    scroll_states.add_frame(test_frame(1, LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(100.0, 100.0)), LayoutSize::new(83.0, 1000.0), 1));
+
+    let start = Instant::now();
+    let cursor = LayoutPoint::new(10.0, 10.0);
+
+    // One notch down (winit reports negative values when scrolling down): 100px in 150ms
+    assert!(!scroll_states.on_wheel_event(cursor, &MouseScrollDelta::LineDelta(0.0, -1.0), TouchPhase::Moved, start));
+    assert_eq!(scroll_states.get_offset(&DomHash(1)), LayoutVector2D::zero());
+
+    assert!(scroll_states.animate(start + Duration::from_millis(75)));
+    assert!((scroll_states.get_offset(&DomHash(1)).y - 50.0).abs() < 0.01);
+
+    assert!(scroll_states.animate(start + Duration::from_millis(150)));
+    assert!((scroll_states.get_offset(&DomHash(1)).y - 100.0).abs() < 0.01);
+    assert!(!scroll_states.animate(start + Duration::from_millis(200)));
+
+    // Natural scrolling inverts the direction, without smooth scrolling the offset changes immediately
+    scroll_states.options.natural_scrolling = true;
+    scroll_states.options.smooth_scroll_duration = None;
+    assert!(scroll_states.on_wheel_event(cursor, &MouseScrollDelta::LineDelta(0.0, -0.5), TouchPhase::Moved, start));
+    assert!((scroll_states.get_offset(&DomHash(1)).y - 50.0).abs() < 0.01);
+}
+
+#[test]
+fn test_momentum_scrolling() {
+    let mut scroll_states = ScrollStates::new(ScrollOptions::default());
+    scroll_states.begin_frame();
+    scroll_states.add_frame(test_frame(1, LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(100.0, 100.0)), LayoutSize::new(83.0, 1000.0), 1));
+
+    let start = Instant::now();
+    let cursor = LayoutPoint::new(10.0, 10.0);
+    let pixel_delta = |y| MouseScrollDelta::PixelDelta(LogicalPosition::new(0.0, y));
+
+    // 10px every 10ms = 1000px per second
+    assert!(scroll_states.on_wheel_event(cursor, &pixel_delta(-10.0), TouchPhase::Moved, start));
+    assert!(scroll_states.on_wheel_event(cursor, &pixel_delta(-10.0), TouchPhase::Moved, start + Duration::from_millis(10)));
+    assert!(!scroll_states.on_wheel_event(cursor, &pixel_delta(0.0), TouchPhase::Ended, start + Duration::from_millis(20)));
+    assert!((scroll_states.get_offset(&DomHash(1)).y - 20.0).abs() < 0.01);
+
+    // The content keeps moving after the fingers are lifted ...
+    assert!(scroll_states.animate(start + Duration::from_millis(120)));
+    assert!((scroll_states.get_offset(&DomHash(1)).y - 120.0).abs() < 0.1);
+
+    // ... but slows down
+    assert!(scroll_states.animate(start + Duration::from_millis(220)));
+    let offset = scroll_states.get_offset(&DomHash(1)).y;
+    assert!(offset > 120.0 && offset < 220.0);
+
+    // Without momentum scrolling, the content stops immediately
+    scroll_states.options.momentum_scrolling = false;
+    scroll_states.on_wheel_event(cursor, &pixel_delta(-10.0), TouchPhase::Moved, start + Duration::from_millis(300));
+    scroll_states.on_wheel_event(cursor, &pixel_delta(-10.0), TouchPhase::Moved, start + Duration::from_millis(310));
+    scroll_states.on_wheel_event(cursor, &pixel_delta(0.0), TouchPhase::Ended, start + Duration::from_millis(320));
+    assert!(!scroll_states.animate(start + Duration::from_millis(420)));
+}
+
+#[test]
+fn test_scroll_key_duplicate_ids() {
+    use dom::{Dom, NodeType};
+
+    struct TestLayout { }
+
+    impl Layout for TestLayout {
+        fn layout(&self) -> Dom<Self> {
+            Dom::new(NodeType::Div)
+                .with_child(Dom::new(NodeType::Div).with_id("list"))
+                .with_child(Dom::new(NodeType::Div).with_id("list"))
+                .with_child(Dom::new(NodeType::Div).with_id("sidebar"))
+        }
+    }
+
+    let dom = TestLayout { }.layout();
+    let arena = dom.arena.borrow();
+    let find_nodes = |id: &str| arena.linear_iter().filter(|node_id| arena[*node_id].data.id.as_ref().map(|s| s.as_str()) == Some(id)).collect::<Vec<NodeId>>();
+
+    // Containers with the same id must not share one scroll offset
+    let lists = find_nodes("list");
+    assert_eq!(lists.len(), 2);
+    assert!(get_scroll_key(&arena, lists[0]) != get_scroll_key(&arena, lists[1]));
+
+    // A unique id is used as the key on its own, independent of the position in the DOM
+    let sidebar = find_nodes("sidebar")[0];
+    let mut hasher = XxHash::default();
+    String::from("sidebar").hash(&mut hasher);
+    assert_eq!(get_scroll_key(&arena, sidebar), DomHash(hasher.finish()));
+}
@@ -1,8 +1,13 @@
-use std::sync::atomic::{Ordering, AtomicUsize};
+use std::{
+    mem,
+    cell::RefCell,
+    collections::BTreeMap,
+    sync::atomic::{Ordering, AtomicUsize},
+};
 use {
     FastHashMap,
-    css_parser::{FontId, FontSize},
-    text_layout::Words,
+    css_parser::{FontId, FontSize, FontFamily, LayoutTextDirection},
+    text_layout::{Words, SemanticWordItem, GlyphInstance, GlyphInfo},
 };
 
 static TEXT_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -21,19 +26,62 @@ pub struct TextId {
 }
 
 /// Cache for accessing large amounts of text
+///
+/// The strings are kept until they are deleted, but the layout of the strings (the words)
+/// can be limited to a memory budget (see `set_memory_budget`): if the budget is exceeded,
+/// the words of the least recently used texts are evicted and laid out again the next time
+/// the text is drawn.
 #[derive(Debug, Default, Clone)]
 pub struct TextCache {
     /// Caches the layout of the strings / words.
     ///
     /// TextId -> FontId (to look up by font)
-    /// FontId -> FontSize (to categorize by size within a font)
-    /// FontSize -> layouted words (to cache the glyph widths on a per-font-size basis)
-    pub cached_strings: FastHashMap<TextId, FastHashMap<FontId, FastHashMap<FontSize, Words>>>,
+    /// FontId -> (FontSize, direction) (to categorize by size and paragraph direction within a font)
+    /// (FontSize, direction) -> layouted words (to cache the glyph widths on a per-font-size basis)
+    ///
+    /// Insert words via `insert_words`, otherwise they don't count towards the memory budget.
+    pub cached_strings: FastHashMap<TextId, FastHashMap<FontId, FastHashMap<(FontSize, LayoutTextDirection), Words>>>,
     /// Mapping from the TextID to the actual, UTF-8 String
     ///
     /// This is stored outside of the actual glyph calculation, because usually you don't
     /// need the string, except for rebuilding a cached string (for example, when the font is changed)
     pub string_cache: FastHashMap<TextId, String>,
+    /// The font fallback chain of each text, per font family (see `AppResources::get_text_fallback_chain`).
+    /// Evicted together with the words of the text.
+    fallback_chains: RefCell<FastHashMap<TextId, FastHashMap<FontFamily, Vec<FontId>>>>,
+    /// Maximum (estimated) size of the cached words in bytes, `None` if the cache is unbounded
+    memory_budget: Option<usize>,
+    /// Estimated size of the cached words of each text in bytes
+    cached_bytes: FastHashMap<TextId, usize>,
+    /// Sum of `cached_bytes`
+    total_cached_bytes: usize,
+    /// When the words of a text were last used (counted in cache accesses)
+    last_used: FastHashMap<TextId, u64>,
+    /// Inverse of `last_used`, the least recently used text comes first
+    lru_queue: BTreeMap<u64, TextId>,
+    /// Incremented on every cache access
+    current_tick: u64,
+    /// How many texts had their words evicted since the cache was created
+    evicted_count: usize,
+}
+
+/// Statistics about the memory usage of a `TextCache`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TextCacheStats {
+    /// Number of texts in the cache
+    pub text_count: usize,
+    /// Size of all strings, in bytes
+    pub string_bytes: usize,
+    /// Number of texts that have cached words
+    pub cached_text_count: usize,
+    /// Number of cached layouts (one per text, font, font size and direction)
+    pub cached_layout_count: usize,
+    /// Estimated size of all cached words, in bytes
+    pub cached_bytes: usize,
+    /// The memory budget for the cached words, see `TextCache::set_memory_budget`
+    pub memory_budget: Option<usize>,
+    /// How many texts had their words evicted (because the memory budget was exceeded)
+    pub evicted_count: usize,
 }
 
 impl TextCache {
@@ -46,13 +94,145 @@ impl TextCache {
 
     pub fn delete_text(&mut self, id: TextId) {
         self.string_cache.remove(&id);
-        self.cached_strings.remove(&id);
+        self.evict_words(id);
     }
 
     pub fn clear_all_texts(&mut self) {
         self.string_cache.clear();
         self.cached_strings.clear();
+        self.fallback_chains.borrow_mut().clear();
+        self.cached_bytes.clear();
+        self.total_cached_bytes = 0;
+        self.last_used.clear();
+        self.lru_queue.clear();
+    }
+
+    /// Limits the (estimated) memory usage of the cached words to `max_bytes`, evicting the
+    /// words of the least recently used texts if necessary. `None` removes the limit.
+    ///
+    /// The strings themselves are never evicted, only their layout.
+    pub fn set_memory_budget(&mut self, max_bytes: Option<usize>) {
+        self.memory_budget = max_bytes;
+        self.evict_to_budget(None);
+    }
+
+    pub fn get_memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    pub fn get_stats(&self) -> TextCacheStats {
+        TextCacheStats {
+            text_count: self.string_cache.len(),
+            string_bytes: self.string_cache.values().map(|s| s.len()).sum(),
+            cached_text_count: self.cached_strings.len(),
+            cached_layout_count: self.cached_strings.values().flat_map(|fonts| fonts.values()).map(|sizes| sizes.len()).sum(),
+            cached_bytes: self.total_cached_bytes,
+            memory_budget: self.memory_budget,
+            evicted_count: self.evicted_count,
+        }
+    }
+
+    /// Returns the cached words of the text and marks the text as recently used
+    pub(crate) fn get_words(&mut self, id: &TextId, font: &FontId, direction: LayoutTextDirection, size: &FontSize) -> Option<&Words> {
+        if self.cached_strings.contains_key(id) {
+            self.touch(*id);
+        }
+        self.cached_strings.get(id).and_then(|fonts| fonts.get(font)).and_then(|sizes| sizes.get(&(*size, direction)))
+    }
+
+    /// Returns the cached words of the text in any size of the font (for scaling them to a new size)
+    pub(crate) fn get_words_of_any_size(&self, id: &TextId, font: &FontId, direction: LayoutTextDirection) -> Option<(&FontSize, &Words)> {
+        self.cached_strings.get(id)
+            .and_then(|fonts| fonts.get(font))
+            .and_then(|sizes| sizes.iter().find(|((_, size_direction), _)| *size_direction == direction))
+            .and_then(|((size, _), words)| Some((size, words)))
+    }
+
+    /// Caches the words of the text, then evicts the least recently used texts if the cache exceeds
+    /// the memory budget. The words of the text itself are never evicted, even if they exceed the budget.
+    pub(crate) fn insert_words(&mut self, id: TextId, font: FontId, direction: LayoutTextDirection, size: FontSize, words: Words) {
+        let new_bytes = estimate_words_size(&words);
+
+        let old_words = self.cached_strings
+            .entry(id).or_insert_with(|| FastHashMap::default())
+            .entry(font).or_insert_with(|| FastHashMap::default())
+            .insert((size, direction), words);
+
+        let old_bytes = old_words.as_ref().map(|w| estimate_words_size(w)).unwrap_or(0);
+        let text_bytes = self.cached_bytes.entry(id).or_insert(0);
+        *text_bytes = *text_bytes + new_bytes - old_bytes;
+        self.total_cached_bytes = self.total_cached_bytes + new_bytes - old_bytes;
+
+        self.touch(id);
+        self.evict_to_budget(Some(id));
+    }
+
+    /// Returns the cached font fallback chain of the text
+    pub(crate) fn get_fallback_chain(&self, id: &TextId, font_family: &FontFamily) -> Option<Vec<FontId>> {
+        self.fallback_chains.borrow().get(id).and_then(|font_families| font_families.get(font_family)).cloned()
+    }
+
+    pub(crate) fn insert_fallback_chain(&self, id: TextId, font_family: FontFamily, chain: Vec<FontId>) {
+        self.fallback_chains.borrow_mut().entry(id).or_insert_with(|| FastHashMap::default()).insert(font_family, chain);
+    }
+
+    /// Removes the fallback chains of all texts, i.e. because a font was added or deleted
+    pub(crate) fn clear_fallback_chains(&self) {
+        self.fallback_chains.borrow_mut().clear();
+    }
+
+    /// Removes the cached words of the text, but keeps the string
+    pub(crate) fn evict_words(&mut self, id: TextId) {
+        self.cached_strings.remove(&id);
+        self.fallback_chains.borrow_mut().remove(&id);
+        if let Some(bytes) = self.cached_bytes.remove(&id) {
+            self.total_cached_bytes -= bytes;
+        }
+        if let Some(tick) = self.last_used.remove(&id) {
+            self.lru_queue.remove(&tick);
+        }
     }
+
+    fn touch(&mut self, id: TextId) {
+        self.current_tick += 1;
+        if let Some(old_tick) = self.last_used.insert(id, self.current_tick) {
+            self.lru_queue.remove(&old_tick);
+        }
+        self.lru_queue.insert(self.current_tick, id);
+    }
+
+    /// Evicts the least recently used texts (except for `keep`) until the cache fits into the budget
+    fn evict_to_budget(&mut self, keep: Option<TextId>) {
+        let budget = match self.memory_budget {
+            Some(b) => b,
+            None => return,
+        };
+
+        while self.total_cached_bytes > budget {
+            let least_recently_used = self.lru_queue.values().cloned().find(|id| Some(*id) != keep);
+            match least_recently_used {
+                Some(id) => {
+                    self.evict_words(id);
+                    self.evicted_count += 1;
+                },
+                None => break,
+            }
+        }
+    }
+}
+
+/// Estimates the heap size of the words in bytes
+fn estimate_words_size(words: &Words) -> usize {
+    let items_size = words.items.iter().map(|item| match item {
+        SemanticWordItem::Word(w) => {
+            w.glyphs.capacity() * mem::size_of::<GlyphInstance>() +
+            w.glyph_infos.capacity() * mem::size_of::<GlyphInfo>() +
+            (w.word_boundaries.capacity() + w.grapheme_boundaries.capacity()) * mem::size_of::<usize>()
+        },
+        _ => 0,
+    }).sum::<usize>();
+
+    mem::size_of::<Words>() + words.items.capacity() * mem::size_of::<SemanticWordItem>() + items_size
 }
 
 // Empty test, for some reason codecov doesn't detect any files (and therefore
@@ -61,4 +241,39 @@ impl TextCache {
 #[test]
 fn __codecov_test_text_cache_file() {
 
+}
+
+#[test]
+fn test_text_cache_eviction() {
+    let font = FontId::BuiltinFont("serif".into());
+    let size = FontSize::px(10.0);
+    let ltr = LayoutTextDirection::Ltr;
+    let words = || Words { items: vec![SemanticWordItem::Tab; 100], longest_word_width: 0.0 };
+    let words_size = estimate_words_size(&words());
+
+    let mut cache = TextCache::default();
+    let ids = (0..3).map(|i| cache.add_text(format!("text {}", i))).collect::<Vec<_>>();
+    for id in &ids {
+        cache.insert_words(*id, font.clone(), ltr, size, words());
+    }
+    assert_eq!(cache.get_stats().cached_bytes, 3 * words_size);
+
+    // The words are cached separately for each direction
+    assert!(cache.get_words(&ids[0], &font, LayoutTextDirection::Rtl, &size).is_none());
+
+    // The first text was used last, so the second one is evicted first
+    assert!(cache.get_words(&ids[0], &font, ltr, &size).is_some());
+    cache.set_memory_budget(Some(2 * words_size));
+    assert!(cache.get_words(&ids[1], &font, ltr, &size).is_none());
+
+    let stats = cache.get_stats();
+    assert_eq!((stats.text_count, stats.cached_text_count, stats.cached_bytes, stats.evicted_count), (3, 2, 2 * words_size, 1));
+
+    // Re-inserting the evicted words evicts the third text, the strings are kept
+    cache.insert_words(ids[1], font.clone(), ltr, size, words());
+    assert!(cache.get_words(&ids[2], &font, ltr, &size).is_none());
+    assert_eq!(cache.string_cache.len(), 3);
+
+    cache.delete_text(ids[0]);
+    assert_eq!(cache.get_stats().cached_bytes, words_size);
 }
\ No newline at end of file
@@ -1,16 +1,20 @@
 #![allow(unused_variables, dead_code)]
 
+use std::ops::Range;
 use webrender::api::LayoutPixel;
 use euclid::{TypedRect, TypedSize2D, TypedPoint2D};
-use rusttype::{Font, Scale, GlyphId};
+use rusttype::{Font, Scale};
 use {
     app_resources::AppResources,
     display_list::TextInfo,
     css_parser::{
         TextAlignmentHorz, FontSize, BackgroundColor,
-        FontId, TextAlignmentVert, LineHeight, LayoutOverflow
+        FontId, FontFamily, TextAlignmentVert, LineHeight, LetterSpacing, LayoutOverflow, ColorU, TextWrap,
+        RectStyle, WhiteSpace, WordBreak, OverflowWrap, TextOverflowStyle, LineClamp, TabSize, TabStops,
+        LayoutTextDirection,
     },
     text_cache::{TextId, TextCache},
+    text_shaping::{Shaper, ShapingOptions, ShapedGlyph},
 };
 
 pub use webrender::api::GlyphInstance;
@@ -38,19 +42,69 @@ pub struct Words {
     pub longest_word_width: f32,
 }
 
-/// A `Word` contains information about the layout of a single word
+/// A `Word` contains information about the layout of a single word, i.e. the text
+/// between two line break opportunities (UAX #14)
 #[derive(Debug, Clone)]
 pub struct Word {
     /// Glyphs, positions are relative to the first character of the word
     pub glyphs: Vec<GlyphInstance>,
     /// The sum of the width of all the characters
     pub total_width: f32,
+    /// How many spaces follow the word (collapsed to 0 or 1, except for `white-space: pre / pre-wrap`).
+    /// 0 if the word ends without a space, i.e. between two CJK characters or after a hyphen
+    pub spaces_after: usize,
+    /// If the word ends with a soft hyphen (`\u{AD}`): the hyphen glyph that is
+    /// only shown if the line is broken after this word
+    pub hyphen: Option<GlyphInstance>,
+    /// Width of the `hyphen` glyph
+    pub hyphen_width: f32,
+    /// Indices of the glyphs that start a new word (UAX #29), used for `overflow-wrap`
+    pub word_boundaries: Vec<usize>,
+    /// Indices of the glyphs that start a new grapheme cluster, used for `overflow-wrap`
+    pub grapheme_boundaries: Vec<usize>,
+    /// For each glyph: the characters that the glyph was created from, its width and font
+    pub glyph_infos: Vec<GlyphInfo>,
+    /// The embedding level of the word (UAX #9), odd levels are right-to-left
+    pub bidi_level: u8,
+    /// Index of the font (in the font fallback chain) that the word is shaped with
+    pub font_index: usize,
 }
 
-/// Either a white-space delimited word, tab or return character
+/// Information about a glyph that webrender doesn't need, but which is necessary
+/// to map the glyph back to the text (i.e. for hit-testing and positioning the caret)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlyphInfo {
+    /// Index of the (first) character in the (NFC-normalized) text that the glyph
+    /// was created from, i.e. the logical position of the glyph
+    pub char_index: usize,
+    /// How many characters the glyph was created from: more than one for ligatures,
+    /// 0 if a character was shaped into multiple glyphs (for all but the first glyph)
+    pub char_count: usize,
+    /// Horizontal advance of the glyph
+    pub advance: f32,
+    /// Index of the font (in the font fallback chain) that the glyph is rendered with
+    pub font_index: usize,
+    /// Whether the glyph is part of a right-to-left run
+    pub is_rtl: bool,
+}
+
+impl Word {
+    /// Whether the word is written from right to left. The glyphs of right-to-left words
+    /// are already positioned from right to left, but stay in logical order.
+    pub fn is_rtl(&self) -> bool {
+        self.bidi_level % 2 == 1
+    }
+
+    /// Width of the word, including the spaces after it
+    pub fn get_width_with_spaces(&self, space_width: f32) -> f32 {
+        self.total_width + self.spaces_after as f32 * space_width
+    }
+}
+
+/// Either a word, tab or return character
 #[derive(Debug, Clone)]
 pub enum SemanticWordItem {
-    /// Encountered a word (delimited by spaces)
+    /// Encountered a word (delimited by spaces or other line break opportunities)
     Word(Word),
     // `\t` or `x09`
     Tab,
@@ -113,12 +167,6 @@ impl TextOverflow {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct HarfbuzzAdjustment(pub f32);
-
-#[derive(Debug, Copy, Clone)]
-struct KnuthPlassAdjustment(pub f32);
-
 /// Holds info necessary for layouting / styling scrollbars
 #[derive(Debug, Clone)]
 pub(crate) struct ScrollbarInfo {
@@ -135,14 +183,143 @@ pub(crate) struct ScrollbarInfo {
     pub(crate) background_color: BackgroundColor,
 }
 
+impl Default for ScrollbarInfo {
+    fn default() -> Self {
+        Self {
+            width: 17,
+            padding: 2,
+            background_color: BackgroundColor(ColorU { r: 241, g: 241, b: 241, a: 255 }),
+            triangle_color: BackgroundColor(ColorU { r: 163, g: 163, b: 163, a: 255 }),
+            bar_color: BackgroundColor(ColorU { r: 193, g: 193, b: 193, a: 255 }),
+        }
+    }
+}
+
+/// The `white-space`, `word-break`, `overflow-wrap` and `direction` properties of a text
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct TextBreakOptions {
+    pub(crate) white_space: WhiteSpace,
+    pub(crate) word_break: WordBreak,
+    pub(crate) overflow_wrap: OverflowWrap,
+    /// Base direction of the paragraphs (UAX #9)
+    pub(crate) direction: LayoutTextDirection,
+}
+
+impl TextBreakOptions {
+    pub(crate) fn new(style: &RectStyle) -> Self {
+        Self {
+            white_space: style.white_space.unwrap_or_default(),
+            word_break: style.word_break.unwrap_or_default(),
+            overflow_wrap: style.overflow_wrap.unwrap_or_default(),
+            direction: style.get_text_direction(),
+        }
+    }
+
+    /// Cached texts are split with the default options - except for the direction,
+    /// the words are cached separately for each direction
+    pub(crate) fn is_cacheable(&self) -> bool {
+        TextBreakOptions { direction: LayoutTextDirection::default(), .. *self } == TextBreakOptions::default()
+    }
+
+    /// Words that are too long for a line are broken (`overflow-wrap: anywhere / break-word`)
+    pub(crate) fn breaks_long_words(&self) -> bool {
+        self.overflow_wrap != OverflowWrap::Normal && self.white_space.wraps()
+    }
+}
+
+/// The `tab-size` and `tab-stops` properties of a text
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct TabOptions {
+    pub(crate) tab_size: TabSize,
+    pub(crate) tab_stops: TabStops,
+}
+
+impl TabOptions {
+    pub(crate) fn new(style: &RectStyle) -> Self {
+        Self {
+            tab_size: style.tab_size.unwrap_or_default(),
+            tab_stops: style.tab_stops.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// The `text-overflow` and `line-clamp` properties of a text
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct TextTruncationOptions {
+    pub(crate) text_overflow: TextOverflowStyle,
+    pub(crate) line_clamp: LineClamp,
+}
+
+impl TextTruncationOptions {
+    pub(crate) fn new(style: &RectStyle) -> Self {
+        Self {
+            text_overflow: style.text_overflow.clone().unwrap_or_default(),
+            line_clamp: style.line_clamp.unwrap_or_default(),
+        }
+    }
+}
+
+/// How the text is truncated, resolved from the `TextTruncationOptions` with the font of the text
+#[derive(Debug, Clone)]
+pub(crate) struct TextTruncation {
+    /// The "…" (or custom string) that is appended to the truncated lines, shaped with the first font
+    pub(crate) marker: Word,
+    /// `text-overflow: ellipsis / "string"`: lines that are wider than the rectangle are truncated
+    pub(crate) truncates_long_lines: bool,
+    /// `line-clamp: N`: the lines after the N-th line are dropped
+    pub(crate) max_lines: Option<usize>,
+    /// Whether the lines are wrapped at the width of the rectangle
+    pub(crate) wraps: bool,
+}
+
+impl TextTruncation {
+    /// Returns `None` if the text is never truncated (`text-overflow: clip` and `line-clamp: none`)
+    pub(crate) fn new<'a>(options: &TextTruncationOptions, white_space: WhiteSpace, font: &Font<'a>, shaper: &Shaper, font_size: Scale)
+    -> Option<Self>
+    {
+        use font::font_has_glyph;
+
+        let max_lines = match options.line_clamp {
+            LineClamp::None => None,
+            LineClamp::Lines(lines) => Some(lines),
+        };
+
+        if options.text_overflow == TextOverflowStyle::Clip && max_lines.is_none() {
+            return None;
+        }
+
+        // `line-clamp` always ends in an ellipsis, even if `text-overflow` is `clip`
+        let marker_chars = match options.text_overflow {
+            TextOverflowStyle::String(ref s) => s.chars().collect::<Vec<char>>(),
+            TextOverflowStyle::Clip | TextOverflowStyle::Ellipsis => {
+                if font_has_glyph(font, '\u{2026}') { vec!['\u{2026}'] } else { vec!['.'; 3] }
+            },
+        };
+
+        // The marker doesn't belong to the text, so it can't be hit-tested or selected
+        let mut marker = shape_word(&marker_chars, &vec![0; marker_chars.len()], 0, font, shaper, get_units_to_px(font, font_size));
+        marker.glyph_infos.iter_mut().for_each(|info| info.char_count = 0);
+
+        Some(Self {
+            marker: marker,
+            truncates_long_lines: options.text_overflow != TextOverflowStyle::Clip,
+            max_lines: max_lines,
+            wraps: white_space.wraps(),
+        })
+    }
+}
+
 /// Temporary struct that contains various metrics related to a font -
 /// useful so we don't have to access the font to look up certain widths
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct FontMetrics {
     /// Width of the space character
     pub space_width: f32,
-    /// Usually 4 * space_width
+    /// Distance between two tab stops, usually 4 * space_width (`tab-size`)
     pub tab_width: f32,
+    /// Explicit positions of the tab stops, relative to the start of the line (`tab-stops`).
+    /// After the last explicit tab stop, the tab stops are `tab_width` apart.
+    pub tab_stops: Vec<f32>,
     /// font_size * line_height
     pub vertical_advance: f32,
     /// Font size (for rusttype) in **pt** (not px)
@@ -163,9 +340,12 @@ pub struct FontMetrics {
 /// - `horiz_alignment`: Usually parsed from the `text-align` attribute: horizontal alignment of the text
 /// - `vert_alignment`: Usually parsed from the `align-items` attribute on the parent node
 ///    or the `align-self` on the child node: horizontal alignment of the text
-/// - `font`: The font to use for layouting (only the ID)
+/// - `font_family`: The fonts to use for layouting (only the IDs). Characters that aren't in the
+///   first font are rendered with the next font of the family that contains them, or with a system font
 /// - `font_size`: The font size (without line height)
 /// - `line_height`: The line height (100% = 1.0). I.e. `line-height = 1.2;` scales the text vertically by 1.2x
+/// - `letter_spacing`: Additional space after each grapheme cluster, parsed from the `letter-spacing` attribute
+/// - `tab_options`: The distance between the tab stops and the explicit tab stops (`tab-size` / `tab-stops`)
 /// - `text`: The actual text to layout. Will be unicode-normalized after the Unicode Normalization Form C
 ///   (canonical decomposition followed by canonical composition).
 /// - `overflow`: If the scrollbars should be show, parsed from the `overflow-{x / y}` fields
@@ -173,7 +353,8 @@ pub struct FontMetrics {
 ///
 /// ## Returns
 ///
-/// - `Vec<GlyphInstance>`: The layouted glyphs. If a scrollbar is necessary, they will be layouted so that
+/// - `Vec<(FontId, Vec<GlyphInstance>)>`: The layouted glyphs, grouped by the font they have to be
+///   rendered with. If a scrollbar is necessary, they will be layouted so that
 ///   the scrollbar has space to the left or bottom (so it doesn't overlay the text)
 /// - `TextOverflowPass2`: This is internally used for aligning text (horizontally / vertically), but
 ///   it is necessary for drawing the scrollbars later on, to determine the height of the bar. Contains
@@ -183,81 +364,136 @@ pub(crate) fn get_glyphs(
     bounds: &TypedRect<f32, LayoutPixel>,
     horiz_alignment: TextAlignmentHorz,
     vert_alignment: TextAlignmentVert,
-    target_font_id: &FontId,
+    font_family: &FontFamily,
     target_font_size: &FontSize,
     line_height: Option<LineHeight>,
+    letter_spacing: Option<LetterSpacing>,
+    text_wrap: TextWrap,
+    shaping_options: &ShapingOptions,
+    break_options: &TextBreakOptions,
+    tab_options: &TabOptions,
+    truncation_options: &TextTruncationOptions,
     text: &TextInfo,
     overflow: &LayoutOverflow,
     scrollbar_info: &ScrollbarInfo)
--> (Vec<GlyphInstance>, TextOverflowPass2)
+-> (Vec<(FontId, Vec<GlyphInstance>)>, TextOverflowPass2)
 {
     let mut bounds = *bounds;
 
-    let target_font = match app_resources.get_font(target_font_id) {
-        Some(s) => s,
-        None => panic!("Drawing with invalid font!: {:?}", target_font_id),
+    // The fonts of the font family, followed by the system fonts for the missing characters
+    let font_ids = match text {
+        TextInfo::Cached(text_id) => app_resources.get_text_fallback_chain(font_family, text_id).unwrap_or_default(),
+        TextInfo::Uncached(s) => app_resources.get_font_fallback_chain(font_family, s),
     };
 
-    let font_metrics = calculate_font_metrics(&target_font.0, target_font_size, line_height);
+    let target_fonts = font_ids.iter().filter_map(|id| app_resources.get_font(id)).collect::<Vec<_>>();
+
+    if target_fonts.is_empty() {
+        panic!("Drawing with invalid font!: {:?}", font_family);
+    }
+
+    let font_metrics = calculate_font_metrics(&target_fonts[0].0, target_font_size, line_height).with_tab_options(tab_options);
+
+    let shapers = target_fonts.iter().map(|f| Shaper::new(&f.1, shaping_options)).collect::<Vec<_>>();
+    let fonts = target_fonts.iter().zip(shapers.iter()).map(|(f, shaper)| (&*f.0, shaper)).collect::<Vec<_>>();
+
+    // The cached words are split with the fallback chain of the first font only
+    let is_single_font = font_family.fonts.len() == 1 && font_family.fonts[0] == font_ids[0];
 
     // (1) Split the text into semantic items (word, tab or newline) OR get the cached
     // text and scale it accordingly.
     //
-    // This function also normalizes the unicode characters and calculates kerning.
+    // This function also normalizes the unicode characters, finds the line break
+    // opportunities (UAX #14) and shapes the words (ligatures, kerning and mark positioning).
     //
     // NOTE: This should be revisited, the caching does unnecessary cloning.
     let words_owned;
     let words = match text {
+        // The cache only stores words shaped and split with the default options
+        TextInfo::Cached(text_id) if is_single_font && *shaping_options == ShapingOptions::default() && break_options.is_cacheable() => {
+            get_words_cached(text_id, &fonts, &font_ids[0], break_options.direction, target_font_size, font_metrics.font_size_no_line_height, &mut app_resources.text_cache)
+        },
         TextInfo::Cached(text_id) => {
-            get_words_cached(text_id, &target_font.0, target_font_id, target_font_size, font_metrics.font_size_no_line_height, &mut app_resources.text_cache)
+            words_owned = split_text_into_words(&app_resources.text_cache.string_cache[text_id], &fonts, font_metrics.font_size_no_line_height, break_options);
+            &words_owned
         },
         TextInfo::Uncached(s) => {
-            words_owned = split_text_into_words(s, &target_font.0, font_metrics.font_size_no_line_height);
+            words_owned = split_text_into_words(s, &fonts, font_metrics.font_size_no_line_height, break_options);
             &words_owned
         },
     };
 
+    // `letter-spacing`: the cached words are shared, so the spacing is applied to a copy
+    let words_spaced;
+    let words = match get_letter_spacing_px(letter_spacing) {
+        Some(letter_spacing) => {
+            words_spaced = with_letter_spacing(words, letter_spacing);
+            &words_spaced
+        },
+        None => words,
+    };
+
+    // `overflow-wrap: anywhere / break-word`: break the words that don't fit on a line
+    let words_broken;
+    let words = if break_options.breaks_long_words() && words.longest_word_width > bounds.size.width {
+        words_broken = break_long_words(words, bounds.size.width);
+        &words_broken
+    } else {
+        words
+    };
+
+    let truncation = TextTruncation::new(truncation_options, break_options.white_space, fonts[0].0, fonts[0].1, font_metrics.font_size_no_line_height);
+
     // Prevent negative width / rect height or a too small rectangle -
-    // the rect must be at least wide enough for the longest word
-    bounds.size.width = bounds.size.width.max(words.longest_word_width);
+    // the rect must be at least wide enough for the longest word (unless the long words are truncated)
+    let min_width = if truncation.as_ref().map(|t| t.truncates_long_lines).unwrap_or(false) { 0.0 } else { words.longest_word_width };
+    bounds.size.width = bounds.size.width.max(min_width);
     bounds.size.height = bounds.size.height.abs();
 
-    // (2) Calculate the additions / subtractions that have to be take into account
-    // let harfbuzz_adjustments = calculate_harfbuzz_adjustments(&text, &target_font.0);
+    let mut line_breaking = LineBreaking::new(horiz_alignment, text_wrap, break_options.white_space);
 
-    // (3) Determine if the words will overflow the bounding rectangle
-    let overflow_pass_1 = estimate_overflow_pass_1(&words, &bounds.size, &font_metrics, &overflow);
-
-    // (4) If the lines overflow, subtract the space needed for the scrollbars and calculate the length
-    // again (TODO: already layout characters here?)
-    let (new_size, overflow_pass_2) =
-        estimate_overflow_pass_2(&words, &bounds.size, &font_metrics, &overflow, scrollbar_info, overflow_pass_1);
+    // The text is truncated at the greedy line breaks, the Knuth-Plass breaks could need more lines
+    if truncation.is_some() {
+        line_breaking.optimal = false;
+    }
 
-    let max_horizontal_text_width = if overflow.allows_horizontal_overflow() { None } else { Some(new_size.width) };
+    // (2) Determine if the words will overflow the bounding rectangle
+    let overflow_pass_1 = estimate_overflow_pass_1(&words, &bounds.size, &font_metrics, &overflow, line_breaking);
 
-    // (5) Align text to the left, initial layout of glyphs
-    let (mut positioned_glyphs, line_break_offsets, _, _) =
-        words_to_left_aligned_glyphs(words, &target_font.0, max_horizontal_text_width, &font_metrics);
+    // (3) If the lines overflow, subtract the space needed for the scrollbars and calculate the length
+    // again (TODO: already layout characters here?). Truncates the text for `text-overflow` and `line-clamp`.
+    let (new_size, overflow_pass_2, truncated_words) =
+        estimate_overflow_pass_2(&words, &bounds.size, &font_metrics, &overflow, scrollbar_info, truncation.as_ref(), line_breaking, overflow_pass_1);
 
-    // (6) Add the harfbuzz adjustments to the positioned glyphs
-    // apply_harfbuzz_adjustments(&mut positioned_glyphs, harfbuzz_adjustments);
+    let words = truncated_words.as_ref().unwrap_or(words);
 
-    // (7) Calculate the Knuth-Plass adjustments for the (now layouted) glyphs
-    let knuth_plass_adjustments = calculate_knuth_plass_adjustments(&positioned_glyphs, &line_break_offsets);
+    let max_horizontal_text_width = if overflow.allows_horizontal_overflow() { None } else { Some(new_size.width) };
 
-    // (8) Add the Knuth-Plass adjustments to the positioned glyphs
-    apply_knuth_plass_adjustments(&mut positioned_glyphs, knuth_plass_adjustments);
+    // (4) Break the words into lines and align them to the left (or justify them), initial layout of glyphs
+    let (mut positioned_glyphs, glyph_infos, line_break_offsets, _, _) =
+        words_to_left_aligned_glyphs(words, max_horizontal_text_width, &font_metrics, line_breaking);
 
-    // (9) Align text horizontally (early return if left-aligned)
+    // (5) Align text horizontally (early return if left-aligned)
     align_text_horz(horiz_alignment, &mut positioned_glyphs, &line_break_offsets, &overflow_pass_2);
 
-    // (10) Align text vertically (early return if text overflows)
+    // (6) Align text vertically (early return if text overflows)
     align_text_vert(vert_alignment, &mut positioned_glyphs, &line_break_offsets, &overflow_pass_2);
 
-    // (11) Add the self.origin to all the glyphs to bring them from glyph space into world space
+    // (7) Add the self.origin to all the glyphs to bring them from glyph space into world space
     add_origin(&mut positioned_glyphs, bounds.origin.x, bounds.origin.y);
 
-    (positioned_glyphs, overflow_pass_2)
+    // (8) Group the glyphs by font, since each font has to be pushed separately
+    let mut glyphs_by_font = vec![Vec::new(); font_ids.len()];
+    for (glyph, glyph_info) in positioned_glyphs.into_iter().zip(glyph_infos.into_iter()) {
+        glyphs_by_font[glyph_info.font_index].push(glyph);
+    }
+
+    let glyphs_by_font = font_ids.into_iter()
+        .zip(glyphs_by_font.into_iter())
+        .filter(|(_, glyphs)| !glyphs.is_empty())
+        .collect();
+
+    (glyphs_by_font, overflow_pass_2)
 }
 
 impl FontMetrics {
@@ -266,6 +502,37 @@ impl FontMetrics {
     pub fn new<'a>(font: &Font<'a>, font_size: &FontSize, line_height: Option<LineHeight>) -> Self {
         calculate_font_metrics(font, font_size, line_height)
     }
+
+    /// Sets the distance between the tab stops (`tab-size`) and the explicit tab stops (`tab-stops`)
+    pub(crate) fn with_tab_options(mut self, tab_options: &TabOptions) -> Self {
+        self.tab_width = match tab_options.tab_size {
+            TabSize::Spaces(spaces) => spaces as f32 * self.space_width,
+            TabSize::Length(length) => length.to_pixels(),
+        };
+        self.tab_stops = tab_options.tab_stops.0.iter().map(|stop| stop.to_pixels()).collect();
+        self
+    }
+
+    /// Returns the position of the tab stop that a tab at `caret` (relative to the start of the line) advances to
+    pub fn get_next_tab_stop(&self, caret: f32) -> f32 {
+        get_next_tab_stop(caret, self.tab_width, &self.tab_stops)
+    }
+}
+
+/// Returns the first explicit tab stop after the `caret` or, after the last explicit
+/// tab stop, the next multiple of `tab_width` (counted from the last explicit tab stop)
+fn get_next_tab_stop(caret: f32, tab_width: f32, tab_stops: &[f32]) -> f32 {
+    if let Some(tab_stop) = tab_stops.iter().find(|tab_stop| **tab_stop > caret) {
+        return *tab_stop;
+    }
+
+    // `tab-size: 0` - tabs are not rendered
+    if tab_width <= 0.0 {
+        return caret;
+    }
+
+    let origin = tab_stops.last().cloned().unwrap_or(0.0);
+    origin + ((caret - origin) / tab_width).floor() * tab_width + tab_width
 }
 
 fn calculate_font_metrics<'a>(font: &Font<'a>, font_size: &FontSize, line_height: Option<LineHeight>) -> FontMetrics {
@@ -278,7 +545,7 @@ fn calculate_font_metrics<'a>(font: &Font<'a>, font_size: &FontSize, line_height
     let space_glyph = font.glyph(' ').scaled(font_size_no_line_height);
     let height_for_1px = font.glyph(' ').standalone().get_data().unwrap().scale_for_1_pixel;
     let space_width = space_glyph.h_metrics().advance_width;
-    let tab_width = 4.0 * space_width; // TODO: make this configurable
+    let tab_width = 4.0 * space_width;
 
     let v_metrics_scaled = font.v_metrics(font_size_with_line_height);
     let v_advance_scaled = v_metrics_scaled.ascent - v_metrics_scaled.descent + v_metrics_scaled.line_gap;
@@ -287,72 +554,56 @@ fn calculate_font_metrics<'a>(font: &Font<'a>, font_size: &FontSize, line_height
         vertical_advance: v_advance_scaled,
         space_width,
         tab_width,
+        tab_stops: Vec::new(),
         height_for_1px,
         font_size_with_line_height,
         font_size_no_line_height,
     }
 }
 
-fn get_words_cached<'a>(
+/// Returns the cached words of the text, scaled from another font size if necessary.
+///
+/// If the words aren't cached (i.e. the text was added with `add_text_uncached` or its words
+/// were evicted from the `TextCache`), the text is laid out again and cached.
+fn get_words_cached<'a, 'b>(
     text_id: &TextId,
-    font: &Font<'a>,
+    fonts: &[(&Font<'b>, &Shaper)],
     font_id: &FontId,
+    direction: LayoutTextDirection,
     font_size: &FontSize,
     font_size_no_line_height: Scale,
     text_cache: &'a mut TextCache)
 -> &'a Words
 {
-    use std::collections::hash_map::Entry::*;
-    use FastHashMap;
-
-    let mut should_words_be_scaled = false;
-
-    match text_cache.cached_strings.entry(*text_id) {
-        Occupied(mut font_hash_map) => {
-
-            let font_size_map = font_hash_map.get_mut().entry(font_id.clone()).or_insert_with(|| FastHashMap::default());
-            let is_new_font = font_size_map.is_empty();
-
-            match font_size_map.entry(*font_size) {
-                Occupied(existing_font_size_words) => { }
-                Vacant(v) => {
-                    if is_new_font {
-                        v.insert(split_text_into_words(&text_cache.string_cache[text_id], font, font_size_no_line_height));
-                    } else {
-                        // If we can get the words from any other size, we can just scale them here
-                        // ex. if an existing font size gets scaled.
-                       should_words_be_scaled = true;
-                    }
-                }
-            }
-        },
-        Vacant(_) => { },
-    }
-
-    // We have an entry in the font size -> words cache already, but it's not the right font size
-    // instead of recalculating the words, we simply scale them up.
-    if should_words_be_scaled {
-        let words_cloned = {
-            let font_size_map = &text_cache.cached_strings[&text_id][&font_id];
-            let (old_font_size, next_words_for_font) = font_size_map.iter().next().unwrap();
-            let mut words_cloned: Words = next_words_for_font.clone();
-            let scale_factor = font_size.0.to_pixels() / old_font_size.0.to_pixels();
-
-            scale_words(&mut words_cloned, scale_factor);
-            words_cloned
+    let is_cached = text_cache.get_words(text_id, font_id, direction, font_size).is_some();
+
+    if !is_cached {
+        let words = match text_cache.get_words_of_any_size(text_id, font_id, direction) {
+            // We have an entry in the font size -> words cache already, but it's not the right font size
+            // instead of recalculating the words, we simply scale them up.
+            Some((old_font_size, words_for_font)) => {
+                let mut words_cloned: Words = words_for_font.clone();
+                let scale_factor = font_size.0.to_pixels() / old_font_size.0.to_pixels();
+                scale_words(&mut words_cloned, scale_factor);
+                words_cloned
+            },
+            None => {
+                let break_options = TextBreakOptions { direction: direction, .. TextBreakOptions::default() };
+                split_text_into_words(&text_cache.string_cache[text_id], fonts, font_size_no_line_height, &break_options)
+            },
         };
-
-        text_cache.cached_strings.get_mut(&text_id).unwrap().get_mut(&font_id).unwrap().insert(*font_size, words_cloned);
+        text_cache.insert_words(*text_id, font_id.clone(), direction, *font_size, words);
     }
 
-    text_cache.cached_strings.get(&text_id).unwrap().get(&font_id).unwrap().get(&font_size).unwrap()
+    text_cache.get_words(text_id, font_id, direction, font_size).unwrap()
 }
 
 fn scale_words(words: &mut Words, scale_factor: f32) {
-    // Scale the horizontal width of the words to match the new font size
+    // Scale the width of the words to match the new font size
     // Since each word has a local origin (i.e. the first character of each word
-    // is at (0, 0)), we can simply scale the X position of each glyph by a
-    // certain factor.
+    // is at (0, 0)), we can simply scale the position of each glyph by a
+    // certain factor. The Y position has to be scaled, too, since marks are
+    // offset vertically from their base glyph.
     //
     // So if we previously had a 12pt font and now a 13pt font,
     // we simply scale each glyph position by 13 / 12. This is faster than
@@ -360,247 +611,855 @@ fn scale_words(words: &mut Words, scale_factor: f32) {
     // large amount of text.
     for word in words.items.iter_mut() {
         if let SemanticWordItem::Word(ref mut w) = word {
-            w.glyphs.iter_mut().for_each(|g| g.point.x *= scale_factor);
+            w.glyphs.iter_mut().for_each(|g| { g.point.x *= scale_factor; g.point.y *= scale_factor; });
             w.total_width *= scale_factor;
+            w.hyphen.iter_mut().for_each(|g| { g.point.x *= scale_factor; g.point.y *= scale_factor; });
+            w.hyphen_width *= scale_factor;
+            w.glyph_infos.iter_mut().for_each(|info| info.advance *= scale_factor);
         }
     }
 }
 
-/// This function is also used in the `text_cache` module for caching large strings.
-///
-/// It is one of the most expensive functions, use with care.
-pub(crate) fn split_text_into_words<'a>(text: &str, font: &Font<'a>, font_size: Scale)
--> Words
-{
-    use unicode_normalization::UnicodeNormalization;
+/// Returns the `letter-spacing` in pixels, `None` if the letters aren't spaced out
+pub(crate) fn get_letter_spacing_px(letter_spacing: Option<LetterSpacing>) -> Option<f32> {
+    letter_spacing.map(|ls| ls.0.to_pixels()).and_then(|px| if px == 0.0 { None } else { Some(px) })
+}
 
-    let mut words = Vec::new();
+/// `letter-spacing`: Returns a copy of the words with `letter_spacing` pixels added after
+/// each grapheme cluster (so that combining marks stay on top of their base character)
+pub(crate) fn with_letter_spacing(words: &Words, letter_spacing: f32) -> Words {
+    let mut words = words.clone();
+    let mut longest_word_width: f32 = 0.0;
 
-    let mut word_caret = 0.0;
-    let mut cur_word_length = 0.0;
-    let mut chars_in_this_word = Vec::new();
-    let mut glyphs_in_this_word = Vec::new();
-    let mut last_glyph = None;
+    for word in words.items.iter_mut() {
+        if let SemanticWordItem::Word(ref mut w) = word {
+            if !w.glyphs.is_empty() {
+                let is_rtl = w.is_rtl();
+                let grapheme_count = w.grapheme_boundaries.len() + 1;
+                let mut grapheme_idx = 0;
+
+                for (glyph_idx, (glyph, info)) in w.glyphs.iter_mut().zip(w.glyph_infos.iter_mut()).enumerate() {
+                    let starts_grapheme = glyph_idx == 0 || w.grapheme_boundaries.contains(&glyph_idx);
+                    if glyph_idx > 0 && starts_grapheme {
+                        grapheme_idx += 1;
+                    }
+                    // Right-to-left words are positioned from the right, so the space
+                    // after a grapheme is on its left
+                    let spaces_before = if is_rtl { grapheme_count - 1 - grapheme_idx } else { grapheme_idx };
+                    glyph.point.x += spaces_before as f32 * letter_spacing;
+                    if starts_grapheme {
+                        info.advance += letter_spacing;
+                    }
+                }
 
-    // In case the rectangle is smaller than the longest word,
-    // we need to expand the rectangle to be that size
-    let mut longest_word_width = 0.0;
+                let added_width = grapheme_count as f32 * letter_spacing;
+                w.total_width += added_width;
+                w.hyphen.iter_mut().for_each(|g| g.point.x += added_width);
+            }
+            longest_word_width = longest_word_width.max(w.total_width);
+        }
+    }
 
-    fn end_word(words: &mut Vec<SemanticWordItem>,
-                glyphs_in_this_word: &mut Vec<GlyphInstance>,
-                cur_word_length: &mut f32,
-                word_caret: &mut f32,
-                longest_word_width: &mut f32,
-                last_glyph: &mut Option<GlyphId>)
-    {
-        // End of word
-        words.push(SemanticWordItem::Word(Word {
-            glyphs: glyphs_in_this_word.drain(..).collect(),
-            total_width: *cur_word_length,
-        }));
+    words.longest_word_width = longest_word_width;
+    words
+}
 
-        if cur_word_length > longest_word_width {
-            *longest_word_width = *cur_word_length;
-        }
+/// A text that is measured with `AppResources::measure_text`: either a string or the ID of
+/// a text in the text cache (the words of cached texts are re-used)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextRef<'a> {
+    Uncached(&'a str),
+    Cached(TextId),
+}
 
-        // Reset everything
-        *last_glyph = None;
-        *word_caret = 0.0;
-        *cur_word_length = 0.0;
+impl<'a> From<&'a str> for TextRef<'a> {
+    fn from(text: &'a str) -> Self {
+        TextRef::Uncached(text)
     }
+}
 
-    let v_metrics_font = font.v_metrics_unscaled();
-    // Warning: rusttype has a bit of a weird layout system - you have to
-    // subtract the descent from the ascent to get the proper vertical height
-    let v_metrics_height_unscaled = Scale::uniform(v_metrics_font.ascent - v_metrics_font.descent);
-
-    for cur_char in text.nfc() {
-        match cur_char {
-            '\t' => {
-                // End of word + tab
-                if !chars_in_this_word.is_empty() {
-                    end_word(
-                        &mut words,
-                        &mut glyphs_in_this_word,
-                        &mut cur_word_length,
-                        &mut word_caret,
-                        &mut longest_word_width,
-                        &mut last_glyph);
-                }
-                words.push(SemanticWordItem::Tab);
-            },
-            '\n' => {
-                // End of word + newline
-                if !chars_in_this_word.is_empty() {
-                    end_word(
-                        &mut words,
-                        &mut glyphs_in_this_word,
-                        &mut cur_word_length,
-                        &mut word_caret,
-                        &mut longest_word_width,
-                        &mut last_glyph);
-                }
-                words.push(SemanticWordItem::Return);
-            },
-            ' ' => {
-                if !chars_in_this_word.is_empty() {
-                    end_word(
-                        &mut words,
-                        &mut glyphs_in_this_word,
-                        &mut cur_word_length,
-                        &mut word_caret,
-                        &mut longest_word_width,
-                        &mut last_glyph);
-                }
-            },
-            cur_char =>  {
-                // Regular character
-                let g = font.glyph(cur_char);
-                let id = g.id();
-
-                // calculate the real width
-                let glyph_metrics = g.standalone().get_data().unwrap();
-                let h_metrics = g.scaled(v_metrics_height_unscaled).h_metrics();
-                let mut horiz_advance = h_metrics.advance_width
-                                    * glyph_metrics.scale_for_1_pixel
-                                    * (font_size.x * (96.0 / 72.0)
-                                    * DEFAULT_CHARACTER_WIDTH_MULTIPLIER);
-
-                // horiz_advance *= 96.0 / 72.0;
-
-                if let Some(last) = last_glyph {
-                    word_caret += font.pair_kerning(font_size, last, id);
-                }
+impl<'a> From<&'a String> for TextRef<'a> {
+    fn from(text: &'a String) -> Self {
+        TextRef::Uncached(&text[..])
+    }
+}
+
+impl<'a> From<TextId> for TextRef<'a> {
+    fn from(text_id: TextId) -> Self {
+        TextRef::Cached(text_id)
+    }
+}
 
-                let word_caret_saved = word_caret;
+/// Style of a text that is measured with `AppResources::measure_text_with_options`
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct TextMeasureOptions {
+    /// `line-height` of the text (100% if not set)
+    pub line_height: Option<LineHeight>,
+    /// `letter-spacing` of the text (0px if not set)
+    pub letter_spacing: Option<LetterSpacing>,
+}
 
-                last_glyph = Some(id);
-                word_caret += horiz_advance;
-                cur_word_length += horiz_advance;
+/// Measures the text like it would be laid out with the font `font_id` (and the system fonts for
+/// the characters that the font doesn't contain). Returns `(width, height, line_count)`, the width
+/// is the width of the widest line. Returns `None` if the font or the cached text doesn't exist.
+pub(crate) fn measure_text(
+    app_resources: &mut AppResources,
+    text: TextRef,
+    font_id: &FontId,
+    font_size: FontSize,
+    max_width: Option<f32>,
+    options: &TextMeasureOptions)
+-> Option<(f32, f32, usize)>
+{
+    let first_font = app_resources.get_font(font_id)?;
 
-                glyphs_in_this_word.push(GlyphInstance {
-                    index: id.0,
-                    point: TypedPoint2D::new(word_caret_saved, 0.0),
-                });
+    let font_family = FontFamily::new(vec![font_id.clone()]);
+    let font_ids = match text {
+        TextRef::Cached(text_id) => app_resources.get_text_fallback_chain(&font_family, &text_id)?,
+        TextRef::Uncached(s) => app_resources.get_font_fallback_chain(&font_family, s),
+    };
 
-                chars_in_this_word.push(cur_char);
-            }
-        }
-    }
+    let target_fonts = font_ids.iter().filter_map(|id| app_resources.get_font(id)).collect::<Vec<_>>();
+    let font_metrics = calculate_font_metrics(&first_font.0, &font_size, options.line_height);
 
-    // Push last word
-    if !chars_in_this_word.is_empty() {
-        end_word(
-            &mut words,
-            &mut glyphs_in_this_word,
-            &mut cur_word_length,
-            &mut word_caret,
-            &mut longest_word_width,
-            &mut last_glyph);
-    }
+    let shapers = target_fonts.iter().map(|f| Shaper::new(&f.1, &ShapingOptions::default())).collect::<Vec<_>>();
+    let fonts = target_fonts.iter().zip(shapers.iter()).map(|(f, shaper)| (&*f.0, shaper)).collect::<Vec<_>>();
 
-    Words {
-        items: words,
-        longest_word_width: longest_word_width,
+    // Texts that were cached with `AppResources::cache_text` re-use (or scale) the cached words
+    let is_cached = match text {
+        TextRef::Cached(text_id) => app_resources.text_cache.cached_strings.contains_key(&text_id),
+        TextRef::Uncached(_) => false,
+    };
+
+    let words_owned;
+    let words = match text {
+        TextRef::Cached(text_id) if is_cached => {
+            get_words_cached(&text_id, &fonts, font_id, LayoutTextDirection::default(), &font_size, font_metrics.font_size_no_line_height, &mut app_resources.text_cache)
+        },
+        TextRef::Cached(text_id) => {
+            words_owned = split_text_into_words(&app_resources.text_cache.string_cache[&text_id], &fonts, font_metrics.font_size_no_line_height, &TextBreakOptions::default());
+            &words_owned
+        },
+        TextRef::Uncached(s) => {
+            words_owned = split_text_into_words(s, &fonts, font_metrics.font_size_no_line_height, &TextBreakOptions::default());
+            &words_owned
+        },
+    };
+
+    let words_spaced;
+    let words = match get_letter_spacing_px(options.letter_spacing) {
+        Some(letter_spacing) => {
+            words_spaced = with_letter_spacing(words, letter_spacing);
+            &words_spaced
+        },
+        None => words,
+    };
+
+    let size = get_intrinsic_text_size(words, &font_metrics, max_width, LineBreaking::default());
+    Some((size.content_width, size.content_height, size.line_count))
+}
+
+/// A word that is currently being split off the text
+#[derive(Debug, Default)]
+struct PendingWord {
+    chars: Vec<char>,
+    char_indices: Vec<usize>,
+    bidi_level: u8,
+    font_index: usize,
+    spaces_after: usize,
+    soft_hyphen: bool,
+}
+
+impl PendingWord {
+    fn is_empty(&self) -> bool {
+        self.chars.is_empty() && self.spaces_after == 0 && !self.soft_hyphen
     }
 }
 
-// First pass: calculate if the words will overflow (using the tabs)
-#[inline(always)]
-fn estimate_overflow_pass_1(
-    words: &Words,
-    rect_dimensions: &TypedSize2D<f32, LayoutPixel>,
-    font_metrics: &FontMetrics,
-    overflow: &LayoutOverflow)
--> TextOverflowPass1
+/// This function is also used in the `text_cache` module for caching large strings.
+///
+/// It is one of the most expensive functions, use with care.
+///
+/// `fonts` is the font fallback chain: each character is rendered with the first font
+/// that contains it (or with the first font, if no font contains it).
+pub(crate) fn split_text_into_words<'a>(text: &str, fonts: &[(&Font<'a>, &Shaper)], font_size: Scale, break_options: &TextBreakOptions)
+-> Words
 {
-    use self::SemanticWordItem::*;
+    use font::font_has_glyph;
 
-    let words = &words.items;
+    use unicode_normalization::UnicodeNormalization;
+    use unicode_bidi::{BidiInfo, Level};
+    use xi_unicode::LineBreakIterator;
 
-    let FontMetrics { space_width, tab_width, vertical_advance, .. } = *font_metrics;
+    let text = text.nfc().collect::<String>();
+    let white_space = break_options.white_space;
 
-    let max_text_line_len_horizontal = 0.0;
+    // Embedding levels of each byte (UAX #9). With `direction: rtl`, each paragraph is right-to-left,
+    // otherwise the base direction of each paragraph is determined by its first strong character.
+    let paragraph_level = match break_options.direction {
+        LayoutTextDirection::Rtl => Some(Level::rtl()),
+        LayoutTextDirection::Ltr => None,
+    };
+    let bidi_info = BidiInfo::new(&text, paragraph_level);
 
-    // Determine the maximum width and height that the text needs for layout
+    let mut words = Vec::new();
+    let mut pending_word = PendingWord::default();
 
-    // This is actually tricky. Horizontal scrollbars and vertical scrollbars
-    // behave differently.
-    //
-    // Vertical scrollbars always show the length - when the
-    // vertical length = the height of the rectangle, the scrollbar (the actual bar)
-    // is 0.5x the height of the rectangle, aligned at the top.
-    //
-    // Horizontal scrollbars, on the other hand, are 1.0x the width of the rectangle,
-    // when the width is filled.
+    let units_to_px = fonts.iter().map(|(font, _)| get_units_to_px(font, font_size)).collect::<Vec<f32>>();
 
-    // TODO: this is duplicated code
+    // Visible hyphen for soft hyphens, in case the line is broken there (always from the first font)
+    let hyphen = shape_word(&['-'], &[0], 0, fonts[0].0, fonts[0].1, units_to_px[0]);
 
-    let mut max_hor_len = None;
+    let end_word = |words: &mut Vec<SemanticWordItem>, pending_word: &mut PendingWord| {
+        use unicode_segmentation::UnicodeSegmentation;
+        use std::mem;
 
-    let vertical_length = {
-        if overflow.allows_horizontal_overflow() {
-            // If we can overflow horizontally, we only need to sum up the `Return`
-            // characters, since the actual length of the line doesn't matter
-            words.iter().filter(|w| w.is_return()).count() as f32 * vertical_advance
+        let pending_word = mem::replace(pending_word, PendingWord::default());
+
+        // `word-break: break-all`: every grapheme cluster can be put on a new line
+        let pieces = if break_options.word_break == WordBreak::BreakAll && pending_word.chars.len() > 1 {
+            let word_string = pending_word.chars.iter().collect::<String>();
+            let mut piece_start = 0;
+            word_string.graphemes(true).map(|g| {
+                let piece_end = piece_start + g.chars().count();
+                let piece = (piece_start, piece_end);
+                piece_start = piece_end;
+                piece
+            }).collect()
         } else {
-            // TODO: should this be cached? The calculation is probably quick, but this
-            // is essentially the same thing as we do in the actual text layout stage
-            let mut max_line_cursor: f32 = 0.0;
-            let mut cur_line_cursor = 0.0;
-            let mut cur_line = 0;
+            vec![(0, pending_word.chars.len())]
+        };
 
-            for w in words {
-                match w {
-                    Word(w) => {
-                        if cur_line_cursor + w.total_width > rect_dimensions.width {
-                            max_line_cursor = max_line_cursor.max(cur_line_cursor);
-                            cur_line_cursor = 0.0;
-                            cur_line += 1;
-                        }
-                        cur_line_cursor += w.total_width + space_width;
-                    },
-                    // TODO: also check for rect break after tabs? Kinda pointless, isn't it?
-                    Tab => cur_line_cursor += tab_width,
-                    Return => {
-                        max_line_cursor = max_line_cursor.max(cur_line_cursor);
-                        cur_line_cursor = 0.0;
-                        cur_line += 1;
-                    }
+        let last_piece = pieces.len() - 1;
+
+        for (piece_idx, (piece_start, piece_end)) in pieces.into_iter().enumerate() {
+            let (font, shaper) = fonts[pending_word.font_index];
+            let mut word = shape_word(
+                &pending_word.chars[piece_start..piece_end],
+                &pending_word.char_indices[piece_start..piece_end],
+                pending_word.bidi_level,
+                font, shaper, units_to_px[pending_word.font_index]);
+            word.font_index = pending_word.font_index;
+            word.glyph_infos.iter_mut().for_each(|info| info.font_index = pending_word.font_index);
+            if piece_idx == last_piece {
+                word.spaces_after = pending_word.spaces_after;
+                if pending_word.soft_hyphen {
+                    word.hyphen = hyphen.glyphs.get(0).and_then(|g| Some(GlyphInstance {
+                        index: g.index,
+                        point: TypedPoint2D::new(word.total_width, 0.0),
+                    }));
+                    word.hyphen_width = hyphen.total_width;
                 }
             }
-
-            max_hor_len = Some(cur_line_cursor);
-
-            cur_line as f32 * vertical_advance
+            words.push(SemanticWordItem::Word(word));
         }
     };
 
-    let vertical_length = if vertical_length > rect_dimensions.height {
-        TextOverflow::IsOverflowing(vertical_length - rect_dimensions.height)
-    } else {
-        TextOverflow::InBounds(rect_dimensions.height - vertical_length)
-    };
+    let mut segment_start = 0;
+    // Logical index of the next character in the text
+    let mut next_char_idx = 0;
 
-    let horizontal_length = {
+    // Each segment ends at a line break opportunity (UAX #14)
+    for (segment_end, is_hard_break) in LineBreakIterator::new(&text) {
 
-        let horz_max = if overflow.allows_horizontal_overflow() {
+        let segment = &text[segment_start..segment_end];
 
-            let mut cur_line_cursor = 0.0;
-            let mut max_line_cursor: f32 = 0.0;
+        // `word-break: keep-all`: only spaces are line break opportunities, not the boundaries between CJK characters
+        let ends_with_space = segment.chars().last().and_then(|c| Some(c.is_whitespace())).unwrap_or(false);
+        if break_options.word_break == WordBreak::KeepAll && !is_hard_break && !ends_with_space {
+            continue;
+        }
 
-            for w in words {
-                match w {
-                    Word(w) => cur_line_cursor += w.total_width,
-                    Tab => cur_line_cursor += tab_width,
-                    Return => {
-                        max_line_cursor = max_line_cursor.max(cur_line_cursor);
-                        cur_line_cursor = 0.0;
-                    }
-                }
-            }
+        let segment_byte_offset = segment_start;
+        segment_start = segment_end;
 
-            max_line_cursor
+        let mut chars = segment.char_indices().peekable();
+
+        while let Some((byte_idx, cur_char)) = chars.next() {
+            let char_idx = next_char_idx;
+            next_char_idx += 1;
+
+            match cur_char {
+                // `\r\n` only counts as one line break
+                '\r' if chars.peek().and_then(|(_, c)| Some(*c)) == Some('\n') => { },
+                '\n' | '\r' | '\u{2028}' | '\u{2029}' if white_space.preserves_newlines() => {
+                    if !pending_word.is_empty() {
+                        end_word(&mut words, &mut pending_word);
+                    }
+                    words.push(SemanticWordItem::Return);
+                },
+                '\t' if !white_space.collapses_spaces() => {
+                    if !pending_word.is_empty() {
+                        end_word(&mut words, &mut pending_word);
+                    }
+                    words.push(SemanticWordItem::Tab);
+                },
+                ' ' | '\t' | '\n' | '\r' | '\u{2028}' | '\u{2029}' => {
+                    if !white_space.collapses_spaces() {
+                        pending_word.spaces_after += 1;
+                    } else if !pending_word.chars.is_empty() {
+                        // Collapsed spaces at the start of a line are removed
+                        pending_word.spaces_after = 1;
+                    }
+                },
+                // Zero-width space: line break opportunity, but not rendered
+                '\u{200B}' => { },
+                // Soft hyphen: line break opportunity, only rendered if the line is broken there
+                '\u{AD}' => { pending_word.soft_hyphen = true; },
+                cur_char => {
+                    // Regular character - words are also split where the direction or the font changes
+                    let bidi_level = bidi_info.levels[segment_byte_offset + byte_idx].number();
+                    let level_changed = !pending_word.chars.is_empty() && pending_word.bidi_level != bidi_level;
+
+                    // Stay in the font of the current word if possible, so that
+                    // combining marks are shaped together with their base character
+                    let font_index = if !pending_word.chars.is_empty() && font_has_glyph(fonts[pending_word.font_index].0, cur_char) {
+                        pending_word.font_index
+                    } else {
+                        fonts.iter().position(|(font, _)| font_has_glyph(font, cur_char)).unwrap_or(0)
+                    };
+                    let font_changed = !pending_word.chars.is_empty() && pending_word.font_index != font_index;
+
+                    if pending_word.spaces_after > 0 || pending_word.soft_hyphen || level_changed || font_changed {
+                        end_word(&mut words, &mut pending_word);
+                    }
+                    pending_word.chars.push(cur_char);
+                    pending_word.char_indices.push(char_idx);
+                    pending_word.bidi_level = bidi_level;
+                    pending_word.font_index = font_index;
+                },
+            }
+        }
+
+        // End of the segment = line break opportunity
+        if !pending_word.is_empty() {
+            end_word(&mut words, &mut pending_word);
+        }
+    }
+
+    // In case the rectangle is smaller than the longest word,
+    // we need to expand the rectangle to be that size
+    let longest_word_width = words.iter().fold(0.0, |longest, item| match item {
+        SemanticWordItem::Word(w) => w.total_width.max(longest),
+        _ => longest,
+    });
+
+    Words {
+        items: words,
+        longest_word_width: longest_word_width,
+    }
+}
+
+/// Factor to convert the font units of the `font` into pixels
+fn get_units_to_px<'a>(font: &Font<'a>, font_size: Scale) -> f32 {
+    // Warning: rusttype has a bit of a weird layout system - you have to
+    // subtract the descent from the ascent to get the proper vertical height
+    let v_metrics_font = font.v_metrics_unscaled();
+    font_size.x * (96.0 / 72.0) * DEFAULT_CHARACTER_WIDTH_MULTIPLIER / (v_metrics_font.ascent - v_metrics_font.descent)
+}
+
+/// Shapes a single word, the glyph positions are relative to the start of the word.
+///
+/// `char_indices` are the logical indices of the `chars` in the text. Right-to-left words
+/// (odd `bidi_level`) are shaped in logical order, then the glyphs are mirrored.
+fn shape_word<'a>(chars: &[char], char_indices: &[usize], bidi_level: u8, font: &Font<'a>, shaper: &Shaper, units_to_px: f32) -> Word {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let shaped = shaper.shape(font, chars);
+    let is_rtl = bidi_level % 2 == 1;
+
+    let mut word_caret = 0.0;
+    let carets = shaped.iter().map(|shaped| {
+        let caret = word_caret;
+        word_caret += shaped.x_advance * units_to_px;
+        caret
+    }).collect::<Vec<f32>>();
+    let total_width = word_caret;
+
+    // (caret, mirrored caret) of the last glyph with an advance, marks are positioned relative to it
+    let mut last_base = (0.0, 0.0);
+
+    let glyphs = shaped.iter().zip(carets.iter()).map(|(shaped, caret)| {
+        let advance = shaped.x_advance * units_to_px;
+        let x = if !is_rtl {
+            *caret
+        } else if advance == 0.0 {
+            // zero-width marks keep their offset relative to their base glyph
+            let (base_caret, base_mirrored) = last_base;
+            base_mirrored + (*caret - base_caret)
+        } else {
+            let mirrored = total_width - caret - advance;
+            last_base = (*caret, mirrored);
+            mirrored
+        };
+        GlyphInstance {
+            index: shaped.glyph_id as u32,
+            point: TypedPoint2D::new(
+                x + shaped.x_offset * units_to_px,
+                -shaped.y_offset * units_to_px),
+        }
+    }).collect();
+
+    let word_string = chars.iter().collect::<String>();
+    let word_boundaries = get_glyph_boundaries(&word_string, word_string.split_word_bound_indices().map(|(i, _)| i), &shaped);
+    let grapheme_boundaries = get_glyph_boundaries(&word_string, word_string.grapheme_indices(true).map(|(i, _)| i), &shaped);
+
+    // The glyphs are in logical order, so the characters of a glyph
+    // range from its cluster to the cluster of the next glyph
+    let glyph_infos = shaped.iter().enumerate().map(|(glyph_idx, g)| {
+        let is_first_glyph_of_cluster = glyph_idx == 0 || shaped[glyph_idx - 1].cluster != g.cluster;
+        let next_cluster = shaped[(glyph_idx + 1)..].iter().map(|next| next.cluster).find(|c| *c > g.cluster).unwrap_or(chars.len());
+        GlyphInfo {
+            char_index: char_indices[g.cluster],
+            char_count: if is_first_glyph_of_cluster { next_cluster - g.cluster } else { 0 },
+            advance: g.x_advance * units_to_px,
+            font_index: 0,
+            is_rtl: is_rtl,
+        }
+    }).collect();
+
+    Word {
+        glyphs: glyphs,
+        total_width: total_width,
+        spaces_after: 0,
+        hyphen: None,
+        hyphen_width: 0.0,
+        word_boundaries: word_boundaries,
+        grapheme_boundaries: grapheme_boundaries,
+        glyph_infos: glyph_infos,
+        bidi_level: bidi_level,
+        font_index: 0,
+    }
+}
+
+/// Converts the byte offsets of boundaries in the `text` into the indices of the glyphs
+/// that start at these boundaries (boundaries inside of ligatures are dropped)
+fn get_glyph_boundaries<I: Iterator<Item=usize>>(text: &str, byte_offsets: I, shaped: &[ShapedGlyph])
+-> Vec<usize>
+{
+    byte_offsets
+        .filter(|byte_offset| *byte_offset != 0)
+        .filter_map(|byte_offset| {
+            let char_idx = text[..byte_offset].chars().count();
+            shaped.iter().position(|g| g.cluster == char_idx)
+        })
+        .collect()
+}
+
+/// `overflow-wrap: anywhere / break-word`: Breaks the words that are wider than `max_width`
+/// into multiple words - at word boundaries (UAX #29) if possible, otherwise between grapheme clusters.
+///
+/// Right-to-left words are not broken (yet).
+pub(crate) fn break_long_words(words: &Words, max_width: f32)
+-> Words
+{
+    let mut items = Vec::with_capacity(words.items.len());
+    let mut longest_word_width = 0.0;
+
+    for item in &words.items {
+        match item {
+            SemanticWordItem::Word(word) if word.total_width > max_width && !word.is_rtl() => {
+                for piece in break_word(word, max_width) {
+                    if piece.total_width > longest_word_width {
+                        longest_word_width = piece.total_width;
+                    }
+                    items.push(SemanticWordItem::Word(piece));
+                }
+            },
+            other => {
+                if let SemanticWordItem::Word(word) = other {
+                    if word.total_width > longest_word_width {
+                        longest_word_width = word.total_width;
+                    }
+                }
+                items.push(other.clone());
+            },
+        }
+    }
+
+    Words {
+        items: items,
+        longest_word_width: longest_word_width,
+    }
+}
+
+fn break_word(word: &Word, max_width: f32)
+-> Vec<Word>
+{
+    let glyph_count = word.glyphs.len();
+    let glyph_x = |glyph_idx: usize| if glyph_idx < glyph_count { word.glyphs[glyph_idx].point.x } else { word.total_width };
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+
+    while start < glyph_count {
+        let start_x = glyph_x(start);
+        let fits = |boundary: &usize| *boundary > start && glyph_x(*boundary) - start_x <= max_width;
+
+        let end = if glyph_x(glyph_count) - start_x <= max_width {
+            glyph_count
+        } else {
+            word.word_boundaries.iter().cloned().filter(|b| fits(b)).last()
+            .or_else(|| word.grapheme_boundaries.iter().cloned().filter(|b| fits(b)).last())
+            // At least one grapheme cluster has to be on every line
+            .or_else(|| word.grapheme_boundaries.iter().cloned().find(|b| *b > start))
+            .unwrap_or(glyph_count)
+        };
+
+        pieces.push(get_word_slice(word, start, end));
+        start = end;
+    }
+
+    pieces
+}
+
+/// Returns the glyphs `start..end` of the (left-to-right) `word` as a new word. The spaces
+/// and the hyphen after the word are only kept if the slice reaches the end of the word.
+fn get_word_slice(word: &Word, start: usize, end: usize)
+-> Word
+{
+    let glyph_count = word.glyphs.len();
+    let glyph_x = |glyph_idx: usize| if glyph_idx < glyph_count { word.glyphs[glyph_idx].point.x } else { word.total_width };
+    let start_x = glyph_x(start);
+
+    let is_last_piece = end == glyph_count;
+    let sub_boundaries = |boundaries: &[usize]| boundaries.iter().filter(|b| **b > start && **b < end).map(|b| b - start).collect();
+
+    Word {
+        glyphs: word.glyphs[start..end].iter().map(|g| {
+            let mut g = *g;
+            g.point.x -= start_x;
+            g
+        }).collect(),
+        total_width: glyph_x(end) - start_x,
+        spaces_after: if is_last_piece { word.spaces_after } else { 0 },
+        hyphen: if is_last_piece { word.hyphen.and_then(|h| Some(GlyphInstance { index: h.index, point: TypedPoint2D::new(h.point.x - start_x, h.point.y) })) } else { None },
+        hyphen_width: if is_last_piece { word.hyphen_width } else { 0.0 },
+        word_boundaries: sub_boundaries(&word.word_boundaries),
+        grapheme_boundaries: sub_boundaries(&word.grapheme_boundaries),
+        glyph_infos: word.glyph_infos[start..end].to_vec(),
+        bidi_level: word.bidi_level,
+        font_index: word.font_index,
+    }
+}
+
+/// `text-overflow` and `line-clamp`: Drops the lines after `truncation.max_lines` and cuts off the
+/// lines that are wider than `max_width` (if `truncation.truncates_long_lines`), so that the last
+/// visible line (or the cut-off line) ends in the `truncation.marker`.
+///
+/// The lines are broken with the greedy algorithm. Returns `None` if the text doesn't have to be truncated.
+pub(crate) fn truncate_words(words: &Words, max_width: Option<f32>, truncation: &TextTruncation, font_metrics: &FontMetrics)
+-> Option<Words>
+{
+    use self::SemanticWordItem::*;
+
+    let items = &words.items;
+
+    let soft_line_breaks = match max_width {
+        Some(max_width) if truncation.wraps => get_greedy_line_breaks(items, max_width, font_metrics),
+        _ => Vec::new(),
+    };
+
+    // (range of the items on the line, whether the line ends with a `Return`)
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut next_soft_break = soft_line_breaks.iter().peekable();
+
+    for (item_idx, item) in items.iter().enumerate() {
+        if item.is_return() {
+            lines.push((line_start..item_idx, true));
+            line_start = item_idx + 1;
+        } else if next_soft_break.peek() == Some(&&item_idx) {
+            next_soft_break.next();
+            lines.push((line_start..item_idx, false));
+            line_start = item_idx;
+        }
+    }
+    lines.push((line_start..items.len(), false));
+
+    let line_count = truncation.max_lines.unwrap_or(lines.len()).min(lines.len());
+    let is_clamped = line_count < lines.len();
+
+    let mut truncated_items = Vec::with_capacity(items.len());
+    let mut is_truncated = false;
+
+    for (line_idx, (line, ends_with_return)) in lines.into_iter().take(line_count).enumerate() {
+        let line_items = &items[line];
+        let is_last_line = line_idx == line_count - 1;
+        let is_too_long = truncation.truncates_long_lines && max_width.map(|w| get_line_width(line_items, font_metrics) > w).unwrap_or(false);
+
+        if is_too_long || (is_clamped && is_last_line) {
+            truncated_items.extend(truncate_line(line_items, max_width, &truncation.marker, font_metrics));
+            is_truncated = true;
+        } else {
+            truncated_items.extend(line_items.iter().cloned());
+        }
+
+        if ends_with_return && !is_last_line {
+            truncated_items.push(Return);
+        }
+    }
+
+    if !is_truncated {
+        return None;
+    }
+
+    let longest_word_width = truncated_items.iter().fold(0.0, |longest: f32, item| match item {
+        Word(w) => longest.max(w.total_width),
+        _ => longest,
+    });
+
+    Some(Words {
+        items: truncated_items,
+        longest_word_width: longest_word_width,
+    })
+}
+
+/// Width of a line, without the spaces after the last word
+fn get_line_width(line_items: &[SemanticWordItem], font_metrics: &FontMetrics)
+-> f32
+{
+    use self::SemanticWordItem::*;
+
+    let mut line_width = 0.0;
+
+    for (item_idx, item) in line_items.iter().enumerate() {
+        match item {
+            Word(w) if item_idx == line_items.len() - 1 => line_width += w.total_width,
+            Word(w) => line_width += w.get_width_with_spaces(font_metrics.space_width),
+            Tab => line_width = font_metrics.get_next_tab_stop(line_width),
+            Return => { },
+        }
+    }
+
+    line_width
+}
+
+/// Keeps as many words (or grapheme clusters) of the line as fit in front of the `marker`,
+/// then appends the marker
+fn truncate_line(line_items: &[SemanticWordItem], max_width: Option<f32>, marker: &Word, font_metrics: &FontMetrics)
+-> Vec<SemanticWordItem>
+{
+    use self::SemanticWordItem::*;
+
+    let available_width = max_width.unwrap_or(::std::f32::MAX) - marker.total_width;
+    let mut truncated_items = Vec::new();
+    let mut caret = 0.0;
+
+    for item in line_items {
+        match item {
+            Word(w) => {
+                if caret + w.total_width <= available_width {
+                    caret += w.get_width_with_spaces(font_metrics.space_width);
+                    truncated_items.push(Word(w.clone()));
+                } else {
+                    // Cut the word between two grapheme clusters (right-to-left words are dropped entirely)
+                    let glyphs_that_fit = w.grapheme_boundaries.iter()
+                        .filter(|b| w.glyphs[**b].point.x <= available_width - caret)
+                        .last();
+                    match glyphs_that_fit {
+                        Some(end) if !w.is_rtl() => truncated_items.push(Word(get_word_slice(w, 0, *end))),
+                        _ => { },
+                    }
+                    break;
+                }
+            },
+            Tab => {
+                let next_tab_stop = font_metrics.get_next_tab_stop(caret);
+                if next_tab_stop > available_width {
+                    break;
+                }
+                caret = next_tab_stop;
+                truncated_items.push(Tab);
+            },
+            Return => { },
+        }
+    }
+
+    // The marker directly follows the last character
+    if let Some(Word(last_word)) = truncated_items.last_mut() {
+        last_word.spaces_after = 0;
+        last_word.hyphen = None;
+        last_word.hyphen_width = 0.0;
+    }
+
+    truncated_items.push(Word(marker.clone()));
+    truncated_items
+}
+
+/// Content-based size of a block of text, used for `width: auto / min-content / max-content`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct TextIntrinsicSize {
+    /// Width of the longest word - the text can't get any narrower than this
+    pub(crate) min_content_width: f32,
+    /// Width of the longest line if the text is not wrapped
+    pub(crate) max_content_width: f32,
+    /// Width of the longest line if the text is wrapped at `max_width` (or not wrapped at all)
+    pub(crate) content_width: f32,
+    /// Height of the text if it is wrapped at `max_width` (or not wrapped at all)
+    pub(crate) content_height: f32,
+    /// Number of lines if the text is wrapped at `max_width` (or not wrapped at all)
+    pub(crate) line_count: usize,
+}
+
+/// Calculates the intrinsic size of the words, without positioning the glyphs.
+///
+/// If `max_width` is set, the height is calculated as if the text was wrapped at `max_width`
+/// (with the same line breaks as the laid out text, see `get_soft_line_breaks`).
+pub(crate) fn get_intrinsic_text_size(words: &Words, font_metrics: &FontMetrics, max_width: Option<f32>, line_breaking: LineBreaking)
+-> TextIntrinsicSize
+{
+    use self::SemanticWordItem::*;
+
+    let FontMetrics { space_width, vertical_advance, .. } = *font_metrics;
+
+    let soft_line_breaks = get_soft_line_breaks(&words.items, max_width, font_metrics, line_breaking);
+    let mut next_soft_break = soft_line_breaks.iter().peekable();
+
+    let mut max_content_width: f32 = 0.0;
+    let mut content_width: f32 = 0.0;
+    // Caret of the text if it isn't wrapped
+    let mut line_caret = 0.0;
+    // Caret of the text if it is wrapped at max_width
+    let mut wrapped_line_caret = 0.0;
+    let mut num_lines = 1;
+
+    for (item_idx, item) in words.items.iter().enumerate() {
+        match item {
+            Word(w) => {
+                max_content_width = max_content_width.max(line_caret + w.total_width);
+                line_caret += w.get_width_with_spaces(space_width);
+
+                if next_soft_break.peek() == Some(&&item_idx) {
+                    next_soft_break.next();
+                    wrapped_line_caret = 0.0;
+                    num_lines += 1;
+                }
+                content_width = content_width.max(wrapped_line_caret + w.total_width);
+                wrapped_line_caret += w.get_width_with_spaces(space_width);
+            },
+            Tab => {
+                line_caret = font_metrics.get_next_tab_stop(line_caret);
+                wrapped_line_caret = font_metrics.get_next_tab_stop(wrapped_line_caret);
+            },
+            Return => {
+                line_caret = 0.0;
+                wrapped_line_caret = 0.0;
+                num_lines += 1;
+            },
+        }
+    }
+
+    TextIntrinsicSize {
+        min_content_width: words.longest_word_width,
+        max_content_width: max_content_width,
+        content_width: content_width,
+        content_height: num_lines as f32 * vertical_advance * DEFAULT_LINE_HEIGHT_MULTIPLIER,
+        line_count: num_lines,
+    }
+}
+
+// First pass: calculate if the words will overflow (using the tabs)
+#[inline(always)]
+fn estimate_overflow_pass_1(
+    words: &Words,
+    rect_dimensions: &TypedSize2D<f32, LayoutPixel>,
+    font_metrics: &FontMetrics,
+    overflow: &LayoutOverflow,
+    line_breaking: LineBreaking)
+-> TextOverflowPass1
+{
+    use self::SemanticWordItem::*;
+
+    let words = &words.items;
+
+    let FontMetrics { space_width, vertical_advance, .. } = *font_metrics;
+
+    let max_text_line_len_horizontal = 0.0;
+
+    // Determine the maximum width and height that the text needs for layout
+
+    // This is actually tricky. Horizontal scrollbars and vertical scrollbars
+    // behave differently.
+    //
+    // Vertical scrollbars always show the length - when the
+    // vertical length = the height of the rectangle, the scrollbar (the actual bar)
+    // is 0.5x the height of the rectangle, aligned at the top.
+    //
+    // Horizontal scrollbars, on the other hand, are 1.0x the width of the rectangle,
+    // when the width is filled.
+
+    // TODO: this is duplicated code
+
+    let mut max_hor_len = None;
+
+    let vertical_length = {
+        if overflow.allows_horizontal_overflow() {
+            // If we can overflow horizontally, we only need to sum up the `Return`
+            // characters, since the actual length of the line doesn't matter
+            words.iter().filter(|w| w.is_return()).count() as f32 * vertical_advance
+        } else {
+            // TODO: should this be cached? The calculation is probably quick, but this
+            // is essentially the same thing as we do in the actual text layout stage
+            let soft_line_breaks = get_soft_line_breaks(words, Some(rect_dimensions.width), font_metrics, line_breaking);
+            let mut next_soft_break = soft_line_breaks.iter().peekable();
+
+            let mut max_line_cursor: f32 = 0.0;
+            let mut cur_line_cursor = 0.0;
+            let mut cur_line = 0;
+
+            for (word_idx, w) in words.iter().enumerate() {
+                match w {
+                    Word(w) => {
+                        if next_soft_break.peek() == Some(&&word_idx) {
+                            next_soft_break.next();
+                            max_line_cursor = max_line_cursor.max(cur_line_cursor);
+                            cur_line_cursor = 0.0;
+                            cur_line += 1;
+                        }
+                        cur_line_cursor += w.get_width_with_spaces(space_width);
+                    },
+                    // TODO: also check for rect break after tabs? Kinda pointless, isn't it?
+                    Tab => cur_line_cursor = font_metrics.get_next_tab_stop(cur_line_cursor),
+                    Return => {
+                        max_line_cursor = max_line_cursor.max(cur_line_cursor);
+                        cur_line_cursor = 0.0;
+                        cur_line += 1;
+                    }
+                }
+            }
+
+            max_hor_len = Some(cur_line_cursor);
+
+            cur_line as f32 * vertical_advance
+        }
+    };
+
+    let vertical_length = if vertical_length > rect_dimensions.height {
+        TextOverflow::IsOverflowing(vertical_length - rect_dimensions.height)
+    } else {
+        TextOverflow::InBounds(rect_dimensions.height - vertical_length)
+    };
+
+    let horizontal_length = {
+
+        let horz_max = if overflow.allows_horizontal_overflow() {
+
+            let mut cur_line_cursor = 0.0;
+            let mut max_line_cursor: f32 = 0.0;
+
+            for w in words {
+                match w {
+                    Word(w) => cur_line_cursor += w.total_width,
+                    Tab => cur_line_cursor = font_metrics.get_next_tab_stop(cur_line_cursor),
+                    Return => {
+                        max_line_cursor = max_line_cursor.max(cur_line_cursor);
+                        cur_line_cursor = 0.0;
+                    }
+                }
+            }
+
+            max_line_cursor
         } else {
            max_hor_len.unwrap()
         };
@@ -625,8 +1484,10 @@ fn estimate_overflow_pass_2(
     font_metrics: &FontMetrics,
     overflow: &LayoutOverflow,
     scrollbar_info: &ScrollbarInfo,
+    truncation: Option<&TextTruncation>,
+    line_breaking: LineBreaking,
     pass1: TextOverflowPass1)
--> (TypedSize2D<f32, LayoutPixel>, TextOverflowPass2)
+-> (TypedSize2D<f32, LayoutPixel>, TextOverflowPass2, Option<Words>)
 {
     let FontMetrics { space_width, tab_width, vertical_advance, .. } = *font_metrics;
 
@@ -648,9 +1509,15 @@ fn estimate_overflow_pass_2(
         new_size.width -= scrollbar_info.width as f32;
     }
 
-    // If the words are not overflowing, just take the result from the first pass
-    let recalc_scrollbar_info = if pass1.horizontal.is_overflowing() || pass1.vertical.is_overflowing() {
-        estimate_overflow_pass_1(words, &new_size, font_metrics, overflow)
+    // `text-overflow` / `line-clamp`: truncate the text to the (reduced) width of the rectangle
+    let max_width = if overflow.allows_horizontal_overflow() { None } else { Some(new_size.width) };
+    let truncated_words = truncation.and_then(|t| truncate_words(words, max_width, t, font_metrics));
+
+    // If the words are not overflowing (and not truncated), just take the result from the first pass
+    let recalc_scrollbar_info = if let Some(ref truncated_words) = truncated_words {
+        estimate_overflow_pass_1(truncated_words, &new_size, font_metrics, overflow, line_breaking)
+    } else if pass1.horizontal.is_overflowing() || pass1.vertical.is_overflowing() {
+        estimate_overflow_pass_1(words, &new_size, font_metrics, overflow, line_breaking)
     } else {
         pass1
     };
@@ -658,51 +1525,364 @@ fn estimate_overflow_pass_2(
     (new_size, TextOverflowPass2 {
         horizontal: recalc_scrollbar_info.horizontal,
         vertical: recalc_scrollbar_info.vertical,
-    })
+    }, truncated_words)
 }
 
-#[inline(always)]
-fn calculate_harfbuzz_adjustments<'a>(text: &str, font: &Font<'a>)
--> Vec<HarfbuzzAdjustment>
+/// How the words are broken into lines (if the text has a maximum width)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct LineBreaking {
+    /// Use the Knuth-Plass (total-fit) algorithm instead of greedily filling each line
+    pub(crate) optimal: bool,
+    /// Stretch the spaces so that every line (except the last line of a paragraph) fills the width
+    pub(crate) justify: bool,
+    /// `white-space: nowrap / pre`: lines are only broken at newlines
+    pub(crate) no_wrap: bool,
+}
+
+impl LineBreaking {
+    /// `text-align: justify` and `text-wrap: pretty` break the lines with the Knuth-Plass algorithm,
+    /// otherwise the (faster) greedy algorithm is used
+    pub(crate) fn new(horiz_alignment: TextAlignmentHorz, text_wrap: TextWrap, white_space: WhiteSpace) -> Self {
+        let justify = horiz_alignment == TextAlignmentHorz::Justify;
+        Self {
+            optimal: justify || text_wrap == TextWrap::Pretty,
+            justify: justify,
+            no_wrap: !white_space.wraps(),
+        }
+    }
+}
+
+/// Returns the indices of the words that start a new line because the previous line was full
+/// (not including the lines started by a `Return`). The layout, the overflow estimation and the
+/// intrinsic size of a text all use this, so that they agree on where the lines are broken.
+fn get_soft_line_breaks(words: &[SemanticWordItem], max_horizontal_width: Option<f32>, font_metrics: &FontMetrics, line_breaking: LineBreaking)
+-> Vec<usize>
+{
+    match max_horizontal_width {
+        _ if line_breaking.no_wrap => Vec::new(),
+        Some(max) if line_breaking.optimal => get_knuth_plass_line_breaks(words, max, font_metrics),
+        Some(max) => get_greedy_line_breaks(words, max, font_metrics),
+        None => Vec::new(),
+    }
+}
+
+/// Knuth-Plass: badness penalty for every line, so that fewer lines are preferred
+const KNUTH_PLASS_LINE_PENALTY: f32 = 10.0;
+/// Knuth-Plass: penalty for a paragraph ending with a single word on the last line
+const KNUTH_PLASS_ORPHAN_PENALTY: f32 = 1000.0;
+/// Knuth-Plass: how far a space may be stretched (relative to its width) before a line counts as "bad"
+const KNUTH_PLASS_SPACE_STRETCH: f32 = 0.5;
+
+/// Greedy line breaking: fills each line with as many words as possible.
+///
+/// Returns the indices of the words (in `words`) that start a new line,
+/// not including the lines started by a `Return`.
+fn get_greedy_line_breaks(words: &[SemanticWordItem], max_horizontal_width: f32, font_metrics: &FontMetrics)
+-> Vec<usize>
+{
+    use self::SemanticWordItem::*;
+
+    let FontMetrics { space_width, .. } = *font_metrics;
+
+    let mut line_breaks = Vec::new();
+    let mut word_caret = 0.0;
+
+    for (word_idx, word) in words.iter().enumerate() {
+        match word {
+            Word(word) => {
+                // A word that is wider than the whole line (and isn't broken by `overflow-wrap`)
+                // gets its own line instead of leaving an empty line behind
+                if word_caret > 0.0 && word_caret + word.total_width > max_horizontal_width {
+                    line_breaks.push(word_idx);
+                    word_caret = 0.0;
+                }
+                word_caret += word.get_width_with_spaces(space_width);
+            },
+            Tab => { word_caret = font_metrics.get_next_tab_stop(word_caret); },
+            Return => { word_caret = 0.0; },
+        }
+    }
+
+    line_breaks
+}
+
+/// Knuth-Plass line breaking: chooses the line breaks of each paragraph so that the
+/// sum of the "demerits" of all lines is minimal, i.e. lines are filled as evenly as possible,
+/// instead of leaving a very short line behind because the next word didn't fit.
+///
+/// Returns the same format as `get_greedy_line_breaks`
+fn get_knuth_plass_line_breaks(words: &[SemanticWordItem], max_horizontal_width: f32, font_metrics: &FontMetrics)
+-> Vec<usize>
+{
+    use self::SemanticWordItem::*;
+
+    let FontMetrics { space_width, .. } = *font_metrics;
+
+    let mut line_breaks = Vec::new();
+    let mut paragraph_start = 0;
+
+    // Each paragraph (delimited by a `Return`) is broken independently
+    for paragraph_end in (0..words.len()).filter(|i| words[*i].is_return()).chain(Some(words.len())) {
+
+        let paragraph = &words[paragraph_start..paragraph_end];
+        let paragraph_len = paragraph.len();
+
+        // min_demerits[i]: minimal demerits of all lines before item i, if a line starts at i
+        // previous_break[i]: where the line that ends at item i starts
+        let mut min_demerits = vec![::std::f32::INFINITY; paragraph_len + 1];
+        let mut previous_break = vec![0; paragraph_len + 1];
+        min_demerits[0] = 0.0;
+
+        for line_start in 0..paragraph_len {
+            if min_demerits[line_start] == ::std::f32::INFINITY {
+                continue;
+            }
+
+            let mut line_width = 0.0;
+            let mut words_on_line = 0_usize;
+            // number of spaces between the words (which can be stretched)
+            let mut inner_spaces = 0_usize;
+            let mut has_candidate = false;
+
+            for line_end in (line_start + 1)..=paragraph_len {
+                // width of the line, including the trailing spaces of the last word
+                match &paragraph[line_end - 1] {
+                    Word(word) => {
+                        if line_end - 1 > line_start {
+                            if let Word(previous) = &paragraph[line_end - 2] {
+                                inner_spaces += previous.spaces_after;
+                            }
+                        }
+                        line_width += word.get_width_with_spaces(space_width);
+                        words_on_line += 1;
+                    },
+                    Tab => { line_width = font_metrics.get_next_tab_stop(line_width); },
+                    Return => { },
+                }
+
+                // Lines may only be broken before words
+                let is_last_line = line_end == paragraph_len;
+                if !is_last_line && !is_word(&paragraph[line_end]) {
+                    continue;
+                }
+
+                let natural_width = match &paragraph[line_end - 1] {
+                    Word(word) => line_width - word.spaces_after as f32 * space_width + word.hyphen_width,
+                    _ => line_width,
+                };
+
+                // Once the line overflows, longer lines will overflow, too - except
+                // if there is no other way to break the line (i.e. a single word that is too wide)
+                if natural_width > max_horizontal_width && has_candidate {
+                    break;
+                }
+                has_candidate = true;
+
+                let demerits = if is_last_line {
+                    // The last line doesn't have to be filled
+                    let has_orphan = words_on_line == 1 && line_start != 0;
+                    if has_orphan { KNUTH_PLASS_ORPHAN_PENALTY } else { 0.0 }
+                } else {
+                    let stretch = inner_spaces.max(1) as f32 * space_width * KNUTH_PLASS_SPACE_STRETCH;
+                    let ratio = (max_horizontal_width - natural_width).max(0.0) / stretch.max(::std::f32::EPSILON);
+                    let badness = 100.0 * ratio.powi(3);
+                    (KNUTH_PLASS_LINE_PENALTY + badness).powi(2)
+                };
+
+                let total_demerits = min_demerits[line_start] + demerits;
+                if total_demerits < min_demerits[line_end] {
+                    min_demerits[line_end] = total_demerits;
+                    previous_break[line_end] = line_start;
+                }
+            }
+        }
+
+        // Walk back from the end of the paragraph to collect the chosen line breaks
+        let mut paragraph_breaks = Vec::new();
+        let mut current = paragraph_len;
+        while current > 0 {
+            current = previous_break[current];
+            if current > 0 {
+                paragraph_breaks.push(paragraph_start + current);
+            }
+        }
+        paragraph_breaks.reverse();
+        line_breaks.extend(paragraph_breaks);
+
+        paragraph_start = paragraph_end + 1;
+    }
+
+    line_breaks
+}
+
+fn is_word(item: &SemanticWordItem) -> bool {
+    match item {
+        SemanticWordItem::Word(_) => true,
+        _ => false,
+    }
+}
+
+/// Returns the width of the spaces between the words of the line starting at `line_start`.
+///
+/// This is the regular space width, except for justified lines that don't end a paragraph.
+/// Only the first space of a run of (preserved) spaces is stretched.
+fn get_line_space_width(
+    words: &[SemanticWordItem],
+    line_start: usize,
+    soft_line_breaks: &[usize],
+    max_horizontal_width: Option<f32>,
+    font_metrics: &FontMetrics,
+    justify: bool)
+-> f32
+{
+    use self::SemanticWordItem::*;
+
+    let FontMetrics { space_width, .. } = *font_metrics;
+
+    let max_horizontal_width = match max_horizontal_width {
+        Some(s) if justify => s,
+        _ => return space_width,
+    };
+
+    let mut natural_width = 0.0;
+    // number of gaps between the words (runs of spaces)
+    let mut gaps_on_line = 0_usize;
+    let mut last_word_spaces = 0;
+    let mut ends_with_soft_break = false;
+
+    for (word_idx, word) in words.iter().enumerate().skip(line_start) {
+        if word_idx != line_start && soft_line_breaks.binary_search(&word_idx).is_ok() {
+            ends_with_soft_break = true;
+            break;
+        }
+        match word {
+            Word(word) => {
+                if last_word_spaces > 0 {
+                    gaps_on_line += 1;
+                }
+                natural_width += word.get_width_with_spaces(space_width);
+                last_word_spaces = word.spaces_after;
+            },
+            Tab => { natural_width = font_metrics.get_next_tab_stop(natural_width); last_word_spaces = 0; },
+            Return => break,
+        }
+    }
+
+    // The last line of a paragraph and lines without spaces are not justified
+    if !ends_with_soft_break || gaps_on_line == 0 {
+        return space_width;
+    }
+
+    // the spaces after the last word are not stretched
+    let natural_width = natural_width - last_word_spaces as f32 * space_width;
+    space_width + (max_horizontal_width - natural_width).max(0.0) / gaps_on_line as f32
+}
+
+/// Returns the indices of the `words` in visual order: the items of each line are
+/// reordered by their embedding levels (UAX #9, rule L2), lines are not reordered.
+fn get_visual_order(words: &[SemanticWordItem], soft_line_breaks: &[usize])
+-> Vec<usize>
 {
-    /*
-    use harfbuzz_rs::*;
-    use harfbuzz_rs::rusttype::SetRustTypeFuncs;
+    use self::SemanticWordItem::*;
 
-    let path = "path/to/some/font_file.otf";
-    let index = 0; //< face index in the font file
-    let face = Face::from_file(path, index).unwrap();
-    let mut font = Font::new(face);
+    let mut visual_order = Vec::with_capacity(words.len());
+    let mut line_start = 0;
 
-    font.set_rusttype_funcs();
+    for word_idx in 0..=words.len() {
+        let is_line_end = word_idx == words.len()
+            || words[word_idx].is_return()
+            || soft_line_breaks.binary_search(&word_idx).is_ok();
 
-    let output = UnicodeBuffer::new().add_str(text).shape(&font, &[]);
-    let positions = output.get_glyph_positions();
-    let infos = output.get_glyph_infos();
+        if !is_line_end {
+            continue;
+        }
+
+        let line = &words[line_start..word_idx];
+        let word_level = |item: &SemanticWordItem| match item { Word(w) => Some(w.bidi_level), _ => None };
+
+        // Tabs get the lower level of the surrounding words, so they don't interrupt a right-to-left run
+        let levels = line.iter().enumerate().map(|(i, item)| word_level(item).unwrap_or_else(|| {
+            let previous = line[..i].iter().rev().filter_map(|w| word_level(w)).next();
+            let next = line[(i + 1)..].iter().filter_map(|w| word_level(w)).next();
+            match (previous, next) {
+                (Some(p), Some(n)) => p.min(n),
+                (Some(l), None) | (None, Some(l)) => l,
+                (None, None) => 0,
+            }
+        })).collect::<Vec<u8>>();
+
+        visual_order.extend(reorder_levels(&levels).into_iter().map(|i| line_start + i));
+
+        if word_idx < words.len() && words[word_idx].is_return() {
+            visual_order.push(word_idx);
+            line_start = word_idx + 1;
+        } else {
+            line_start = word_idx;
+        }
+    }
+
+    visual_order
+}
+
+/// UAX #9, rule L2: From the highest level to the lowest odd level, reverse
+/// any sequence of items that are at that level or higher.
+fn reorder_levels(levels: &[u8])
+-> Vec<usize>
+{
+    let mut order = (0..levels.len()).collect::<Vec<usize>>();
+
+    let max_level = levels.iter().cloned().max().unwrap_or(0);
+    let min_odd_level = match levels.iter().cloned().filter(|l| l % 2 == 1).min() {
+        Some(s) => s,
+        None => return order,
+    };
 
-    for (position, info) in positions.iter().zip(infos) {
-        println!("gid: {:?}, cluster: {:?}, x_advance: {:?}, x_offset: {:?}, y_offset: {:?}",
-            info.codepoint, info.cluster, position.x_advance, position.x_offset, position.y_offset);
+    for level in (min_odd_level..=max_level).rev() {
+        let mut run_start = 0;
+        while run_start < order.len() {
+            if levels[order[run_start]] < level {
+                run_start += 1;
+                continue;
+            }
+            let run_end = (run_start..order.len()).find(|i| levels[order[*i]] < level).unwrap_or(order.len());
+            order[run_start..run_end].reverse();
+            run_start = run_end;
+        }
     }
-    */
-    Vec::new() // TODO
+
+    order
 }
 
 /// If `max_horizontal_width` is `None`, it means that the text is allowed to overflow
-/// the rectangle horizontally
-fn words_to_left_aligned_glyphs<'a>(
+/// the rectangle horizontally.
+///
+/// Returns the positioned glyphs, the `GlyphInfo` of each glyph, the line break offsets
+/// and the minimal width and height of the text.
+fn words_to_left_aligned_glyphs(
     words: &Words,
-    font: &Font<'a>,
     max_horizontal_width: Option<f32>,
-    font_metrics: &FontMetrics)
--> (Vec<GlyphInstance>, Vec<(usize, f32)>, f32, f32)
+    font_metrics: &FontMetrics,
+    line_breaking: LineBreaking)
+-> (Vec<GlyphInstance>, Vec<GlyphInfo>, Vec<(usize, f32)>, f32, f32)
 {
     let words = &words.items;
 
-    let FontMetrics { space_width, tab_width, vertical_advance, font_size_no_line_height, .. } = *font_metrics;
+    let FontMetrics { space_width: unstretched_space_width, vertical_advance, font_size_no_line_height, .. } = *font_metrics;
+
+    // Indices of the words that start a new line because the previous line was full.
+    // If we don't have a maximum horizontal width, the text can overflow the
+    // bounding rectangle in the horizontal direction
+    let soft_line_breaks = get_soft_line_breaks(words, max_horizontal_width, font_metrics, line_breaking);
+
+    // Mixed left-to-right / right-to-left text is reordered for display. Since the words
+    // are only reordered within their line, a line starts at the same (visual) index
+    // as the (logical) index in `soft_line_breaks`.
+    let visual_order = get_visual_order(words, &soft_line_breaks);
 
     // left_aligned_glyphs stores the X and Y coordinates of the positioned glyphs
     let mut left_aligned_glyphs = Vec::<GlyphInstance>::new();
+    // the GlyphInfo of each glyph in left_aligned_glyphs
+    let mut glyph_infos = Vec::<GlyphInfo>::new();
 
     enum WordCaretMax {
         SomeMaxWidth(f32),
@@ -719,17 +1899,14 @@ fn words_to_left_aligned_glyphs<'a>(
     let mut word_caret = 0.0;
     let mut current_line_num = 0;
     let mut max_word_caret = 0.0;
+    // Width of the spaces on the current line (only differs from the space_width for justified text)
+    let mut line_space_width = None;
 
-    for word in words {
+    for (visual_idx, word_idx) in visual_order.into_iter().enumerate() {
         use self::SemanticWordItem::*;
-        match word {
+        match &words[word_idx] {
             Word(word) => {
-                let text_overflows_rect = match max_horizontal_width {
-                    Some(max) => word_caret + word.total_width > max,
-                    // If we don't have a maximum horizontal width, the text can overflow the
-                    // bounding rectangle in the horizontal direction
-                    None => false,
-                };
+                let text_overflows_rect = soft_line_breaks.binary_search(&visual_idx).is_ok();
 
                 if text_overflows_rect {
                     let space_until_horz_return = match max_horizontal_width {
@@ -746,22 +1923,81 @@ fn words_to_left_aligned_glyphs<'a>(
                     }
                     word_caret = 0.0;
                     current_line_num += 1;
+                    line_space_width = None;
+                }
+
+                let space_width = match line_space_width {
+                    Some(s) => s,
+                    None => {
+                        let s = get_line_space_width(words, visual_idx, &soft_line_breaks, max_horizontal_width, font_metrics, line_breaking.justify);
+                        line_space_width = Some(s);
+                        s
+                    }
+                };
+
+                let push_y = (current_line_num + 1) as f32 * vertical_advance * DEFAULT_LINE_HEIGHT_MULTIPLIER;
+
+                // (logically) last word of the line
+                let ends_line = word_idx + 1 == words.len()
+                    || words[word_idx + 1].is_return()
+                    || soft_line_breaks.binary_search(&(word_idx + 1)).is_ok();
+
+                let spaces_width = if word.spaces_after > 0 {
+                    space_width + (word.spaces_after - 1) as f32 * unstretched_space_width
+                } else {
+                    0.0
+                };
+
+                // The soft hyphen is only visible if the line is broken after the word
+                let hyphen = if soft_line_breaks.binary_search(&(word_idx + 1)).is_ok() { word.hyphen } else { None };
+                // The hyphen is rendered with the first font, in place of the soft hyphen (after the last character)
+                let hyphen_info = GlyphInfo {
+                    char_index: word.glyph_infos.iter().map(|info| info.char_index + info.char_count).max().unwrap_or(0),
+                    char_count: 0,
+                    advance: word.hyphen_width,
+                    font_index: 0,
+                    is_rtl: word.is_rtl(),
+                };
+
+                // The spaces after a right-to-left word are on its left side,
+                // the hyphen of a right-to-left word is on its left side, too
+                if word.is_rtl() {
+                    if !ends_line {
+                        word_caret += spaces_width;
+                    }
+                    if let Some(mut hyphen) = hyphen {
+                        hyphen.point.x = word_caret;
+                        hyphen.point.y += push_y;
+                        left_aligned_glyphs.push(hyphen);
+                        glyph_infos.push(hyphen_info);
+                        word_caret += word.hyphen_width;
+                    }
                 }
 
-                for glyph in &word.glyphs {
+                for (glyph, glyph_info) in word.glyphs.iter().zip(word.glyph_infos.iter()) {
                     let mut new_glyph = *glyph;
-                    let push_x = word_caret;
-                    let push_y = (current_line_num + 1) as f32 * vertical_advance * DEFAULT_LINE_HEIGHT_MULTIPLIER;
-                    new_glyph.point.x += push_x;
+                    new_glyph.point.x += word_caret;
                     new_glyph.point.y += push_y;
                     left_aligned_glyphs.push(new_glyph);
+                    glyph_infos.push(*glyph_info);
                 }
 
-                // Add the word width to the current word_caret
-                word_caret += word.total_width + space_width;
+                // Add the word width (and the spaces after the word) to the current word_caret
+                word_caret += word.total_width;
+
+                if !word.is_rtl() {
+                    if let Some(mut hyphen) = hyphen {
+                        hyphen.point.x = word_caret;
+                        hyphen.point.y += push_y;
+                        left_aligned_glyphs.push(hyphen);
+                        glyph_infos.push(hyphen_info);
+                        word_caret += word.hyphen_width;
+                    }
+                    word_caret += spaces_width;
+                }
             },
             Tab => {
-                word_caret += tab_width;
+                word_caret = font_metrics.get_next_tab_stop(word_caret);
             },
             Return => {
                 // TODO: dupliated code
@@ -777,6 +2013,7 @@ fn words_to_left_aligned_glyphs<'a>(
                 }
                 word_caret = 0.0;
                 current_line_num += 1;
+                line_space_width = None;
             },
         }
     }
@@ -804,27 +2041,7 @@ fn words_to_left_aligned_glyphs<'a>(
         (line, space_r)
     }).collect();
 
-    (left_aligned_glyphs, line_break_offsets, min_enclosing_width, min_enclosing_height)
-}
-
-#[inline(always)]
-fn apply_harfbuzz_adjustments(positioned_glyphs: &mut [GlyphInstance], harfbuzz_adjustments: Vec<HarfbuzzAdjustment>)
-{
-    // TODO
-}
-
-#[inline(always)]
-fn calculate_knuth_plass_adjustments(positioned_glyphs: &[GlyphInstance], line_break_offsets: &[(usize, f32)])
--> Vec<KnuthPlassAdjustment>
-{
-    // TODO
-    Vec::new()
-}
-
-#[inline(always)]
-fn apply_knuth_plass_adjustments(positioned_glyphs: &mut [GlyphInstance], knuth_plass_adjustments: Vec<KnuthPlassAdjustment>)
-{
-    // TODO
+    (left_aligned_glyphs, glyph_infos, line_break_offsets, min_enclosing_width, min_enclosing_height)
 }
 
 #[inline(always)]
@@ -867,10 +2084,12 @@ fn align_text_horz(alignment: TextAlignmentHorz, glyphs: &mut [GlyphInstance], l
     // i.e. the last line has to end with the last glyph
     assert!(glyphs.len() - 1 == line_breaks[line_breaks.len() - 1].0);
 
+    // `Start` and `End` are resolved before the text is laid out (see `TextAlignmentHorz::resolve`)
     let multiply_factor = match alignment {
-        Left => { return; },
+        // Justified lines are already stretched to the full width
+        Left | Start | Justify => { return; },
         Center => 0.5, // move the line by the half width
-        Right => 1.0, // move the line by the full width
+        Right | End => 1.0, // move the line by the full width
     };
 
     // If we have the characters "ABC\n\nDEF", this will result in:
@@ -940,6 +2159,420 @@ fn add_origin(positioned_glyphs: &mut [GlyphInstance], x: f32, y: f32)
     }
 }
 
+/// A span of a `RichText`, with its font family and size resolved against the style of the node
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct RichTextSpanInfo<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) font_family: &'a FontFamily,
+    pub(crate) font_size: FontSize,
+}
+
+/// Horizontal and vertical metrics of the first font of a span, in pixels
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub(crate) struct RichTextSpanMetrics {
+    pub(crate) space_width: f32,
+    pub(crate) tab_width: f32,
+    /// Height of the font above the baseline
+    pub(crate) ascent: f32,
+    /// Depth of the font below the baseline (positive)
+    pub(crate) descent: f32,
+    /// Height of the line box of the span (including the `line-height`)
+    pub(crate) line_height: f32,
+}
+
+/// A line of a `RichTextLayout`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RichTextLine {
+    /// The glyphs of the line (indices into `RichTextLayout::glyphs`)
+    pub(crate) glyph_range: Range<usize>,
+    /// Width of the line, without the spaces at the end of the line
+    pub(crate) width: f32,
+    /// Top edge of the line
+    pub(crate) top: f32,
+    /// The baseline that the glyphs of all spans on the line are aligned to
+    pub(crate) baseline: f32,
+    /// Height of the line: the distance from the baseline to the top of the highest span
+    /// plus the distance from the baseline to the bottom of the deepest span
+    pub(crate) height: f32,
+}
+
+/// A `RichText`, laid out as one paragraph
+#[derive(Debug, Clone)]
+pub(crate) struct RichTextLayout {
+    /// The glyphs of all spans, in visual order
+    pub(crate) glyphs: Vec<GlyphInstance>,
+    /// The `font_index` of each `GlyphInfo` is an index into `fonts`, the `char_index`
+    /// is the index of the character in the (NFC-normalized) text of all spans
+    pub(crate) glyph_infos: Vec<GlyphInfo>,
+    /// The font fallback chains of all spans, concatenated: (index of the span, font)
+    pub(crate) fonts: Vec<(usize, FontId)>,
+    /// The metrics of each span
+    pub(crate) span_metrics: Vec<RichTextSpanMetrics>,
+    pub(crate) lines: Vec<RichTextLine>,
+    /// Width of the longest line
+    pub(crate) width: f32,
+    /// Sum of the heights of all lines
+    pub(crate) height: f32,
+    /// Width of the longest word, i.e. the min-content width of the text
+    pub(crate) longest_word_width: f32,
+}
+
+/// A run of glyphs of the same span on one line, used for text decorations and hit-testing
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct RichTextSpanRun {
+    pub(crate) span_index: usize,
+    /// Horizontal extent of the glyphs, vertical extent of the line
+    pub(crate) bounds: TypedRect<f32, LayoutPixel>,
+    pub(crate) baseline: f32,
+}
+
+impl RichTextLayout {
+    /// Moves the lines according to the text alignment and the glyphs into the `bounds`
+    pub(crate) fn align(&mut self, bounds: &TypedRect<f32, LayoutPixel>, horiz_alignment: TextAlignmentHorz, vert_alignment: TextAlignmentVert) {
+        // Rich text is never justified, `Start` and `End` are resolved before the text is laid out
+        let horz_factor = match horiz_alignment {
+            TextAlignmentHorz::Left | TextAlignmentHorz::Start | TextAlignmentHorz::Justify => 0.0,
+            TextAlignmentHorz::Center => 0.5,
+            TextAlignmentHorz::Right | TextAlignmentHorz::End => 1.0,
+        };
+
+        let vert_factor = match vert_alignment {
+            TextAlignmentVert::Top => 0.0,
+            TextAlignmentVert::Center => 0.5,
+            TextAlignmentVert::Bottom => 1.0,
+        };
+
+        // Overflowing text starts at the top edge of the rectangle
+        let y_offset = bounds.origin.y + (bounds.size.height - self.height).max(0.0) * vert_factor;
+
+        for line in &mut self.lines {
+            let x_offset = bounds.origin.x + (bounds.size.width - line.width).max(0.0) * horz_factor;
+            for glyph in &mut self.glyphs[line.glyph_range.clone()] {
+                glyph.point.x += x_offset;
+                glyph.point.y += y_offset;
+            }
+            line.top += y_offset;
+            line.baseline += y_offset;
+        }
+    }
+
+    /// Returns the runs of consecutive glyphs that belong to the same span, line by line
+    pub(crate) fn get_span_runs(&self) -> Vec<RichTextSpanRun> {
+
+        let mut runs = Vec::<RichTextSpanRun>::new();
+
+        for line in &self.lines {
+            let mut current_run: Option<(usize, f32, f32)> = None;
+
+            let glyphs = self.glyphs[line.glyph_range.clone()].iter().zip(self.glyph_infos[line.glyph_range.clone()].iter());
+
+            for (glyph, glyph_info) in glyphs {
+                let span_index = self.fonts[glyph_info.font_index].0;
+                let (glyph_left, glyph_right) = (glyph.point.x, glyph.point.x + glyph_info.advance);
+                current_run = match current_run {
+                    Some((run_span, left, right)) if run_span == span_index => Some((run_span, left.min(glyph_left), right.max(glyph_right))),
+                    other => {
+                        if let Some(run) = other {
+                            runs.push(Self::span_run(line, run));
+                        }
+                        Some((span_index, glyph_left, glyph_right))
+                    },
+                };
+            }
+
+            if let Some(run) = current_run {
+                runs.push(Self::span_run(line, run));
+            }
+        }
+
+        runs
+    }
+
+    fn span_run(line: &RichTextLine, (span_index, left, right): (usize, f32, f32)) -> RichTextSpanRun {
+        RichTextSpanRun {
+            span_index: span_index,
+            bounds: TypedRect::new(TypedPoint2D::new(left, line.top), TypedSize2D::new(right - left, line.height)),
+            baseline: line.baseline,
+        }
+    }
+}
+
+/// Lays out the spans of a `RichText` as one paragraph, starting at (0, 0). Each span is shaped
+/// with its own font fallback chain and size, lines can be broken inside of and between spans.
+///
+/// If `max_width` is `None`, lines are only broken at newlines.
+pub(crate) fn layout_rich_text(
+    app_resources: &AppResources,
+    spans: &[RichTextSpanInfo],
+    max_width: Option<f32>,
+    line_height: Option<LineHeight>,
+    letter_spacing: Option<LetterSpacing>,
+    shaping_options: &ShapingOptions,
+    break_options: &TextBreakOptions,
+    tab_options: &TabOptions)
+-> RichTextLayout
+{
+    use unicode_normalization::UnicodeNormalization;
+
+    let mut items = Vec::new();
+    let mut item_spans = Vec::new();
+    let mut fonts = Vec::new();
+    let mut span_metrics = Vec::with_capacity(spans.len());
+    // index of the first character of the current span in the text of all spans
+    let mut char_offset = 0;
+
+    for (span_idx, span) in spans.iter().enumerate() {
+
+        let (font_ids, span_fonts): (Vec<FontId>, Vec<_>) = app_resources.get_font_fallback_chain(span.font_family, span.text)
+            .into_iter()
+            .filter_map(|id| app_resources.get_font(&id).and_then(|font| Some((id, font))))
+            .unzip();
+
+        let span_char_count = span.text.nfc().count();
+
+        if span_fonts.is_empty() {
+            error!("rich text span {:?} has no font assigned!", span.text);
+            span_metrics.push(RichTextSpanMetrics::default());
+            char_offset += span_char_count;
+            continue;
+        }
+
+        let first_font = &span_fonts[0].0;
+        let font_metrics = calculate_font_metrics(first_font, &span.font_size, line_height).with_tab_options(tab_options);
+        let units_to_px = get_units_to_px(first_font, font_metrics.font_size_no_line_height);
+        let v_metrics = first_font.v_metrics_unscaled();
+
+        span_metrics.push(RichTextSpanMetrics {
+            space_width: font_metrics.space_width,
+            tab_width: font_metrics.tab_width,
+            ascent: v_metrics.ascent * units_to_px,
+            descent: -v_metrics.descent * units_to_px,
+            line_height: font_metrics.vertical_advance * DEFAULT_LINE_HEIGHT_MULTIPLIER,
+        });
+
+        let shapers = span_fonts.iter().map(|f| Shaper::new(&f.1, shaping_options)).collect::<Vec<_>>();
+        let span_fonts = span_fonts.iter().zip(shapers.iter()).map(|(f, shaper)| (&*f.0, shaper)).collect::<Vec<_>>();
+        let mut words = split_text_into_words(span.text, &span_fonts, font_metrics.font_size_no_line_height, break_options);
+        if let Some(letter_spacing) = get_letter_spacing_px(letter_spacing) {
+            words = with_letter_spacing(&words, letter_spacing);
+        }
+
+        // The font indices of the words refer to the fonts of all spans
+        let font_offset = fonts.len();
+        for mut item in words.items {
+            if let SemanticWordItem::Word(ref mut word) = item {
+                word.font_index += font_offset;
+                for glyph_info in &mut word.glyph_infos {
+                    glyph_info.font_index += font_offset;
+                    glyph_info.char_index += char_offset;
+                }
+            }
+            items.push(item);
+            item_spans.push(span_idx);
+        }
+
+        fonts.extend(font_ids.into_iter().map(|id| (span_idx, id)));
+        char_offset += span_char_count;
+    }
+
+    let max_width = if break_options.white_space.wraps() { max_width } else { None };
+
+    // The explicit tab stops are shared by all spans, `tab-size` depends on the font of the span
+    let tab_stops = tab_options.tab_stops.0.iter().map(|stop| stop.to_pixels()).collect::<Vec<_>>();
+
+    rich_text_words_to_lines(&items, &item_spans, &span_metrics, &tab_stops, fonts, max_width)
+}
+
+/// Breaks the words of all spans into lines (greedily) and positions them. `item_spans` is the
+/// index of the span of each item, the hyphen of a word is rendered with the first font of its span.
+/// A tab advances to the next of the `tab_stops`, or by the `tab_width` of its span.
+fn rich_text_words_to_lines(
+    items: &[SemanticWordItem],
+    item_spans: &[usize],
+    span_metrics: &[RichTextSpanMetrics],
+    tab_stops: &[f32],
+    fonts: Vec<(usize, FontId)>,
+    max_width: Option<f32>)
+-> RichTextLayout
+{
+    use self::SemanticWordItem::*;
+
+    // Lines are broken between spans just like between words, but each
+    // word is followed by the spaces of its own font
+    let soft_line_breaks = match max_width {
+        Some(max_width) => {
+            let mut line_breaks = Vec::new();
+            let mut word_caret = 0.0;
+            for (item_idx, (item, span_idx)) in items.iter().zip(item_spans.iter()).enumerate() {
+                let metrics = &span_metrics[*span_idx];
+                match item {
+                    Word(word) => {
+                        if word_caret > 0.0 && word_caret + word.total_width > max_width {
+                            line_breaks.push(item_idx);
+                            word_caret = 0.0;
+                        }
+                        word_caret += word.get_width_with_spaces(metrics.space_width);
+                    },
+                    Tab => { word_caret = get_next_tab_stop(word_caret, metrics.tab_width, tab_stops); },
+                    Return => { word_caret = 0.0; },
+                }
+            }
+            line_breaks
+        },
+        None => Vec::new(),
+    };
+
+    let first_font_of_span = (0..span_metrics.len())
+        .map(|span_idx| fonts.iter().position(|(span, _)| *span == span_idx).unwrap_or(0))
+        .collect::<Vec<usize>>();
+
+    /// A line whose baseline is not known yet
+    struct LineBox {
+        glyph_start: usize,
+        width: f32,
+        /// Maximum distance from the baseline to the top of a span (including the half-leading)
+        above_baseline: f32,
+        /// Maximum distance from the baseline to the bottom of a span (including the half-leading)
+        below_baseline: f32,
+    }
+
+    impl LineBox {
+        fn new(glyph_start: usize) -> Self {
+            Self { glyph_start: glyph_start, width: 0.0, above_baseline: 0.0, below_baseline: 0.0 }
+        }
+
+        fn add_span(&mut self, metrics: &RichTextSpanMetrics) {
+            let half_leading = (metrics.line_height - (metrics.ascent + metrics.descent)) / 2.0;
+            self.above_baseline = self.above_baseline.max(metrics.ascent + half_leading);
+            self.below_baseline = self.below_baseline.max(metrics.descent + half_leading);
+        }
+    }
+
+    let mut glyphs = Vec::<GlyphInstance>::new();
+    let mut glyph_infos = Vec::<GlyphInfo>::new();
+    let mut line_boxes = Vec::new();
+    let mut current_line = LineBox::new(0);
+    let mut word_caret = 0.0;
+
+    for (visual_idx, item_idx) in get_visual_order(items, &soft_line_breaks).into_iter().enumerate() {
+
+        let span_idx = item_spans[item_idx];
+        let metrics = &span_metrics[span_idx];
+
+        if soft_line_breaks.binary_search(&visual_idx).is_ok() {
+            line_boxes.push(current_line);
+            current_line = LineBox::new(glyphs.len());
+            word_caret = 0.0;
+        }
+
+        // Empty lines and tabs also have the height of their span
+        current_line.add_span(metrics);
+
+        match &items[item_idx] {
+            Word(word) => {
+                let is_broken_after = soft_line_breaks.binary_search(&(item_idx + 1)).is_ok();
+                let ends_line = item_idx + 1 == items.len() || items[item_idx + 1].is_return() || is_broken_after;
+                let spaces_width = if ends_line { 0.0 } else { word.spaces_after as f32 * metrics.space_width };
+
+                // The soft hyphen is only visible if the line is broken after the word
+                let hyphen = if is_broken_after { word.hyphen } else { None };
+                let hyphen_info = GlyphInfo {
+                    char_index: word.glyph_infos.iter().map(|info| info.char_index + info.char_count).max().unwrap_or(0),
+                    char_count: 0,
+                    advance: word.hyphen_width,
+                    font_index: first_font_of_span[span_idx],
+                    is_rtl: word.is_rtl(),
+                };
+
+                // The spaces and the hyphen of a right-to-left word are on its left side
+                if word.is_rtl() {
+                    word_caret += spaces_width;
+                    if let Some(mut hyphen) = hyphen {
+                        hyphen.point.x = word_caret;
+                        glyphs.push(hyphen);
+                        glyph_infos.push(hyphen_info);
+                        word_caret += word.hyphen_width;
+                    }
+                }
+
+                for (glyph, glyph_info) in word.glyphs.iter().zip(word.glyph_infos.iter()) {
+                    let mut new_glyph = *glyph;
+                    new_glyph.point.x += word_caret;
+                    glyphs.push(new_glyph);
+                    glyph_infos.push(*glyph_info);
+                }
+
+                word_caret += word.total_width;
+
+                if !word.is_rtl() {
+                    if let Some(mut hyphen) = hyphen {
+                        hyphen.point.x = word_caret;
+                        glyphs.push(hyphen);
+                        glyph_infos.push(hyphen_info);
+                        word_caret += word.hyphen_width;
+                    }
+                    word_caret += spaces_width;
+                }
+            },
+            Tab => {
+                word_caret = get_next_tab_stop(word_caret, metrics.tab_width, tab_stops);
+            },
+            Return => {
+                current_line.width = word_caret;
+                line_boxes.push(current_line);
+                current_line = LineBox::new(glyphs.len());
+                word_caret = 0.0;
+                continue;
+            },
+        }
+
+        current_line.width = word_caret;
+    }
+
+    line_boxes.push(current_line);
+
+    // Align the glyphs of every line on the baseline of the line
+    let mut lines = Vec::with_capacity(line_boxes.len());
+    let mut line_top = 0.0;
+
+    for (line_idx, line_box) in line_boxes.iter().enumerate() {
+        let glyph_end = line_boxes.get(line_idx + 1).and_then(|next| Some(next.glyph_start)).unwrap_or(glyphs.len());
+        let baseline = line_top + line_box.above_baseline;
+        let height = line_box.above_baseline + line_box.below_baseline;
+
+        for glyph in &mut glyphs[line_box.glyph_start..glyph_end] {
+            glyph.point.y += baseline;
+        }
+
+        lines.push(RichTextLine {
+            glyph_range: line_box.glyph_start..glyph_end,
+            width: line_box.width,
+            top: line_top,
+            baseline: baseline,
+            height: height,
+        });
+
+        line_top += height;
+    }
+
+    let width = lines.iter().fold(0.0, |max: f32, line| max.max(line.width));
+    let longest_word_width = items.iter().fold(0.0, |longest: f32, item| match item {
+        Word(w) => longest.max(w.total_width),
+        _ => longest,
+    });
+
+    RichTextLayout {
+        glyphs: glyphs,
+        glyph_infos: glyph_infos,
+        fonts: fonts,
+        span_metrics: span_metrics.to_vec(),
+        lines: lines,
+        width: width,
+        height: line_top,
+        longest_word_width: longest_word_width,
+    }
+}
+
 // -------------------------- PUBLIC API -------------------------- //
 
 pub type IndexOfLineBreak = usize;
@@ -950,8 +2583,11 @@ pub type RemainingSpaceToRight = f32;
 pub struct LayoutTextResult {
     /// The words, broken into
     pub words: Words,
-    /// Left-aligned glyphs
+    /// Left-aligned glyphs, in visual order (right-to-left runs are reordered)
     pub layouted_glyphs: Vec<GlyphInstance>,
+    /// For each glyph in `layouted_glyphs`: the characters that the glyph was created from
+    /// (i.e. the logical position of the glyph) and its width
+    pub glyph_infos: Vec<GlyphInfo>,
     /// The line_breaks contain:
     ///
     /// - The index of the glyph at which the line breaks (index into the `self.layouted_glyphs`)
@@ -965,6 +2601,9 @@ pub struct LayoutTextResult {
 }
 
 /// Layout a string of text horizontally, given a font with its metrics.
+///
+/// Mixed left-to-right and right-to-left text is laid out with the Unicode Bidirectional
+/// Algorithm (UAX #9), the base direction of each paragraph is determined by its first strong character.
 pub fn layout_text<'a>(
     text: &str,
     font: &Font<'a>,
@@ -976,13 +2615,171 @@ pub fn layout_text<'a>(
     //
     // This function simply lays out a text, without trying to fit it into a rectangle.
     // This function does not calculate any overflow.
-    let words = split_text_into_words(text, font, font_metrics.font_size_no_line_height);
-    let (layouted_glyphs, line_breaks, min_width, min_height) =
-        words_to_left_aligned_glyphs(&words, font, None, &font_metrics);
+    // The raw font bytes aren't available here, so only the `kern` table is used
+    let words = split_text_into_words(text, &[(font, &Shaper::kerning_only())], font_metrics.font_size_no_line_height, &TextBreakOptions::default());
+    let (layouted_glyphs, glyph_infos, line_breaks, min_width, min_height) =
+        words_to_left_aligned_glyphs(&words, None, &font_metrics, LineBreaking::default());
 
     LayoutTextResult {
-        words, layouted_glyphs, line_breaks, min_width, min_height, font_metrics,
+        words, layouted_glyphs, glyph_infos, line_breaks, min_width, min_height, font_metrics,
+    }
+}
+
+/// Width of the caret rectangle returned by `get_caret_rect`
+pub const CARET_WIDTH: f32 = 1.0;
+
+/// The part of a layouted glyph that belongs to one character
+/// (the width of ligatures is divided evenly between their characters)
+#[derive(Debug, Copy, Clone)]
+struct CharBox {
+    char_index: usize,
+    line: usize,
+    x: f32,
+    width: f32,
+    is_rtl: bool,
+}
+
+impl CharBox {
+    /// X position of the caret in front of the character (in reading direction)
+    fn leading_edge(&self) -> f32 {
+        if self.is_rtl { self.x + self.width } else { self.x }
+    }
+
+    /// X position of the caret behind the character (in reading direction)
+    fn trailing_edge(&self) -> f32 {
+        if self.is_rtl { self.x } else { self.x + self.width }
+    }
+
+    fn distance_to(&self, x: f32) -> f32 {
+        if x < self.x {
+            self.x - x
+        } else if x > self.x + self.width {
+            x - (self.x + self.width)
+        } else {
+            0.0
+        }
+    }
+}
+
+fn get_line_height(layout: &LayoutTextResult) -> f32 {
+    layout.font_metrics.vertical_advance * DEFAULT_LINE_HEIGHT_MULTIPLIER
+}
+
+fn get_char_boxes(layout: &LayoutTextResult) -> Vec<CharBox> {
+
+    let line_height = get_line_height(layout);
+    let mut char_boxes = Vec::new();
+
+    for (glyph, glyph_info) in layout.layouted_glyphs.iter().zip(layout.glyph_infos.iter()) {
+        if glyph_info.char_count == 0 {
+            continue;
+        }
+        // glyphs are positioned on the baseline of their line
+        let line = ((glyph.point.y / line_height).round() as usize).saturating_sub(1);
+        let char_width = glyph_info.advance / glyph_info.char_count as f32;
+        for i in 0..glyph_info.char_count {
+            // the first character of a right-to-left ligature is on its right side
+            let visual_i = if glyph_info.is_rtl { glyph_info.char_count - 1 - i } else { i };
+            char_boxes.push(CharBox {
+                char_index: glyph_info.char_index + i,
+                line: line,
+                x: glyph.point.x + visual_i as f32 * char_width,
+                width: char_width,
+                is_rtl: glyph_info.is_rtl,
+            });
+        }
+    }
+
+    char_boxes
+}
+
+/// Returns the index of the character (in the NFC-normalized text) in front of which the caret
+/// should be placed if the user clicks on the `point` (relative to the origin of the layouted text).
+///
+/// Clicking on the first half of a character (in reading direction) returns the index of the
+/// character, clicking on the second half returns the index of the next character.
+pub fn get_char_index_at_point(layout: &LayoutTextResult, point: TypedPoint2D<f32, LayoutPixel>) -> usize {
+
+    let char_boxes = get_char_boxes(layout);
+    let clicked_line = (point.y / get_line_height(layout)).floor().max(0.0) as usize;
+
+    // If the clicked line is empty, use the nearest line that contains characters
+    let line = match char_boxes.iter().map(|b| b.line).min_by_key(|line| (*line as isize - clicked_line as isize).abs()) {
+        Some(s) => s,
+        None => return 0,
+    };
+
+    let nearest = char_boxes.iter()
+        .filter(|b| b.line == line)
+        .min_by(|a, b| a.distance_to(point.x).partial_cmp(&b.distance_to(point.x)).unwrap_or(::std::cmp::Ordering::Equal))
+        .unwrap();
+
+    let is_left_half = point.x < nearest.x + nearest.width / 2.0;
+    if is_left_half != nearest.is_rtl { nearest.char_index } else { nearest.char_index + 1 }
+}
+
+/// Returns the rectangle of the caret in front of the character at `char_index`
+/// (relative to the origin of the layouted text). The caret is `CARET_WIDTH` wide and one line high.
+///
+/// If `char_index` is not rendered as a glyph (i.e. a space or the end of the text),
+/// the caret is placed behind the previous character.
+pub fn get_caret_rect(layout: &LayoutTextResult, char_index: usize) -> TypedRect<f32, LayoutPixel> {
+
+    let char_boxes = get_char_boxes(layout);
+    let line_height = get_line_height(layout);
+
+    let (x, line) = match char_boxes.iter().find(|b| b.char_index == char_index) {
+        Some(b) => (b.leading_edge(), b.line),
+        None => match char_boxes.iter().filter(|b| b.char_index < char_index).max_by_key(|b| b.char_index) {
+            Some(b) => {
+                // skip the spaces between the previous character and the caret
+                let skipped_width = (char_index - b.char_index - 1) as f32 * layout.font_metrics.space_width;
+                let x = if b.is_rtl { b.trailing_edge() - skipped_width } else { b.trailing_edge() + skipped_width };
+                (x, b.line)
+            },
+            None => (0.0, 0),
+        },
+    };
+
+    TypedRect::new(TypedPoint2D::new(x, line as f32 * line_height), TypedSize2D::new(CARET_WIDTH, line_height))
+}
+
+/// Returns the rectangles (relative to the origin of the layouted text) that have to be
+/// highlighted to show the `selection` of characters, at least one rectangle per line.
+///
+/// Since right-to-left runs are reordered, a selection can consist of multiple rectangles per line.
+pub fn get_selection_rects(layout: &LayoutTextResult, selection: Range<usize>) -> Vec<TypedRect<f32, LayoutPixel>> {
+
+    let mut char_boxes = get_char_boxes(layout);
+    char_boxes.sort_by(|a, b| (a.line, a.x).partial_cmp(&(b.line, b.x)).unwrap_or(::std::cmp::Ordering::Equal));
+
+    let line_height = get_line_height(layout);
+
+    // (line, start x, end x) of the selected characters that are next to each other
+    let mut rects = Vec::<(usize, f32, f32)>::new();
+    let mut current_rect = None;
+
+    for b in &char_boxes {
+        let is_selected = selection.start <= b.char_index && b.char_index < selection.end;
+        match (is_selected, current_rect) {
+            (true, Some((line, start_x, _))) if line == b.line => {
+                current_rect = Some((line, start_x, b.x + b.width));
+            },
+            (true, _) => {
+                rects.extend(current_rect);
+                current_rect = Some((b.line, b.x, b.x + b.width));
+            },
+            (false, _) => {
+                rects.extend(current_rect.take());
+            },
+        }
     }
+
+    rects.extend(current_rect);
+
+    rects.into_iter().map(|(line, start_x, end_x)| {
+        TypedRect::new(TypedPoint2D::new(start_x, line as f32 * line_height), TypedSize2D::new(end_x - start_x, line_height))
+    }).collect()
 }
 
 #[test]
@@ -1004,4 +2801,326 @@ fn test_it_should_add_origin() {
     assert_eq!(instances[0].point.y as usize, 0);
     assert_eq!(instances[1].point.x as usize, 33);
     assert_eq!(instances[1].point.y as usize, 10);
+}
+
+#[cfg(test)]
+fn test_word(index: u32, width: f32) -> Word {
+    Word {
+        glyphs: vec![GlyphInstance { index: index, point: TypedPoint2D::new(0.0, 0.0) }],
+        total_width: width,
+        spaces_after: 1,
+        hyphen: None,
+        hyphen_width: 0.0,
+        word_boundaries: Vec::new(),
+        grapheme_boundaries: Vec::new(),
+        glyph_infos: vec![GlyphInfo { char_index: index as usize, char_count: 1, advance: width, font_index: 0, is_rtl: false }],
+        bidi_level: 0,
+        font_index: 0,
+    }
+}
+
+#[cfg(test)]
+fn test_words(widths: &[f32]) -> Words {
+    Words {
+        items: widths.iter().enumerate().map(|(i, width)| SemanticWordItem::Word(test_word(i as u32, *width))).collect(),
+        longest_word_width: widths.iter().cloned().fold(0.0, f32::max),
+    }
+}
+
+#[cfg(test)]
+fn test_font_metrics() -> FontMetrics {
+    FontMetrics {
+        space_width: 10.0,
+        tab_width: 40.0,
+        tab_stops: Vec::new(),
+        vertical_advance: 20.0,
+        font_size_with_line_height: Scale::uniform(15.0),
+        font_size_no_line_height: Scale::uniform(15.0),
+        height_for_1px: 1.0,
+    }
+}
+
+#[test]
+fn test_knuth_plass_line_breaks() {
+    // "aaa bb cc ddddd" in a 60px wide rectangle: the greedy algorithm
+    // leaves "cc" alone on the second line, Knuth-Plass moves "bb" down instead
+    let words = test_words(&[30.0, 20.0, 20.0, 50.0]);
+    let font_metrics = test_font_metrics();
+
+    assert_eq!(get_greedy_line_breaks(&words.items, 60.0, &font_metrics), vec![2, 3]);
+    assert_eq!(get_knuth_plass_line_breaks(&words.items, 60.0, &font_metrics), vec![1, 3]);
+
+    // The intrinsic size of the text is measured with the same line breaks
+    let justified = LineBreaking::new(TextAlignmentHorz::Justify, TextWrap::Wrap, WhiteSpace::Normal);
+    let no_wrap = LineBreaking::new(TextAlignmentHorz::Left, TextWrap::Wrap, WhiteSpace::NoWrap);
+    let greedy_size = get_intrinsic_text_size(&words, &font_metrics, Some(60.0), LineBreaking::default());
+    let optimal_size = get_intrinsic_text_size(&words, &font_metrics, Some(60.0), justified);
+    let no_wrap_size = get_intrinsic_text_size(&words, &font_metrics, Some(60.0), no_wrap);
+    assert_eq!((greedy_size.content_width, greedy_size.line_count), (60.0, 3));
+    assert_eq!((optimal_size.content_width, optimal_size.line_count), (50.0, 3));
+    assert_eq!((no_wrap_size.content_width, no_wrap_size.line_count), (150.0, 1));
+
+    // paragraphs are broken independently, a too wide word gets its own line
+    let mut words = test_words(&[30.0, 20.0, 20.0, 50.0]);
+    words.items.insert(2, SemanticWordItem::Return);
+    words.items.push(SemanticWordItem::Word(test_word(4, 100.0)));
+    assert_eq!(get_knuth_plass_line_breaks(&words.items, 60.0, &font_metrics), vec![4, 5]);
+    assert_eq!(get_greedy_line_breaks(&words.items, 60.0, &font_metrics), vec![4, 5]);
+}
+
+#[test]
+fn test_justified_text() {
+    let words = test_words(&[30.0, 20.0, 20.0, 50.0]);
+    let font_metrics = test_font_metrics();
+    let line_y = |line: usize| (line + 1) as f32 * 20.0 * DEFAULT_LINE_HEIGHT_MULTIPLIER;
+
+    let pretty = LineBreaking::new(TextAlignmentHorz::Left, TextWrap::Pretty, WhiteSpace::Normal);
+    let (glyphs, _, line_breaks, _, _) = words_to_left_aligned_glyphs(&words, Some(60.0), &font_metrics, pretty);
+    let positions = glyphs.iter().map(|g| (g.point.x, g.point.y)).collect::<Vec<_>>();
+    assert_eq!(positions, vec![(0.0, line_y(0)), (0.0, line_y(1)), (30.0, line_y(1)), (0.0, line_y(2))]);
+    assert_eq!(line_breaks.iter().map(|(glyph, _)| *glyph).collect::<Vec<_>>(), vec![0, 2, 3]);
+
+    // the space on the second line is stretched so that "cc" ends at the right edge,
+    // the first line (one word) and the last line are not justified
+    let justified = LineBreaking::new(TextAlignmentHorz::Justify, TextWrap::Wrap, WhiteSpace::Normal);
+    let (glyphs, _, _, _, _) = words_to_left_aligned_glyphs(&words, Some(60.0), &font_metrics, justified);
+    let positions = glyphs.iter().map(|g| (g.point.x, g.point.y)).collect::<Vec<_>>();
+    assert_eq!(positions, vec![(0.0, line_y(0)), (0.0, line_y(1)), (40.0, line_y(1)), (0.0, line_y(2))]);
+}
+
+
+#[test]
+fn test_soft_hyphen_and_nowrap() {
+    let font_metrics = test_font_metrics();
+    let line_y = |line: usize| (line + 1) as f32 * 20.0 * DEFAULT_LINE_HEIGHT_MULTIPLIER;
+
+    // "hy\u{AD}phen": the hyphen is only shown if the line is broken after "hy"
+    let mut words = test_words(&[20.0, 30.0]);
+    if let SemanticWordItem::Word(ref mut hy) = words.items[0] {
+        hy.spaces_after = 0;
+        hy.hyphen = Some(GlyphInstance { index: 9, point: TypedPoint2D::new(20.0, 0.0) });
+        hy.hyphen_width = 5.0;
+    }
+
+    let (glyphs, _, _, _, _) = words_to_left_aligned_glyphs(&words, Some(100.0), &font_metrics, LineBreaking::default());
+    assert_eq!(glyphs.iter().map(|g| (g.index, g.point.x)).collect::<Vec<_>>(), vec![(0, 0.0), (1, 20.0)]);
+
+    let (glyphs, _, _, _, _) = words_to_left_aligned_glyphs(&words, Some(40.0), &font_metrics, LineBreaking::default());
+    let positions = glyphs.iter().map(|g| (g.index, g.point.x, g.point.y)).collect::<Vec<_>>();
+    assert_eq!(positions, vec![(0, 0.0, line_y(0)), (9, 20.0, line_y(0)), (1, 0.0, line_y(1))]);
+
+    // white-space: nowrap never breaks the line
+    let no_wrap = LineBreaking::new(TextAlignmentHorz::Left, TextWrap::Wrap, WhiteSpace::NoWrap);
+    let (glyphs, _, _, _, _) = words_to_left_aligned_glyphs(&words, Some(40.0), &font_metrics, no_wrap);
+    assert_eq!(glyphs.iter().map(|g| (g.index, g.point.x)).collect::<Vec<_>>(), vec![(0, 0.0), (1, 20.0)]);
+}
+
+#[test]
+fn test_break_long_words() {
+    // A 100px word with 4 glyphs (25px each), a word boundary before the third glyph
+    let mut word = test_word(0, 100.0);
+    word.glyphs = (0..4).map(|i| GlyphInstance { index: i, point: TypedPoint2D::new(i as f32 * 25.0, 0.0) }).collect();
+    word.glyph_infos = (0..4).map(|i| GlyphInfo { char_index: i, char_count: 1, advance: 25.0, font_index: 0, is_rtl: false }).collect();
+    word.word_boundaries = vec![2];
+    word.grapheme_boundaries = vec![1, 2, 3];
+
+    let words = Words { items: vec![SemanticWordItem::Word(word)], longest_word_width: 100.0 };
+
+    // Prefers word boundaries ...
+    let broken = break_long_words(&words, 60.0);
+    let pieces = broken.items.iter().map(|w| match w {
+        SemanticWordItem::Word(w) => (w.glyphs.iter().map(|g| g.point.x).collect::<Vec<_>>(), w.total_width, w.spaces_after),
+        _ => panic!("expected a word"),
+    }).collect::<Vec<_>>();
+    assert_eq!(pieces, vec![(vec![0.0, 25.0], 50.0, 0), (vec![0.0, 25.0], 50.0, 1)]);
+    assert_eq!(broken.longest_word_width, 50.0);
+
+    // ... over grapheme boundaries, at least one grapheme is on every line
+    let broken = break_long_words(&words, 10.0);
+    assert_eq!(broken.items.len(), 4);
+    assert_eq!(broken.longest_word_width, 25.0);
+}
+
+#[test]
+fn test_truncate_words() {
+    let words = test_words(&[30.0, 30.0, 30.0, 30.0]);
+    let font_metrics = test_font_metrics();
+    let widths = |words: &Words| words.items.iter().map(|w| match w {
+        SemanticWordItem::Word(w) => (w.total_width, w.spaces_after),
+        _ => panic!("expected a word"),
+    }).collect::<Vec<_>>();
+
+    // `line-clamp: 1`: the words break into two lines, the first line ends in the marker
+    let clamp = TextTruncation { marker: test_word(9, 10.0), truncates_long_lines: false, max_lines: Some(1), wraps: true };
+    let truncated = truncate_words(&words, Some(75.0), &clamp, &font_metrics).unwrap();
+    assert_eq!(widths(&truncated), vec![(30.0, 0), (10.0, 1)]);
+    assert!(truncate_words(&words, Some(200.0), &clamp, &font_metrics).is_none());
+
+    // `white-space: nowrap; text-overflow: ellipsis`: the single line is cut off
+    let ellipsis = TextTruncation { marker: test_word(9, 10.0), truncates_long_lines: true, max_lines: None, wraps: false };
+    let truncated = truncate_words(&words, Some(115.0), &ellipsis, &font_metrics).unwrap();
+    assert_eq!(widths(&truncated), vec![(30.0, 1), (30.0, 0), (10.0, 1)]);
+    assert!(truncate_words(&words, None, &ellipsis, &font_metrics).is_none());
+}
+
+#[test]
+fn test_bidi_reordering() {
+    assert_eq!(reorder_levels(&[0, 0, 0]), vec![0, 1, 2]);
+    assert_eq!(reorder_levels(&[0, 1, 1, 0]), vec![0, 2, 1, 3]);
+    // right-to-left paragraph with an embedded left-to-right run
+    assert_eq!(reorder_levels(&[1, 2, 2, 1]), vec![3, 1, 2, 0]);
+
+    // "abc DEF GHI" (uppercase = right-to-left): the right-to-left words are reordered,
+    // the space after a right-to-left word is on its left side
+    let font_metrics = test_font_metrics();
+    let mut words = test_words(&[30.0, 30.0, 30.0]);
+    for item in words.items.iter_mut().skip(1) {
+        if let SemanticWordItem::Word(ref mut w) = item {
+            w.bidi_level = 1;
+        }
+    }
+
+    let (glyphs, glyph_infos, _, min_width, _) = words_to_left_aligned_glyphs(&words, None, &font_metrics, LineBreaking::default());
+    assert_eq!(glyphs.iter().map(|g| (g.index, g.point.x)).collect::<Vec<_>>(), vec![(0, 0.0), (2, 40.0), (1, 80.0)]);
+    assert_eq!(glyph_infos.iter().map(|info| info.char_index).collect::<Vec<_>>(), vec![0, 2, 1]);
+    assert_eq!(min_width, 110.0);
+
+    // lines are not reordered, only the words within a line
+    let (glyphs, _, _, _, _) = words_to_left_aligned_glyphs(&words, Some(75.0), &font_metrics, LineBreaking::default());
+    assert_eq!(glyphs.iter().map(|g| g.index).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert_eq!(glyphs.iter().map(|g| g.point.x).collect::<Vec<_>>(), vec![0.0, 40.0, 0.0]);
+}
+
+#[test]
+fn test_fallback_font_indices() {
+    // the second word is rendered with a fallback font, the hyphen always with the first font
+    let font_metrics = test_font_metrics();
+    let mut words = test_words(&[20.0, 30.0]);
+    if let SemanticWordItem::Word(ref mut w) = words.items[1] {
+        w.font_index = 1;
+        w.glyph_infos[0].font_index = 1;
+        w.spaces_after = 0;
+        w.hyphen = Some(GlyphInstance { index: 9, point: TypedPoint2D::new(30.0, 0.0) });
+        w.hyphen_width = 5.0;
+    }
+    words.items.push(SemanticWordItem::Word(test_word(2, 40.0)));
+
+    let (glyphs, glyph_infos, _, _, _) = words_to_left_aligned_glyphs(&words, Some(70.0), &font_metrics, LineBreaking::default());
+    assert_eq!(glyphs.iter().map(|g| g.index).collect::<Vec<_>>(), vec![0, 1, 9, 2]);
+    assert_eq!(glyph_infos.iter().map(|info| info.font_index).collect::<Vec<_>>(), vec![0, 1, 0, 0]);
+}
+
+#[test]
+fn test_caret_and_hit_testing() {
+    // "ﬁ x": a ligature for the first two characters, then a space and "x"
+    let font_metrics = test_font_metrics();
+    let mut words = test_words(&[20.0, 10.0]);
+    if let SemanticWordItem::Word(ref mut w) = words.items[0] {
+        w.glyph_infos[0].char_count = 2;
+    }
+    if let SemanticWordItem::Word(ref mut w) = words.items[1] {
+        w.glyph_infos[0].char_index = 3;
+    }
+
+    let (layouted_glyphs, glyph_infos, line_breaks, min_width, min_height) =
+        words_to_left_aligned_glyphs(&words, None, &font_metrics, LineBreaking::default());
+    let layout = LayoutTextResult {
+        words, layouted_glyphs, glyph_infos, line_breaks, min_width, min_height, font_metrics,
+    };
+
+    let caret_x = |char_index| get_caret_rect(&layout, char_index).origin.x;
+    assert_eq!((0..5).map(caret_x).collect::<Vec<_>>(), vec![0.0, 10.0, 20.0, 30.0, 40.0]);
+    assert_eq!(get_caret_rect(&layout, 1).size, TypedSize2D::new(CARET_WIDTH, 30.0));
+
+    let hit = |x, y| get_char_index_at_point(&layout, TypedPoint2D::new(x, y));
+    assert_eq!(hit(12.0, 5.0), 1);
+    assert_eq!(hit(16.0, 5.0), 2);
+    // below the last line: the nearest line is used
+    assert_eq!(hit(28.0, 40.0), 3);
+    assert_eq!(hit(100.0, 0.0), 4);
+
+    let selection = get_selection_rects(&layout, 1..4);
+    assert_eq!(selection, vec![TypedRect::new(TypedPoint2D::new(10.0, 0.0), TypedSize2D::new(30.0, 30.0))]);
+    assert_eq!(get_selection_rects(&layout, 2..3), Vec::new());
+}
+
+#[test]
+fn test_rich_text_baseline_and_wrapping() {
+    let small = RichTextSpanMetrics { space_width: 10.0, tab_width: 40.0, ascent: 8.0, descent: 2.0, line_height: 15.0 };
+    let large = RichTextSpanMetrics { space_width: 20.0, tab_width: 80.0, ascent: 16.0, descent: 4.0, line_height: 30.0 };
+
+    let mut large_word = test_word(1, 50.0);
+    large_word.font_index = 1;
+    large_word.glyph_infos[0].font_index = 1;
+
+    let items = vec![
+        SemanticWordItem::Word(test_word(0, 30.0)),
+        SemanticWordItem::Word(large_word),
+        SemanticWordItem::Word(test_word(2, 30.0)),
+    ];
+    let fonts = vec![(0, FontId::BuiltinFont("small".into())), (1, FontId::BuiltinFont("large".into()))];
+
+    // The line is broken between the spans, the first line has the baseline and height of the large span
+    let layout = rich_text_words_to_lines(&items, &[0, 1, 0], &[small, large], &[], fonts, Some(100.0));
+    let positions = layout.glyphs.iter().map(|g| (g.point.x, g.point.y)).collect::<Vec<_>>();
+    assert_eq!(positions, vec![(0.0, 21.0), (40.0, 21.0), (0.0, 40.5)]);
+    assert_eq!(layout.lines.iter().map(|l| (l.width, l.height)).collect::<Vec<_>>(), vec![(90.0, 30.0), (30.0, 15.0)]);
+    assert_eq!(layout.height, 45.0);
+
+    let runs = layout.get_span_runs();
+    assert_eq!(runs.iter().map(|r| r.span_index).collect::<Vec<_>>(), vec![0, 1, 0]);
+    assert_eq!(runs[1].bounds, TypedRect::new(TypedPoint2D::new(40.0, 0.0), TypedSize2D::new(50.0, 30.0)));
+}
+
+#[test]
+fn test_letter_spacing_and_measuring() {
+    // A base glyph with a combining mark, followed by a second grapheme
+    let mut word = test_word(0, 20.0);
+    word.glyphs = vec![0.0, 10.0, 10.0].into_iter().enumerate().map(|(i, x)| GlyphInstance { index: i as u32, point: TypedPoint2D::new(x, 0.0) }).collect();
+    word.glyph_infos = vec![10.0, 0.0, 10.0].into_iter().enumerate().map(|(i, advance)| GlyphInfo { char_index: i, char_count: 1, advance: advance, font_index: 0, is_rtl: false }).collect();
+    word.grapheme_boundaries = vec![2];
+
+    // The mark stays on its base glyph, the space is added after each grapheme
+    let spaced = with_letter_spacing(&Words { items: vec![SemanticWordItem::Word(word)], longest_word_width: 20.0 }, 2.0);
+    match &spaced.items[0] {
+        SemanticWordItem::Word(w) => {
+            assert_eq!(w.glyphs.iter().map(|g| g.point.x).collect::<Vec<_>>(), vec![0.0, 10.0, 12.0]);
+            assert_eq!(w.total_width, 24.0);
+        },
+        _ => panic!("expected a word"),
+    }
+    assert_eq!(spaced.longest_word_width, 24.0);
+
+    // Wrapped at 75px, the text "30 30 | 30" has two lines, the first one is 70px wide
+    let size = get_intrinsic_text_size(&test_words(&[30.0, 30.0, 30.0]), &test_font_metrics(), Some(75.0), LineBreaking::default());
+    assert_eq!((size.content_width, size.line_count), (70.0, 2));
+    assert_eq!(size.content_height, 2.0 * 20.0 * DEFAULT_LINE_HEIGHT_MULTIPLIER);
+    assert_eq!(size.max_content_width, 110.0);
+}
+
+#[test]
+fn test_tab_stops() {
+    // Tabs advance to the next multiple of the tab width, even at a tab stop
+    assert_eq!(get_next_tab_stop(0.0, 40.0, &[]), 40.0);
+    assert_eq!(get_next_tab_stop(15.0, 40.0, &[]), 40.0);
+    assert_eq!(get_next_tab_stop(40.0, 40.0, &[]), 80.0);
+    assert_eq!(get_next_tab_stop(15.0, 0.0, &[]), 15.0);
+
+    // Explicit tab stops come first, then the tab width is counted from the last one
+    assert_eq!(get_next_tab_stop(15.0, 40.0, &[100.0, 250.0]), 100.0);
+    assert_eq!(get_next_tab_stop(100.0, 40.0, &[100.0, 250.0]), 250.0);
+    assert_eq!(get_next_tab_stop(260.0, 40.0, &[100.0, 250.0]), 290.0);
+
+    // The columns of "30 <tab> 30" and "40 <tab> 30" line up, "70 <tab> 30" continues at the tab width
+    let mut words = test_words(&[30.0]);
+    words.items.extend(vec![SemanticWordItem::Tab, SemanticWordItem::Word(test_word(1, 30.0)), SemanticWordItem::Return]);
+    words.items.extend(test_words(&[40.0]).items);
+    words.items.extend(vec![SemanticWordItem::Tab, SemanticWordItem::Word(test_word(3, 30.0)), SemanticWordItem::Return]);
+    words.items.extend(test_words(&[70.0]).items);
+    words.items.extend(vec![SemanticWordItem::Tab, SemanticWordItem::Word(test_word(5, 30.0))]);
+
+    let mut font_metrics = test_font_metrics();
+    font_metrics.tab_stops = vec![60.0];
+    let (glyphs, _, _, _, _) = words_to_left_aligned_glyphs(&words, None, &font_metrics, LineBreaking::default());
+    assert_eq!(glyphs.iter().map(|g| g.point.x).collect::<Vec<_>>(), vec![0.0, 60.0, 0.0, 60.0, 0.0, 100.0]);
 }
\ No newline at end of file
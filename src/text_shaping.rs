@@ -0,0 +1,831 @@
+//! Minimal OpenType shaping of a single run of text.
+//!
+//! Reads the `GSUB` and `GPOS` tables directly from the raw font bytes and applies:
+//!
+//! - `GSUB` single substitutions (lookup type 1) and ligatures (lookup type 4)
+//! - `GPOS` pair kerning (lookup type 2) and mark-to-base positioning (lookup type 4)
+//!
+//! The features are looked up in the script of the first character of the run that
+//! belongs to a script (see `get_script_tag`), falling back to the `DFLT` / `latn` script.
+//! If the font has no `GPOS` kerning, the legacy `kern` table is used instead (via rusttype).
+//! Malformed or truncated tables are skipped silently, the text is then simply layouted
+//! with the advances from the `hmtx` table.
+//!
+//! # Limitations
+//!
+//! This is not a complete shaping engine, it is only good enough for scripts that don't
+//! need any glyph reordering or joining (Latin, Greek, Cyrillic, CJK, Hebrew with marks):
+//!
+//! - Contextual and chaining contextual substitutions (`GSUB` lookup types 5 and 6) are
+//!   ignored, as well as multiple, alternate and reverse substitutions (types 2, 3 and 8)
+//! - Cursive attachment, mark-to-ligature and mark-to-mark positioning (`GPOS` lookup
+//!   types 3, 5 and 6) and single / contextual positioning (types 1, 7 and 8) are ignored
+//! - There is no joining analysis, so the positional forms of Arabic, Syriac, etc.
+//!   (`init`, `medi`, `fina`, `isol`) are never applied and these scripts are rendered
+//!   with their isolated forms. Indic scripts are not reordered.
+//! - A run is shaped with a single script, even if it contains characters of multiple scripts
+
+use std::cell::RefCell;
+use rusttype::{Font, Scale, GlyphId};
+use css_parser::{FontKerning, FontFeatureSettings};
+
+/// Features that are enabled by default, unless turned off via `font-feature-settings`
+const DEFAULT_FEATURES: [&'static [u8; 4]; 5] = [b"liga", b"clig", b"rlig", b"kern", b"mark"];
+
+/// `lookupFlag` bit: skip over glyphs of the `GDEF` glyph class "mark"
+const IGNORE_MARKS: u16 = 0x0008;
+/// `GDEF` glyph class of combining marks
+const GLYPH_CLASS_MARK: u16 = 3;
+
+const GSUB_SINGLE: u16 = 1;
+const GSUB_LIGATURE: u16 = 4;
+const GSUB_EXTENSION: u16 = 7;
+const GPOS_PAIR: u16 = 2;
+const GPOS_MARK_TO_BASE: u16 = 4;
+const GPOS_EXTENSION: u16 = 9;
+
+/// Which OpenType features should be applied when shaping a text,
+/// derived from the `font-kerning` and `font-feature-settings` properties
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ShapingOptions {
+    /// Feature tag + value, a value of 0 disables the feature
+    features: Vec<([u8; 4], u32)>,
+}
+
+impl Default for ShapingOptions {
+    fn default() -> Self {
+        Self {
+            features: DEFAULT_FEATURES.iter().map(|tag| (**tag, 1)).collect(),
+        }
+    }
+}
+
+impl ShapingOptions {
+
+    pub(crate) fn new(font_kerning: Option<FontKerning>, font_feature_settings: Option<&FontFeatureSettings>) -> Self {
+        let mut options = Self::default();
+
+        if font_kerning == Some(FontKerning::None) {
+            options.set(*b"kern", 0);
+        }
+
+        // `font-feature-settings` overrides `font-kerning`
+        if let Some(settings) = font_feature_settings {
+            for feature in &settings.0 {
+                options.set(feature.tag, feature.value);
+            }
+        }
+
+        options
+    }
+
+    fn set(&mut self, tag: [u8; 4], value: u32) {
+        match self.features.iter_mut().find(|f| f.0 == tag) {
+            Some(f) => { f.1 = value; return; },
+            None => { },
+        }
+        self.features.push((tag, value));
+    }
+
+    pub(crate) fn is_enabled(&self, tag: &[u8; 4]) -> bool {
+        self.features.iter().any(|f| f.0 == *tag && f.1 != 0)
+    }
+}
+
+/// A glyph after shaping, all values are in (unscaled) font units
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct ShapedGlyph {
+    pub(crate) glyph_id: u16,
+    /// Index of the first character of the run that this glyph was created from
+    /// (ligatures are created from multiple characters)
+    pub(crate) cluster: usize,
+    /// How far the pen moves after this glyph
+    pub(crate) x_advance: f32,
+    /// Offset of the glyph from the pen position (positive = to the right)
+    pub(crate) x_offset: f32,
+    /// Offset of the glyph from the baseline (positive = upwards)
+    pub(crate) y_offset: f32,
+}
+
+/// The lookups of the enabled features for one script of a font
+#[derive(Debug, Clone)]
+struct ScriptLookups {
+    gsub_lookups: Vec<u16>,
+    gpos_lookups: Vec<u16>,
+    /// Kern with the legacy `kern` table, because the font has no `GPOS` kerning
+    legacy_kerning: bool,
+}
+
+impl ScriptLookups {
+
+    fn new(gsub: Option<&[u8]>, gpos: Option<&[u8]>, options: &ShapingOptions, script: Option<&[u8; 4]>) -> Self {
+        let is_enabled = |tag: &[u8; 4]| options.is_enabled(tag);
+        let gsub_lookups = gsub.and_then(|t| get_feature_lookups(t, script, &is_enabled)).unwrap_or_default();
+        let gpos_lookups = gpos.and_then(|t| get_feature_lookups(t, script, &is_enabled)).unwrap_or_default();
+
+        let has_gpos_kerning = gpos
+            .and_then(|t| get_feature_lookups(t, script, &|tag: &[u8; 4]| tag == b"kern"))
+            .map(|lookups| !lookups.is_empty())
+            .unwrap_or(false);
+
+        Self {
+            gsub_lookups: gsub_lookups,
+            gpos_lookups: gpos_lookups,
+            legacy_kerning: options.is_enabled(b"kern") && !has_gpos_kerning,
+        }
+    }
+}
+
+/// Shapes runs of text for one font. The lookups for the enabled features are resolved
+/// upfront for the default script and lazily for every other script that is shaped.
+#[derive(Debug, Clone)]
+pub(crate) struct Shaper<'a> {
+    gsub: Option<&'a [u8]>,
+    gpos: Option<&'a [u8]>,
+    /// `GDEF` glyph class definition, necessary to skip marks during kerning
+    glyph_classes: Option<&'a [u8]>,
+    options: ShapingOptions,
+    /// Lookups of the `DFLT` / `latn` script, used for runs without any script-specific character
+    default_lookups: ScriptLookups,
+    /// Lookups of the scripts that were already shaped, by OpenType script tag
+    script_lookups: RefCell<Vec<([u8; 4], ScriptLookups)>>,
+}
+
+impl<'a> Shaper<'a> {
+
+    pub(crate) fn new(font_bytes: &'a [u8], options: &ShapingOptions) -> Self {
+        let gsub = find_table(font_bytes, b"GSUB");
+        let gpos = find_table(font_bytes, b"GPOS");
+        let glyph_classes = find_table(font_bytes, b"GDEF")
+            .and_then(|gdef| match read_u16(gdef, 4) {
+                Some(0) | None => None,
+                Some(offset) => get_subtable(gdef, offset as usize),
+            });
+
+        Self {
+            gsub: gsub,
+            gpos: gpos,
+            glyph_classes: glyph_classes,
+            options: options.clone(),
+            default_lookups: ScriptLookups::new(gsub, gpos, options, None),
+            script_lookups: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Shaper for when the raw font bytes aren't available - only applies the `kern` table
+    pub(crate) fn kerning_only() -> Self {
+        let options = ShapingOptions::default();
+        Self {
+            gsub: None,
+            gpos: None,
+            glyph_classes: None,
+            default_lookups: ScriptLookups::new(None, None, &options, None),
+            options: options,
+            script_lookups: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the lookups for the given script, resolving them on first use
+    fn get_script_lookups(&self, script: &[u8; 4]) -> ScriptLookups {
+        if let Some(cached) = self.script_lookups.borrow().iter().find(|s| s.0 == *script) {
+            return cached.1.clone();
+        }
+        let lookups = ScriptLookups::new(self.gsub, self.gpos, &self.options, Some(script));
+        self.script_lookups.borrow_mut().push((*script, lookups.clone()));
+        lookups
+    }
+
+    /// Maps the characters to glyphs and applies the substitutions + positioning
+    pub(crate) fn shape<'b>(&self, font: &Font<'b>, text: &[char]) -> Vec<ShapedGlyph> {
+
+        let script_lookups;
+        let lookups = match get_script_tag(text) {
+            Some(script) => { script_lookups = self.get_script_lookups(script); &script_lookups },
+            None => &self.default_lookups,
+        };
+
+        let mut glyphs = text.iter().map(|c| font.glyph(*c).id().0 as u16).collect::<Vec<u16>>();
+        let mut clusters = (0..text.len()).collect::<Vec<usize>>();
+
+        if let Some(gsub) = self.gsub {
+            for lookup_index in &lookups.gsub_lookups {
+                if let Some(lookup) = get_lookup(gsub, *lookup_index, GSUB_EXTENSION) {
+                    apply_substitution(&lookup, &mut glyphs, &mut clusters);
+                }
+            }
+        }
+
+        // Warning: rusttype scales the font by its height (ascent - descent), not by the em size,
+        // so in order to get the metrics in font units, we have to scale it by the height itself.
+        let v_metrics = font.v_metrics_unscaled();
+        let font_units = Scale::uniform(v_metrics.ascent - v_metrics.descent);
+
+        let mut shaped = glyphs.iter().zip(clusters.iter()).map(|(glyph_id, cluster)| ShapedGlyph {
+            glyph_id: *glyph_id,
+            cluster: *cluster,
+            x_advance: font.glyph(GlyphId(*glyph_id as u32)).scaled(font_units).h_metrics().advance_width,
+            x_offset: 0.0,
+            y_offset: 0.0,
+        }).collect::<Vec<ShapedGlyph>>();
+
+        if let Some(gpos) = self.gpos {
+            for lookup_index in &lookups.gpos_lookups {
+                if let Some(lookup) = get_lookup(gpos, *lookup_index, GPOS_EXTENSION) {
+                    apply_positioning(&lookup, &mut shaped, self.glyph_classes);
+                }
+            }
+        }
+
+        if lookups.legacy_kerning {
+            for i in 1..shaped.len() {
+                let (first, second) = (shaped[i - 1].glyph_id as u32, shaped[i].glyph_id as u32);
+                shaped[i - 1].x_advance += font.pair_kerning(font_units, GlyphId(first), GlyphId(second));
+            }
+        }
+
+        shaped
+    }
+}
+
+/// A lookup with its extension subtables already resolved
+struct Lookup<'a> {
+    lookup_type: u16,
+    flags: u16,
+    subtables: Vec<&'a [u8]>,
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(((bytes[0] as u16) << 8) | bytes[1] as u16)
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).and_then(|v| Some(v as i16))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let high = read_u16(data, offset)? as u32;
+    let low = read_u16(data, offset + 2)? as u32;
+    Some((high << 16) | low)
+}
+
+fn get_subtable(data: &[u8], offset: usize) -> Option<&[u8]> {
+    data.get(offset..)
+}
+
+/// Reads the 16-bit offset at `offset` and returns the subtable it points to
+fn get_offset16_subtable(data: &[u8], offset: usize) -> Option<&[u8]> {
+    get_subtable(data, read_u16(data, offset)? as usize)
+}
+
+/// Returns the table with the given tag from the table directory. For font
+/// collections, the first font is used (same as in `font::rusttype_load_font`).
+fn find_table<'a>(font: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    find_table_at(font, 0, tag)
+}
+
+/// Same as `find_table`, but uses the font at `index` of a font collection
+pub(crate) fn find_table_at<'a>(font: &'a [u8], index: usize, tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let directory = if font.get(0..4)? == &b"ttcf"[..] { read_u32(font, 12 + index * 4)? as usize } else { 0 };
+    let num_tables = read_u16(font, directory + 4)? as usize;
+
+    for i in 0..num_tables {
+        let record = directory + 12 + i * 16;
+        if font.get(record..record + 4)? == &tag[..] {
+            let offset = read_u32(font, record + 8)? as usize;
+            let length = read_u32(font, record + 12)? as usize;
+            return font.get(offset..offset + length);
+        }
+    }
+
+    None
+}
+
+/// Returns the index of the glyph in the coverage table, or `None` if the glyph isn't covered
+fn get_coverage_index(coverage: &[u8], glyph: u16) -> Option<u16> {
+    match read_u16(coverage, 0)? {
+        1 => {
+            // sorted array of glyph IDs
+            let count = read_u16(coverage, 2)? as usize;
+            let (mut low, mut high) = (0, count);
+            while low < high {
+                let mid = (low + high) / 2;
+                let current = read_u16(coverage, 4 + mid * 2)?;
+                if current == glyph {
+                    return Some(mid as u16);
+                } else if current < glyph {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            None
+        },
+        2 => {
+            // sorted ranges of glyph IDs: start, end, coverage index of start
+            let count = read_u16(coverage, 2)? as usize;
+            let (mut low, mut high) = (0, count);
+            while low < high {
+                let mid = (low + high) / 2;
+                let record = 4 + mid * 6;
+                let start = read_u16(coverage, record)?;
+                let end = read_u16(coverage, record + 2)?;
+                if glyph < start {
+                    high = mid;
+                } else if glyph > end {
+                    low = mid + 1;
+                } else {
+                    return Some(read_u16(coverage, record + 4)? + (glyph - start));
+                }
+            }
+            None
+        },
+        _ => None,
+    }
+}
+
+/// Returns the class of a glyph in a class definition table (0 if the glyph isn't assigned)
+fn get_glyph_class(class_def: &[u8], glyph: u16) -> u16 {
+    fn get_glyph_class_inner(class_def: &[u8], glyph: u16) -> Option<u16> {
+        match read_u16(class_def, 0)? {
+            1 => {
+                let start = read_u16(class_def, 2)?;
+                let count = read_u16(class_def, 4)?;
+                if glyph < start || glyph - start >= count {
+                    return None;
+                }
+                read_u16(class_def, 6 + (glyph - start) as usize * 2)
+            },
+            2 => {
+                let count = read_u16(class_def, 2)? as usize;
+                for i in 0..count {
+                    let record = 4 + i * 6;
+                    if glyph >= read_u16(class_def, record)? && glyph <= read_u16(class_def, record + 2)? {
+                        return read_u16(class_def, record + 4);
+                    }
+                }
+                None
+            },
+            _ => None,
+        }
+    }
+
+    get_glyph_class_inner(class_def, glyph).unwrap_or(0)
+}
+
+/// Returns the indices of the lookups of all wanted features, in lookup order.
+///
+/// Only the features of the default language of the given `script` are considered.
+/// If the font doesn't support that script, the `DFLT` or `latn` script is used
+/// (or the first script, if neither exist).
+fn get_feature_lookups(table: &[u8], script: Option<&[u8; 4]>, is_wanted: &Fn(&[u8; 4]) -> bool) -> Option<Vec<u16>> {
+    let feature_list = get_offset16_subtable(table, 6)?;
+    let feature_count = read_u16(feature_list, 0)?;
+    let feature_indices = get_script_feature_indices(table, script).unwrap_or_else(|| (0..feature_count).collect());
+
+    let mut lookups = Vec::new();
+
+    for feature_index in feature_indices {
+        let record = 2 + feature_index as usize * 6;
+        let tag = match feature_list.get(record..record + 4) {
+            Some(t) => [t[0], t[1], t[2], t[3]],
+            None => continue,
+        };
+        if !is_wanted(&tag) {
+            continue;
+        }
+        let feature = match get_offset16_subtable(feature_list, record + 4) {
+            Some(f) => f,
+            None => continue,
+        };
+        let lookup_count = read_u16(feature, 2).unwrap_or(0) as usize;
+        lookups.extend((0..lookup_count).filter_map(|i| read_u16(feature, 4 + i * 2)));
+    }
+
+    lookups.sort();
+    lookups.dedup();
+    Some(lookups)
+}
+
+fn get_script_feature_indices(table: &[u8], script: Option<&[u8; 4]>) -> Option<Vec<u16>> {
+    let script_list = get_offset16_subtable(table, 4)?;
+    let script_count = read_u16(script_list, 0)? as usize;
+
+    let find_script = |wanted: &[u8; 4]| (0..script_count).find(|i| {
+        script_list.get(2 + i * 6..6 + i * 6).map(|tag| tag == &wanted[..]).unwrap_or(false)
+    });
+
+    let script_index = script.and_then(find_script)
+        .or_else(|| find_script(b"DFLT"))
+        .or_else(|| find_script(b"latn"))
+        .or_else(|| if script_count > 0 { Some(0) } else { None })?;
+    let script = get_offset16_subtable(script_list, 2 + script_index * 6 + 4)?;
+
+    // Use the default language or the first language if there is no default one
+    let lang_sys = match read_u16(script, 0)? {
+        0 => if read_u16(script, 2)? > 0 { get_offset16_subtable(script, 4 + 4)? } else { return None; },
+        offset => get_subtable(script, offset as usize)?,
+    };
+
+    let mut indices = Vec::new();
+    let required_feature = read_u16(lang_sys, 2)?;
+    if required_feature != 0xFFFF {
+        indices.push(required_feature);
+    }
+    let count = read_u16(lang_sys, 4)? as usize;
+    indices.extend((0..count).filter_map(|i| read_u16(lang_sys, 6 + i * 2)));
+    Some(indices)
+}
+
+/// Returns the OpenType script tag of the first character of the text that belongs to a
+/// script, or `None` if the text only consists of characters that are used by all scripts
+/// (digits, punctuation, whitespace) or of a script that isn't handled here
+fn get_script_tag(text: &[char]) -> Option<&'static [u8; 4]> {
+    text.iter().find_map(|c| match *c as u32 {
+        0x41..=0x5A | 0x61..=0x7A | 0xC0..=0xD6 | 0xD8..=0xF6 | 0xF8..=0x24F | 0x1E00..=0x1EFF => Some(b"latn"),
+        0x370..=0x3FF | 0x1F00..=0x1FFF => Some(b"grek"),
+        0x400..=0x52F => Some(b"cyrl"),
+        0x530..=0x58F => Some(b"armn"),
+        0x591..=0x5FF | 0xFB1D..=0xFB4F => Some(b"hebr"),
+        0x600..=0x6FF | 0x750..=0x77F | 0x8A0..=0x8FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Some(b"arab"),
+        0xE00..=0xE7F => Some(b"thai"),
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Some(b"hang"),
+        0x3040..=0x30FF => Some(b"kana"),
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF => Some(b"hani"),
+        _ => None,
+    })
+}
+
+fn get_lookup(table: &[u8], lookup_index: u16, extension_type: u16) -> Option<Lookup> {
+    let lookup_list = get_offset16_subtable(table, 8)?;
+    let lookup = get_offset16_subtable(lookup_list, 2 + lookup_index as usize * 2)?;
+
+    let mut lookup_type = read_u16(lookup, 0)?;
+    let flags = read_u16(lookup, 2)?;
+    let subtable_count = read_u16(lookup, 4)? as usize;
+
+    let mut subtables = Vec::with_capacity(subtable_count);
+    for i in 0..subtable_count {
+        let subtable = get_offset16_subtable(lookup, 6 + i * 2)?;
+        if read_u16(lookup, 0)? == extension_type {
+            // extension subtables only contain a 32-bit offset to the real subtable
+            lookup_type = read_u16(subtable, 2)?;
+            subtables.push(get_subtable(subtable, read_u32(subtable, 4)? as usize)?);
+        } else {
+            subtables.push(subtable);
+        }
+    }
+
+    Some(Lookup {
+        lookup_type: lookup_type,
+        flags: flags,
+        subtables: subtables,
+    })
+}
+
+fn apply_substitution(lookup: &Lookup, glyphs: &mut Vec<u16>, clusters: &mut Vec<usize>) {
+    let mut i = 0;
+    while i < glyphs.len() {
+        for subtable in &lookup.subtables {
+            let applied = match lookup.lookup_type {
+                GSUB_SINGLE => apply_single_substitution(subtable, glyphs, i),
+                GSUB_LIGATURE => apply_ligature_substitution(subtable, glyphs, clusters, i),
+                _ => None,
+            };
+            if applied.is_some() {
+                break;
+            }
+        }
+        i += 1;
+    }
+}
+
+fn apply_single_substitution(subtable: &[u8], glyphs: &mut Vec<u16>, i: usize) -> Option<()> {
+    let coverage_index = get_coverage_index(get_offset16_subtable(subtable, 2)?, glyphs[i])?;
+    glyphs[i] = match read_u16(subtable, 0)? {
+        1 => (glyphs[i] as i32 + read_i16(subtable, 4)? as i32) as u16,
+        2 => read_u16(subtable, 6 + coverage_index as usize * 2)?,
+        _ => return None,
+    };
+    Some(())
+}
+
+fn apply_ligature_substitution(subtable: &[u8], glyphs: &mut Vec<u16>, clusters: &mut Vec<usize>, i: usize) -> Option<()> {
+    let coverage_index = get_coverage_index(get_offset16_subtable(subtable, 2)?, glyphs[i])?;
+    let ligature_set = get_offset16_subtable(subtable, 6 + coverage_index as usize * 2)?;
+    let ligature_count = read_u16(ligature_set, 0)? as usize;
+
+    // Ligatures are ordered by preference, the first matching one wins
+    for l in 0..ligature_count {
+        let ligature = match get_offset16_subtable(ligature_set, 2 + l * 2) {
+            Some(s) => s,
+            None => continue,
+        };
+        let component_count = read_u16(ligature, 2)? as usize;
+        if component_count == 0 || i + component_count > glyphs.len() {
+            continue;
+        }
+        let matches = (1..component_count).all(|c| read_u16(ligature, 4 + (c - 1) * 2) == Some(glyphs[i + c]));
+        if matches {
+            glyphs[i] = read_u16(ligature, 0)?;
+            glyphs.drain(i + 1..i + component_count);
+            clusters.drain(i + 1..i + component_count);
+            return Some(());
+        }
+    }
+
+    None
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+struct ValueRecord {
+    x_placement: f32,
+    y_placement: f32,
+    x_advance: f32,
+}
+
+fn get_value_record_size(value_format: u16) -> usize {
+    (value_format & 0xFF).count_ones() as usize * 2
+}
+
+fn read_value_record(data: &[u8], offset: usize, value_format: u16) -> ValueRecord {
+    let mut record = ValueRecord::default();
+    let mut position = offset;
+    // Only the first 4 fields are plain values, the rest are offsets to device tables
+    for bit in 0..8 {
+        if value_format & (1 << bit) == 0 {
+            continue;
+        }
+        let value = read_i16(data, position).unwrap_or(0) as f32;
+        match bit {
+            0 => record.x_placement = value,
+            1 => record.y_placement = value,
+            2 => record.x_advance = value,
+            _ => { },
+        }
+        position += 2;
+    }
+    record
+}
+
+fn apply_positioning(lookup: &Lookup, glyphs: &mut [ShapedGlyph], glyph_classes: Option<&[u8]>) {
+    match lookup.lookup_type {
+        GPOS_PAIR => apply_pair_positioning(lookup, glyphs, glyph_classes),
+        GPOS_MARK_TO_BASE => apply_mark_to_base_positioning(lookup, glyphs),
+        _ => { },
+    }
+}
+
+fn apply_pair_positioning(lookup: &Lookup, glyphs: &mut [ShapedGlyph], glyph_classes: Option<&[u8]>) {
+
+    let is_skipped = |glyph: u16| {
+        lookup.flags & IGNORE_MARKS != 0 &&
+        glyph_classes.map(|classes| get_glyph_class(classes, glyph) == GLYPH_CLASS_MARK).unwrap_or(false)
+    };
+
+    for first in 0..glyphs.len() {
+        if is_skipped(glyphs[first].glyph_id) {
+            continue;
+        }
+        let second = match (first + 1..glyphs.len()).find(|g| !is_skipped(glyphs[*g].glyph_id)) {
+            Some(s) => s,
+            None => break,
+        };
+        for subtable in &lookup.subtables {
+            if let Some((value_1, value_2)) = get_pair_adjustment(subtable, glyphs[first].glyph_id, glyphs[second].glyph_id) {
+                glyphs[first].x_advance += value_1.x_advance;
+                glyphs[first].x_offset += value_1.x_placement;
+                glyphs[first].y_offset += value_1.y_placement;
+                glyphs[second].x_advance += value_2.x_advance;
+                glyphs[second].x_offset += value_2.x_placement;
+                glyphs[second].y_offset += value_2.y_placement;
+                break;
+            }
+        }
+    }
+}
+
+fn get_pair_adjustment(subtable: &[u8], first: u16, second: u16) -> Option<(ValueRecord, ValueRecord)> {
+    let coverage_index = get_coverage_index(get_offset16_subtable(subtable, 2)?, first)?;
+    let value_format_1 = read_u16(subtable, 4)?;
+    let value_format_2 = read_u16(subtable, 6)?;
+    let size_1 = get_value_record_size(value_format_1);
+    let size_2 = get_value_record_size(value_format_2);
+
+    match read_u16(subtable, 0)? {
+        1 => {
+            // Individual glyph pairs
+            let pair_set = get_offset16_subtable(subtable, 10 + coverage_index as usize * 2)?;
+            let pair_count = read_u16(pair_set, 0)? as usize;
+            let record_size = 2 + size_1 + size_2;
+            let record = (0..pair_count).map(|p| 2 + p * record_size).find(|r| read_u16(pair_set, *r) == Some(second))?;
+            Some((read_value_record(pair_set, record + 2, value_format_1), read_value_record(pair_set, record + 2 + size_1, value_format_2)))
+        },
+        2 => {
+            // Pairs of glyph classes
+            let class_1 = get_glyph_class(get_offset16_subtable(subtable, 8)?, first) as usize;
+            let class_2 = get_glyph_class(get_offset16_subtable(subtable, 10)?, second) as usize;
+            let class_1_count = read_u16(subtable, 12)? as usize;
+            let class_2_count = read_u16(subtable, 14)? as usize;
+            if class_1 >= class_1_count || class_2 >= class_2_count {
+                return None;
+            }
+            let record = 16 + (class_1 * class_2_count + class_2) * (size_1 + size_2);
+            Some((read_value_record(subtable, record, value_format_1), read_value_record(subtable, record + size_1, value_format_2)))
+        },
+        _ => None,
+    }
+}
+
+fn read_anchor(anchor: &[u8]) -> Option<(f32, f32)> {
+    Some((read_i16(anchor, 2)? as f32, read_i16(anchor, 4)? as f32))
+}
+
+fn apply_mark_to_base_positioning(lookup: &Lookup, glyphs: &mut [ShapedGlyph]) {
+    for subtable in &lookup.subtables {
+        apply_mark_to_base_subtable(subtable, glyphs);
+    }
+}
+
+fn apply_mark_to_base_subtable(subtable: &[u8], glyphs: &mut [ShapedGlyph]) -> Option<()> {
+    let mark_coverage = get_offset16_subtable(subtable, 2)?;
+    let base_coverage = get_offset16_subtable(subtable, 4)?;
+    let mark_class_count = read_u16(subtable, 6)? as usize;
+    let mark_array = get_offset16_subtable(subtable, 8)?;
+    let base_array = get_offset16_subtable(subtable, 10)?;
+
+    for mark in 0..glyphs.len() {
+        let mark_index = match get_coverage_index(mark_coverage, glyphs[mark].glyph_id) {
+            Some(s) => s as usize,
+            None => continue,
+        };
+
+        // The base is the previous glyph that isn't a mark itself
+        let base = match (0..mark).rev().find(|g| get_coverage_index(mark_coverage, glyphs[*g].glyph_id).is_none()) {
+            Some(s) => s,
+            None => continue,
+        };
+        let base_index = match get_coverage_index(base_coverage, glyphs[base].glyph_id) {
+            Some(s) => s as usize,
+            None => continue,
+        };
+
+        let mark_record = 2 + mark_index * 4;
+        let mark_class = read_u16(mark_array, mark_record)? as usize;
+        if mark_class >= mark_class_count {
+            continue;
+        }
+        let mark_anchor = read_anchor(get_offset16_subtable(mark_array, mark_record + 2)?)?;
+        let base_anchor_offset = read_u16(base_array, 2 + (base_index * mark_class_count + mark_class) * 2)?;
+        if base_anchor_offset == 0 {
+            continue;
+        }
+        let base_anchor = read_anchor(get_subtable(base_array, base_anchor_offset as usize)?)?;
+
+        // Move the mark so that its anchor sits on the anchor of the base glyph
+        let pen_distance: f32 = glyphs[base..mark].iter().map(|g| g.x_advance).sum();
+        glyphs[mark].x_offset = glyphs[base].x_offset + base_anchor.0 - mark_anchor.0 - pen_distance;
+        glyphs[mark].y_offset = glyphs[base].y_offset + base_anchor.1 - mark_anchor.1;
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_u16(data: &mut Vec<u8>, value: u16) {
+        data.push((value >> 8) as u8);
+        data.push(value as u8);
+    }
+
+    #[test]
+    fn test_shaping_options() {
+        use css_parser::FontFeature;
+
+        let options = ShapingOptions::default();
+        assert!(options.is_enabled(b"liga"));
+        assert!(options.is_enabled(b"kern"));
+        assert!(!options.is_enabled(b"smcp"));
+
+        let options = ShapingOptions::new(Some(FontKerning::None), None);
+        assert!(!options.is_enabled(b"kern"));
+
+        // font-feature-settings overrides font-kerning
+        let settings = FontFeatureSettings(vec![
+            FontFeature { tag: *b"kern", value: 1 },
+            FontFeature { tag: *b"liga", value: 0 },
+            FontFeature { tag: *b"smcp", value: 1 },
+        ]);
+        let options = ShapingOptions::new(Some(FontKerning::None), Some(&settings));
+        assert!(options.is_enabled(b"kern"));
+        assert!(!options.is_enabled(b"liga"));
+        assert!(options.is_enabled(b"smcp"));
+    }
+
+    #[test]
+    fn test_coverage_and_class_def() {
+        // format 1: glyphs 3, 7, 9
+        let mut coverage = Vec::new();
+        for v in &[1, 3, 3, 7, 9] { push_u16(&mut coverage, *v); }
+        assert_eq!(get_coverage_index(&coverage, 7), Some(1));
+        assert_eq!(get_coverage_index(&coverage, 8), None);
+
+        // format 2: glyphs 10..=20 starting at coverage index 5
+        let mut coverage = Vec::new();
+        for v in &[2, 1, 10, 20, 5] { push_u16(&mut coverage, *v); }
+        assert_eq!(get_coverage_index(&coverage, 12), Some(7));
+        assert_eq!(get_coverage_index(&coverage, 21), None);
+
+        // format 2: glyphs 4..=6 are class 3
+        let mut class_def = Vec::new();
+        for v in &[2, 1, 4, 6, 3] { push_u16(&mut class_def, *v); }
+        assert_eq!(get_glyph_class(&class_def, 5), 3);
+        assert_eq!(get_glyph_class(&class_def, 7), 0);
+    }
+
+    #[test]
+    fn test_script_selection() {
+        let chars = |s: &str| s.chars().collect::<Vec<char>>();
+        assert_eq!(get_script_tag(&chars("12 (abc)")), Some(b"latn"));
+        assert_eq!(get_script_tag(&chars("1 \u{0633}\u{0644}\u{0627}\u{0645}")), Some(b"arab"));
+        assert_eq!(get_script_tag(&chars("\u{0416}")), Some(b"cyrl"));
+        assert_eq!(get_script_tag(&chars("123 !?")), None);
+
+        // Script list with the scripts "DFLT" (feature 0) and "arab" (features 1 + 2)
+        let mut table = Vec::new();
+        for v in &[
+            1, 0, 10, 0, 0,                         // version, script / feature / lookup list offsets
+            2, 0x4446, 0x4C54, 14, 0x6172, 0x6162, 26, // script list: "DFLT", "arab"
+            4, 0, 0, 0xFFFF, 1, 0,                  // "DFLT": default language, feature 0
+            4, 0, 0, 0xFFFF, 2, 1, 2,               // "arab": default language, features 1 + 2
+        ] { push_u16(&mut table, *v); }
+
+        assert_eq!(get_script_feature_indices(&table, None), Some(vec![0]));
+        assert_eq!(get_script_feature_indices(&table, Some(b"arab")), Some(vec![1, 2]));
+        // unsupported scripts fall back to "DFLT"
+        assert_eq!(get_script_feature_indices(&table, Some(b"cyrl")), Some(vec![0]));
+    }
+
+    #[test]
+    fn test_ligature_substitution() {
+        // Ligature substitution subtable: glyphs 1 + 2 + 3 => 50, 1 + 2 => 40
+        let mut subtable = Vec::new();
+        for v in &[
+            1, 8, 1, 14,                // format, coverage offset, ligature set count, ligature set offset
+            1, 1, 1,                    // coverage: format 1, 1 glyph, glyph 1
+            2, 6, 14,                   // ligature set: 2 ligatures, offsets
+            50, 3, 2, 3,                // ligature 1 + 2 + 3
+            40, 2, 2,                   // ligature 1 + 2
+        ] { push_u16(&mut subtable, *v); }
+
+        let mut glyphs = vec![1, 2, 3, 1, 2, 5];
+        let mut clusters = (0..glyphs.len()).collect::<Vec<usize>>();
+        let lookup = Lookup { lookup_type: GSUB_LIGATURE, flags: 0, subtables: vec![&subtable[..]] };
+        apply_substitution(&lookup, &mut glyphs, &mut clusters);
+
+        assert_eq!(glyphs, vec![50, 40, 5]);
+        assert_eq!(clusters, vec![0, 3, 5]);
+    }
+
+    #[test]
+    fn test_pair_and_mark_positioning() {
+        fn glyph(glyph_id: u16, x_advance: f32) -> ShapedGlyph {
+            ShapedGlyph { glyph_id: glyph_id, cluster: 0, x_advance: x_advance, x_offset: 0.0, y_offset: 0.0 }
+        }
+
+        // Pair adjustment format 1: "A" (1) followed by "V" (2) => x_advance -80
+        let mut pair_subtable = Vec::new();
+        for v in &[
+            1, 12, 4, 0, 1, 18,         // format, coverage offset, value formats, pair set count, pair set offset
+            1, 1, 1,                    // coverage: glyph 1
+            1, 2, (-80i16) as u16,      // pair set: 1 pair, second glyph, x_advance
+        ] { push_u16(&mut pair_subtable, *v); }
+
+        let mut glyphs = vec![glyph(1, 600.0), glyph(2, 600.0), glyph(1, 600.0)];
+        let lookup = Lookup { lookup_type: GPOS_PAIR, flags: 0, subtables: vec![&pair_subtable[..]] };
+        apply_positioning(&lookup, &mut glyphs, None);
+        assert_eq!(glyphs[0].x_advance, 520.0);
+        assert_eq!(glyphs[1].x_advance, 600.0);
+        assert_eq!(glyphs[2].x_advance, 600.0);
+
+        // Mark to base: mark 9 (anchor 50, 0) on base 1 (anchor 300, 700)
+        let mut mark_subtable = Vec::new();
+        for v in &[
+            1, 12, 18, 1, 24, 36,       // format, mark / base coverage, mark class count, mark / base array
+            1, 1, 9,                    // mark coverage: glyph 9
+            1, 1, 1,                    // base coverage: glyph 1
+            1, 0, 6,                    // mark array: 1 record, class 0, anchor offset
+            1, 50, 0,                   // mark anchor
+            1, 4,                       // base array: 1 record, anchor offset
+            1, 300, 700,                // base anchor
+        ] { push_u16(&mut mark_subtable, *v); }
+
+        let mut glyphs = vec![glyph(1, 600.0), glyph(9, 0.0)];
+        let lookup = Lookup { lookup_type: GPOS_MARK_TO_BASE, flags: 0, subtables: vec![&mark_subtable[..]] };
+        apply_positioning(&lookup, &mut glyphs, None);
+        assert_eq!(glyphs[1].x_offset, 300.0 - 50.0 - 600.0);
+        assert_eq!(glyphs[1].y_offset, 700.0);
+    }
+}
@@ -7,10 +7,17 @@ use glium::glutin::dpi::LogicalSize;
 use webrender::api::LayoutPixel;
 use euclid::{TypedRect, TypedPoint2D, TypedSize2D};
 use {
+    FastHashMap,
     id_tree::{NodeId, Arena},
     dom::NodeData,
     cache::{EditVariableCache, DomTreeCache, DomChangeSet},
     traits::Layout,
+    css_parser::{
+        RectLayout, RectStyle, FontSize, FontFamily, LineHeight, LetterSpacing, TabSize, TabStops,
+        FontKerning, FontFeatureSettings, TextAlignmentHorz, TextWrap, WhiteSpace, WordBreak,
+        OverflowWrap, TextOverflowStyle, LineClamp,
+    },
+    window::LayoutEngine,
 };
 
 /// A set of cassowary `Variable`s representing the
@@ -42,6 +49,126 @@ fn __codecov_test_constraints_file() {
 
 }
 
+/// The content of a rectangle that its intrinsic size depends on, but that isn't part of the
+/// hash of the DOM node (the DOM node only stores the `TextId` of a text or the `ImageId` of an image)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum LayoutContent {
+    None,
+    /// Hash of the string of a `NodeType::Text`
+    Text(u64),
+    /// Width and height of an image, `None` if the image isn't loaded
+    Image(Option<(f32, f32)>),
+}
+
+/// The properties of a rectangle that its layout constraints depend on. If these didn't
+/// change since the last frame (and the DOM node didn't change), the constraints don't have
+/// to be rebuilt - i.e. changing the background color of a rectangle doesn't trigger a re-layout.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RectLayoutInputs {
+    layout: RectLayout,
+    content: LayoutContent,
+    // Everything that the intrinsic size of text depends on (see `get_intrinsic_text_size`)
+    font_size: Option<FontSize>,
+    font_family: Option<FontFamily>,
+    line_height: Option<LineHeight>,
+    letter_spacing: Option<LetterSpacing>,
+    tab_size: Option<TabSize>,
+    tab_stops: Option<TabStops>,
+    font_kerning: Option<FontKerning>,
+    font_feature_settings: Option<FontFeatureSettings>,
+    text_align: Option<TextAlignmentHorz>,
+    text_wrap: Option<TextWrap>,
+    white_space: Option<WhiteSpace>,
+    word_break: Option<WordBreak>,
+    overflow_wrap: Option<OverflowWrap>,
+    text_overflow: Option<TextOverflowStyle>,
+    line_clamp: Option<LineClamp>,
+}
+
+impl RectLayoutInputs {
+
+    pub(crate) fn new(layout: &RectLayout, style: &RectStyle, content: LayoutContent) -> Self {
+        Self {
+            layout: layout.clone(),
+            content: content,
+            font_size: style.font_size,
+            font_family: style.font_family.clone(),
+            line_height: style.line_height,
+            letter_spacing: style.letter_spacing,
+            tab_size: style.tab_size,
+            tab_stops: style.tab_stops.clone(),
+            font_kerning: style.font_kerning,
+            font_feature_settings: style.font_feature_settings.clone(),
+            text_align: style.text_align,
+            text_wrap: style.text_wrap,
+            white_space: style.white_space,
+            word_break: style.word_break,
+            overflow_wrap: style.overflow_wrap,
+            text_overflow: style.text_overflow.clone(),
+            line_clamp: style.line_clamp,
+        }
+    }
+
+    /// Same as `RectLayoutInputs::new(layout, style, content) == *self`, but without cloning
+    /// the layout and style (this is called for every rectangle on every frame)
+    pub(crate) fn is_same(&self, layout: &RectLayout, style: &RectStyle, content: LayoutContent) -> bool {
+        self.layout == *layout &&
+        self.content == content &&
+        self.font_size == style.font_size &&
+        self.font_family == style.font_family &&
+        self.line_height == style.line_height &&
+        self.letter_spacing == style.letter_spacing &&
+        self.tab_size == style.tab_size &&
+        self.tab_stops == style.tab_stops &&
+        self.font_kerning == style.font_kerning &&
+        self.font_feature_settings == style.font_feature_settings &&
+        self.text_align == style.text_align &&
+        self.text_wrap == style.text_wrap &&
+        self.white_space == style.white_space &&
+        self.word_break == style.word_break &&
+        self.overflow_wrap == style.overflow_wrap &&
+        self.text_overflow == style.text_overflow &&
+        self.line_clamp == style.line_clamp
+    }
+}
+
+/// Bounds of all DOM nodes after the last layout, so that callbacks can query
+/// where a node was drawn (i.e. to position a popover next to the clicked node)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct SolvedLayout {
+    /// Bounds of each node, relative to the top left of the window, indexed by the `NodeId`
+    pub(crate) rects: Vec<TypedRect<f32, LayoutPixel>>,
+    /// CSS id (`Dom::with_id`) of the nodes that have one
+    pub(crate) ids: FastHashMap<String, NodeId>,
+}
+
+impl SolvedLayout {
+
+    /// Stores the bounds of all nodes of the DOM. If multiple nodes have the
+    /// same CSS id, the first one (in DOM order) is stored
+    pub(crate) fn new<T: Layout>(arena: &Arena<NodeData<T>>, ui_solver: &UiSolver) -> Self {
+        let rects = arena.linear_iter().map(|node_id| ui_solver.query_bounds_of_rect(node_id)).collect();
+        let mut ids = FastHashMap::default();
+        for node_id in arena.linear_iter() {
+            if let Some(id) = &arena[node_id].data.id {
+                ids.entry(id.clone()).or_insert(node_id);
+            }
+        }
+        Self {
+            rects: rects,
+            ids: ids,
+        }
+    }
+
+    pub(crate) fn get_bounds(&self, node_id: NodeId) -> Option<TypedRect<f32, LayoutPixel>> {
+        self.rects.get(node_id.index()).cloned()
+    }
+
+    pub(crate) fn get_bounds_by_id(&self, id: &str) -> Option<TypedRect<f32, LayoutPixel>> {
+        self.ids.get(id).and_then(|node_id| self.get_bounds(*node_id))
+    }
+}
+
 /// Stores the variables of the root width and height (but not the values themselves)
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub(crate) struct WindowSizeConstraints {
@@ -75,11 +202,17 @@ pub struct UiSolver {
     solved_values: BTreeMap<Variable, f64>,
     /// The cache of the previous frames DOM tree
     dom_tree_cache: DomTreeCache,
+    /// The layout-relevant properties of each rectangle in the previous frame
+    layout_inputs: BTreeMap<NodeId, RectLayoutInputs>,
+    /// Whether the rectangles are laid out by the cassowary solver or by the `flex_layout` module
+    layout_engine: LayoutEngine,
+    /// Bounds of each rectangle (indexed by `NodeId`) if the `layout_engine` is `LayoutEngine::Flexbox`
+    direct_layout: Vec<TypedRect<f32, LayoutPixel>>,
 }
 
 impl UiSolver {
 
-    pub(crate) fn new(window_size: &LogicalSize) -> Self {
+    pub(crate) fn new(window_size: &LogicalSize, layout_engine: LayoutEngine) -> Self {
 
         let mut solver = Solver::new();
         let window_constraints = WindowSizeConstraints::new();
@@ -96,16 +229,44 @@ impl UiSolver {
             window_constraints: window_constraints,
             edit_variable_cache: EditVariableCache::empty(),
             dom_tree_cache: DomTreeCache::empty(),
+            layout_inputs: BTreeMap::new(),
+            layout_engine: layout_engine,
+            direct_layout: Vec::new(),
         }
     }
 
+    /// Diffs the new DOM against the DOM of the last frame. Removes the constraints and variables of all
+    /// nodes that don't exist anymore and creates new variables for the added nodes. The constraints
+    /// of the added nodes have to be inserted by the caller.
     pub(crate) fn update_dom<T: Layout>(&mut self, root: &NodeId, arena: &Arena<NodeData<T>>) -> DomChangeSet {
         let changeset = self.dom_tree_cache.update(*root, arena);
+        for removed_node in &changeset.removed_nodes {
+            self.remove_constraints_for_rect(*removed_node);
+            self.layout_inputs.remove(removed_node);
+        }
         self.edit_variable_cache.initialize_new_rectangles(&changeset);
-        self.edit_variable_cache.remove_unused_variables();
+        self.edit_variable_cache.remove_unused_variables(&changeset);
         changeset
     }
 
+    /// Stores the layout inputs of the rectangle for the next frame. Returns `None` if the inputs
+    /// are the same as in the last frame, otherwise returns if the descendants of the rectangle
+    /// are affected, too: if its `position` or the width that is available to its content has changed.
+    pub(crate) fn update_layout_inputs(&mut self, rect_id: NodeId, layout: &RectLayout, style: &RectStyle, content: LayoutContent) -> Option<bool> {
+        let descendants_changed = match self.layout_inputs.get(&rect_id) {
+            Some(previous) if previous.is_same(layout, style, content) => return None,
+            Some(previous) => {
+                previous.layout.position != layout.position ||
+                previous.layout.width != layout.width ||
+                previous.layout.max_width != layout.max_width ||
+                previous.layout.padding != layout.padding
+            },
+            None => true,
+        };
+        self.layout_inputs.insert(rect_id, RectLayoutInputs::new(layout, style, content));
+        Some(descendants_changed)
+    }
+
     pub(crate) fn insert_css_constraints_for_rect(&mut self, constraints: &[Constraint]) {
         self.solver.add_constraints(constraints).unwrap();
     }
@@ -122,8 +283,21 @@ impl UiSolver {
         }
     }
 
+    pub(crate) fn get_layout_engine(&self) -> LayoutEngine {
+        self.layout_engine
+    }
+
+    /// Stores the result of the `flex_layout`, replaces the layout of the last frame
+    pub(crate) fn set_direct_layout(&mut self, rects: Vec<TypedRect<f32, LayoutPixel>>) {
+        self.direct_layout = rects;
+    }
+
     pub(crate) fn query_bounds_of_rect(&self, rect_id: NodeId) -> TypedRect<f32, LayoutPixel> {
 
+        if self.layout_engine == LayoutEngine::Flexbox {
+            return self.direct_layout.get(rect_id.index()).cloned().unwrap_or(TypedRect::zero());
+        }
+
         let display_rect = self.get_rect_constraints(rect_id).unwrap();
 
         let top = self.solved_values.get(&display_rect.top).and_then(|x| Some(*x)).unwrap_or(0.0);
@@ -135,14 +309,22 @@ impl UiSolver {
     }
 
     pub(crate) fn get_rect_constraints(&self, rect_id: NodeId) -> Option<RectConstraintVariables> {
-        let dom_hash = &self.dom_tree_cache.previous_layout.arena.get(&rect_id)?;
-        self.edit_variable_cache.map.get(&dom_hash.data).and_then(|rect| Some(rect.1))
+        self.edit_variable_cache.map.get(&rect_id).cloned()
     }
 
     pub(crate) fn push_added_constraints(&mut self, rect_id: NodeId, constraints: Vec<Constraint>) {
         self.added_constraints.entry(rect_id).or_insert_with(|| Vec::new()).extend(constraints);
     }
 
+    /// Removes the constraints of a single rectangle from the solver, so that they can be rebuilt
+    pub(crate) fn remove_constraints_for_rect(&mut self, rect_id: NodeId) {
+        if let Some(constraints) = self.added_constraints.remove(&rect_id) {
+            for constraint in &constraints {
+                self.solver.remove_constraint(constraint).unwrap();
+            }
+        }
+    }
+
     pub(crate) fn clear_all_constraints(&mut self) {
         for entry in self.added_constraints.values() {
             for constraint in entry {
@@ -155,4 +337,52 @@ impl UiSolver {
     pub(crate) fn get_window_constraints(&self) -> WindowSizeConstraints {
         self.window_constraints
     }
+}
+
+#[test]
+fn test_solved_layout_query() {
+    let mut layout = SolvedLayout::default();
+    let rect = TypedRect::new(TypedPoint2D::new(10.0, 20.0), TypedSize2D::new(30.0, 40.0));
+    layout.rects = vec![TypedRect::zero(), rect];
+    layout.ids.insert(String::from("popover"), NodeId::new(1));
+
+    assert_eq!(layout.get_bounds(NodeId::new(1)), Some(rect));
+    assert_eq!(layout.get_bounds(NodeId::new(2)), None);
+    assert_eq!(layout.get_bounds_by_id("popover"), Some(rect));
+    assert_eq!(layout.get_bounds_by_id("missing"), None);
+}
+
+#[test]
+fn test_update_layout_inputs() {
+    use webrender::api::ColorU;
+    use css_parser::{BackgroundColor, PixelValue, CssMetric, LayoutWidth};
+
+    let mut ui_solver = UiSolver::new(&LogicalSize::new(800.0, 600.0), LayoutEngine::default());
+    let mut layout = RectLayout::default();
+    let mut style = RectStyle::default();
+    let node_id = NodeId::new(0);
+
+    let content = LayoutContent::Text(1);
+
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, content), Some(true));
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, content), None);
+
+    // Colors don't change the layout
+    style.background_color = Some(BackgroundColor(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, content), None);
+
+    // The letter spacing changes the size of text
+    style.letter_spacing = Some(LetterSpacing(PixelValue::from_metric(CssMetric::Px, 2.0)));
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, content), Some(false));
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, content), None);
+
+    // A different text with the same `TextId` or a loaded image changes the intrinsic size
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, LayoutContent::Text(2)), Some(false));
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, LayoutContent::Image(None)), Some(false));
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, LayoutContent::Image(Some((20.0, 10.0)))), Some(false));
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, LayoutContent::Image(Some((20.0, 10.0)))), None);
+
+    // The width changes the width that is available to the text of the descendants
+    layout.width = Some(LayoutWidth(PixelValue::from_metric(CssMetric::Px, 100.0)));
+    assert_eq!(ui_solver.update_layout_inputs(node_id, &layout, &style, LayoutContent::None), Some(true));
 }
\ No newline at end of file
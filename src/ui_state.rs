@@ -3,7 +3,7 @@ use std::{
     collections::BTreeMap,
 };
 use {
-    window::{WindowInfo, WindowId},
+    window::{WindowInfo, WindowId, EventPhase},
     traits::Layout,
     dom::{Callback, Dom, On},
     app_state::AppState,
@@ -15,6 +15,7 @@ use {
 pub struct UiState<T: Layout> {
     pub dom: Dom<T>,
     pub tag_ids_to_callbacks: BTreeMap<TagId, BTreeMap<On, Callback<T>>>,
+    pub tag_ids_to_capture_callbacks: BTreeMap<TagId, BTreeMap<On, Callback<T>>>,
     pub tag_ids_to_default_callbacks: BTreeMap<TagId, BTreeMap<On, DefaultCallbackId>>,
     pub node_ids_to_tag_ids: BTreeMap<NodeId, TagId>,
     pub tag_ids_to_node_ids: BTreeMap<TagId, NodeId>,
@@ -26,12 +27,14 @@ impl<T: Layout> fmt::Debug for UiState<T> {
             "UiState {{ \
                 \tdom: {:?}, \
                 \ttag_ids_to_callbacks: {:?}, \
+                \ttag_ids_to_capture_callbacks: {:?}, \
                 \ttag_ids_to_default_callbacks: {:?}, \
                 \tnode_ids_to_tag_ids: {:?} \
                 \ttag_ids_to_node_ids: {:?} \
             }}",
         self.dom,
         self.tag_ids_to_callbacks,
+        self.tag_ids_to_capture_callbacks,
         self.tag_ids_to_default_callbacks,
         self.node_ids_to_tag_ids,
         self.tag_ids_to_node_ids)
@@ -63,6 +66,14 @@ impl<T: Layout> UiState<T> {
             }
         };
 
+        Self::from_dom(dom)
+    }
+
+    /// Wraps the DOM in a root `Div` and collects the callbacks of all nodes
+    pub(crate) fn from_dom(dom: Dom<T>) -> Self {
+
+        use dom::NodeType;
+
         // Tree should have a single root element
         let dom = {
             let mut parent_dom = Dom::with_capacity(NodeType::Div, dom.len());
@@ -71,12 +82,14 @@ impl<T: Layout> UiState<T> {
         };
 
         let mut tag_ids_to_callbacks = BTreeMap::new();
+        let mut tag_ids_to_capture_callbacks = BTreeMap::new();
         let mut tag_ids_to_default_callbacks = BTreeMap::new();
         let mut node_ids_to_tag_ids = BTreeMap::new();
         let mut tag_ids_to_node_ids = BTreeMap::new();
 
         dom.collect_callbacks(
             &mut tag_ids_to_callbacks,
+            &mut tag_ids_to_capture_callbacks,
             &mut tag_ids_to_default_callbacks,
             &mut node_ids_to_tag_ids,
             &mut tag_ids_to_node_ids);
@@ -84,6 +97,7 @@ impl<T: Layout> UiState<T> {
         UiState {
             dom,
             tag_ids_to_callbacks,
+            tag_ids_to_capture_callbacks,
             tag_ids_to_default_callbacks,
             node_ids_to_tag_ids,
             tag_ids_to_node_ids,
@@ -91,6 +105,234 @@ impl<T: Layout> UiState<T> {
     }
 }
 
+/// Which callback of a node is invoked in a step of the event dispatch
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DispatchedCallback {
+    /// Callback added via `Dom::with_capture_callback`
+    Capture,
+    /// Callback added via `Dom::with_callback`
+    Bubble,
+    /// Default callback of a widget, see `FakeWindow::push_callback`
+    Default,
+}
+
+/// A single callback invocation while an event is dispatched
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct EventDispatchStep {
+    pub(crate) node_id: NodeId,
+    pub(crate) phase: EventPhase,
+    pub(crate) on: On,
+    pub(crate) callback: DispatchedCallback,
+}
+
+impl<T: Layout> UiState<T> {
+    /// Returns the callbacks that have to be invoked for the event `on`, in the order in which
+    /// they have to be invoked. Like in the HTML DOM, the event is dispatched along the target
+    /// and its ancestors:
+    ///
+    /// - First, the capturing callbacks are invoked, from the root down to the target.
+    /// - Then the event bubbles up from the target to the root. On each node, the default
+    ///   callback (the built-in behaviour of a widget) is invoked **before** the callback of the
+    ///   user, so the user-defined callback can already see the changes made by the widget.
+    ///
+    /// Whether a step is actually invoked depends on the `EventDispatchState`.
+    pub(crate) fn get_event_dispatch_order(&self, target: NodeId, on: On) -> Vec<EventDispatchStep> {
+
+        let arena = self.dom.arena.borrow();
+
+        // The target and all of its ancestors, from the target up to the root
+        let propagation_path = target.ancestors(&*arena).collect::<Vec<NodeId>>();
+
+        let phase_of = |node_id: NodeId, bubbling: EventPhase| if node_id == target { EventPhase::Target } else { bubbling };
+        let has_callback = |node_id: &NodeId, callback: DispatchedCallback| {
+            self.node_ids_to_tag_ids.get(node_id).and_then(|tag_id| match callback {
+                DispatchedCallback::Capture => self.tag_ids_to_capture_callbacks.get(tag_id).and_then(|list| Some(list.contains_key(&on))),
+                DispatchedCallback::Bubble => self.tag_ids_to_callbacks.get(tag_id).and_then(|list| Some(list.contains_key(&on))),
+                DispatchedCallback::Default => self.tag_ids_to_default_callbacks.get(tag_id).and_then(|list| Some(list.contains_key(&on))),
+            }).unwrap_or(false)
+        };
+
+        let mut steps = Vec::new();
+
+        for node_id in propagation_path.iter().rev() {
+            if has_callback(node_id, DispatchedCallback::Capture) {
+                steps.push(EventDispatchStep { node_id: *node_id, phase: phase_of(*node_id, EventPhase::Capture), on: on, callback: DispatchedCallback::Capture });
+            }
+        }
+
+        for node_id in propagation_path.iter() {
+            for callback in &[DispatchedCallback::Default, DispatchedCallback::Bubble] {
+                if has_callback(node_id, *callback) {
+                    steps.push(EventDispatchStep { node_id: *node_id, phase: phase_of(*node_id, EventPhase::Bubble), on: on, callback: *callback });
+                }
+            }
+        }
+
+        steps
+    }
+}
+
+/// Keeps track of `FakeWindow::stop_propagation` and `FakeWindow::prevent_default`
+/// while a single event is dispatched
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct EventDispatchState {
+    /// The phase in which the propagation was stopped
+    propagation_stopped_in: Option<EventPhase>,
+    default_prevented: bool,
+}
+
+impl EventDispatchState {
+    /// Returns whether the callback of the step should be invoked
+    pub(crate) fn should_invoke(&self, step: &EventDispatchStep) -> bool {
+        match step.callback {
+            // Stopping the propagation doesn't affect the default callbacks. Preventing the
+            // default only affects the default callbacks that haven't been invoked yet.
+            DispatchedCallback::Default => !self.default_prevented,
+            // If the propagation is stopped on the target, the remaining callbacks of the
+            // target are still invoked (i.e. the bubbling callback after the capturing one)
+            DispatchedCallback::Capture | DispatchedCallback::Bubble => match self.propagation_stopped_in {
+                None => true,
+                Some(EventPhase::Target) => step.phase == EventPhase::Target,
+                Some(_) => false,
+            },
+        }
+    }
+
+    /// Updates the state after the callback of the step was invoked
+    pub(crate) fn update(&mut self, step: &EventDispatchStep, propagation_stopped: bool, default_prevented: bool) {
+        if propagation_stopped && self.propagation_stopped_in.is_none() {
+            self.propagation_stopped_in = Some(step.phase);
+        }
+        self.default_prevented = self.default_prevented || default_prevented;
+    }
+}
+
+#[cfg(test)]
+fn test_dispatch_ui_state() -> UiState<TestDispatchLayout> {
+
+    use dom::NodeType;
+    use default_callbacks::get_new_unique_default_callback_id;
+
+    let mut target = Dom::new(NodeType::Div)
+        .with_id("target")
+        .with_capture_callback(On::MouseUp, Callback(test_dispatch_callback))
+        .with_callback(On::MouseUp, Callback(test_dispatch_callback));
+    target.push_default_callback_id(On::MouseUp, get_new_unique_default_callback_id());
+
+    let parent = Dom::new(NodeType::Div)
+        .with_id("parent")
+        .with_callback(On::MouseUp, Callback(test_dispatch_callback))
+        .with_child(target);
+
+    let mut root = Dom::new(NodeType::Div)
+        .with_id("root")
+        .with_capture_callback(On::MouseUp, Callback(test_dispatch_callback))
+        .with_callback(On::MouseUp, Callback(test_dispatch_callback))
+        .with_child(parent);
+    root.push_default_callback_id(On::MouseUp, get_new_unique_default_callback_id());
+
+    UiState::from_dom(root)
+}
+
+#[cfg(test)]
+struct TestDispatchLayout { }
+
+#[cfg(test)]
+impl Layout for TestDispatchLayout {
+    fn layout(&self) -> Dom<Self> {
+        Dom::new(::dom::NodeType::Div)
+    }
+}
+
+#[cfg(test)]
+fn test_dispatch_callback(_: &mut AppState<TestDispatchLayout>, _: ::window::WindowEvent) -> ::dom::UpdateScreen {
+    ::dom::UpdateScreen::DontRedraw
+}
+
+/// Simulates the dispatch of an event, returns the invoked steps. `stop_propagation_at` and
+/// `prevent_default_at` are the steps that call `stop_propagation` / `prevent_default`.
+#[cfg(test)]
+fn simulate_dispatch(steps: &[EventDispatchStep], stop_propagation_at: Option<usize>, prevent_default_at: Option<usize>) -> Vec<EventDispatchStep> {
+    let mut state = EventDispatchState::default();
+    let mut invoked = Vec::new();
+    for (idx, step) in steps.iter().enumerate() {
+        if !state.should_invoke(step) {
+            continue;
+        }
+        invoked.push(*step);
+        state.update(step, stop_propagation_at == Some(idx), prevent_default_at == Some(idx));
+    }
+    invoked
+}
+
+#[test]
+fn test_event_dispatch_order() {
+
+    use self::DispatchedCallback::{Capture, Bubble, Default};
+    use window::EventPhase::{Capture as CapturePhase, Target as TargetPhase, Bubble as BubblePhase};
+
+    let ui_state = test_dispatch_ui_state();
+    let find_node = |id: &str| {
+        let arena = ui_state.dom.arena.borrow();
+        let node_id = arena.linear_iter().find(|node_id| arena[*node_id].data.id.as_ref().map(|s| s.as_str()) == Some(id));
+        node_id.unwrap()
+    };
+    let (root, parent, target) = (find_node("root"), find_node("parent"), find_node("target"));
+
+    let steps = ui_state.get_event_dispatch_order(target, On::MouseUp);
+    let summary = |steps: &[EventDispatchStep]| steps.iter().map(|s| (s.node_id, s.phase, s.callback)).collect::<Vec<_>>();
+
+    assert!(steps.iter().all(|s| s.on == On::MouseUp));
+    assert_eq!(summary(&steps), vec![
+        (root, CapturePhase, Capture),
+        (target, TargetPhase, Capture),
+        (target, TargetPhase, Default),
+        (target, TargetPhase, Bubble),
+        (parent, BubblePhase, Bubble),
+        (root, BubblePhase, Default),
+        (root, BubblePhase, Bubble),
+    ]);
+
+    // No callbacks registered for this event
+    assert!(ui_state.get_event_dispatch_order(target, On::MouseDown).is_empty());
+
+    // Stopping the propagation in the capturing callback of the target still invokes the
+    // bubbling callback of the target, default callbacks are never affected
+    assert_eq!(summary(&simulate_dispatch(&steps, Some(1), None)), vec![
+        (root, CapturePhase, Capture),
+        (target, TargetPhase, Capture),
+        (target, TargetPhase, Default),
+        (target, TargetPhase, Bubble),
+        (root, BubblePhase, Default),
+    ]);
+
+    // Stopping the propagation on an ancestor skips all following user-defined callbacks
+    assert_eq!(summary(&simulate_dispatch(&steps, Some(0), None)), vec![
+        (root, CapturePhase, Capture),
+        (target, TargetPhase, Default),
+        (root, BubblePhase, Default),
+    ]);
+
+    // Preventing the default while bubbling only affects the default callbacks that didn't run yet
+    assert_eq!(summary(&simulate_dispatch(&steps, None, Some(3))), vec![
+        (root, CapturePhase, Capture),
+        (target, TargetPhase, Capture),
+        (target, TargetPhase, Default),
+        (target, TargetPhase, Bubble),
+        (parent, BubblePhase, Bubble),
+        (root, BubblePhase, Bubble),
+    ]);
+
+    // Preventing the default while capturing prevents all default callbacks
+    assert_eq!(summary(&simulate_dispatch(&steps, None, Some(0))), vec![
+        (root, CapturePhase, Capture),
+        (target, TargetPhase, Capture),
+        (target, TargetPhase, Bubble),
+        (parent, BubblePhase, Bubble),
+        (root, BubblePhase, Bubble),
+    ]);
+}
+
 // Empty test, for some reason codecov doesn't detect any files (and therefore
 // doesn't report codecov % correctly) except if they have at least one test in
 // the file. This is an empty test, which should be updated later on
@@ -21,7 +21,6 @@ use glium::{
 };
 use gleam::gl::{self, Gl};
 use {
-    cache::DomHash,
     FastHashMap,
     dom::{Texture, Callback, UpdateScreen},
     daemon::{Daemon, DaemonId},
@@ -31,7 +30,9 @@ use {
     compositor::Compositor,
     app::FrameEventInfo,
     app_resources::AppResources,
-    ui_solver::UiSolver,
+    ui_solver::{UiSolver, SolvedLayout},
+    scroll::ScrollStates,
+    font::FontRenderOptions,
     id_tree::NodeId,
     default_callbacks::{DefaultCallbackSystem, DefaultCallbackId},
 };
@@ -59,6 +60,14 @@ pub struct FakeWindow<T: Layout> {
     /// but not change any window properties from underneath - this would
     /// lead to mismatch between the
     pub(crate) read_only_window: Rc<Display>,
+    /// Bounds of the nodes in the last frame
+    pub(crate) solved_layout: SolvedLayout,
+    /// Scroll offsets that were requested via `scroll_to`, applied before the next frame
+    pub(crate) scroll_requests: Vec<(NodeId, LayoutVector2D)>,
+    /// Set by `stop_propagation`, reset before an event is dispatched
+    pub(crate) propagation_stopped: bool,
+    /// Set by `prevent_default`, reset before an event is dispatched
+    pub(crate) default_prevented: bool,
 }
 
 impl<T: Layout> FakeWindow<T> {
@@ -91,6 +100,40 @@ impl<T: Layout> FakeWindow<T> {
         self.state.mouse_state
     }
 
+    /// Returns the bounds of the node (relative to the top left of the window) as it
+    /// was laid out in the last frame. The `NodeId` is the same as in the `WindowEvent`
+    /// that is passed to callbacks, so this can be used to position a popover next to
+    /// the clicked node.
+    ///
+    /// Returns `None` if the node doesn't exist or the window hasn't been rendered yet.
+    pub fn get_node_bounds(&self, node_id: NodeId) -> Option<LayoutRect> {
+        self.solved_layout.get_bounds(node_id)
+    }
+
+    /// Same as `get_node_bounds`, but looks up the node by its CSS id (see `Dom::with_id`).
+    /// If multiple nodes have the same id, returns the bounds of the first node.
+    pub fn get_node_bounds_by_id(&self, id: &str) -> Option<LayoutRect> {
+        self.solved_layout.get_bounds_by_id(id)
+    }
+
+    /// Scrolls a scroll container (a node with `overflow: scroll` or `overflow: auto`)
+    /// so that the content is moved by `offset` pixels to the left / top. The offset is
+    /// clamped to the size of the content and takes effect in the next frame.
+    ///
+    /// Does nothing if the node isn't a scroll container, or if its content fits into it.
+    pub fn scroll_to(&mut self, node_id: NodeId, offset: LayoutVector2D) {
+        self.scroll_requests.push((node_id, offset));
+    }
+
+    /// Same as `scroll_to`, but looks up the node by its CSS id (see `Dom::with_id`).
+    /// Returns `false` if no node with the given id was rendered in the last frame.
+    pub fn scroll_to_id(&mut self, id: &str, offset: LayoutVector2D) -> bool {
+        match self.solved_layout.ids.get(id).cloned() {
+            Some(node_id) => { self.scroll_to(node_id, offset); true },
+            None => false,
+        }
+    }
+
     /// Adds a default callback to the window. The default callbacks are
     /// cleared after every frame, so two-way data binding widgets have to call this
     /// on every frame they want to insert a default callback.
@@ -103,6 +146,38 @@ impl<T: Layout> FakeWindow<T> {
         self.default_callbacks.push_callback(data, default_callback_id, callback.get_callback_ptr(), callback.get_callback_fn());
         default_callback_id
     }
+
+    /// Stops the event that is currently dispatched from propagating further: the callbacks
+    /// of the remaining nodes in the capture / bubble path won't be called. If called on the
+    /// target of the event, the remaining callbacks of the target itself are still called.
+    /// Only has an effect when called from inside a callback.
+    pub fn stop_propagation(&mut self) {
+        self.propagation_stopped = true;
+    }
+
+    /// Prevents the default callbacks (i.e. the built-in behaviour of widgets, see `push_callback`)
+    /// that haven't run yet from running for the event that is currently dispatched. The default
+    /// callback of a node runs before the bubbling callback of the same node, so this only affects
+    /// the ancestors of the node or - if called while capturing - all nodes. Only has an effect
+    /// when called from inside a callback.
+    pub fn prevent_default(&mut self) {
+        self.default_prevented = true;
+    }
+
+    /// Returns whether `stop_propagation` was called for the current event
+    pub fn is_propagation_stopped(&self) -> bool {
+        self.propagation_stopped
+    }
+
+    /// Returns whether `prevent_default` was called for the current event
+    pub fn is_default_prevented(&self) -> bool {
+        self.default_prevented
+    }
+
+    pub(crate) fn reset_event_state(&mut self) {
+        self.propagation_stopped = false;
+        self.default_prevented = false;
+    }
 }
 
 /// Read-only window which can be used to create / draw
@@ -181,19 +256,43 @@ impl<T: Layout> fmt::Debug for FakeWindow<T> {
     }
 }
 
+/// Phase of the event dispatch in which a callback is invoked
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EventPhase {
+    /// The event travels from the root down to the parent of the hit node,
+    /// only callbacks added via `Dom::with_capture_callback` are invoked
+    Capture,
+    /// The callback is attached to the hit node itself
+    Target,
+    /// The event travels from the parent of the hit node up to the root
+    Bubble,
+}
+
 /// Window event that is passed to the user when a callback is invoked
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct WindowEvent {
     /// The ID of the window that the event was clicked on (for indexing into
     /// `app_state.windows`). `app_state.windows[event.window]` should never panic.
     pub window: usize,
-    /// The ID of the node that was hit. You can use this to query information about
-    /// the node, but please don't hard-code any if / else statements based on the `NodeId`
+    /// The ID of the node that was hit (the target of the event). You can use this to query
+    /// information about the node, but please don't hard-code any if / else statements based on the `NodeId`
+    ///
+    /// Only the topmost hit node is the target. Nodes below it that were hit as well, but
+    /// that aren't ancestors of the target (for example overlapping siblings), don't receive
+    /// the event anymore - attach the callback to a common ancestor instead.
     pub hit_dom_node: NodeId,
-    /// The (x, y) position of the mouse cursor, **relative to top left of the element that was hit**.
+    /// The ID of the node whose callback is currently invoked - either the hit node
+    /// or one of its ancestors
+    pub current_dom_node: NodeId,
+    /// Whether the callback is invoked while capturing, on the target or while bubbling
+    pub phase: EventPhase,
+    /// The (x, y) position of the mouse cursor, **relative to top left of the `current_dom_node`**
+    /// (i.e. the node whose callback is invoked, which is not necessarily the hit node).
     pub cursor_relative_to_item: (f32, f32),
     /// The (x, y) position of the mouse cursor, **relative to top left of the window**.
     pub cursor_in_viewport: (f32, f32),
+    /// If the hit node is a `NodeType::RichText`: the index of the clickable span that was hit
+    pub hit_text_span: Option<usize>,
 }
 
 impl WindowEvent {
@@ -202,8 +301,11 @@ impl WindowEvent {
         Self {
             window: 0,
             hit_dom_node: NodeId::new(0),
+            current_dom_node: NodeId::new(0),
+            phase: EventPhase::Target,
             cursor_relative_to_item: (0.0, 0.0),
             cursor_in_viewport: (0.0, 0.0),
+            hit_text_span: None,
         }
     }
 }
@@ -231,6 +333,12 @@ pub struct WindowCreateOptions<T: Layout> {
     pub update_behaviour: UpdateBehaviour,
     /// Renderer type: Hardware-with-software-fallback, pure software or pure hardware renderer?
     pub renderer_type: RendererType,
+    /// Which layout engine should be used to lay out the rectangles of the window
+    pub layout_engine: LayoutEngine,
+    /// Scroll direction, line height and smooth / momentum scrolling of the mouse wheel
+    pub scroll_options: ScrollOptions,
+    /// Antialiasing, hinting and subpixel positioning of the text in this window
+    pub font_render_options: FontRenderOptions,
     /// Win32 menu callbacks
     pub menu_callbacks: HashMap<u16, Callback<T>>,
     /// Sets the window icon (Windows and Linux only). Usually 16x16 px or 32x32px
@@ -253,6 +361,9 @@ impl<T: Layout> Default for WindowCreateOptions<T> {
             mouse_mode: MouseMode::default(),
             update_behaviour: UpdateBehaviour::default(),
             renderer_type: RendererType::default(),
+            layout_engine: LayoutEngine::default(),
+            scroll_options: ScrollOptions::default(),
+            font_render_options: FontRenderOptions::default(),
             menu_callbacks: HashMap::new(),
             window_icon: None,
             taskbar_icon: None,
@@ -286,6 +397,54 @@ impl Default for RendererType {
     }
 }
 
+/// Selects how the rectangles of a window are laid out.
+///
+/// `Cassowary` (the default) translates the CSS into constraints for the cassowary
+/// solver and only rebuilds the constraints of changed rectangles. `Flexbox` lays out
+/// the whole tree directly on every relayout, following the CSS flexbox algorithm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LayoutEngine {
+    Cassowary,
+    Flexbox,
+}
+
+impl Default for LayoutEngine {
+    fn default() -> Self {
+        LayoutEngine::Cassowary
+    }
+}
+
+/// How mouse wheel and touchpad events scroll the window
+/// (the scroll containers as well as `MouseState::scroll_x / scroll_y`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScrollOptions {
+    /// Inverts the scroll direction, so that the content follows the fingers on a
+    /// touchpad instead of the scrollbar. Default: `false`
+    pub natural_scrolling: bool,
+    /// How many pixels one line of a mouse wheel notch scrolls. Default: `100.0`
+    pub pixels_per_line: f32,
+    /// Mouse wheel notches are animated over this duration instead of jumping
+    /// to the new offset. `None` disables smooth scrolling. Default: 150ms
+    pub smooth_scroll_duration: Option<Duration>,
+    /// Whether touchpad scrolling keeps going after the fingers are lifted. Default: `true`
+    pub momentum_scrolling: bool,
+    /// Fraction of the momentum scrolling speed that is left after one second,
+    /// between `0.0` (stops immediately) and `1.0` (never slows down). Default: `0.05`
+    pub momentum_friction: f32,
+}
+
+impl Default for ScrollOptions {
+    fn default() -> Self {
+        Self {
+            natural_scrolling: false,
+            pixels_per_line: 100.0,
+            smooth_scroll_duration: Some(Duration::from_millis(150)),
+            momentum_scrolling: true,
+            momentum_friction: 0.05,
+        }
+    }
+}
+
 /// Should the window be updated only if the mouse cursor is hovering over it?
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum UpdateBehaviour {
@@ -486,8 +645,10 @@ pub struct Window<T: Layout> {
     pub(crate) ui_solver: UiSolver,
     /// Currently running animations / transitions
     pub(crate) animations: FastHashMap<DaemonId, Daemon<AnimationState>>,
-    /// States of scrolling animations, updated every frame
-    pub(crate) scroll_states: FastHashMap<DomHash, ScrollState>,
+    /// Scroll offsets of the scroll containers (`overflow: scroll` / `overflow: auto`)
+    pub(crate) scroll_states: ScrollStates,
+    /// Default rendering options for the fonts of this window
+    pub(crate) font_render_options: FontRenderOptions,
     // The background thread that is running for this window.
     // pub(crate) background_thread: Option<JoinHandle<()>>,
     /// The css (how the current window is styled)
@@ -510,23 +671,6 @@ pub struct Window<T: Layout> {
 #[derive(Debug, Copy, Clone)]
 pub struct AnimationState { }
 
-#[derive(Debug, Copy, Clone)]
-pub struct ScrollState {
-    /// Amount in pixel that the current node is scrolled
-    scroll_amount: f32,
-    /// Was the scroll amount used in this frame?
-    used_this_frame: bool,
-}
-
-impl Default for ScrollState {
-    fn default() -> Self {
-        ScrollState {
-            scroll_amount: 0.0,
-            used_this_frame: true,
-        }
-    }
-}
-
 pub(crate) struct WindowInternal {
     pub(crate) last_display_list_builder: BuiltDisplayList,
     pub(crate) api: RenderApi,
@@ -708,7 +852,7 @@ impl<T: Layout> Window<T> {
         let thread = Builder::new().name(options.title.clone()).spawn(move || Self::handle_event(receiver))?;
         */
 
-        let ui_solver = UiSolver::new(&options.state.size.dimensions);
+        let ui_solver = UiSolver::new(&options.state.size.dimensions, options.layout_engine);
 
         renderer.set_external_image_handler(Box::new(Compositor::default()));
 
@@ -719,7 +863,8 @@ impl<T: Layout> Window<T> {
             display: Rc::new(display),
             css: css,
             animations: FastHashMap::default(),
-            scroll_states: FastHashMap::default(),
+            scroll_states: ScrollStates::new(options.scroll_options),
+            font_render_options: options.font_render_options,
             internal: WindowInternal {
                 api: api,
                 epoch: epoch,
@@ -823,31 +968,14 @@ impl<T: Layout> Window<T> {
         self.state.mouse_state.scroll_y = 0.0;
     }
 
-    /// NOTE: This has to be a getter, because we need to update
-    #[must_use]
-    pub(crate) fn get_scroll_amount(&mut self, dom_hash: &DomHash) -> Option<f32> {
-        let entry = self.scroll_states.get_mut(&dom_hash)?;
-        entry.used_this_frame = true;
-        Some(entry.scroll_amount)
-    }
-
-    /// Note: currently scrolling is only done in the vertical direction
-    ///
-    /// Updating the scroll amound does not update the `entry.used_this_frame`,
-    /// since that is only relevant when we are actually querying the renderer.
-    pub(crate) fn scroll_node(&mut self, dom_hash: &DomHash, scroll_by: f32) {
-        if let Some(entry) = self.scroll_states.get_mut(dom_hash) {
-            entry.scroll_amount += scroll_by;
+    /// Applies the scroll offsets that were requested via `FakeWindow::scroll_to`,
+    /// returns whether any scroll container was scrolled
+    pub(crate) fn apply_scroll_requests(&mut self, scroll_requests: &mut Vec<(NodeId, LayoutVector2D)>) -> bool {
+        let mut scrolled = false;
+        for (node_id, offset) in scroll_requests.drain(..) {
+            scrolled |= self.scroll_states.scroll_to(node_id, offset);
         }
-    }
-
-    pub(crate) fn create_new_scroll(&mut self, dom_hash: DomHash) {
-        self.scroll_states.insert(dom_hash, ScrollState::default());
-    }
-
-    /// Removes all scroll states that weren't used in the last frame
-    pub(crate) fn remove_unused_scroll_states(&mut self) {
-        self.scroll_states.retain(|_, state| state.used_this_frame);
+        scrolled
     }
 
     /// Runs all animations currently registered in this DOM
@@ -10,6 +10,7 @@ use std::collections::HashSet;
 use {
     dom::On,
     menu::{ApplicationMenu, ContextMenu},
+    window::ScrollOptions,
 };
 
 const DEFAULT_TITLE: &str = "Azul App";
@@ -74,9 +75,11 @@ pub struct MouseState
     pub right_down: bool,
     //// Is the middle mouse button down?
     pub middle_down: bool,
-    /// Scroll amount in pixels in the horizontal direction. Gets reset to 0 after every frame
+    /// Scroll amount in pixels in the horizontal direction, positive values scroll to the right
+    /// (see `ScrollOptions::natural_scrolling`). Gets reset to 0 after every frame
     pub scroll_x: f64,
-    /// Scroll amount in pixels in the vertical direction. Gets reset to 0 after every frame
+    /// Scroll amount in pixels in the vertical direction, positive values scroll to the bottom
+    /// (see `ScrollOptions::natural_scrolling`). Gets reset to 0 after every frame
     pub scroll_y: f64,
 }
 
@@ -180,7 +183,7 @@ impl WindowState
     //
     // This function also updates / mutates the current window state,
     // so that we are ready for the next frame
-    pub(crate) fn determine_callbacks(&mut self, event: &Event) -> Vec<On> {
+    pub(crate) fn determine_callbacks(&mut self, event: &Event, scroll_options: &ScrollOptions) -> Vec<On> {
 
         use std::collections::HashSet;
         use glium::glutin::{
@@ -251,12 +254,9 @@ impl WindowState
                 }
             },
             WindowEvent::MouseWheel { delta, .. } => {
-                let (scroll_x_px, scroll_y_px) = match delta {
-                    MouseScrollDelta::PixelDelta(LogicalPosition { x, y }) => (*x, *y),
-                    MouseScrollDelta::LineDelta(x, y) => (*x as f64 * 100.0, *y as f64 * 100.0),
-                };
-                self.mouse_state.scroll_x = -scroll_x_px;
-                self.mouse_state.scroll_y = -scroll_y_px; // TODO: "natural scrolling"?
+                let (scroll_x_px, scroll_y_px) = get_scroll_delta_px(delta, scroll_options);
+                self.mouse_state.scroll_x = scroll_x_px;
+                self.mouse_state.scroll_y = scroll_y_px;
                 events_vec.insert(On::Scroll);
             },
             WindowEvent::KeyboardInput { input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(_), .. }, .. } => {
@@ -362,6 +362,20 @@ fn update_mouse_cursor(window: &Window, old: &MouseCursor, new: &MouseCursor) {
     }
 }
 
+/// Converts the delta of a `MouseWheel` event into pixels. Positive values scroll
+/// towards the bottom / right, unless `natural_scrolling` is set.
+pub(crate) fn get_scroll_delta_px(delta: &MouseScrollDelta, scroll_options: &ScrollOptions) -> (f64, f64) {
+    // winit reports positive values when scrolling towards the top / left
+    let (x, y) = match delta {
+        MouseScrollDelta::PixelDelta(LogicalPosition { x, y }) => (*x, *y),
+        MouseScrollDelta::LineDelta(x, y) => {
+            let pixels_per_line = scroll_options.pixels_per_line as f64;
+            (*x as f64 * pixels_per_line, *y as f64 * pixels_per_line)
+        },
+    };
+    if scroll_options.natural_scrolling { (x, y) } else { (-x, -y) }
+}
+
 fn virtual_key_code_to_char(code: VirtualKeyCode) -> Option<char> {
     use glium::glutin::VirtualKeyCode::*;
     match code {